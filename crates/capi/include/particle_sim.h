@@ -0,0 +1,67 @@
+#ifndef PARTICLE_SIM_H
+#define PARTICLE_SIM_H
+
+/* Generated by cbindgen from crates/capi/src/lib.rs, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// An opaque handle to a running simulation.
+typedef struct ParticleSimulation ParticleSimulation;
+
+// Create a simulation with `random_particles` randomly placed particles.
+// Free it with `particle_sim_free`.
+struct ParticleSimulation *particle_sim_new(size_t random_particles);
+
+// Free a simulation. Passing NULL does nothing.
+//
+// # Safety
+// `sim` must be NULL or a pointer returned by `particle_sim_new` that has not been freed.
+void particle_sim_free(struct ParticleSimulation *sim);
+
+// Add a particle and return its id, one that no other particle of the simulation has had.
+//
+// # Safety
+// `sim` must be a valid simulation pointer.
+uint64_t particle_sim_add_particle(struct ParticleSimulation *sim,
+                                   double radius,
+                                   double x,
+                                   double y,
+                                   double vx,
+                                   double vy);
+
+// Advance the simulation by `dt`. Returns 0 on success, or -1 on failure,
+// in which case `particle_sim_last_error` describes what went wrong.
+//
+// # Safety
+// `sim` must be a valid simulation pointer.
+int32_t particle_sim_step(struct ParticleSimulation *sim, double dt);
+
+// The message for the last failed call, or NULL if nothing has failed.
+// The string is owned by the simulation and valid until the next failure or free.
+//
+// # Safety
+// `sim` must be a valid simulation pointer.
+const char *particle_sim_last_error(const struct ParticleSimulation *sim);
+
+// The number of particles in the simulation.
+//
+// # Safety
+// `sim` must be a valid simulation pointer.
+size_t particle_sim_particle_count(const struct ParticleSimulation *sim);
+
+// Copy particle positions into `buffer` as `x0, y0, x1, y1, ...`, writing at most
+// `capacity` doubles. Returns the number of particles written, which is less than
+// the particle count if the buffer is too small.
+//
+// # Safety
+// `sim` must be a valid simulation pointer, and `buffer` must point to at least
+// `capacity` writable doubles.
+size_t particle_sim_positions(const struct ParticleSimulation *sim,
+                              double *buffer,
+                              size_t capacity);
+
+#endif  /* PARTICLE_SIM_H */
@@ -0,0 +1,188 @@
+//! A C API for embedding the simulation in C and C++ programs.
+//! The header is `include/particle_sim.h`, generated by cbindgen.
+//!
+//! Every function taking a `ParticleSimulation` pointer expects one returned by
+//! `particle_sim_new` that has not yet been passed to `particle_sim_free`.
+use particle_core::particle::Particle;
+use particle_core::simulation::Simulation;
+use particle_core::utils::Vec2;
+use std::ffi::{c_char, CString};
+use std::ptr;
+
+/// An opaque handle to a running simulation.
+pub struct ParticleSimulation {
+    simulation: Simulation,
+    last_error: Option<CString>,
+}
+
+/// Create a simulation with `random_particles` randomly placed particles.
+/// Free it with `particle_sim_free`.
+#[no_mangle]
+pub extern "C" fn particle_sim_new(random_particles: usize) -> *mut ParticleSimulation {
+    Box::into_raw(Box::new(ParticleSimulation {
+        simulation: Simulation::with_random_particles(random_particles),
+        last_error: None,
+    }))
+}
+
+/// Free a simulation. Passing NULL does nothing.
+///
+/// # Safety
+/// `sim` must be NULL or a pointer returned by `particle_sim_new` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn particle_sim_free(sim: *mut ParticleSimulation) {
+    if !sim.is_null() {
+        drop(Box::from_raw(sim));
+    }
+}
+
+/// Add a particle and return its id, one that no other particle of the simulation has had.
+///
+/// # Safety
+/// `sim` must be a valid simulation pointer.
+#[no_mangle]
+pub unsafe extern "C" fn particle_sim_add_particle(
+    sim: *mut ParticleSimulation,
+    radius: f64,
+    x: f64,
+    y: f64,
+    vx: f64,
+    vy: f64,
+) -> u64 {
+    let sim = &mut *sim;
+    let id = sim.simulation.allocate_id();
+    sim.simulation
+        .spawn(Particle::new(id, radius, Vec2(x, y), Vec2(vx, vy)));
+    id
+}
+
+/// Advance the simulation by `dt`. Returns 0 on success, or -1 on failure,
+/// in which case `particle_sim_last_error` describes what went wrong.
+///
+/// # Safety
+/// `sim` must be a valid simulation pointer.
+#[no_mangle]
+pub unsafe extern "C" fn particle_sim_step(sim: *mut ParticleSimulation, dt: f64) -> i32 {
+    let sim = &mut *sim;
+    match sim.simulation.step(dt) {
+        Ok(()) => 0,
+        Err(err) => {
+            sim.last_error = CString::new(err.to_string()).ok();
+            -1
+        }
+    }
+}
+
+/// The message for the last failed call, or NULL if nothing has failed.
+/// The string is owned by the simulation and valid until the next failure or free.
+///
+/// # Safety
+/// `sim` must be a valid simulation pointer.
+#[no_mangle]
+pub unsafe extern "C" fn particle_sim_last_error(sim: *const ParticleSimulation) -> *const c_char {
+    match &(*sim).last_error {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// The number of particles in the simulation.
+///
+/// # Safety
+/// `sim` must be a valid simulation pointer.
+#[no_mangle]
+pub unsafe extern "C" fn particle_sim_particle_count(sim: *const ParticleSimulation) -> usize {
+    (*sim).simulation.particle_count()
+}
+
+/// Copy particle positions into `buffer` as `x0, y0, x1, y1, ...`, writing at most
+/// `capacity` doubles. Returns the number of particles written, which is less than
+/// the particle count if the buffer is too small.
+///
+/// # Safety
+/// `sim` must be a valid simulation pointer, and `buffer` must point to at least
+/// `capacity` writable doubles.
+#[no_mangle]
+pub unsafe extern "C" fn particle_sim_positions(
+    sim: *const ParticleSimulation,
+    buffer: *mut f64,
+    capacity: usize,
+) -> usize {
+    if buffer.is_null() {
+        return 0;
+    }
+    let buffer = std::slice::from_raw_parts_mut(buffer, capacity);
+    let mut written = 0;
+    for ((_, particle), slot) in (*sim)
+        .simulation
+        .world()
+        .query::<&Particle>()
+        .iter()
+        .zip(buffer.chunks_exact_mut(2))
+    {
+        slot[0] = particle.position.0;
+        slot[1] = particle.position.1;
+        written += 1;
+    }
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_step_and_read_positions() {
+        unsafe {
+            let sim = particle_sim_new(0);
+            assert_eq!(particle_sim_add_particle(sim, 1.0, 1.0, 1.0, 2.5, 3.5), 0);
+            assert_eq!(
+                particle_sim_add_particle(sim, 1.0, 500.0, 500.0, 0.0, 0.0),
+                1
+            );
+            assert_eq!(particle_sim_step(sim, 1.0), 0);
+            assert!(particle_sim_last_error(sim).is_null());
+            assert_eq!(particle_sim_particle_count(sim), 2);
+
+            let mut buffer = [0.0; 4];
+            assert_eq!(particle_sim_positions(sim, buffer.as_mut_ptr(), 4), 2);
+            assert!(buffer.chunks(2).any(|xy| xy == [3.5, 4.5]));
+            assert!(buffer.chunks(2).any(|xy| xy == [500.0, 500.0]));
+            particle_sim_free(sim);
+        }
+    }
+
+    #[test]
+    fn test_added_particles_get_ids_of_their_own() {
+        unsafe {
+            let sim = particle_sim_new(3);
+            assert_eq!(
+                particle_sim_add_particle(sim, 1.0, 500.0, 500.0, 0.0, 0.0),
+                3
+            );
+            let mut ids: Vec<u64> = (*sim)
+                .simulation
+                .world()
+                .query::<&Particle>()
+                .iter()
+                .map(|(_, particle)| particle.id)
+                .collect();
+            ids.sort();
+            ids.dedup();
+            assert_eq!(ids.len(), 4);
+            particle_sim_free(sim);
+        }
+    }
+
+    #[test]
+    fn test_positions_respects_capacity() {
+        unsafe {
+            let sim = particle_sim_new(10);
+            let mut buffer = [0.0; 5];
+            // Only two whole particles fit into five doubles
+            assert_eq!(particle_sim_positions(sim, buffer.as_mut_ptr(), 5), 2);
+            assert_eq!(particle_sim_positions(sim, ptr::null_mut(), 5), 0);
+            particle_sim_free(sim);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Micro-benchmarks of the vector math and of a single collision, kept apart from
+//! the rest of a step so that changes to the arithmetic (such as the `glam` feature)
+//! can be measured on their own:
+//!
+//! ```text
+//! cargo bench -p particle-core --bench math
+//! cargo bench -p particle-core --bench math --features glam
+//! ```
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use particle_core::particle::Particle;
+use particle_core::utils::Vec2;
+
+fn vectors(c: &mut Criterion) {
+    let a = Vec2(3.5, -1.25);
+    let b = Vec2(-0.75, 8.0);
+    c.bench_function("inner_product", |bencher| {
+        bencher.iter(|| black_box(a).inner_product(&black_box(b)))
+    });
+    c.bench_function("distance", |bencher| {
+        bencher.iter(|| black_box(a).distance(&black_box(b)))
+    });
+}
+
+fn collisions(c: &mut Criterion) {
+    // An off-center hit, so that neither the contact normal nor the response
+    // lies along an axis
+    let a = Particle::new(0, 10.0, Vec2(100.0, 100.0), Vec2(2.0, 0.5));
+    let b = Particle::new(1, 8.0, Vec2(115.0, 106.0), Vec2(-1.0, -0.25));
+    let apart = Particle::new(2, 8.0, Vec2(200.0, 100.0), Vec2::ZERO);
+    c.bench_function("check_pairwise_collision/hit", |bencher| {
+        bencher.iter(|| black_box(&a).check_pairwise_collision(black_box(&b)))
+    });
+    c.bench_function("check_pairwise_collision/miss", |bencher| {
+        bencher.iter(|| black_box(&a).check_pairwise_collision(black_box(&apart)))
+    });
+    let contact = a.contact(&b).expect("the particles overlap");
+    c.bench_function("collision_react", |bencher| {
+        bencher.iter(|| black_box(&a).collision_react(black_box(&b), black_box(&contact)))
+    });
+}
+
+criterion_group!(benches, vectors, collisions);
+criterion_main!(benches);
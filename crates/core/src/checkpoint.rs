@@ -0,0 +1,185 @@
+use crate::components::{Appearance, Charge, GravityScale, Lifetime, Species};
+use crate::error::{SimError, SimResult};
+use crate::obstacles::Obstacle;
+use crate::particle::Particle;
+use crate::portals::Portal;
+use crate::settings::PhysicsSettings;
+use crate::shapes::Body;
+use crate::spawn::SpawnSettings;
+use crate::species::SpeciesTable;
+use crate::time_regions::TimeRegion;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever the layout of `Checkpoint` changes, so that an old file
+/// is rejected instead of being decoded into garbage.
+const FORMAT_VERSION: u32 = 17;
+/// Only the newest few checkpoints are kept in a checkpoint directory.
+const CHECKPOINTS_KEPT: usize = 2;
+const PREFIX: &str = "checkpoint-";
+const EXTENSION: &str = "bin";
+
+/// Everything needed to resume a simulation, created by `Simulation::checkpoint`
+/// and restored by `Simulation::from_checkpoint`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub(crate) frame: u64,
+    pub(crate) next_id: u64,
+    pub(crate) settings: PhysicsSettings,
+    pub(crate) spawn_settings: SpawnSettings,
+    pub(crate) species: SpeciesTable,
+    pub(crate) obstacles: Vec<Obstacle>,
+    pub(crate) portals: Vec<Portal>,
+    pub(crate) time_regions: Vec<TimeRegion>,
+    pub(crate) rng: ChaCha8Rng,
+    pub(crate) entities: Vec<SavedEntity>,
+}
+
+/// One entity and whichever optional components it had.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SavedEntity {
+    pub(crate) particle: Particle,
+    pub(crate) charge: Option<Charge>,
+    pub(crate) species: Option<Species>,
+    pub(crate) gravity_scale: Option<GravityScale>,
+    pub(crate) lifetime: Option<Lifetime>,
+    pub(crate) appearance: Option<Appearance>,
+    pub(crate) body: Option<Body>,
+}
+
+fn checkpoint_error(err: bincode::Error) -> SimError {
+    SimError::Checkpoint(err.to_string())
+}
+
+impl Checkpoint {
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn to_bytes(&self) -> SimResult<Vec<u8>> {
+        bincode::serialize(&(FORMAT_VERSION, self)).map_err(checkpoint_error)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> SimResult<Self> {
+        let (version, checkpoint): (u32, Checkpoint) =
+            bincode::deserialize(bytes).map_err(checkpoint_error)?;
+        if version != FORMAT_VERSION {
+            return Err(SimError::Checkpoint(format!(
+                "format version {} is not supported (expected {})",
+                version, FORMAT_VERSION
+            )));
+        }
+        Ok(checkpoint)
+    }
+
+    pub fn load(path: &Path) -> SimResult<Self> {
+        Checkpoint::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn save_to_dir(&self, dir: &Path) -> SimResult<PathBuf> {
+        //! Write the checkpoint into `dir`, named after its frame, and remove
+        //! older checkpoints. The file is written under a temporary name and
+        //! then renamed, so a crash never leaves a half written checkpoint behind.
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}{:012}.{}", PREFIX, self.frame, EXTENSION));
+        let partial = path.with_extension("partial");
+        fs::write(&partial, self.to_bytes()?)?;
+        fs::rename(&partial, &path)?;
+
+        let checkpoints = list_checkpoints(dir)?;
+        let stale = checkpoints.len().saturating_sub(CHECKPOINTS_KEPT);
+        for (_, old) in checkpoints.into_iter().take(stale) {
+            fs::remove_file(old)?;
+        }
+        Ok(path)
+    }
+}
+
+pub fn latest_checkpoint(dir: &Path) -> SimResult<Option<PathBuf>> {
+    //! The newest checkpoint in `dir`, or `None` if there are none yet.
+    if !dir.exists() {
+        return Ok(None);
+    }
+    Ok(list_checkpoints(dir)?.pop().map(|(_, path)| path))
+}
+
+fn list_checkpoints(dir: &Path) -> SimResult<Vec<(u64, PathBuf)>> {
+    //! Every checkpoint in `dir` with its frame, oldest first.
+    let mut checkpoints = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != EXTENSION) {
+            continue;
+        }
+        let frame = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.strip_prefix(PREFIX))
+            .and_then(|frame| frame.parse::<u64>().ok());
+        if let Some(frame) = frame {
+            checkpoints.push((frame, path));
+        }
+    }
+    checkpoints.sort();
+    Ok(checkpoints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Simulation;
+
+    fn positions(simulation: &Simulation) -> Vec<(u64, f64, f64)> {
+        crate::export::sorted_particles(simulation.world())
+            .iter()
+            .map(|particle| (particle.id, particle.position.0, particle.position.1))
+            .collect()
+    }
+
+    #[test]
+    fn test_resumed_run_matches_original() {
+        let mut original = Simulation::with_seed(7);
+        original.spawn_random(20);
+        for _ in 0..50 {
+            original.step(1.0).unwrap();
+        }
+        let bytes = original.checkpoint().to_bytes().unwrap();
+        let mut resumed = Simulation::from_checkpoint(Checkpoint::from_bytes(&bytes).unwrap());
+        assert_eq!(resumed.frame(), 50);
+
+        for _ in 0..50 {
+            original.step(1.0).unwrap();
+            resumed.step(1.0).unwrap();
+        }
+        // The RNG state is restored too, so new random particles match as well
+        original.spawn_random(3);
+        resumed.spawn_random(3);
+        assert_eq!(positions(&original), positions(&resumed));
+    }
+
+    #[test]
+    fn test_directory_keeps_newest_checkpoints() {
+        let dir = std::env::temp_dir().join(format!("checkpoints-{}", std::process::id()));
+        let mut simulation = Simulation::with_seed(1);
+        simulation.spawn_random(2);
+        for _ in 0..3 {
+            simulation.step(1.0).unwrap();
+            simulation.checkpoint().save_to_dir(&dir).unwrap();
+        }
+
+        let latest = latest_checkpoint(&dir).unwrap().unwrap();
+        assert_eq!(Checkpoint::load(&latest).unwrap().frame(), 3);
+        assert_eq!(list_checkpoints(&dir).unwrap().len(), CHECKPOINTS_KEPT);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_garbage_is_rejected() {
+        assert!(matches!(
+            Checkpoint::from_bytes(b"not a checkpoint"),
+            Err(SimError::Checkpoint(_))
+        ));
+    }
+}
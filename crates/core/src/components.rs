@@ -0,0 +1,31 @@
+// Every simulated body carries a `Particle` component (id, radius, mass,
+// position and velocity), because every system needs those on every frame.
+// Anything optional lives in its own component below, so that a feature
+// only costs memory on the entities that actually use it.
+
+use serde::{Deserialize, Serialize};
+
+/// Electric charge carried by a particle.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Charge(pub f64);
+
+/// Which kind of particle this is, for particles that come in several kinds.
+/// A particle without one counts as species 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Species(pub u32);
+
+/// Multiplies gravity for this particle alone, overriding the scale of its
+/// species. Negative values make it fall upwards.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GravityScale(pub f64);
+
+/// Remaining simulated time before the particle is despawned.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Lifetime(pub f64);
+
+/// Everything a renderer needs to know about how to draw a particle.
+/// The color is RGBA, each channel between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Appearance {
+    pub color: [f32; 4],
+}
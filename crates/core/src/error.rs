@@ -0,0 +1,82 @@
+use std::fmt;
+use std::io;
+
+/// Every fallible operation in the crate reports one of these,
+/// so that `main` can print a friendly message instead of panicking.
+#[derive(Debug)]
+pub enum SimError {
+    /// A command line argument could not be understood.
+    InvalidArgument { name: String, value: String },
+    /// A collision referred to a particle index that does not exist.
+    MissingParticle(usize),
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// A checkpoint could not be encoded, or a file is not a valid checkpoint.
+    Checkpoint(String),
+    /// An obstacle map file could not be read.
+    InvalidMap(String),
+    /// The paranoid checker found the simulation in an impossible state.
+    /// `dump` holds every particle as CSV, for attaching to a bug report.
+    InvariantViolated {
+        frame: u64,
+        reason: String,
+        dump: String,
+    },
+}
+
+pub type SimResult<T> = Result<T, SimError>;
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimError::InvalidArgument { name, value } => {
+                write!(f, "invalid value '{}' for {}", value, name)
+            }
+            SimError::MissingParticle(index) => {
+                write!(f, "no particle exists at index {}", index)
+            }
+            SimError::Io(err) => write!(f, "I/O error: {}", err),
+            SimError::Checkpoint(reason) => write!(f, "bad checkpoint: {}", reason),
+            SimError::InvalidMap(reason) => write!(f, "bad obstacle map: {}", reason),
+            SimError::InvariantViolated { frame, reason, .. } => {
+                write!(f, "invariant violated at frame {}: {}", frame, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SimError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SimError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SimError {
+    fn from(err: io::Error) -> Self {
+        SimError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_is_friendly() {
+        let err = SimError::InvalidArgument {
+            name: "particle count".to_string(),
+            value: "lots".to_string(),
+        };
+        assert_eq!(err.to_string(), "invalid value 'lots' for particle count");
+    }
+
+    #[test]
+    fn test_from_io_error() {
+        let err: SimError = io::Error::new(io::ErrorKind::NotFound, "gone").into();
+        assert!(matches!(err, SimError::Io(_)));
+        assert!(std::error::Error::source(&err).is_some());
+    }
+}
@@ -0,0 +1,393 @@
+use crate::error::{SimError, SimResult};
+use crate::particle::{Particle, HEIGHT, WIDTH};
+use crate::settings::Walls;
+use crate::shapes::{Body, Contact};
+use crate::simulation::Simulation;
+use crate::utils::Vec2;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+/// What a particle is about to run into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    Particle(usize),
+    /// The wall across this axis, 0 for the left and right walls and 1 for the
+    /// floor and the ceiling.
+    Wall(usize),
+}
+
+/// A collision predicted to happen at `time`, unless one of the particles involved
+/// collides with something else first. `counts` are the collision counts of the
+/// particles when the prediction was made, so that a stale prediction is noticed
+/// when it comes up.
+#[derive(Debug, Clone, Copy)]
+struct Prediction {
+    time: f64,
+    particle: usize,
+    target: Target,
+    counts: (u64, u64),
+}
+
+impl PartialEq for Prediction {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Prediction {}
+
+impl PartialOrd for Prediction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Prediction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.time.total_cmp(&other.time)
+    }
+}
+
+/// A hard sphere gas advanced from one collision to the next instead of in steps
+/// of fixed length. The time of every collision is solved for exactly, so particles
+/// never overlap and never miss each other, however fast they move. That makes it
+/// the reference to check the stepped `Simulation` against, see `Comparison`.
+///
+/// Only elastic circles in an empty box with elastic walls are supported, as there
+/// is nothing but straight lines between the collisions.
+#[derive(Debug, Clone)]
+pub struct EventDriven {
+    particles: Vec<Particle>,
+    time: f64,
+    queue: BinaryHeap<Reverse<Prediction>>,
+    /// How many collisions every particle has taken part in.
+    counts: Vec<u64>,
+    collisions: u64,
+    wall_bounces: u64,
+}
+
+impl EventDriven {
+    pub fn new(particles: Vec<Particle>) -> Self {
+        let counts = vec![0; particles.len()];
+        let mut simulation = EventDriven {
+            particles,
+            time: 0.0,
+            queue: BinaryHeap::new(),
+            counts,
+            collisions: 0,
+            wall_bounces: 0,
+        };
+        for index in 0..simulation.particles.len() {
+            simulation.predict(index);
+        }
+        simulation
+    }
+
+    pub fn from_simulation(simulation: &Simulation) -> SimResult<Self> {
+        //! Start from the particles of a stepped simulation, which must not use
+        //! anything but hard circles and elastic walls.
+        let settings = simulation.settings();
+        let unsupported = if settings.gravity != Vec2::ZERO || settings.mutual_gravity.is_some() {
+            Some("gravity")
+        } else if settings.walls != Walls::default() {
+            Some("inelastic walls")
+        } else if settings.max_speed.is_some() {
+            Some("a maximum speed")
+        } else if !simulation.obstacles().is_empty() {
+            Some("obstacles")
+        } else if !simulation.portals().is_empty() {
+            Some("portals")
+        } else if !simulation.time_regions().is_empty() {
+            Some("time regions")
+        } else if simulation.world().query::<&Body>().iter().next().is_some() {
+            Some("shapes")
+        } else {
+            None
+        };
+        if let Some(feature) = unsupported {
+            return Err(SimError::InvalidArgument {
+                name: "the event-driven simulation".to_string(),
+                value: feature.to_string(),
+            });
+        }
+        let mut particles: Vec<Particle> = simulation
+            .world()
+            .query::<&Particle>()
+            .iter()
+            .map(|(_, particle)| *particle)
+            .collect();
+        particles.sort_by_key(|particle| particle.id);
+        Ok(EventDriven::new(particles))
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        //! Every particle as it is at `time`, in order of id.
+        &self.particles
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn collisions(&self) -> u64 {
+        //! How many times two particles have collided so far.
+        self.collisions
+    }
+
+    pub fn wall_bounces(&self) -> u64 {
+        self.wall_bounces
+    }
+
+    pub fn kinetic_energy(&self) -> f64 {
+        self.particles.iter().map(Particle::kinetic_energy).sum()
+    }
+
+    pub fn advance(&mut self, duration: f64) {
+        //! Resolve every collision in the next `duration` units of time, in the
+        //! order they happen, and move the particles on to the end of it.
+        let end = self.time + duration;
+        while let Some(Reverse(next)) = self.queue.peek().copied() {
+            if next.time > end {
+                break;
+            }
+            self.queue.pop();
+            if !self.is_current(&next) {
+                continue;
+            }
+            self.drift(next.time);
+            self.collide(next.particle, next.target);
+            self.predict(next.particle);
+            if let Target::Particle(other) = next.target {
+                self.predict(other);
+            }
+        }
+        self.drift(end);
+    }
+
+    fn is_current(&self, prediction: &Prediction) -> bool {
+        //! Whether neither particle has collided since the prediction was made.
+        let other = match prediction.target {
+            Target::Particle(other) => self.counts[other],
+            Target::Wall(_) => 0,
+        };
+        (self.counts[prediction.particle], other) == prediction.counts
+    }
+
+    fn drift(&mut self, time: f64) {
+        let dt = time - self.time;
+        for particle in self.particles.iter_mut().filter(|particle| !particle.fixed) {
+            particle.position += dt * particle.velocity;
+        }
+        self.time = time;
+    }
+
+    fn collide(&mut self, index: usize, target: Target) {
+        match target {
+            Target::Particle(other) => {
+                let (a, b) = (self.particles[index], self.particles[other]);
+                let offset = b.position - a.position;
+                // They touch, so the contact is at the surface of the first
+                let normal = (1.0 / offset.length()) * offset;
+                let contact = Contact {
+                    normal,
+                    penetration_depth: 0.0,
+                    point: a.position + a.radius * normal,
+                };
+                let (va, vb) = a.collision_react(&b, &contact);
+                self.particles[index].velocity = va;
+                self.particles[other].velocity = vb;
+                // A fixed particle does not move, so the hits already predicted
+                // on it still happen
+                if !b.fixed {
+                    self.counts[other] += 1;
+                }
+                self.collisions += 1;
+            }
+            Target::Wall(axis) => {
+                self.particles[index].velocity[axis] *= -1.0;
+                self.wall_bounces += 1;
+            }
+        }
+        self.counts[index] += 1;
+    }
+
+    fn predict(&mut self, index: usize) {
+        //! Queue the next collision of the particle with every other particle and
+        //! with the walls, assuming nothing gets in the way.
+        let particle = self.particles[index];
+        if particle.fixed {
+            return;
+        }
+        for (other, target) in self.particles.iter().enumerate() {
+            if other == index
+                || particle
+                    .effective_mass()
+                    .min(target.effective_mass())
+                    .is_infinite()
+            {
+                continue;
+            }
+            if let Some(dt) = time_to_hit(&particle, target) {
+                self.queue.push(Reverse(Prediction {
+                    time: self.time + dt,
+                    particle: index,
+                    target: Target::Particle(other),
+                    counts: (self.counts[index], self.counts[other]),
+                }));
+            }
+        }
+        for (axis, size) in [(0, WIDTH), (1, HEIGHT)] {
+            let (position, velocity) = (particle.position[axis], particle.velocity[axis]);
+            let distance = if velocity > 0.0 {
+                size - particle.radius - position
+            } else if velocity < 0.0 {
+                particle.radius - position
+            } else {
+                continue;
+            };
+            self.queue.push(Reverse(Prediction {
+                time: self.time + (distance / velocity).max(0.0),
+                particle: index,
+                target: Target::Wall(axis),
+                counts: (self.counts[index], 0),
+            }));
+        }
+    }
+}
+
+fn time_to_hit(a: &Particle, b: &Particle) -> Option<f64> {
+    //! How long until the two particles touch, if they are heading towards each
+    //! other closely enough to. Particles that already overlap and are still
+    //! approaching collide straight away, like they would in a stepped simulation.
+    let offset = b.position - a.position;
+    let closing = b.velocity - a.velocity;
+    let approach = offset.inner_product(&closing);
+    if approach >= 0.0 {
+        return None;
+    }
+    let speed = closing.inner_product(&closing);
+    let reach = a.radius + b.radius;
+    // The smaller root of |offset + t closing| = reach
+    let discriminant =
+        approach * approach - speed * (offset.inner_product(&offset) - reach * reach);
+    if discriminant < 0.0 {
+        return None;
+    }
+    Some((-(approach + discriminant.sqrt()) / speed).max(0.0))
+}
+
+/// How far a stepped simulation has strayed from an event-driven one that started
+/// from the same particles. Collisions are chaotic, so the particles soon go their
+/// separate ways whatever the step, but the smaller the step the longer the two
+/// agree, and the energy should match throughout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    pub time: f64,
+    /// The root mean square distance between the two copies of every particle.
+    pub rms_distance: f64,
+    pub max_distance: f64,
+    pub exact_energy: f64,
+    pub stepped_energy: f64,
+}
+
+impl Comparison {
+    pub fn measure(exact: &EventDriven, stepped: &Simulation) -> Self {
+        //! Compare the particles with the same ids. Particles that are missing from
+        //! either simulation are left out.
+        let mut squares = 0.0;
+        let mut max_distance: f64 = 0.0;
+        let mut count = 0;
+        for (_, particle) in stepped.world().query::<&Particle>().iter() {
+            let Ok(index) = exact
+                .particles
+                .binary_search_by_key(&particle.id, |particle| particle.id)
+            else {
+                continue;
+            };
+            let distance = particle.position.distance(&exact.particles[index].position);
+            squares += distance * distance;
+            max_distance = max_distance.max(distance);
+            count += 1;
+        }
+        Comparison {
+            time: exact.time,
+            rms_distance: if count == 0 {
+                0.0
+            } else {
+                (squares / count as f64).sqrt()
+            },
+            max_distance,
+            exact_energy: exact.kinetic_energy(),
+            stepped_energy: stepped.kinetic_energy(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::SpawnPattern;
+
+    #[test]
+    fn test_collisions_happen_at_the_exact_time() {
+        // 60 apart and closing at 3 per unit of time, so they touch after 20
+        let mut simulation = EventDriven::new(vec![
+            Particle::new(0, 20.0, Vec2(400.0, 500.0), Vec2(2.0, 0.0)),
+            Particle::new(1, 20.0, Vec2(500.0, 500.0), Vec2(-1.0, 0.0)),
+        ]);
+        simulation.advance(19.9);
+        assert_eq!(simulation.collisions(), 0);
+        simulation.advance(10.1);
+        assert_eq!(simulation.collisions(), 1);
+        // Equal masses swap velocities, at the positions they touched at
+        let [a, b] = simulation.particles() else {
+            unreachable!()
+        };
+        assert!(a.position.distance(&Vec2(430.0, 500.0)) < 1e-9);
+        assert!(b.position.distance(&Vec2(500.0, 500.0)) < 1e-9);
+        assert!(a.velocity.distance(&Vec2(-1.0, 0.0)) < 1e-12);
+        assert!(b.velocity.distance(&Vec2(2.0, 0.0)) < 1e-12);
+    }
+
+    #[test]
+    fn test_small_steps_agree_with_the_exact_simulation() {
+        let compare = |dt: f64| {
+            let mut stepped = Simulation::with_seed(3);
+            stepped.spawn_pattern(SpawnPattern::Grid, 30).unwrap();
+            let mut exact = EventDriven::from_simulation(&stepped).unwrap();
+            for _ in 0..(5.0 / dt).round() as usize {
+                stepped.step(dt).unwrap();
+            }
+            exact.advance(5.0);
+            assert!(exact.collisions() > 5);
+            (Comparison::measure(&exact, &stepped), stepped)
+        };
+        let (coarse, _) = compare(0.1);
+        let (fine, mut stepped) = compare(0.01);
+        assert!(fine.max_distance < 1.0, "{:?}", fine);
+        assert!(fine.max_distance < coarse.max_distance);
+        assert!((fine.exact_energy / fine.stepped_energy - 1.0).abs() < 1e-9);
+
+        stepped.settings_mut().max_speed = Some(5.0);
+        assert!(EventDriven::from_simulation(&stepped).is_err());
+    }
+
+    #[test]
+    fn test_a_pinned_particle_is_hit_by_everything_heading_for_it() {
+        // One hit from below after 30, the other from above after 60
+        let mut simulation = EventDriven::new(vec![
+            Particle::new(0, 10.0, Vec2(500.0, 500.0), Vec2::ZERO).pinned(),
+            Particle::new(1, 10.0, Vec2(500.0, 420.0), Vec2(0.0, 2.0)),
+            Particle::new(2, 10.0, Vec2(500.0, 640.0), Vec2(0.0, -2.0)),
+        ]);
+        simulation.advance(80.0);
+        assert_eq!(simulation.collisions(), 2);
+        let [pinned, below, above] = simulation.particles() else {
+            unreachable!()
+        };
+        assert_eq!(pinned.position, Vec2(500.0, 500.0));
+        assert!(below.velocity.distance(&Vec2(0.0, -2.0)) < 1e-12);
+        assert!(above.velocity.distance(&Vec2(0.0, 2.0)) < 1e-12);
+        assert!(above.position.distance(&Vec2(500.0, 560.0)) < 1e-9);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::shapes::Contact;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Something that happened during a step, for code outside of the simulation to
+/// react to, such as drawing a spark where two particles hit each other.
+/// `Simulation::events` holds the events of the latest step.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// The particles with ids `a` and `b` overlapped, and the collision response
+    /// was applied to them. The contact normal points from `a` into `b`, and
+    /// `impulse` is the size of the momentum they exchanged.
+    Contact {
+        a: u64,
+        b: u64,
+        contact: Contact,
+        impulse: f64,
+    },
+}
+
+/// The pairs of particles that were in contact during the latest step, and for how
+/// many steps in a row they have been. A pair that stays in contact, such as one
+/// particle resting on another, is a persistent contact.
+///
+/// The collision response resolves every pair exactly, in a single pass, so there is
+/// no iterative solver whose impulses could be warm started from the previous step.
+/// The cache is what such a solver would start from.
+#[derive(Debug, Clone, Default)]
+pub struct ContactCache {
+    /// Steps in contact, keyed by the pair of ids, lower id first.
+    pairs: HashMap<(u64, u64), u32>,
+}
+
+impl ContactCache {
+    pub fn new() -> Self {
+        ContactCache::default()
+    }
+
+    fn key(a: u64, b: u64) -> (u64, u64) {
+        (a.min(b), a.max(b))
+    }
+
+    pub fn update(&mut self, events: &[Event]) {
+        //! Replace the pairs with the contacts of the latest step, counting
+        //! up the pairs that were already in contact the step before.
+        let mut pairs = HashMap::with_capacity(events.len());
+        for event in events {
+            match event {
+                Event::Contact { a, b, .. } => {
+                    let key = ContactCache::key(*a, *b);
+                    let steps = self.pairs.get(&key).copied().unwrap_or(0) + 1;
+                    pairs.insert(key, steps);
+                }
+            }
+        }
+        self.pairs = pairs;
+    }
+
+    pub fn forget(&mut self, id: u64) {
+        //! Drop every pair with a particle that has been removed.
+        self.pairs.retain(|(a, b), _| *a != id && *b != id);
+    }
+
+    pub fn steps_in_contact(&self, a: u64, b: u64) -> u32 {
+        //! How many steps in a row the two particles have been in contact,
+        //! 0 if they were not during the latest step.
+        self.pairs
+            .get(&ContactCache::key(a, b))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    pub fn persistent(&self) -> usize {
+        //! The number of pairs that were also in contact the step before.
+        self.pairs.values().filter(|steps| **steps > 1).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Vec2;
+
+    fn contact(a: u64, b: u64) -> Event {
+        Event::Contact {
+            a,
+            b,
+            contact: Contact {
+                normal: Vec2(1.0, 0.0),
+                penetration_depth: 1.0,
+                point: Vec2::ZERO,
+            },
+            impulse: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_persistent_contacts_are_counted() {
+        let mut cache = ContactCache::new();
+        cache.update(&[contact(1, 2), contact(3, 4)]);
+        cache.update(&[contact(2, 1), contact(5, 6)]);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.steps_in_contact(1, 2), 2);
+        assert_eq!(cache.steps_in_contact(3, 4), 0);
+        assert_eq!(cache.persistent(), 1);
+        cache.update(&[]);
+        assert!(cache.is_empty());
+    }
+}
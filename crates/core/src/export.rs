@@ -0,0 +1,155 @@
+mod msd;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod probes;
+mod rdf;
+
+pub use self::msd::MsdExporter;
+pub use self::probes::{Probe, ProbeExporter, ProbeReading};
+pub use self::rdf::{radial_distribution, RdfExporter};
+
+#[cfg(feature = "parquet")]
+pub use self::parquet::ParquetTrajectoryExporter;
+
+use crate::error::SimResult;
+use crate::particle::Particle;
+use hecs::World;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Something that records the state of the simulation after steps.
+/// Exporters are attached with `Simulation::add_exporter`, and are
+/// handed the world after every step.
+pub trait Exporter: Send {
+    fn record(&mut self, frame: u64, world: &World) -> SimResult<()>;
+
+    /// Write out anything still buffered. Called when the simulation is done.
+    fn finish(&mut self) -> SimResult<()> {
+        Ok(())
+    }
+}
+
+/// Every particle, sorted by id, so that exported rows are stable between runs.
+pub fn sorted_particles(world: &World) -> Vec<Particle> {
+    let mut particles: Vec<Particle> = world
+        .query::<&Particle>()
+        .iter()
+        .map(|(_, particle)| *particle)
+        .collect();
+    particles.sort_by_key(|particle| particle.id);
+    particles
+}
+
+/// Pick an exporter for `path` from its extension: `.parquet` files are written
+/// as Parquet when the `parquet` feature is enabled, anything else as CSV.
+pub fn create_exporter(path: &Path, every: u64) -> SimResult<Box<dyn Exporter>> {
+    let is_parquet = path.extension().is_some_and(|ext| ext == "parquet");
+    if !is_parquet {
+        return Ok(Box::new(CsvTrajectoryExporter::create(path, every)?));
+    }
+    #[cfg(feature = "parquet")]
+    {
+        Ok(Box::new(ParquetTrajectoryExporter::create(path, every)?))
+    }
+    #[cfg(not(feature = "parquet"))]
+    {
+        Err(crate::error::SimError::InvalidArgument {
+            name: "trajectory export (built without the parquet feature)".to_string(),
+            value: path.display().to_string(),
+        })
+    }
+}
+
+/// Appends a `frame,id,x,y,vx,vy` row per particle, every `every` frames.
+pub struct CsvTrajectoryExporter {
+    writer: Box<dyn Write + Send>,
+    every: u64,
+}
+
+impl CsvTrajectoryExporter {
+    pub fn create(path: &Path, every: u64) -> SimResult<Self> {
+        let file = File::create(path)?;
+        CsvTrajectoryExporter::new(Box::new(BufWriter::new(file)), every)
+    }
+
+    pub fn new(mut writer: Box<dyn Write + Send>, every: u64) -> SimResult<Self> {
+        //! Write the header to `writer`. An interval of 0 is treated as 1.
+        writeln!(writer, "frame,id,x,y,vx,vy")?;
+        Ok(CsvTrajectoryExporter {
+            writer,
+            every: every.max(1),
+        })
+    }
+}
+
+impl Exporter for CsvTrajectoryExporter {
+    fn record(&mut self, frame: u64, world: &World) -> SimResult<()> {
+        if !frame.is_multiple_of(self.every) {
+            return Ok(());
+        }
+        for particle in sorted_particles(world) {
+            writeln!(
+                self.writer,
+                "{},{},{},{},{},{}",
+                frame,
+                particle.id,
+                particle.position.0,
+                particle.position.1,
+                particle.velocity.0,
+                particle.velocity.1
+            )?;
+        }
+        // The window can be closed at any moment, so never leave a sample half written
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> SimResult<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Vec2;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_csv_rows_are_sampled() {
+        let buffer = SharedBuffer::default();
+        let mut exporter = CsvTrajectoryExporter::new(Box::new(buffer.clone()), 2).unwrap();
+        let mut world = World::new();
+        world.spawn((Particle::new(2, 1.0, Vec2(3.0, 4.0), Vec2(0.5, -0.5)),));
+        world.spawn((Particle::new(1, 1.0, Vec2(1.0, 2.0), Vec2(0.0, 0.0)),));
+
+        for frame in 0..3 {
+            exporter.record(frame, &world).unwrap();
+        }
+
+        let csv = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            csv,
+            "frame,id,x,y,vx,vy\n\
+             0,1,1,2,0,0\n\
+             0,2,3,4,0.5,-0.5\n\
+             2,1,1,2,0,0\n\
+             2,2,3,4,0.5,-0.5\n"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use super::Exporter;
+use crate::error::SimResult;
+use crate::particle::Particle;
+use crate::utils::Vec2;
+use hecs::World;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Tracks how far every particle has moved from where it was first seen, and
+/// appends a `frame,msd` row with the mean squared displacement every `every`
+/// frames. When the simulation is done, the diffusion coefficient fitted to the
+/// rows is added as a final `# diffusion_coefficient=<D>` comment.
+///
+/// In two dimensions the MSD of a diffusing gas grows as `4 D t`. In a closed box
+/// it levels off once particles have crossed it, so the fit is only meaningful
+/// for runs that end before then.
+pub struct MsdExporter {
+    writer: Box<dyn Write + Send>,
+    every: u64,
+    origins: HashMap<u64, Vec2>,
+    first_frame: Option<u64>,
+    /// Every row written so far, as (frames since the first, MSD).
+    samples: Vec<(f64, f64)>,
+}
+
+impl MsdExporter {
+    pub fn create(path: &Path, every: u64) -> SimResult<Self> {
+        let file = File::create(path)?;
+        MsdExporter::new(Box::new(BufWriter::new(file)), every)
+    }
+
+    pub fn new(mut writer: Box<dyn Write + Send>, every: u64) -> SimResult<Self> {
+        //! Write the header to `writer`. An interval of 0 is treated as 1.
+        writeln!(writer, "frame,msd")?;
+        Ok(MsdExporter {
+            writer,
+            every: every.max(1),
+            origins: HashMap::new(),
+            first_frame: None,
+            samples: Vec::new(),
+        })
+    }
+
+    pub fn diffusion_coefficient(&self) -> Option<f64> {
+        //! A quarter of the slope of the least squares line through the samples,
+        //! or `None` until there are at least two of them.
+        let count = self.samples.len() as f64;
+        if self.samples.len() < 2 {
+            return None;
+        }
+        let mean_t = self.samples.iter().map(|(t, _)| t).sum::<f64>() / count;
+        let mean_msd = self.samples.iter().map(|(_, msd)| msd).sum::<f64>() / count;
+        let (covariance, variance) =
+            self.samples
+                .iter()
+                .fold((0.0, 0.0), |(cov, var), (t, msd)| {
+                    (
+                        cov + (t - mean_t) * (msd - mean_msd),
+                        var + (t - mean_t).powi(2),
+                    )
+                });
+        Some(covariance / variance / 4.0)
+    }
+}
+
+impl Exporter for MsdExporter {
+    fn record(&mut self, frame: u64, world: &World) -> SimResult<()> {
+        let first_frame = *self.first_frame.get_or_insert(frame);
+        let mut total = 0.0;
+        let mut count = 0;
+        for (_, particle) in world.query::<&Particle>().iter() {
+            // Pinned particles never move, and would only dilute the mean
+            if particle.fixed {
+                continue;
+            }
+            let origin = *self.origins.entry(particle.id).or_insert(particle.position);
+            total += (particle.position - origin).length().powi(2);
+            count += 1;
+        }
+        if !(frame - first_frame).is_multiple_of(self.every) || count == 0 {
+            return Ok(());
+        }
+        let msd = total / count as f64;
+        self.samples.push(((frame - first_frame) as f64, msd));
+        writeln!(self.writer, "{},{}", frame, msd)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> SimResult<()> {
+        if let Some(coefficient) = self.diffusion_coefficient() {
+            tracing::info!(coefficient, "fitted diffusion coefficient");
+            writeln!(self.writer, "# diffusion_coefficient={}", coefficient)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_msd_of_steady_motion() {
+        let buffer = SharedBuffer::default();
+        let mut exporter = MsdExporter::new(Box::new(buffer.clone()), 2).unwrap();
+        let mut world = World::new();
+        let moving = world.spawn((Particle::new(1, 1.0, Vec2(10.0, 10.0), Vec2::ZERO),));
+        world.spawn((Particle::new(2, 1.0, Vec2(50.0, 50.0), Vec2::ZERO).pinned(),));
+
+        for frame in 0..5 {
+            world.get::<&mut Particle>(moving).unwrap().position = Vec2(10.0 + frame as f64, 10.0);
+            exporter.record(frame, &world).unwrap();
+        }
+        exporter.finish().unwrap();
+
+        // Moving one unit per frame, the MSD is the square of the frame
+        let csv = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[..4], ["frame,msd", "0,0", "2,4", "4,16"]);
+        // The slope of the best line through (0, 0), (2, 4) and (4, 16) is 4
+        assert_eq!(exporter.diffusion_coefficient(), Some(1.0));
+        assert_eq!(lines[4], "# diffusion_coefficient=1");
+    }
+}
@@ -0,0 +1,158 @@
+use super::{sorted_particles, Exporter};
+use crate::error::{SimError, SimResult};
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use hecs::World;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// How many rows are buffered before they are written out as a row group.
+pub const DEFAULT_CHUNK_ROWS: usize = 64 * 1024;
+
+/// Writes the same `frame,id,x,y,vx,vy` columns as the CSV exporter to a Parquet file.
+/// Rows are buffered and written a row group at a time, so memory use stays
+/// bounded no matter how long the run is.
+pub struct ParquetTrajectoryExporter {
+    writer: ArrowWriter<File>,
+    schema: Arc<Schema>,
+    every: u64,
+    chunk_rows: usize,
+    columns: Columns,
+}
+
+#[derive(Default)]
+struct Columns {
+    frame: Vec<u64>,
+    id: Vec<u64>,
+    x: Vec<f64>,
+    y: Vec<f64>,
+    vx: Vec<f64>,
+    vy: Vec<f64>,
+}
+
+impl Columns {
+    fn len(&self) -> usize {
+        self.frame.len()
+    }
+
+    fn take(&mut self, schema: &Arc<Schema>) -> Result<RecordBatch, ParquetError> {
+        let columns = std::mem::take(self);
+        let arrays: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from(columns.frame)),
+            Arc::new(UInt64Array::from(columns.id)),
+            Arc::new(Float64Array::from(columns.x)),
+            Arc::new(Float64Array::from(columns.y)),
+            Arc::new(Float64Array::from(columns.vx)),
+            Arc::new(Float64Array::from(columns.vy)),
+        ];
+        Ok(RecordBatch::try_new(Arc::clone(schema), arrays)?)
+    }
+}
+
+fn to_sim_error(err: ParquetError) -> SimError {
+    SimError::Io(io::Error::other(err))
+}
+
+impl ParquetTrajectoryExporter {
+    pub fn create(path: &Path, every: u64) -> SimResult<Self> {
+        ParquetTrajectoryExporter::with_chunk_rows(path, every, DEFAULT_CHUNK_ROWS)
+    }
+
+    pub fn with_chunk_rows(path: &Path, every: u64, chunk_rows: usize) -> SimResult<Self> {
+        //! An interval of 0 is treated as 1, as is a chunk size of 0.
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("frame", DataType::UInt64, false),
+            Field::new("id", DataType::UInt64, false),
+            Field::new("x", DataType::Float64, false),
+            Field::new("y", DataType::Float64, false),
+            Field::new("vx", DataType::Float64, false),
+            Field::new("vy", DataType::Float64, false),
+        ]));
+        let chunk_rows = chunk_rows.max(1);
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_row_count(Some(chunk_rows))
+            .build();
+        let file = File::create(path)?;
+        let writer = ArrowWriter::try_new(file, Arc::clone(&schema), Some(properties))
+            .map_err(to_sim_error)?;
+        Ok(ParquetTrajectoryExporter {
+            writer,
+            schema,
+            every: every.max(1),
+            chunk_rows,
+            columns: Columns::default(),
+        })
+    }
+
+    fn write_chunk(&mut self) -> SimResult<()> {
+        if self.columns.len() == 0 {
+            return Ok(());
+        }
+        let batch = self.columns.take(&self.schema).map_err(to_sim_error)?;
+        self.writer.write(&batch).map_err(to_sim_error)?;
+        // Close the row group so the writer does not keep its own copy of the rows
+        self.writer.flush().map_err(to_sim_error)
+    }
+}
+
+impl Exporter for ParquetTrajectoryExporter {
+    fn record(&mut self, frame: u64, world: &World) -> SimResult<()> {
+        if !frame.is_multiple_of(self.every) {
+            return Ok(());
+        }
+        for particle in sorted_particles(world) {
+            self.columns.frame.push(frame);
+            self.columns.id.push(particle.id);
+            self.columns.x.push(particle.position.0);
+            self.columns.y.push(particle.position.1);
+            self.columns.vx.push(particle.velocity.0);
+            self.columns.vy.push(particle.velocity.1);
+        }
+        if self.columns.len() >= self.chunk_rows {
+            self.write_chunk()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> SimResult<()> {
+        self.write_chunk()?;
+        self.writer.finish().map_err(to_sim_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+    use crate::utils::Vec2;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    #[test]
+    fn test_rows_are_written_in_chunks() {
+        let path =
+            std::env::temp_dir().join(format!("trajectories-{}.parquet", std::process::id()));
+        let mut exporter = ParquetTrajectoryExporter::with_chunk_rows(&path, 1, 4).unwrap();
+        let mut world = World::new();
+        world.spawn((Particle::new(0, 1.0, Vec2(1.0, 2.0), Vec2(0.0, 0.0)),));
+        world.spawn((Particle::new(1, 1.0, Vec2(3.0, 4.0), Vec2(0.5, -0.5)),));
+
+        for frame in 0..5 {
+            exporter.record(frame, &world).unwrap();
+        }
+        exporter.finish().unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 10);
+        assert_eq!(metadata.num_row_groups(), 3);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+use super::Exporter;
+use crate::error::{SimError, SimResult};
+use crate::particle::Particle;
+use crate::utils::Vec2;
+use hecs::World;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// A named, axis aligned rectangle of the box whose contents are measured.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Probe {
+    pub name: String,
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+/// What a probe measured in one frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProbeReading {
+    pub count: usize,
+    /// Particles per unit of area.
+    pub density: f64,
+    pub mean_speed: f64,
+    /// The mean kinetic energy of the particles' motion relative to their mean
+    /// velocity, so that a region drifting as a whole is not mistaken for a hot one.
+    pub temperature: f64,
+}
+
+impl Probe {
+    pub fn validate(&self) -> SimResult<()> {
+        //! Refuse a rectangle with no area.
+        if self.min_x < self.max_x && self.min_y < self.max_y {
+            Ok(())
+        } else {
+            Err(SimError::InvalidArgument {
+                name: "probe region".to_string(),
+                value: self.name.clone(),
+            })
+        }
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        (self.min_x..self.max_x).contains(&point.0) && (self.min_y..self.max_y).contains(&point.1)
+    }
+
+    pub fn read(&self, world: &World) -> ProbeReading {
+        //! Measure the moving particles whose centers are inside the rectangle.
+        let inside: Vec<Particle> = world
+            .query::<&Particle>()
+            .iter()
+            .map(|(_, particle)| *particle)
+            .filter(|particle| {
+                !particle.fixed && particle.mass.is_finite() && self.contains(particle.position)
+            })
+            .collect();
+        let count = inside.len();
+        let area = (self.max_x - self.min_x) * (self.max_y - self.min_y);
+        if count == 0 {
+            return ProbeReading {
+                count,
+                density: 0.0,
+                mean_speed: 0.0,
+                temperature: 0.0,
+            };
+        }
+
+        let mass: f64 = inside.iter().map(|particle| particle.mass).sum();
+        let momentum = inside.iter().fold(Vec2::ZERO, |total, particle| {
+            total + particle.mass * particle.velocity
+        });
+        let drift = (1.0 / mass) * momentum;
+        let thermal: f64 = inside
+            .iter()
+            .map(|particle| 0.5 * particle.mass * (particle.velocity - drift).length().powi(2))
+            .sum();
+        ProbeReading {
+            count,
+            density: count as f64 / area,
+            mean_speed: inside
+                .iter()
+                .map(|particle| particle.velocity.length())
+                .sum::<f64>()
+                / count as f64,
+            temperature: thermal / count as f64,
+        }
+    }
+}
+
+/// Appends a `frame,probe,count,density,mean_speed,temperature` row per probe
+/// every `every` frames.
+pub struct ProbeExporter {
+    writer: Box<dyn Write + Send>,
+    every: u64,
+    probes: Vec<Probe>,
+}
+
+impl ProbeExporter {
+    pub fn create(path: &Path, every: u64, probes: Vec<Probe>) -> SimResult<Self> {
+        let file = File::create(path)?;
+        ProbeExporter::new(Box::new(BufWriter::new(file)), every, probes)
+    }
+
+    pub fn new(
+        mut writer: Box<dyn Write + Send>,
+        every: u64,
+        probes: Vec<Probe>,
+    ) -> SimResult<Self> {
+        //! Write the header to `writer`. An interval of 0 is treated as 1.
+        for probe in &probes {
+            probe.validate()?;
+        }
+        writeln!(writer, "frame,probe,count,density,mean_speed,temperature")?;
+        Ok(ProbeExporter {
+            writer,
+            every: every.max(1),
+            probes,
+        })
+    }
+}
+
+impl Exporter for ProbeExporter {
+    fn record(&mut self, frame: u64, world: &World) -> SimResult<()> {
+        if !frame.is_multiple_of(self.every) {
+            return Ok(());
+        }
+        for probe in &self.probes {
+            let reading = probe.read(world);
+            writeln!(
+                self.writer,
+                "{},{},{},{},{},{}",
+                frame,
+                probe.name,
+                reading.count,
+                reading.density,
+                reading.mean_speed,
+                reading.temperature
+            )?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> SimResult<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probes_measure_their_region() {
+        let mut world = World::new();
+        world.spawn((Particle::new(1, 1.0, Vec2(10.0, 10.0), Vec2(3.0, 0.0)),));
+        world.spawn((Particle::new(2, 1.0, Vec2(20.0, 10.0), Vec2(1.0, 0.0)),));
+        world.spawn((Particle::new(3, 1.0, Vec2(500.0, 10.0), Vec2(9.0, 0.0)),));
+        let probe = Probe {
+            name: "left".to_string(),
+            min_x: 0.0,
+            min_y: 0.0,
+            max_x: 100.0,
+            max_y: 50.0,
+        };
+
+        let reading = probe.read(&world);
+        assert_eq!(reading.count, 2);
+        assert_eq!(reading.density, 2.0 / 5000.0);
+        assert_eq!(reading.mean_speed, 2.0);
+        // Both move at 1 relative to their mean velocity of 2
+        let mass = Particle::new(1, 1.0, Vec2::ZERO, Vec2::ZERO).mass;
+        assert!((reading.temperature - 0.5 * mass).abs() < 1e-12);
+
+        let empty = Probe {
+            min_x: 100.0,
+            max_x: 100.0,
+            ..probe
+        };
+        assert!(empty.validate().is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use super::Exporter;
+use crate::error::SimResult;
+use crate::particle::{Particle, HEIGHT, WIDTH};
+use hecs::World;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// How many shells the range of the radial distribution function is split into.
+const BINS: usize = 50;
+
+/// Appends the radial distribution function g(r) of the particles every `every`
+/// frames, as `frame,r,g` rows with `r` at the middle of each shell. g(r) is the
+/// density of particles at a distance `r` from a particle, relative to the
+/// density of the whole box: 1 for an ideal gas, with peaks where particles
+/// cluster at preferred distances.
+///
+/// The box has walls rather than wrapping around, so shells reaching past them are
+/// partly empty and g(r) falls off at distances approaching the size of the box.
+pub struct RdfExporter {
+    writer: Box<dyn Write + Send>,
+    every: u64,
+    range: f64,
+}
+
+impl RdfExporter {
+    pub fn create(path: &Path, every: u64, range: f64) -> SimResult<Self> {
+        let file = File::create(path)?;
+        RdfExporter::new(Box::new(BufWriter::new(file)), every, range)
+    }
+
+    pub fn new(mut writer: Box<dyn Write + Send>, every: u64, range: f64) -> SimResult<Self> {
+        //! Write the header to `writer`. An interval of 0 is treated as 1, and
+        //! distances up to `range` are binned.
+        writeln!(writer, "frame,r,g")?;
+        Ok(RdfExporter {
+            writer,
+            every: every.max(1),
+            range,
+        })
+    }
+}
+
+pub fn radial_distribution(world: &World, range: f64, bins: usize) -> Vec<f64> {
+    //! g(r) in `bins` shells of equal width from 0 to `range`. Pairs are found by
+    //! sweeping along x like the broadphase, so only pairs closer than `range`
+    //! along x are ever compared.
+    let mut positions: Vec<_> = world
+        .query::<&Particle>()
+        .iter()
+        .map(|(_, particle)| particle.position)
+        .collect();
+    positions.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let width = range / bins as f64;
+    let mut counts = vec![0u64; bins];
+    for (i, a) in positions.iter().enumerate() {
+        for b in &positions[i + 1..] {
+            if b.0 - a.0 >= range {
+                break;
+            }
+            let distance = a.distance(b);
+            if distance < range {
+                counts[(distance / width) as usize] += 1;
+            }
+        }
+    }
+
+    let count = positions.len() as f64;
+    let pairs = count * (count - 1.0) / 2.0;
+    counts
+        .iter()
+        .enumerate()
+        .map(|(bin, &found)| {
+            let (inner, outer) = (bin as f64 * width, (bin + 1) as f64 * width);
+            let shell = PI * (outer.powi(2) - inner.powi(2));
+            let expected = pairs * shell / (WIDTH * HEIGHT);
+            if expected > 0.0 {
+                found as f64 / expected
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+impl Exporter for RdfExporter {
+    fn record(&mut self, frame: u64, world: &World) -> SimResult<()> {
+        if !frame.is_multiple_of(self.every) {
+            return Ok(());
+        }
+        let width = self.range / BINS as f64;
+        for (bin, g) in radial_distribution(world, self.range, BINS)
+            .into_iter()
+            .enumerate()
+        {
+            writeln!(
+                self.writer,
+                "{},{},{}",
+                frame,
+                (bin as f64 + 0.5) * width,
+                g
+            )?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Vec2;
+
+    #[test]
+    fn test_pairs_land_in_their_shells() {
+        let mut world = World::new();
+        world.spawn((Particle::new(1, 1.0, Vec2(100.0, 100.0), Vec2::ZERO),));
+        world.spawn((Particle::new(2, 1.0, Vec2(103.0, 104.0), Vec2::ZERO),));
+        world.spawn((Particle::new(3, 1.0, Vec2(500.0, 500.0), Vec2::ZERO),));
+
+        let g = radial_distribution(&world, 10.0, 10);
+        // The only pair closer than 10 is 5 apart, in the shell from 5 to 6
+        let expected = 3.0 * PI * (36.0 - 25.0) / (WIDTH * HEIGHT);
+        assert!((g[5] - 1.0 / expected).abs() < 1e-9);
+        assert_eq!(g.iter().filter(|g| **g > 0.0).count(), 1);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::particle::Particle;
+use crate::utils::Vec2;
+
+/// Whether a particle pulls on the others. Infinitely heavy particles would
+/// pull infinitely hard, so they are left out.
+fn attracts(particle: &Particle) -> bool {
+    particle.mass.is_finite() && particle.mass > 0.0
+}
+
+pub fn accelerations(particles: &[Particle], constant: f64) -> Vec<Vec2> {
+    //! The acceleration of every particle under Newtonian gravity from all of the
+    //! others, with a gravitational constant of `constant`. Every pair is visited,
+    //! so this is only fit for a few hundred particles. Distances are counted
+    //! from no closer than touching, so that overlapping particles do not fling
+    //! each other apart.
+    let mut accelerations = vec![Vec2::ZERO; particles.len()];
+    for (i, a) in particles.iter().enumerate() {
+        for (j, b) in particles.iter().enumerate().skip(i + 1) {
+            let offset = b.position - a.position;
+            let distance = offset.length();
+            if distance == 0.0 {
+                continue;
+            }
+            let reach = distance.max(a.radius + b.radius);
+            // The pull per unit of mass, along the unit vector from a to b
+            let pull = (constant / (reach * reach * distance)) * offset;
+            if attracts(b) {
+                accelerations[i] += b.mass * pull;
+            }
+            if attracts(a) {
+                accelerations[j] -= a.mass * pull;
+            }
+        }
+    }
+    accelerations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pull_follows_the_inverse_square_law() {
+        let particles = [
+            Particle::new(1, 1.0, Vec2(0.0, 0.0), Vec2::ZERO),
+            Particle::new(2, 1.0, Vec2(10.0, 0.0), Vec2::ZERO).with_density(2.0),
+            Particle::new(3, 1.0, Vec2(0.0, 20.0), Vec2::ZERO).massive(),
+        ];
+        let accelerations = accelerations(&particles, 100.0);
+        let mass = particles[0].mass;
+        // The massive particle pulls on nothing, but is pulled on
+        assert!(accelerations[0].distance(&Vec2(2.0 * mass, 0.0)) < 1e-12);
+        assert!(accelerations[1].distance(&Vec2(-mass, 0.0)) < 1e-12);
+        assert!(accelerations[2].1 < -0.25 * mass);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::error::{SimError, SimResult};
+use crate::particle::{Particle, HEIGHT, WIDTH};
+use crate::utils::Vec2;
+use hecs::World;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+// Checks that should hold after every step. Enabled with `Simulation::set_paranoid`,
+// they are slow and meant for hunting bugs, not for normal runs.
+
+/// How much the total momentum may change while resolving collisions,
+/// relative to the total momentum magnitude of the particles involved.
+const MOMENTUM_TOLERANCE: f64 = 1e-9;
+
+pub fn check_particles(world: &World, dt: f64) -> Result<(), String> {
+    //! Every particle must have finite values, a positive radius, an id no
+    //! other particle has, and stay in the box. Walls put back any particle that
+    //! crosses them, but in a crowd a collision can push one out again, so it may
+    //! overshoot as long as it is not moving further away than it can travel in
+    //! two steps.
+    let mut ids = HashSet::new();
+    for (_, particle) in world.query::<&Particle>().iter() {
+        if !particle.is_finite() {
+            return Err(format!("particle {} has non-finite values", particle.id));
+        }
+        if particle.radius <= 0.0 {
+            return Err(format!(
+                "particle {} has radius {}",
+                particle.id, particle.radius
+            ));
+        }
+        if !ids.insert(particle.id) {
+            return Err(format!("particle id {} is used twice", particle.id));
+        }
+        let speed = particle.velocity.length();
+        let margin = particle.radius + 2.0 * speed * dt;
+        // Outside the box by more than the margin, and still heading away from it
+        let escaping = |value: f64, velocity: f64, size: f64| {
+            value < -margin && velocity < 0.0 || value > size + margin && velocity > 0.0
+        };
+        if escaping(particle.position.0, particle.velocity.0, WIDTH)
+            || escaping(particle.position.1, particle.velocity.1, HEIGHT)
+        {
+            return Err(format!(
+                "particle {} escaped the box at ({}, {})",
+                particle.id, particle.position.0, particle.position.1
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The sum of `mass * velocity` over every particle, and the sum of the
+/// magnitudes, which is the scale any drift is measured against.
+/// Fixed and massive particles absorb momentum like walls do, so there is
+/// nothing to compare if there are any.
+pub fn total_momentum(world: &World) -> Option<(Vec2, f64)> {
+    let mut momentum = Vec2::ZERO;
+    let mut scale = 0.0;
+    for (_, particle) in world.query::<&Particle>().iter() {
+        if particle.effective_mass().is_infinite() {
+            return None;
+        }
+        let velocity = particle.velocity;
+        momentum += particle.mass * velocity;
+        scale += particle.mass * velocity.inner_product(&velocity).sqrt();
+    }
+    Some((momentum, scale))
+}
+
+pub fn check_momentum(before: (Vec2, f64), after: (Vec2, f64)) -> Result<(), String> {
+    //! Collisions exchange momentum between particles but must not create or
+    //! destroy any. Walls and gravity do change it, so only compare the totals
+    //! from just before and just after resolving collisions.
+    let drift = (after.0 - before.0)
+        .inner_product(&(after.0 - before.0))
+        .sqrt();
+    if drift > MOMENTUM_TOLERANCE * before.1.max(1.0) {
+        return Err(format!(
+            "collisions changed the total momentum from {:?} to {:?}",
+            before.0, after.0
+        ));
+    }
+    Ok(())
+}
+
+pub fn dump_state(world: &World) -> String {
+    //! Every particle, one per line in id order, for attaching to a bug report.
+    let mut dump = String::from("id,radius,mass,x,y,vx,vy\n");
+    for particle in crate::export::sorted_particles(world) {
+        // Writing to a String cannot fail
+        let _ = writeln!(
+            dump,
+            "{},{},{},{},{},{},{}",
+            particle.id,
+            particle.radius,
+            particle.mass,
+            particle.position.0,
+            particle.position.1,
+            particle.velocity.0,
+            particle.velocity.1
+        );
+    }
+    dump
+}
+
+pub fn violation(frame: u64, reason: String, world: &World) -> SimError {
+    tracing::error!(frame, %reason, "invariant violated");
+    SimError::InvariantViolated {
+        frame,
+        reason,
+        dump: dump_state(world),
+    }
+}
+
+pub fn check_step(world: &World, dt: f64, frame: u64) -> SimResult<()> {
+    check_particles(world, dt).map_err(|reason| violation(frame, reason, world))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_world_passes() {
+        let mut world = World::new();
+        world.spawn((Particle::new(1, 5.0, Vec2(10.0, 10.0), Vec2(1.0, 1.0)),));
+        world.spawn((Particle::new(2, 5.0, Vec2(500.0, 500.0), Vec2(-1.0, 1.0)),));
+        assert!(check_particles(&world, 1.0).is_ok());
+    }
+
+    #[test]
+    fn test_broken_particles_are_reported() {
+        let mut duplicate = World::new();
+        duplicate.spawn((Particle::new(1, 5.0, Vec2(10.0, 10.0), Vec2(0.0, 0.0)),));
+        duplicate.spawn((Particle::new(1, 5.0, Vec2(50.0, 50.0), Vec2(0.0, 0.0)),));
+        assert!(check_particles(&duplicate, 1.0)
+            .unwrap_err()
+            .contains("used twice"));
+
+        let mut escaped = World::new();
+        escaped.spawn((Particle::new(1, 5.0, Vec2(-100.0, 10.0), Vec2(-1.0, 0.0)),));
+        assert!(check_particles(&escaped, 1.0)
+            .unwrap_err()
+            .contains("escaped"));
+    }
+
+    #[test]
+    fn test_momentum_drift_is_reported() {
+        let before = (Vec2(10.0, 0.0), 10.0);
+        assert!(check_momentum(before, (Vec2(10.0, 0.0), 10.0)).is_ok());
+        assert!(check_momentum(before, (Vec2(11.0, 0.0), 11.0)).is_err());
+    }
+}
@@ -0,0 +1,27 @@
+//! The physics core of the particle simulator. This crate knows nothing about
+//! windows or rendering, so that it can be driven by the desktop app, a browser,
+//! or anything else.
+pub mod checkpoint;
+pub mod components;
+pub mod error;
+pub mod event_driven;
+pub mod events;
+pub mod export;
+pub mod gravity;
+pub mod invariants;
+pub mod obstacles;
+pub mod particle;
+pub mod particle3d;
+pub mod patterns;
+pub mod portals;
+pub mod profiler;
+pub mod settings;
+pub mod shapes;
+pub mod simulation;
+pub mod simulation3d;
+pub mod spawn;
+pub mod species;
+pub mod sweep_prune;
+pub mod systems;
+pub mod time_regions;
+pub mod utils;
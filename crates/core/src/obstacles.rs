@@ -0,0 +1,253 @@
+use crate::error::{SimError, SimResult};
+use crate::particle::{Particle, HEIGHT, WIDTH};
+use crate::utils::Vec2;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+/// A static, axis aligned box that particles bounce off of.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Obstacle {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Obstacle {
+    pub fn new(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        Obstacle {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
+    }
+
+    pub fn push_out(&self, particle: &mut Particle) -> bool {
+        //! If the particle overlaps the box, move it out the shortest way and
+        //! reflect its velocity elastically. Returns whether the two touched.
+        let Vec2(x, y) = particle.position;
+        let radius = particle.radius;
+        if x + radius <= self.min_x
+            || x - radius >= self.max_x
+            || y + radius <= self.min_y
+            || y - radius >= self.max_y
+        {
+            return false;
+        }
+
+        // The normal points from the closest point of the box towards the center
+        let offset = particle.position
+            - Vec2(
+                x.clamp(self.min_x, self.max_x),
+                y.clamp(self.min_y, self.max_y),
+            );
+        let distance = offset.length();
+        let (normal, depth) = if distance > 0.0 {
+            if distance >= radius {
+                // Only the corners of the bounding boxes overlap
+                return false;
+            }
+            (offset.scale(1.0 / distance), radius - distance)
+        } else {
+            // The center is inside the box, so leave through the nearest side
+            // that does not lie against a wall of the world
+            [
+                (self.min_x > 0.0, Vec2(-1.0, 0.0), x - self.min_x),
+                (self.max_x < WIDTH, Vec2(1.0, 0.0), self.max_x - x),
+                (self.min_y > 0.0, Vec2(0.0, -1.0), y - self.min_y),
+                (self.max_y < HEIGHT, Vec2(0.0, 1.0), self.max_y - y),
+            ]
+            .into_iter()
+            .filter_map(|(open, normal, inside)| open.then_some((normal, inside)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(normal, inside)| (normal, inside + radius))
+            .unwrap_or((Vec2(0.0, 1.0), radius))
+        };
+
+        particle.position += depth * normal;
+        let approach = particle.velocity.inner_product(&normal);
+        if approach < 0.0 {
+            particle.velocity -= (2.0 * approach) * normal;
+        }
+        true
+    }
+}
+
+pub fn load_map(path: &Path) -> SimResult<Vec<Obstacle>> {
+    //! Read a map of obstacles, stretched over the whole box. A `.png` file is
+    //! solid wherever a pixel is dark, anything else is read as a text grid
+    //! where `#` is solid.
+    let is_png = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    let cells = if is_png {
+        read_png(path)?
+    } else {
+        parse_text_map(&fs::read_to_string(path)?)
+    };
+    if cells.first().is_none_or(|row| row.is_empty()) {
+        return Err(SimError::InvalidMap(format!("{} is empty", path.display())));
+    }
+    Ok(obstacles_from_grid(&cells))
+}
+
+pub fn parse_text_map(text: &str) -> Vec<Vec<bool>> {
+    //! One row per line, top row first. Short lines are open at the end.
+    let rows: Vec<&str> = text.lines().collect();
+    let columns = rows
+        .iter()
+        .map(|row| row.chars().count())
+        .max()
+        .unwrap_or(0);
+    rows.iter()
+        .map(|row| {
+            let mut cells: Vec<bool> = row.chars().map(|cell| cell == '#').collect();
+            cells.resize(columns, false);
+            cells
+        })
+        .collect()
+}
+
+fn read_png(path: &Path) -> SimResult<Vec<Vec<bool>>> {
+    //! Dark, opaque pixels are solid.
+    let invalid = |err: png::DecodingError| {
+        SimError::InvalidMap(format!("could not decode {}: {}", path.display(), err))
+    };
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(invalid)?;
+    let size = reader
+        .output_buffer_size()
+        .ok_or_else(|| SimError::InvalidMap(format!("{} is too large", path.display())))?;
+    let mut buffer = vec![0; size];
+    let info = reader.next_frame(&mut buffer).map_err(invalid)?;
+
+    let channels = info.color_type.samples();
+    let has_alpha = matches!(
+        info.color_type,
+        png::ColorType::GrayscaleAlpha | png::ColorType::Rgba
+    );
+    let color_channels = if has_alpha { channels - 1 } else { channels };
+    Ok(buffer
+        .chunks(info.line_size)
+        .take(info.height as usize)
+        .map(|line| {
+            line.chunks(channels)
+                .take(info.width as usize)
+                .map(|pixel| {
+                    let brightness = pixel[..color_channels]
+                        .iter()
+                        .map(|&c| u32::from(c))
+                        .sum::<u32>()
+                        / color_channels as u32;
+                    let opaque = !has_alpha || pixel[channels - 1] >= 128;
+                    opaque && brightness < 128
+                })
+                .collect()
+        })
+        .collect())
+}
+
+pub fn obstacles_from_grid(cells: &[Vec<bool>]) -> Vec<Obstacle> {
+    //! Turn a grid of solid cells (top row first) into boxes covering the world.
+    //! Neighbouring solid cells in a row become one box, and identical runs in
+    //! consecutive rows are merged, so that large walls stay cheap to collide with.
+    let rows = cells.len();
+    let columns = cells.first().map_or(0, |row| row.len());
+    if rows == 0 || columns == 0 {
+        return Vec::new();
+    }
+    let cell_width = WIDTH / columns as f64;
+    let cell_height = HEIGHT / rows as f64;
+
+    // Runs that are still growing downwards, keyed by their columns, with the row they started on
+    let mut open: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut runs: Vec<(usize, usize, usize, usize)> = Vec::new();
+    // One extra empty row at the end closes every run that reaches the bottom
+    for (row, line) in cells.iter().map(Some).chain([None]).enumerate() {
+        let mut still_open = HashMap::new();
+        let line = line.map_or(&[][..], |line| &line[..]);
+        let mut column = 0;
+        while column < line.len() {
+            if !line[column] {
+                column += 1;
+                continue;
+            }
+            let start = column;
+            while column < line.len() && line[column] {
+                column += 1;
+            }
+            let first_row = open.remove(&(start, column)).unwrap_or(row);
+            still_open.insert((start, column), first_row);
+        }
+        for ((start, end), first_row) in open.drain() {
+            runs.push((start, end, first_row, row));
+        }
+        open = still_open;
+    }
+
+    // Sorted so that the same map always gives the same obstacles
+    runs.sort();
+    runs.into_iter()
+        .map(|(start, end, first_row, end_row)| {
+            Obstacle::new(
+                start as f64 * cell_width,
+                HEIGHT - end_row as f64 * cell_height,
+                end as f64 * cell_width,
+                HEIGHT - first_row as f64 * cell_height,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runs_are_merged_into_boxes() {
+        let cells = parse_text_map("##..\n##..\n...#");
+        let obstacles = obstacles_from_grid(&cells);
+        assert_eq!(obstacles.len(), 2);
+        assert_eq!(
+            obstacles[0],
+            Obstacle::new(0.0, HEIGHT - 2.0 * (HEIGHT / 3.0), 500.0, HEIGHT)
+        );
+        assert_eq!(
+            obstacles[1],
+            Obstacle::new(750.0, 0.0, 1000.0, HEIGHT - 2.0 * (HEIGHT / 3.0))
+        );
+    }
+
+    #[test]
+    fn test_particle_bounces_off_of_a_side() {
+        let obstacle = Obstacle::new(100.0, 100.0, 200.0, 200.0);
+        let mut particle = Particle::new(1, 5.0, Vec2(97.0, 150.0), Vec2(3.0, 1.0));
+        assert!(obstacle.push_out(&mut particle));
+        assert_eq!(particle.position.0, 95.0);
+        assert_eq!(particle.velocity, Vec2(-3.0, 1.0));
+
+        let mut clear = Particle::new(2, 5.0, Vec2(50.0, 150.0), Vec2(3.0, 1.0));
+        assert!(!obstacle.push_out(&mut clear));
+    }
+
+    #[test]
+    fn test_particle_inside_leaves_through_nearest_side() {
+        let obstacle = Obstacle::new(100.0, 100.0, 200.0, 200.0);
+        let mut particle = Particle::new(1, 5.0, Vec2(150.0, 190.0), Vec2(0.0, -2.0));
+        obstacle.push_out(&mut particle);
+        assert_eq!(particle.position.1, 205.0);
+        assert_eq!(particle.velocity.1, 2.0);
+
+        // Never out through the edge of the world, even if it is closer
+        let against_wall = Obstacle::new(900.0, 100.0, WIDTH, 200.0);
+        let mut particle = Particle::new(2, 5.0, Vec2(990.0, 140.0), Vec2(0.0, 0.0));
+        against_wall.push_out(&mut particle);
+        assert_eq!(particle.position.0, 990.0);
+        assert_eq!(particle.position.1, 95.0);
+    }
+}
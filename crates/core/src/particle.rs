@@ -0,0 +1,348 @@
+use super::settings::{Side, Walls};
+use super::shapes::{self, Contact};
+use super::spawn::SpawnSettings;
+use super::utils::Vec2;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+pub const WIDTH: f64 = 1000.0;
+pub const HEIGHT: f64 = 1000.0;
+pub const RADIUS_UPPER_BOUND: f64 = 50.0;
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Particle {
+    // The id identifies the particle in logs and exports.
+    // Rendering does not depend on it, sprites are shared between
+    // particles of a similar size and color.
+    pub id: u64,
+    pub radius: f64,
+    //Here we will set mass to be proportional to
+    // the area of the circle, times a density that is 1 unless
+    // the particle was made `with_density`
+    pub mass: f64,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    // A fixed particle never moves, but other particles still bounce off of it.
+    pub fixed: bool,
+}
+
+impl Particle {
+    pub fn new(id: u64, radius: f64, position: Vec2, velocity: Vec2) -> Self {
+        //mass is dependent on radius and therefore should not
+        // be set independently
+        let mass = radius.powi(2) * PI;
+        Particle {
+            id,
+            radius,
+            mass,
+            position,
+            velocity,
+            fixed: false,
+        }
+    }
+
+    pub fn pinned(mut self) -> Self {
+        //! Fix the particle in place, as an anchor or an obstacle.
+        self.fixed = true;
+        self.velocity = Vec2::ZERO;
+        self
+    }
+
+    pub fn massive(mut self) -> Self {
+        //! Give the particle infinite mass. Unlike a fixed particle it keeps
+        //! moving, but collisions never change its velocity, like a piston.
+        self.mass = f64::INFINITY;
+        self
+    }
+
+    pub fn with_density(mut self, density: f64) -> Self {
+        //! Make the particle `density` times as heavy as one of the same size
+        //! made with `new`.
+        self.mass = density * self.radius.powi(2) * PI;
+        self
+    }
+
+    pub fn density(&self) -> f64 {
+        //! The mass per unit of area, infinite for a `massive` particle.
+        self.mass / (self.radius.powi(2) * PI)
+    }
+
+    pub fn effective_mass(&self) -> f64 {
+        //! The mass used in collisions, which is infinite for a fixed particle.
+        if self.fixed {
+            f64::INFINITY
+        } else {
+            self.mass
+        }
+    }
+
+    fn collision_weights(m1: f64, m2: f64) -> (f64, f64) {
+        //! The factors `2 * m2 / (m1 + m2)` and `2 * m1 / (m1 + m2)` from the
+        //! collision formula, taking the limit exactly when a mass is infinite
+        //! instead of dividing infinity by infinity.
+        match (m1.is_infinite(), m2.is_infinite()) {
+            (false, false) => (2.0 * m2 / (m1 + m2), 2.0 * m1 / (m1 + m2)),
+            // The finite particle is mirrored off of the immovable one
+            (false, true) => (2.0, 0.0),
+            (true, false) => (0.0, 2.0),
+            // Neither can be moved, so both carry on as they were
+            (true, true) => (0.0, 0.0),
+        }
+    }
+
+    pub fn new_random(id: u64, rng: &mut impl Rng) -> Self {
+        //!Creates a Random particle with the default `SpawnSettings`,
+        //! somewhere inside of `WIDTH` and `HEIGHT`
+        SpawnSettings::default().random_particle(id, rng)
+    }
+
+    pub fn particle_factory(count: usize) -> Vec<Particle> {
+        //! Generate `count` randomly sized circles.
+        //`` All random values are bounded by the default `SpawnSettings`.
+        let mut rng = rand::thread_rng();
+        (0..count as u64)
+            .map(|id| Particle::new_random(id, &mut rng))
+            .collect()
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        //! Update the positions and velocities of the particle,
+        //! bouncing perfectly elastically off of the walls.
+        self.update_with_walls(dt, &Walls::default());
+    }
+
+    pub fn update_with_walls(&mut self, dt: f64, walls: &Walls) -> [Option<Side>; 2] {
+        //! Update the positions and velocities of the particle,
+        //! bouncing off of walls made of the given materials.
+        //! Returns the sides it bounced off of, at most one per axis.
+        let mut bounced_off = [None, None];
+        if self.fixed {
+            return bounced_off;
+        }
+        // First, update the position by applying the velocity times the dt
+        self.position += dt * self.velocity;
+
+        //Second, check for particles reaching any boundaries, and reverse their velocity.
+        // The particle is also reflected back inside by however far it went past the wall,
+        // shortened by the restitution like the velocity, as if it had bounced at the
+        // moment it touched the wall. Otherwise it would stay outside of the box for a
+        // while, and could be flipped again before it got back in.
+        for (axis, size, low, high) in [
+            (0, WIDTH, Side::Left, Side::Right),
+            (1, HEIGHT, Side::Bottom, Side::Top),
+        ] {
+            let (position, velocity) = (self.position[axis], self.velocity[axis]);
+            let bounced = if position + self.radius >= size && velocity > 0.0 {
+                let overshoot = position + self.radius - size;
+                let wall = walls.side(high);
+                Some((high, size - self.radius - wall.restitution * overshoot))
+            } else if position - self.radius <= 0.0 && velocity < 0.0 {
+                let overshoot = self.radius - position;
+                let wall = walls.side(low);
+                Some((low, self.radius + wall.restitution * overshoot))
+            } else {
+                None
+            };
+            if let Some((side, position)) = bounced {
+                let along = 1 - axis;
+                self.position[axis] = position;
+                (self.velocity[axis], self.velocity[along]) = walls
+                    .side(side)
+                    .bounce(self.velocity[axis], self.velocity[along]);
+                bounced_off[axis] = Some(side);
+            }
+        }
+        bounced_off
+    }
+
+    pub fn is_finite(&self) -> bool {
+        //! False if any value has become NaN or infinite, after which
+        //! every calculation involving the particle is meaningless.
+        //! The mass alone may be infinite, for a `massive` particle.
+        !self.mass.is_nan()
+            && self.radius.is_finite()
+            && self.position.is_finite()
+            && self.velocity.is_finite()
+    }
+
+    pub fn kinetic_energy(&self) -> f64 {
+        //! The energy of the particle's motion, which is zero for a fixed
+        //! particle and left out (zero) for one with infinite mass.
+        if self.fixed || self.mass.is_infinite() {
+            0.0
+        } else {
+            0.5 * self.mass * self.velocity.inner_product(&self.velocity)
+        }
+    }
+
+    pub fn contact(&self, other: &Particle) -> Option<Contact> {
+        //! Where the circles of the two particles overlap, if they do.
+        //! The normal of the contact points from `self` into `other`.
+        // For circles, if the distance is less than the sum of the radii,
+        // then the circles must overlap
+        shapes::circles_contact(self.position, self.radius, other.position, other.radius)
+    }
+
+    pub fn check_pairwise_collision(&self, other: &Particle) -> bool {
+        // Returns a boolean depending on whether or not a collision has occurred
+        self.contact(other).is_some()
+    }
+
+    pub fn collision_react(&self, other: &Particle, contact: &Contact) -> (Vec2, Vec2) {
+        //! Given two particles that are determined to have collided at `contact`,
+        //! perform the physics calcs for an elastic collision.
+        //! Returns a tuple of the new velocities for self and other.
+
+        // If the particles have collided, apply the rules of
+        // an elastic collision
+        let (v1, x1) = (self.velocity, self.position);
+        let (v2, x2) = (other.velocity, other.position);
+
+        //There is a known bug where particles can get stuck together because
+        //they fail to clear each others area before the next re-render after a
+        //collision. In order to prevent this, do not change the velocity of particles
+        // that are currently moving away from each other. Only particles moving towards each other
+        //should be 'colliding', otherwise the particles are in the act of recoiling.
+        if (v2 - v1).inner_product(&contact.normal) >= 0.0 {
+            return (v1, v2);
+        }
+
+        // Quite an ugly formula from wikipedia
+        // https://en.wikipedia.org/wiki/Elastic_collision
+        let (weight_self, weight_other) =
+            Particle::collision_weights(self.effective_mass(), other.effective_mass());
+        let v_self_new = v1
+            - weight_self
+                * (v1 - v2).inner_product(&(x1 - x2))
+                * (1.0 / ((x1 - x2).inner_product(&(x1 - x2))))
+                * (x1 - x2);
+
+        let v_other_new = v2
+            - weight_other
+                * (v2 - v1).inner_product(&(x2 - x1))
+                * (1.0 / ((x2 - x1).inner_product(&(x2 - x1))))
+                * (x2 - x1);
+
+        (v_self_new, v_other_new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Wall;
+
+    #[test]
+    fn test_move_normal() {
+        //First, test a normal particle not hitting a wall
+        let mut test_particle = Particle::new(1, 1.0, Vec2(1.0, 1.0), Vec2(2.5, 3.5));
+
+        test_particle.update(1.0);
+
+        //After the move, particle position_x should be at
+        // position_x + velocity_x * dt = 1.0 + 2.5 * 1 = 3.5
+        assert_eq!(test_particle.position.0, 3.5);
+        //particle position_y should be at
+        // position_y + velocity_y * dt = 1.0 + 3.5 * 1 = 4.5
+        assert_eq!(test_particle.position.1, 4.5);
+    }
+
+    #[test]
+    fn test_move_past_boundary() {
+        // This time velocity_y of -3.5 will cause the particle to go off
+        // the map in the y direction during the next move
+        let mut test_particle = Particle::new(1, 1.0, Vec2(1.0, 1.0), Vec2(2.5, -3.5));
+        test_particle.update(1.0);
+        assert_eq!(test_particle.position.0, 3.5);
+        //The bottom edge would have ended up 3.5 below the floor,
+        // so the particle is reflected to 3.5 above where it touches it
+        assert_eq!(test_particle.position.1, 4.5);
+
+        //Since position_y went outside of the boundary,
+        // the velocity_y should have switched signs to 3.5
+        // velocity_x should be unaffected
+        assert_eq!(test_particle.velocity.0, 2.5);
+        assert_eq!(test_particle.velocity.1, 3.5);
+    }
+
+    #[test]
+    fn test_inelastic_floor_with_friction() {
+        let floor = Wall {
+            restitution: 0.5,
+            friction: 0.25,
+            ..Wall::default()
+        };
+        let walls = Walls {
+            bottom: floor,
+            ..Walls::default()
+        };
+        let mut particle = Particle::new(1, 1.0, Vec2(500.0, 1.0), Vec2(4.0, -2.0));
+        assert_eq!(
+            particle.update_with_walls(1.0, &walls),
+            [None, Some(Side::Bottom)]
+        );
+        // Half the speed into the floor is kept, and the change of 3 in the
+        // vertical velocity allows friction to take 0.75 off of the sliding
+        assert_eq!(particle.velocity.1, 1.0);
+        assert_eq!(particle.velocity.0, 3.25);
+        // It went 2 past the floor, and is reflected back by half of that
+        assert_eq!(particle.position.1, 2.0);
+
+        // Friction stops the sliding at worst, it never reverses it
+        let sticky = Walls::uniform(Wall {
+            restitution: 0.0,
+            friction: 10.0,
+            ..Wall::default()
+        });
+        let mut particle = Particle::new(1, 1.0, Vec2(500.0, 1.0), Vec2(4.0, -2.0));
+        particle.update_with_walls(1.0, &sticky);
+        assert_eq!((particle.velocity.0, particle.velocity.1), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_fixed_particle_does_not_move() {
+        let mut anchor = Particle::new(1, 5.0, Vec2(-10.0, 500.0), Vec2(3.0, 0.0)).pinned();
+        anchor.update(1.0);
+        assert_eq!(anchor.position.0, -10.0);
+        assert_eq!(anchor.velocity.0, 0.0);
+    }
+
+    #[test]
+    fn test_bounce_off_fixed_particle() {
+        let anchor = Particle::new(1, 5.0, Vec2(100.0, 100.0), Vec2(0.0, 0.0)).pinned();
+        let ball = Particle::new(2, 5.0, Vec2(92.0, 100.0), Vec2(4.0, 0.0));
+        let contact = ball.contact(&anchor).unwrap();
+        let (v_ball, v_anchor) = ball.collision_react(&anchor, &contact);
+        // The bounce is an exact mirror image, not an approximation
+        assert_eq!(v_ball, Vec2(-4.0, 0.0));
+        assert_eq!(v_anchor, Vec2(0.0, 0.0));
+    }
+
+    #[test]
+    fn test_massive_particle_keeps_its_velocity() {
+        let piston = Particle::new(1, 20.0, Vec2(100.0, 100.0), Vec2(2.0, 0.0)).massive();
+        let ball = Particle::new(2, 5.0, Vec2(120.0, 100.0), Vec2(-3.0, 0.0));
+        let contact = piston.contact(&ball).unwrap();
+        let (v_piston, v_ball) = piston.collision_react(&ball, &contact);
+        assert_eq!(v_piston, Vec2(2.0, 0.0));
+        // In the piston's frame the ball bounces back at the speed it came in
+        assert_eq!(v_ball, Vec2(7.0, 0.0));
+        assert!(piston.is_finite());
+    }
+
+    #[test]
+    fn test_pairwise_collision_detection() {
+        //These two particles are colliding
+        let p1 = Particle::new(1, 1.5, Vec2(1.0, 2.0), Vec2(2.5, -3.5));
+        let p2 = Particle::new(1, 1.0, Vec2(2.0, 1.0), Vec2(2.5, -3.5));
+        assert!(p1.check_pairwise_collision(&p2));
+        let contact = p1.contact(&p2).unwrap();
+        assert!((contact.normal.length() - 1.0).abs() < 1e-12);
+        assert!((contact.penetration_depth - (2.5 - 2f64.sqrt())).abs() < 1e-12);
+
+        //These two particle are not colliding
+        let p3 = Particle::new(1, 1.5, Vec2(1.0, 2.0), Vec2(2.5, -3.5));
+        let p4 = Particle::new(1, 1.0, Vec2(4.0, 1.0), Vec2(2.5, -3.5));
+        assert!(!p3.check_pairwise_collision(&p4));
+    }
+}
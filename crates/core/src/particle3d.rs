@@ -0,0 +1,130 @@
+use crate::utils::Vec3;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// The depth of the box in the 3D simulation, to go with `WIDTH` and `HEIGHT`.
+pub const DEPTH: f64 = 1000.0;
+
+/// A sphere in the 3D simulation. Its mass is proportional to its volume.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Particle3d {
+    pub id: u64,
+    pub radius: f64,
+    pub mass: f64,
+    pub position: Vec3,
+    pub velocity: Vec3,
+}
+
+fn bounce(position: &mut f64, velocity: &mut f64, radius: f64, size: f64) {
+    //! Reflect one coordinate of a sphere back inside `0..size`,
+    //! the same way that the 2D particles bounce off of an elastic wall.
+    if *position + radius >= size && *velocity > 0.0 {
+        *position = 2.0 * (size - radius) - *position;
+        *velocity = -*velocity;
+    } else if *position - radius <= 0.0 && *velocity < 0.0 {
+        *position = 2.0 * radius - *position;
+        *velocity = -*velocity;
+    }
+}
+
+impl Particle3d {
+    pub fn new(id: u64, radius: f64, position: Vec3, velocity: Vec3) -> Self {
+        Particle3d {
+            id,
+            radius,
+            mass: 4.0 / 3.0 * PI * radius.powi(3),
+            position,
+            velocity,
+        }
+    }
+
+    pub fn update(&mut self, dt: f64, size: Vec3) {
+        //! Move the sphere forward by `dt`, bouncing elastically
+        //! off of the walls of a box with the given size.
+        self.position = self.position + dt * self.velocity;
+        bounce(
+            &mut self.position.0,
+            &mut self.velocity.0,
+            self.radius,
+            size.0,
+        );
+        bounce(
+            &mut self.position.1,
+            &mut self.velocity.1,
+            self.radius,
+            size.1,
+        );
+        bounce(
+            &mut self.position.2,
+            &mut self.velocity.2,
+            self.radius,
+            size.2,
+        );
+    }
+
+    pub fn check_pairwise_collision(&self, other: &Particle3d) -> bool {
+        self.position.distance(&other.position) < self.radius + other.radius
+    }
+
+    pub fn collision_react(&self, other: &Particle3d) -> (Vec3, Vec3) {
+        //! The new velocities of two overlapping spheres after an elastic collision.
+        //! Spheres that are already moving apart are left alone.
+        let (v1, v2) = (self.velocity, other.velocity);
+        let offset = self.position - other.position;
+        let approach = (v1 - v2).inner_product(&offset);
+        if approach >= 0.0 {
+            return (v1, v2);
+        }
+        // The same formula as in 2D, the impulse is along the line between the centers
+        let impulse = approach / offset.inner_product(&offset);
+        let total_mass = self.mass + other.mass;
+        (
+            v1 - (2.0 * other.mass / total_mass * impulse) * offset,
+            v2 + (2.0 * self.mass / total_mass * impulse) * offset,
+        )
+    }
+
+    pub fn kinetic_energy(&self) -> f64 {
+        0.5 * self.mass * self.velocity.inner_product(&self.velocity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOX: Vec3 = Vec3(1000.0, 1000.0, DEPTH);
+
+    #[test]
+    fn test_bounces_off_of_the_back_wall() {
+        let mut particle =
+            Particle3d::new(1, 10.0, Vec3(500.0, 500.0, 985.0), Vec3(0.0, 0.0, 10.0));
+        particle.update(1.0, BOX);
+        assert_eq!(particle.position, Vec3(500.0, 500.0, 985.0));
+        assert_eq!(particle.velocity, Vec3(0.0, 0.0, -10.0));
+    }
+
+    #[test]
+    fn test_collision_conserves_momentum_and_energy() {
+        let a = Particle3d::new(1, 10.0, Vec3(100.0, 100.0, 100.0), Vec3(3.0, 1.0, 2.0));
+        let b = Particle3d::new(2, 20.0, Vec3(115.0, 110.0, 120.0), Vec3(-1.0, 0.0, -2.0));
+        assert!(a.check_pairwise_collision(&b));
+        let (va, vb) = a.collision_react(&b);
+
+        let momentum = |va: Vec3, vb: Vec3| a.mass * va + b.mass * vb;
+        let energy =
+            |va: Vec3, vb: Vec3| a.mass * va.inner_product(&va) + b.mass * vb.inner_product(&vb);
+        let before = momentum(a.velocity, b.velocity);
+        let after = momentum(va, vb);
+        assert!(before.distance(&after) < 1e-6 * a.mass);
+        let energy_before = energy(a.velocity, b.velocity);
+        assert!((energy_before - energy(va, vb)).abs() < 1e-9 * energy_before);
+
+        // Once separating, a second reaction changes nothing
+        let (a, b) = (
+            Particle3d { velocity: va, ..a },
+            Particle3d { velocity: vb, ..b },
+        );
+        assert_eq!(a.collision_react(&b), (va, vb));
+    }
+}
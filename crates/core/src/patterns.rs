@@ -0,0 +1,439 @@
+use crate::error::{SimError, SimResult};
+use crate::particle::{Particle, HEIGHT, WIDTH};
+use crate::utils::Vec2;
+use hecs::World;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+/// Room left between neighbouring particles in the regular patterns.
+const GAP: f64 = 1.0;
+/// The share of a disk's area the particles placed in it may cover.
+/// Random placement gets stuck long before disks are packed tightly.
+const DISK_PACKING: f64 = 0.4;
+const PLACEMENT_ATTEMPTS: usize = 10_000;
+/// How far from the expected speeds a Newton's cradle may be, relative to the
+/// speed of the strike, to still count as right.
+const CRADLE_TOLERANCE: f64 = 1e-6;
+/// How much denser than the planets the star of the orbits pattern is.
+const STAR_DENSITY: f64 = 50.0;
+
+/// Where the initial particles are placed. Every pattern except `Random`
+/// guarantees that no two particles overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum SpawnPattern {
+    /// Anywhere in the box, overlaps allowed.
+    #[default]
+    Random,
+    /// A square lattice in the middle of the box.
+    Grid,
+    /// Evenly spaced around a circle.
+    Ring,
+    /// Scattered over a filled disk in the middle of the box.
+    Disk,
+    /// Two disks on either side of the box, heading towards each other at `speed`.
+    TwoClusters { speed: f64 },
+    /// Newton's cradle: a row of identical particles across the middle of the box,
+    /// all at rest except the first, which strikes the others at `speed`. If the
+    /// collisions are right, the strike passes along the row and only the last
+    /// particle leaves it, at the same speed, as `CradleOutcome` checks.
+    Cradle { speed: f64 },
+    /// A heavy star in the middle of the box with the other particles on circular
+    /// orbits around it, at random angles and ever wider radii. Two particles make
+    /// a two-body system and three a three-body one. The orbital speeds depend on
+    /// the strength of mutual gravity, so the simulation sets them once the
+    /// particles are in place, see `launch_orbits`.
+    Orbits,
+}
+
+/// What a Newton's cradle had done once the strike reached the far end of the row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CradleOutcome {
+    /// How many particles were moving.
+    pub moving: usize,
+    /// The speed of the particle at the far end.
+    pub speed: f64,
+}
+
+impl CradleOutcome {
+    pub fn measure(world: &World) -> Option<Self> {
+        //! The outcome as soon as the particle at the far (right) end of the row
+        //! has started moving, `None` before then.
+        let particles: Vec<Particle> = world
+            .query::<&Particle>()
+            .iter()
+            .map(|(_, particle)| *particle)
+            .collect();
+        let last = particles
+            .iter()
+            .max_by(|a, b| a.position.0.total_cmp(&b.position.0))?;
+        let speed = last.velocity.length();
+        if speed == 0.0 {
+            return None;
+        }
+        let moving = particles
+            .iter()
+            .filter(|particle| particle.velocity.length() > CRADLE_TOLERANCE * speed)
+            .count();
+        Some(CradleOutcome { moving, speed })
+    }
+
+    pub fn is_correct(&self, speed: f64) -> bool {
+        //! Whether exactly one particle left the row, at the speed of the strike.
+        self.moving == 1 && (self.speed - speed).abs() <= CRADLE_TOLERANCE * speed
+    }
+}
+
+impl fmt::Display for SpawnPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpawnPattern::Random => write!(f, "random"),
+            SpawnPattern::Grid => write!(f, "grid"),
+            SpawnPattern::Ring => write!(f, "ring"),
+            SpawnPattern::Disk => write!(f, "disk"),
+            SpawnPattern::TwoClusters { speed } => write!(f, "two-clusters:{}", speed),
+            SpawnPattern::Cradle { speed } => write!(f, "cradle:{}", speed),
+            SpawnPattern::Orbits => write!(f, "orbits"),
+        }
+    }
+}
+
+impl FromStr for SpawnPattern {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        //! Parse `random`, `grid`, `ring`, `disk`, `two-clusters[:<speed>]`,
+        //! `cradle[:<speed>]` or `orbits`.
+        let invalid = || SimError::InvalidArgument {
+            name: "spawn pattern".to_string(),
+            value: value.to_string(),
+        };
+        match value.split_once(':') {
+            None => match value {
+                "random" => Ok(SpawnPattern::Random),
+                "grid" => Ok(SpawnPattern::Grid),
+                "ring" => Ok(SpawnPattern::Ring),
+                "disk" => Ok(SpawnPattern::Disk),
+                "two-clusters" => Ok(SpawnPattern::TwoClusters { speed: 10.0 }),
+                "cradle" => Ok(SpawnPattern::Cradle { speed: 10.0 }),
+                "orbits" => Ok(SpawnPattern::Orbits),
+                _ => Err(invalid()),
+            },
+            Some(("two-clusters", speed)) => {
+                let speed = speed.trim().parse().map_err(|_| invalid())?;
+                Ok(SpawnPattern::TwoClusters { speed })
+            }
+            Some(("cradle", speed)) => match speed.trim().parse::<f64>() {
+                Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(SpawnPattern::Cradle { speed }),
+                _ => Err(invalid()),
+            },
+            Some(_) => Err(invalid()),
+        }
+    }
+}
+
+impl SpawnPattern {
+    pub fn arrange(&self, particles: &mut [Particle], rng: &mut impl Rng) -> SimResult<()> {
+        //! Move `particles` into this pattern. Their radii and velocities are kept,
+        //! except that `TwoClusters` adds the clusters' velocities and `Cradle`
+        //! makes every particle a copy of the first, at rest but for the striker.
+        //! Fails if the particles do not fit in the box.
+        let center = (WIDTH / 2.0, HEIGHT / 2.0);
+        match *self {
+            SpawnPattern::Random => Ok(()),
+            SpawnPattern::Grid => self.grid(particles),
+            SpawnPattern::Ring => self.ring(particles),
+            SpawnPattern::Disk => self.disk(particles, center, WIDTH.min(HEIGHT) / 2.0, rng),
+            SpawnPattern::TwoClusters { speed } => {
+                let count = particles.len();
+                let (left, right) = particles.split_at_mut(count / 2);
+                let limit = WIDTH.min(HEIGHT) / 4.0;
+                self.disk(left, (WIDTH / 4.0, HEIGHT / 2.0), limit, rng)
+                    .and_then(|_| self.disk(right, (WIDTH * 0.75, HEIGHT / 2.0), limit, rng))
+                    .map_err(|_| self.too_many(count))?;
+                for particle in left.iter_mut() {
+                    particle.velocity.0 += speed;
+                }
+                for particle in right.iter_mut() {
+                    particle.velocity.0 -= speed;
+                }
+                Ok(())
+            }
+            SpawnPattern::Cradle { speed } => self.cradle(particles, speed),
+            SpawnPattern::Orbits => self.orbits(particles, rng),
+        }
+    }
+
+    fn too_many(&self, count: usize) -> SimError {
+        SimError::InvalidArgument {
+            name: format!("particle count for the {} pattern", self),
+            value: count.to_string(),
+        }
+    }
+
+    fn grid(&self, particles: &mut [Particle]) -> SimResult<()> {
+        let spacing = 2.0 * max_radius(particles) + GAP;
+        let columns = (particles.len() as f64).sqrt().ceil().max(1.0) as usize;
+        let rows = particles.len().div_ceil(columns);
+        if columns as f64 * spacing > WIDTH || rows as f64 * spacing > HEIGHT {
+            return Err(self.too_many(particles.len()));
+        }
+        let left = (WIDTH - columns as f64 * spacing) / 2.0 + spacing / 2.0;
+        let bottom = (HEIGHT - rows as f64 * spacing) / 2.0 + spacing / 2.0;
+        for (index, particle) in particles.iter_mut().enumerate() {
+            particle.position.0 = left + (index % columns) as f64 * spacing;
+            particle.position.1 = bottom + (index / columns) as f64 * spacing;
+        }
+        Ok(())
+    }
+
+    fn cradle(&self, particles: &mut [Particle], speed: f64) -> SimResult<()> {
+        let Some(&first) = particles.first() else {
+            return Ok(());
+        };
+        let spacing = 2.0 * first.radius + GAP;
+        let length = particles.len() as f64 * spacing;
+        if length > WIDTH {
+            return Err(self.too_many(particles.len()));
+        }
+        let left = (WIDTH - length) / 2.0 + spacing / 2.0;
+        for (index, particle) in particles.iter_mut().enumerate() {
+            particle.radius = first.radius;
+            particle.mass = first.mass;
+            particle.position.0 = left + index as f64 * spacing;
+            particle.position.1 = HEIGHT / 2.0;
+            particle.velocity.0 = if index == 0 { speed } else { 0.0 };
+            particle.velocity.1 = 0.0;
+        }
+        Ok(())
+    }
+
+    fn orbits(&self, particles: &mut [Particle], rng: &mut impl Rng) -> SimResult<()> {
+        let Some((star, planets)) = particles.split_first_mut() else {
+            return Ok(());
+        };
+        *star = star.with_density(STAR_DENSITY);
+        star.position = Vec2(WIDTH / 2.0, HEIGHT / 2.0);
+        star.velocity = Vec2::ZERO;
+        if planets.is_empty() {
+            return Ok(());
+        }
+        // The star wobbles as the planets pull on it, so keep well clear of the walls
+        let largest = max_radius(planets);
+        let inner = star.radius + largest + GAP;
+        let outer = 0.8 * (WIDTH.min(HEIGHT) / 2.0 - largest);
+        let count = planets.len();
+        let spacing = (outer - inner) / count.saturating_sub(1).max(1) as f64;
+        if inner > outer || (count > 1 && spacing < 2.0 * largest + GAP) {
+            return Err(self.too_many(count + 1));
+        }
+        for (index, planet) in planets.iter_mut().enumerate() {
+            let radius = if count == 1 {
+                (inner + outer) / 2.0
+            } else {
+                inner + index as f64 * spacing
+            };
+            let angle = rng.gen::<f64>() * 2.0 * PI;
+            planet.position = star.position + radius * Vec2(angle.cos(), angle.sin());
+            planet.velocity = Vec2::ZERO;
+        }
+        Ok(())
+    }
+
+    fn ring(&self, particles: &mut [Particle]) -> SimResult<()> {
+        if particles.is_empty() {
+            return Ok(());
+        }
+        // The angle needed between each particle and the next, at a given ring radius
+        let angles = |ring_radius: f64| -> Vec<f64> {
+            (0..particles.len())
+                .map(|index| {
+                    let next = &particles[(index + 1) % particles.len()];
+                    let distance = particles[index].radius + next.radius + GAP;
+                    2.0 * (distance / (2.0 * ring_radius)).min(1.0).asin()
+                })
+                .collect()
+        };
+        let largest = max_radius(particles);
+        let limit = WIDTH.min(HEIGHT) / 2.0 - largest;
+        let mut ring_radius = WIDTH.min(HEIGHT) / 3.0;
+        while angles(ring_radius).iter().sum::<f64>() > 2.0 * PI {
+            ring_radius *= 1.05;
+            if ring_radius > limit {
+                return Err(self.too_many(particles.len()));
+            }
+        }
+
+        // Share out whatever is left of the circle evenly
+        let angles = angles(ring_radius);
+        let spare = (2.0 * PI - angles.iter().sum::<f64>()) / particles.len() as f64;
+        let mut angle: f64 = 0.0;
+        for (particle, step) in particles.iter_mut().zip(angles) {
+            particle.position.0 = WIDTH / 2.0 + ring_radius * angle.cos();
+            particle.position.1 = HEIGHT / 2.0 + ring_radius * angle.sin();
+            angle += step + spare;
+        }
+        Ok(())
+    }
+
+    fn disk(
+        &self,
+        particles: &mut [Particle],
+        center: (f64, f64),
+        limit: f64,
+        rng: &mut impl Rng,
+    ) -> SimResult<()> {
+        //! Random sequential placement: each particle is dropped at a random spot
+        //! in the disk until it lands somewhere that it overlaps nothing.
+        let area: f64 = particles
+            .iter()
+            .map(|p| PI * (p.radius + GAP).powi(2))
+            .sum();
+        let disk_radius = (area / DISK_PACKING / PI).sqrt().max(max_radius(particles));
+        if disk_radius > limit {
+            return Err(self.too_many(particles.len()));
+        }
+
+        // Placing the largest particles first makes it much easier to fit the rest
+        let mut order: Vec<usize> = (0..particles.len()).collect();
+        order.sort_by(|a, b| particles[*b].radius.total_cmp(&particles[*a].radius));
+        let mut placed: Vec<usize> = Vec::with_capacity(particles.len());
+        for index in order {
+            let radius = particles[index].radius;
+            let reach = (disk_radius - radius).max(0.0);
+            let spot = (0..PLACEMENT_ATTEMPTS).find_map(|_| {
+                // The square root spreads the points evenly over the area of the disk
+                let distance = reach * rng.gen::<f64>().sqrt();
+                let angle = rng.gen::<f64>() * 2.0 * PI;
+                let (x, y) = (
+                    center.0 + distance * angle.cos(),
+                    center.1 + distance * angle.sin(),
+                );
+                let free = placed.iter().all(|&other| {
+                    let other = &particles[other];
+                    let gap = radius + other.radius + GAP;
+                    (x - other.position.0).powi(2) + (y - other.position.1).powi(2) >= gap * gap
+                });
+                free.then_some((x, y))
+            });
+            let (x, y) = spot.ok_or_else(|| self.too_many(particles.len()))?;
+            particles[index].position.0 = x;
+            particles[index].position.1 = y;
+            placed.push(index);
+        }
+        Ok(())
+    }
+}
+
+pub fn launch_orbits(particles: &mut [Particle], constant: f64) {
+    //! Set the planets of the `Orbits` pattern off on circular orbits around the
+    //! star, the first particle, under mutual gravity of strength `constant`.
+    //! Each orbit is worked out as if the star and that planet were alone, and
+    //! the star is given the opposite of the planets' momentum, so that the
+    //! system as a whole stays put.
+    let Some((star, planets)) = particles.split_first_mut() else {
+        return;
+    };
+    let mut momentum = Vec2::ZERO;
+    for planet in planets.iter_mut() {
+        let offset = planet.position - star.position;
+        let distance = offset.length();
+        if distance == 0.0 {
+            continue;
+        }
+        // Anticlockwise, around the shared center of mass of the pair
+        let speed = (constant * (star.mass + planet.mass) / distance).sqrt();
+        let share = star.mass / (star.mass + planet.mass);
+        planet.velocity = (share * speed / distance) * offset.perp();
+        momentum += planet.mass * planet.velocity;
+    }
+    star.velocity = (-1.0 / star.mass) * momentum;
+}
+
+fn max_radius(particles: &[Particle]) -> f64 {
+    particles.iter().map(|p| p.radius).fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn particles(count: u64, radius: f64) -> Vec<Particle> {
+        (0..count)
+            .map(|id| Particle::new(id, radius + (id % 3) as f64, Vec2(0.0, 0.0), Vec2(1.0, 0.0)))
+            .collect()
+    }
+
+    fn assert_no_overlaps(particles: &[Particle]) {
+        for (index, a) in particles.iter().enumerate() {
+            for b in &particles[index + 1..] {
+                assert!(!a.check_pairwise_collision(b), "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_patterns_do_not_overlap() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        for pattern in [
+            SpawnPattern::Grid,
+            SpawnPattern::Ring,
+            SpawnPattern::Disk,
+            SpawnPattern::TwoClusters { speed: 5.0 },
+        ] {
+            let mut particles = particles(60, 8.0);
+            pattern.arrange(&mut particles, &mut rng).unwrap();
+            assert_no_overlaps(&particles);
+        }
+    }
+
+    #[test]
+    fn test_clusters_head_towards_each_other() {
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        let mut particles = particles(20, 5.0);
+        SpawnPattern::TwoClusters { speed: 5.0 }
+            .arrange(&mut particles, &mut rng)
+            .unwrap();
+        assert!(particles[..10]
+            .iter()
+            .all(|p| p.position.0 < WIDTH / 2.0 && p.velocity.0 == 6.0));
+        assert!(particles[10..]
+            .iter()
+            .all(|p| p.position.0 > WIDTH / 2.0 && p.velocity.0 == -4.0));
+    }
+
+    #[test]
+    fn test_overfull_pattern_is_an_error() {
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let mut particles = particles(500, 40.0);
+        assert!(SpawnPattern::Grid
+            .arrange(&mut particles, &mut rng)
+            .is_err());
+        assert!(SpawnPattern::Disk
+            .arrange(&mut particles, &mut rng)
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_patterns() {
+        assert_eq!("ring".parse::<SpawnPattern>().unwrap(), SpawnPattern::Ring);
+        assert_eq!(
+            "two-clusters:3".parse::<SpawnPattern>().unwrap(),
+            SpawnPattern::TwoClusters { speed: 3.0 }
+        );
+        assert_eq!(
+            "cradle:4".parse::<SpawnPattern>().unwrap(),
+            SpawnPattern::Cradle { speed: 4.0 }
+        );
+        assert!("cradle:-4".parse::<SpawnPattern>().is_err());
+        assert_eq!(
+            "orbits".parse::<SpawnPattern>().unwrap(),
+            SpawnPattern::Orbits
+        );
+        assert!("spiral".parse::<SpawnPattern>().is_err());
+    }
+}
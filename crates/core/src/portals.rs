@@ -0,0 +1,119 @@
+use crate::error::{SimError, SimResult};
+use crate::particle::Particle;
+use crate::utils::Vec2;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Two linked circular regions of the same size. A particle whose center moves
+/// into either of them comes out of the other at the same spot relative to its
+/// center, keeping its speed. Going from `a` to `b` the particle is turned
+/// anticlockwise by `rotation` radians, and back again going from `b` to `a`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Portal {
+    pub a: Vec2,
+    pub b: Vec2,
+    pub radius: f64,
+    pub rotation: f64,
+}
+
+impl Portal {
+    pub fn new(a: Vec2, b: Vec2, radius: f64) -> Self {
+        Portal {
+            a,
+            b,
+            radius,
+            rotation: 0.0,
+        }
+    }
+
+    pub fn with_rotation(self, rotation: f64) -> Self {
+        Portal { rotation, ..self }
+    }
+
+    pub fn teleport(&self, particle: &mut Particle, dt: f64) -> bool {
+        //! Move the particle through the portal if its center entered either end
+        //! during the last `dt`. Returns whether it was moved.
+        //! A particle that has just come out of one end starts inside it,
+        //! so it is not sent straight back.
+        let (position, velocity) = (particle.position, particle.velocity);
+        let previous = position - dt * velocity;
+        for (from, to, rotation) in [
+            (self.a, self.b, self.rotation),
+            (self.b, self.a, -self.rotation),
+        ] {
+            if position.distance(&from) < self.radius && previous.distance(&from) >= self.radius {
+                particle.position = to + (position - from).rotate(rotation);
+                particle.velocity = velocity.rotate(rotation);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl FromStr for Portal {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        //! Parse `<ax>,<ay>,<bx>,<by>,<radius>` with an optional
+        //! `,<degrees>` rotation on the end.
+        let invalid = || SimError::InvalidArgument {
+            name: "portal".to_string(),
+            value: value.to_string(),
+        };
+        let params: Vec<f64> = value
+            .split(',')
+            .map(|param| param.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+        let (ax, ay, bx, by, radius, degrees) = match params.as_slice() {
+            [ax, ay, bx, by, radius] => (*ax, *ay, *bx, *by, *radius, 0.0),
+            [ax, ay, bx, by, radius, degrees] => (*ax, *ay, *bx, *by, *radius, *degrees),
+            _ => return Err(invalid()),
+        };
+        if !(radius.is_finite() && radius > 0.0 && degrees.is_finite()) {
+            return Err(invalid());
+        }
+        Ok(Portal::new(Vec2(ax, ay), Vec2(bx, by), radius).with_rotation(degrees.to_radians()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_particle_comes_out_of_the_other_end() {
+        let portal = Portal::new(Vec2(100.0, 500.0), Vec2(900.0, 200.0), 20.0);
+        let mut particle = Particle::new(1, 5.0, Vec2(85.0, 505.0), Vec2(10.0, 0.0));
+        assert!(portal.teleport(&mut particle, 1.0));
+        assert_eq!(particle.position, Vec2(885.0, 205.0));
+        assert_eq!(particle.velocity, Vec2(10.0, 0.0));
+
+        // Now that it is inside the exit, it stays there
+        particle.update(1.0);
+        assert!(!portal.teleport(&mut particle, 1.0));
+    }
+
+    #[test]
+    fn test_rotation_turns_the_particle() {
+        let portal = Portal::new(Vec2(100.0, 500.0), Vec2(900.0, 200.0), 20.0)
+            .with_rotation(std::f64::consts::FRAC_PI_2);
+        let mut particle = Particle::new(1, 5.0, Vec2(85.0, 500.0), Vec2(10.0, 0.0));
+        portal.teleport(&mut particle, 1.0);
+        assert!((particle.position.0 - 900.0).abs() < 1e-9);
+        assert!((particle.position.1 - 185.0).abs() < 1e-9);
+        assert!(particle.velocity.0.abs() < 1e-9);
+        assert!((particle.velocity.1 - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse() {
+        let portal: Portal = "100,500,900,200,20,90".parse().unwrap();
+        assert_eq!(portal.b, Vec2(900.0, 200.0));
+        assert!((portal.rotation - std::f64::consts::FRAC_PI_2).abs() < 1e-12);
+        assert_eq!("1,2,3,4,5".parse::<Portal>().unwrap().rotation, 0.0);
+        assert!("1,2,3,4".parse::<Portal>().is_err());
+        assert!("1,2,3,4,-5".parse::<Portal>().is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::error::SimResult;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+use web_time::Instant;
+
+/// How many frames the rolling average shown in the HUD covers.
+const HISTORY_LENGTH: usize = 60;
+
+/// How many frames the CSV is flushed after, so that a run that is stopped
+/// rather than ending loses little more than the last second of it.
+const FLUSH_INTERVAL: u64 = 60;
+
+/// The parts of a frame that are timed separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Integration,
+    Sort,
+    SweepAndPrune,
+    NarrowPhase,
+    CollisionResponse,
+    Rendering,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 6] = [
+        Phase::Integration,
+        Phase::Sort,
+        Phase::SweepAndPrune,
+        Phase::NarrowPhase,
+        Phase::CollisionResponse,
+        Phase::Rendering,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Integration => "integration",
+            Phase::Sort => "sort",
+            Phase::SweepAndPrune => "sweep_and_prune",
+            Phase::NarrowPhase => "narrow_phase",
+            Phase::CollisionResponse => "collision_response",
+            Phase::Rendering => "rendering",
+        }
+    }
+}
+
+/// Time spent in each phase during a single frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameTimings([Duration; Phase::ALL.len()]);
+
+impl FrameTimings {
+    pub fn get(&self, phase: Phase) -> Duration {
+        self.0[phase as usize]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.0.iter().sum()
+    }
+}
+
+/// Collects per-phase timings for every frame, keeps a short history for
+/// display, and optionally appends every frame to a CSV file.
+#[derive(Default)]
+pub struct Profiler {
+    frame: u64,
+    current: FrameTimings,
+    history: VecDeque<FrameTimings>,
+    csv: Option<Box<dyn Write + Send>>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    pub fn export_csv(&mut self, path: &Path) -> SimResult<()> {
+        //! Start appending a row per frame to the CSV file at `path`.
+        let file = File::create(path)?;
+        self.set_csv_writer(Box::new(BufWriter::new(file)))
+    }
+
+    pub fn set_csv_writer(&mut self, mut writer: Box<dyn Write + Send>) -> SimResult<()> {
+        //! Write the CSV header to `writer`, and use it for every following frame.
+        let columns: Vec<String> = Phase::ALL
+            .iter()
+            .map(|phase| format!("{}_us", phase.name()))
+            .collect();
+        writeln!(writer, "frame,{},total_us", columns.join(","))?;
+        self.csv = Some(writer);
+        Ok(())
+    }
+
+    pub fn time<T>(&mut self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        //! Run `f`, adding the time it took to `phase` for the current frame.
+        let start = Instant::now();
+        let result = f();
+        self.record(phase, start.elapsed());
+        result
+    }
+
+    pub fn record(&mut self, phase: Phase, duration: Duration) {
+        self.current.0[phase as usize] += duration;
+    }
+
+    pub fn end_frame(&mut self) -> SimResult<()> {
+        //! Finish the current frame, storing its timings and writing them to the CSV.
+        let timings = std::mem::take(&mut self.current);
+        if let Some(csv) = self.csv.as_mut() {
+            let columns: Vec<String> = Phase::ALL
+                .iter()
+                .map(|phase| timings.get(*phase).as_micros().to_string())
+                .collect();
+            writeln!(
+                csv,
+                "{},{},{}",
+                self.frame,
+                columns.join(","),
+                timings.total().as_micros()
+            )?;
+            if (self.frame + 1).is_multiple_of(FLUSH_INTERVAL) {
+                csv.flush()?;
+            }
+        }
+
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(timings);
+        self.frame += 1;
+        Ok(())
+    }
+
+    pub fn average(&self) -> FrameTimings {
+        //! Average timings over the recent frames.
+        let mut average = FrameTimings::default();
+        if self.history.is_empty() {
+            return average;
+        }
+        for phase in Phase::ALL {
+            let sum: Duration = self.history.iter().map(|frame| frame.get(phase)).sum();
+            average.0[phase as usize] = sum / self.history.len() as u32;
+        }
+        average
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        //! One human readable line per phase, for display in the HUD.
+        let average = self.average();
+        let mut lines: Vec<String> = Phase::ALL
+            .iter()
+            .map(|phase| {
+                format!(
+                    "{}: {:.3} ms",
+                    phase.name(),
+                    average.get(*phase).as_secs_f64() * 1000.0
+                )
+            })
+            .collect();
+        lines.push(format!(
+            "total: {:.3} ms",
+            average.total().as_secs_f64() * 1000.0
+        ));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// A writer that can still be read after being handed to the profiler.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_and_average() {
+        let mut profiler = Profiler::new();
+        profiler.record(Phase::Sort, Duration::from_micros(10));
+        profiler.record(Phase::Sort, Duration::from_micros(10));
+        profiler.end_frame().unwrap();
+        profiler.record(Phase::Sort, Duration::from_micros(40));
+        profiler.end_frame().unwrap();
+
+        let average = profiler.average();
+        assert_eq!(average.get(Phase::Sort), Duration::from_micros(30));
+        assert_eq!(average.get(Phase::Rendering), Duration::ZERO);
+        assert_eq!(average.total(), Duration::from_micros(30));
+    }
+
+    #[test]
+    fn test_time_returns_closure_result() {
+        let mut profiler = Profiler::new();
+        assert_eq!(profiler.time(Phase::Integration, || 7), 7);
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let buffer = SharedBuffer::default();
+        let mut profiler = Profiler::new();
+        profiler.set_csv_writer(Box::new(buffer.clone())).unwrap();
+        profiler.record(Phase::Rendering, Duration::from_micros(250));
+        profiler.end_frame().unwrap();
+
+        let csv = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "frame,integration_us,sort_us,sweep_and_prune_us,narrow_phase_us,collision_response_us,rendering_us,total_us"
+        );
+        assert_eq!(lines[1], "0,0,0,0,0,0,250,250");
+    }
+
+    #[test]
+    fn test_csv_is_flushed_as_it_goes() {
+        let buffer = SharedBuffer::default();
+        let mut profiler = Profiler::new();
+        profiler
+            .set_csv_writer(Box::new(BufWriter::new(buffer.clone())))
+            .unwrap();
+        for _ in 0..FLUSH_INTERVAL {
+            profiler.end_frame().unwrap();
+        }
+        let csv = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(csv.lines().count(), FLUSH_INTERVAL as usize + 1);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::error::{SimError, SimResult};
+use crate::particle::HEIGHT;
+use crate::sweep_prune::SweepAxis;
+use crate::utils::Vec2;
+use rand::Rng;
+use rand_distr::StandardNormal;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Physics parameters that apply to the whole simulation,
+/// and that can be changed while it is running.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PhysicsSettings {
+    /// Acceleration applied to every particle. The y axis points up,
+    /// so a falling gravity has a negative y component.
+    pub gravity: Vec2,
+    /// What to do with a particle whose state has become NaN or infinite.
+    pub non_finite: NonFinitePolicy,
+    /// How bouncy and how rough each side of the box is.
+    pub walls: Walls,
+    /// The fastest a particle may move. Anything faster after a step is slowed
+    /// down to it, keeping its direction.
+    pub max_speed: Option<f64>,
+    /// What to do with a particle that has ended up outside of the box.
+    pub out_of_bounds: OutOfBoundsPolicy,
+    /// The axis the broadphase sorts along.
+    pub sweep_axis: SweepAxis,
+    /// A fluid filling the box that particles float or sink in under gravity.
+    pub medium: Option<Medium>,
+    /// The gravitational constant of the particles' pull on each other, if they
+    /// attract at all. Every pair is considered, so this is slow with many particles.
+    pub mutual_gravity: Option<f64>,
+}
+
+/// A fluid filling the box, with a density that changes linearly from the floor to
+/// the ceiling. Under gravity it pushes every particle up with the weight of the fluid
+/// it displaces, so a particle lighter than the fluid around it rises and a heavier
+/// one sinks, each towards the height where the two densities match. The fluid only
+/// pushes, it does not slow the particles down.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Medium {
+    pub floor_density: f64,
+    pub ceiling_density: f64,
+}
+
+impl Medium {
+    pub fn uniform(density: f64) -> Self {
+        Medium {
+            floor_density: density,
+            ceiling_density: density,
+        }
+    }
+
+    pub fn density_at(&self, height: f64) -> f64 {
+        //! The density of the fluid at `height` above the floor, held at the
+        //! floor and ceiling values outside of the box.
+        let share = (height / HEIGHT).clamp(0.0, 1.0);
+        self.floor_density + share * (self.ceiling_density - self.floor_density)
+    }
+
+    pub fn gravity_share(&self, height: f64, density: f64) -> f64 {
+        //! How much of gravity is left for a particle of `density` at `height`
+        //! once the fluid holds it up: 1 in a fluid with no density, 0 where the
+        //! particle is exactly as dense as the fluid, and negative where it floats.
+        1.0 - self.density_at(height) / density
+    }
+}
+
+/// The material of one side of the box.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Wall {
+    /// The share of the velocity towards the wall that is kept, reversed, after
+    /// a bounce. 1 is perfectly elastic and 0 stops the particle dead.
+    pub restitution: f64,
+    /// Coulomb friction coefficient. Each bounce slows the velocity along the wall
+    /// by up to `friction` times the change in velocity across it.
+    pub friction: f64,
+    /// Makes a thermal wall: a particle bouncing off of it leaves with a velocity
+    /// drawn from a gas at this temperature, as if the wall had absorbed it and
+    /// emitted it again, instead of being reflected. Shaped bodies keep their spin.
+    pub temperature: Option<f64>,
+}
+
+impl Default for Wall {
+    fn default() -> Self {
+        Wall {
+            restitution: 1.0,
+            friction: 0.0,
+            temperature: None,
+        }
+    }
+}
+
+impl Wall {
+    pub fn bounce(&self, normal: f64, tangential: f64) -> (f64, f64) {
+        //! The velocity across (`normal`) and along (`tangential`) the wall after
+        //! a bounce. Friction can stop the sliding, but never reverse it.
+        let new_normal = -self.restitution * normal;
+        let slowdown = (self.friction * (new_normal - normal).abs()).min(tangential.abs());
+        (new_normal, tangential - slowdown.copysign(tangential))
+    }
+
+    pub fn thermalize(&self, mass: f64, inward: Vec2, rng: &mut impl Rng) -> Option<Vec2> {
+        //! The velocity a particle of `mass` leaves a thermal wall with, or `None`
+        //! for an ordinary wall. With a Boltzmann constant of 1, the velocity along
+        //! the wall is normally distributed with a variance of `temperature / mass`,
+        //! and the speed away from it follows the Rayleigh distribution of that
+        //! spread, as faster particles leave the wall more often.
+        let temperature = self.temperature?;
+        if !(mass.is_finite() && mass > 0.0) {
+            return None;
+        }
+        let spread = (temperature / mass).sqrt();
+        let away = spread * (-2.0 * (1.0 - rng.gen::<f64>()).ln()).sqrt();
+        let along = spread * rng.sample::<f64, _>(StandardNormal);
+        Some(away * inward + along * inward.perp())
+    }
+}
+
+/// One side of the box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+impl Side {
+    pub fn inward(self) -> Vec2 {
+        //! The unit normal of this side, pointing into the box.
+        match self {
+            Side::Left => Vec2(1.0, 0.0),
+            Side::Right => Vec2(-1.0, 0.0),
+            Side::Bottom => Vec2(0.0, 1.0),
+            Side::Top => Vec2(0.0, -1.0),
+        }
+    }
+}
+
+impl FromStr for Side {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        //! Parse `left`, `right`, `bottom` or `top`.
+        match value {
+            "left" => Ok(Side::Left),
+            "right" => Ok(Side::Right),
+            "bottom" => Ok(Side::Bottom),
+            "top" => Ok(Side::Top),
+            _ => Err(SimError::InvalidArgument {
+                name: "side of the box".to_string(),
+                value: value.to_string(),
+            }),
+        }
+    }
+}
+
+/// The four sides of the box.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Walls {
+    pub left: Wall,
+    pub right: Wall,
+    pub bottom: Wall,
+    pub top: Wall,
+}
+
+impl Walls {
+    pub fn uniform(wall: Wall) -> Self {
+        //! Every side made of the same material.
+        Walls {
+            left: wall,
+            right: wall,
+            bottom: wall,
+            top: wall,
+        }
+    }
+
+    pub fn side(&self, side: Side) -> &Wall {
+        match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+            Side::Bottom => &self.bottom,
+            Side::Top => &self.top,
+        }
+    }
+
+    pub fn side_mut(&mut self, side: Side) -> &mut Wall {
+        match side {
+            Side::Left => &mut self.left,
+            Side::Right => &mut self.right,
+            Side::Bottom => &mut self.bottom,
+            Side::Top => &mut self.top,
+        }
+    }
+}
+
+/// How the simulation recovers from particles with non-finite values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum NonFinitePolicy {
+    /// Zero a non-finite velocity and pull a non-finite position back into the box.
+    /// A particle with a broken radius or mass cannot be repaired and is removed.
+    #[default]
+    Clamp,
+    /// Remove the particle from the simulation.
+    Remove,
+}
+
+/// How the simulation recovers particles that escaped the box, which only a
+/// violent collision pushing a particle through a wall can cause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OutOfBoundsPolicy {
+    /// Move the particle back to the nearest spot inside the box, heading inwards.
+    #[default]
+    Teleport,
+    /// Remove the particle from the simulation.
+    Remove,
+}
+
+impl FromStr for OutOfBoundsPolicy {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        //! Parse `teleport` or `remove`.
+        match value {
+            "teleport" => Ok(OutOfBoundsPolicy::Teleport),
+            "remove" => Ok(OutOfBoundsPolicy::Remove),
+            _ => Err(SimError::InvalidArgument {
+                name: "out of bounds policy".to_string(),
+                value: value.to_string(),
+            }),
+        }
+    }
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        PhysicsSettings {
+            gravity: Vec2(0.0, 0.0),
+            non_finite: NonFinitePolicy::default(),
+            walls: Walls::default(),
+            max_speed: None,
+            out_of_bounds: OutOfBoundsPolicy::default(),
+            sweep_axis: SweepAxis::default(),
+            medium: None,
+            mutual_gravity: None,
+        }
+    }
+}
@@ -0,0 +1,793 @@
+use crate::error::{SimError, SimResult};
+use crate::particle::{Particle, HEIGHT, WIDTH};
+use crate::settings::{Side, Wall, Walls};
+use crate::utils::Vec2;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+/// Corners within this distance of the deepest one push as well, so that a box
+/// lying flat on another pushes through the middle of the shared edge instead
+/// of through one of its corners, which would set it spinning.
+const CONTACT_TOLERANCE: f64 = 1e-3;
+
+/// The outline of a body, centered on the position of its particle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+    Circle {
+        radius: f64,
+    },
+    /// A rectangle, lined up with the axes before it is rotated.
+    Box {
+        half_width: f64,
+        half_height: f64,
+    },
+    /// Any convex polygon. The vertices are relative to its centroid,
+    /// in anticlockwise order.
+    ConvexPolygon {
+        vertices: Vec<Vec2>,
+    },
+    /// Every point within `radius` of a segment, which lies along the x axis
+    /// before it is rotated: a rectangle with round ends, like a rod or a pill.
+    Capsule {
+        half_length: f64,
+        radius: f64,
+    },
+}
+
+impl Shape {
+    pub fn regular_polygon(sides: usize, radius: f64) -> Self {
+        //! A polygon with `sides` equal sides, its corners `radius` from the center.
+        let vertices = (0..sides)
+            .map(|corner| Vec2(radius, 0.0).rotate(2.0 * PI * corner as f64 / sides as f64))
+            .collect();
+        Shape::ConvexPolygon { vertices }
+    }
+
+    pub fn local_vertices(&self) -> Vec<Vec2> {
+        //! The corners before rotation, in anticlockwise order. A circle has none,
+        //! and those of a capsule are the ends of its segment.
+        match self {
+            Shape::Circle { .. } => Vec::new(),
+            Shape::Box {
+                half_width: w,
+                half_height: h,
+            } => vec![Vec2(-w, -h), Vec2(*w, -h), Vec2(*w, *h), Vec2(-w, *h)],
+            Shape::ConvexPolygon { vertices } => vertices.clone(),
+            Shape::Capsule { half_length, .. } => {
+                vec![Vec2(-half_length, 0.0), Vec2(*half_length, 0.0)]
+            }
+        }
+    }
+
+    pub fn rounding(&self) -> f64 {
+        //! How far the outline reaches past the corners, in every direction.
+        match self {
+            Shape::Circle { radius } | Shape::Capsule { radius, .. } => *radius,
+            Shape::Box { .. } | Shape::ConvexPolygon { .. } => 0.0,
+        }
+    }
+
+    pub fn bounding_radius(&self) -> f64 {
+        //! The radius of the smallest circle around the center that holds the whole shape.
+        match self {
+            Shape::Circle { radius } => *radius,
+            _ => {
+                self.local_vertices()
+                    .iter()
+                    .map(Vec2::length)
+                    .fold(0.0, f64::max)
+                    + self.rounding()
+            }
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        match self {
+            Shape::Circle { radius } => PI * radius.powi(2),
+            Shape::Box {
+                half_width,
+                half_height,
+            } => 4.0 * half_width * half_height,
+            Shape::ConvexPolygon { vertices } => polygon_moments(vertices).0,
+            Shape::Capsule {
+                half_length,
+                radius,
+            } => 4.0 * half_length * radius + PI * radius.powi(2),
+        }
+    }
+
+    pub fn inertia(&self, mass: f64) -> f64 {
+        //! The moment of inertia about the center of a body of uniform density.
+        match self {
+            Shape::Circle { radius } => 0.5 * mass * radius.powi(2),
+            Shape::Box {
+                half_width,
+                half_height,
+            } => mass * (half_width.powi(2) + half_height.powi(2)) / 3.0,
+            Shape::ConvexPolygon { vertices } => mass * polygon_moments(vertices).1,
+            Shape::Capsule {
+                half_length: h,
+                radius: r,
+            } => {
+                // The rectangle in the middle, and the two half discs at the ends,
+                // which together make a disc whose mass is off center
+                let (middle, ends) = (4.0 * h * r, PI * r.powi(2));
+                let per_area = mass / (middle + ends);
+                per_area * middle * (h.powi(2) + r.powi(2)) / 3.0
+                    + per_area * ends * (r.powi(2) / 2.0 + h.powi(2) + 8.0 * h * r / (3.0 * PI))
+            }
+        }
+    }
+}
+
+fn polygon_moments(vertices: &[Vec2]) -> (f64, f64) {
+    //! The area of a polygon, and its second moment of area about the origin
+    //! divided by that area. Both are summed over the triangles between the
+    //! origin and each edge.
+    let mut area = 0.0;
+    let mut moment = 0.0;
+    for (index, a) in vertices.iter().enumerate() {
+        let b = vertices[(index + 1) % vertices.len()];
+        let cross = a.cross(&b);
+        area += cross / 2.0;
+        moment += cross * (a.inner_product(a) + a.inner_product(&b) + b.inner_product(&b)) / 12.0;
+    }
+    (area, if area > 0.0 { moment / area } else { 0.0 })
+}
+
+/// Gives a particle a `Shape`, and lets it spin. Particles without a body are
+/// circles of their own radius that never turn. The particle's `radius` should
+/// be the `bounding_radius` of the shape, which the broadphase, obstacles and
+/// portals keep treating the body as.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Body {
+    pub shape: Shape,
+    /// Anticlockwise rotation from the shape's own axes, in radians.
+    pub angle: f64,
+    /// Anticlockwise, in radians per unit of time.
+    pub angular_velocity: f64,
+}
+
+impl Body {
+    pub fn new(shape: Shape) -> Self {
+        Body {
+            shape,
+            angle: 0.0,
+            angular_velocity: 0.0,
+        }
+    }
+
+    pub fn particle(&self, id: u64, position: Vec2, velocity: Vec2) -> Particle {
+        //! A particle to carry this body, whose radius holds the whole shape
+        //! and whose mass is the area of the shape.
+        let mut particle = Particle::new(id, self.shape.bounding_radius(), position, velocity);
+        particle.mass = self.shape.area();
+        particle
+    }
+
+    pub fn vertices(&self, position: Vec2) -> Vec<Vec2> {
+        //! The corners of the shape after rotating it and moving it to `position`.
+        self.shape
+            .local_vertices()
+            .into_iter()
+            .map(|vertex| position + vertex.rotate(self.angle))
+            .collect()
+    }
+
+    fn inverse_inertia(&self, particle: &Particle) -> f64 {
+        //! Zero for a body that cannot be turned, as an infinite mass cannot be moved.
+        let inertia = self.shape.inertia(particle.effective_mass());
+        if inertia.is_finite() && inertia > 0.0 {
+            1.0 / inertia
+        } else {
+            0.0
+        }
+    }
+
+    pub fn update_with_walls(
+        &mut self,
+        particle: &mut Particle,
+        dt: f64,
+        walls: &Walls,
+    ) -> [Option<Side>; 2] {
+        //! Move and turn the body by `dt`, then bounce it off of any wall that
+        //! one of its corners (or the round end of a capsule) went past. The bounce
+        //! pushes at that corner, so it spins the body as well as reflecting it.
+        //! Returns the sides it bounced off of, at most one per axis.
+        let mut bounced_off = [None, None];
+        if particle.fixed {
+            return bounced_off;
+        }
+        self.angle += dt * self.angular_velocity;
+        if let Shape::Circle { .. } = self.shape {
+            return particle.update_with_walls(dt, walls);
+        }
+        particle.position += dt * particle.velocity;
+        let rounding = self.shape.rounding();
+
+        for (axis, size, low, high) in [
+            (0, WIDTH, Side::Left, Side::Right),
+            (1, HEIGHT, Side::Bottom, Side::Top),
+        ] {
+            let vertices = self.vertices(particle.position);
+            let by_axis = |a: &&Vec2, b: &&Vec2| a[axis].total_cmp(&b[axis]);
+            let (Some(&lowest), Some(&highest)) = (
+                vertices.iter().min_by(by_axis),
+                vertices.iter().max_by(by_axis),
+            ) else {
+                continue;
+            };
+            // The normal of the wall points into the box
+            let mut normal = Vec2::ZERO;
+            let (side, corner, overshoot) = if lowest[axis] - rounding < 0.0 {
+                normal[axis] = 1.0;
+                (low, lowest, rounding - lowest[axis])
+            } else if highest[axis] + rounding > size {
+                normal[axis] = -1.0;
+                (high, highest, highest[axis] + rounding - size)
+            } else {
+                continue;
+            };
+            // Put the corner back on the wall before it pushes off of it
+            particle.position += overshoot * normal;
+            let touching = corner + (overshoot - rounding) * normal;
+            if self.bounce(particle, walls.side(side), touching, normal) {
+                bounced_off[axis] = Some(side);
+            }
+        }
+        bounced_off
+    }
+
+    fn bounce(&mut self, particle: &mut Particle, wall: &Wall, point: Vec2, normal: Vec2) -> bool {
+        //! Apply the impulse of a wall pushing at `point` along `normal`, and the
+        //! friction that it causes. A wall cannot be moved, so the result does
+        //! not depend on the mass of the body and everything is per unit of mass.
+        //! Returns false if the corner was already moving away from the wall.
+        let inverse_inertia = 1.0 / self.shape.inertia(1.0);
+        let offset = point - particle.position;
+        let point_velocity = |particle: &Particle, body: &Body| {
+            particle.velocity + body.angular_velocity * offset.perp()
+        };
+        let approach = point_velocity(particle, self).inner_product(&normal);
+        if approach >= 0.0 {
+            return false;
+        }
+        let lever = offset.cross(&normal);
+        let impulse =
+            -(1.0 + wall.restitution) * approach / (1.0 + lever.powi(2) * inverse_inertia);
+        particle.velocity += impulse * normal;
+        self.angular_velocity += lever * impulse * inverse_inertia;
+
+        // Friction can stop the sliding of the corner, but never reverse it
+        let tangent = normal.perp();
+        let slide = point_velocity(particle, self).inner_product(&tangent);
+        let lever = offset.cross(&tangent);
+        let limit = wall.friction * impulse;
+        let friction = (-slide / (1.0 + lever.powi(2) * inverse_inertia)).clamp(-limit, limit);
+        particle.velocity += friction * tangent;
+        self.angular_velocity += lever * friction * inverse_inertia;
+        true
+    }
+}
+
+/// Where and how deeply two bodies overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Contact {
+    /// A unit vector pointing from the first body into the second.
+    pub normal: Vec2,
+    /// How far the bodies would have to move apart along `normal` to just touch.
+    pub penetration_depth: f64,
+    /// The middle of the overlap, where the bodies push on each other.
+    pub point: Vec2,
+}
+
+/// A body's outline in world coordinates.
+enum Outline {
+    Circle(Vec2, f64),
+    Polygon(Vec<Vec2>),
+    /// The ends of the segment, and the radius around it.
+    Capsule(Vec<Vec2>, f64),
+}
+
+impl Outline {
+    fn rounded(&self) -> (&[Vec2], f64) {
+        //! The outline as a point, segment or polygon, and a radius around it.
+        match self {
+            Outline::Circle(center, radius) => (std::slice::from_ref(center), *radius),
+            Outline::Polygon(polygon) => (polygon, 0.0),
+            Outline::Capsule(ends, radius) => (ends, *radius),
+        }
+    }
+}
+
+fn outline(particle: &Particle, body: Option<&Body>) -> Outline {
+    match body {
+        None => Outline::Circle(particle.position, particle.radius),
+        Some(Body {
+            shape: Shape::Circle { radius },
+            ..
+        }) => Outline::Circle(particle.position, *radius),
+        Some(
+            body @ Body {
+                shape: Shape::Capsule { radius, .. },
+                ..
+            },
+        ) => Outline::Capsule(body.vertices(particle.position), *radius),
+        Some(body) => Outline::Polygon(body.vertices(particle.position)),
+    }
+}
+
+pub fn contact(
+    a: &Particle,
+    body_a: Option<&Body>,
+    b: &Particle,
+    body_b: Option<&Body>,
+) -> Option<Contact> {
+    //! Find out whether two bodies overlap, and if so how. A particle
+    //! without a body is a circle of its radius.
+    match (outline(a, body_a), outline(b, body_b)) {
+        (Outline::Circle(center_a, radius_a), Outline::Circle(center_b, radius_b)) => {
+            circles_contact(center_a, radius_a, center_b, radius_b)
+        }
+        (Outline::Polygon(polygon_a), Outline::Polygon(polygon_b)) => {
+            polygons_contact(&polygon_a, &polygon_b)
+        }
+        (Outline::Polygon(polygon), Outline::Circle(center, radius)) => {
+            polygon_circle_contact(&polygon, center, radius)
+        }
+        (Outline::Circle(center, radius), Outline::Polygon(polygon)) => {
+            polygon_circle_contact(&polygon, center, radius).map(|contact| Contact {
+                normal: -contact.normal,
+                ..contact
+            })
+        }
+        // Anything involving a capsule
+        (outline_a, outline_b) => {
+            let ((core_a, radius_a), (core_b, radius_b)) =
+                (outline_a.rounded(), outline_b.rounded());
+            rounded_contact(core_a, radius_a, core_b, radius_b)
+        }
+    }
+}
+
+pub(crate) fn circles_contact(
+    center_a: Vec2,
+    radius_a: f64,
+    center_b: Vec2,
+    radius_b: f64,
+) -> Option<Contact> {
+    let offset = center_b - center_a;
+    let distance = offset.length();
+    if distance >= radius_a + radius_b {
+        return None;
+    }
+    // Coincident circles can be pushed apart in any direction
+    let normal = if distance > 0.0 {
+        offset.normalize()
+    } else {
+        Vec2(1.0, 0.0)
+    };
+    let depth = radius_a + radius_b - distance;
+    Some(Contact {
+        normal,
+        penetration_depth: depth,
+        point: center_a + (radius_a - depth / 2.0) * normal,
+    })
+}
+
+fn edges(polygon: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    //! Every edge as its first corner and its outward unit normal.
+    polygon.iter().enumerate().map(|(index, &start)| {
+        let end = polygon[(index + 1) % polygon.len()];
+        (start, -(end - start).perp().normalize())
+    })
+}
+
+fn project(polygon: &[Vec2], axis: Vec2) -> (f64, f64) {
+    polygon
+        .iter()
+        .map(|vertex| vertex.inner_product(&axis))
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), along| {
+            (min.min(along), max.max(along))
+        })
+}
+
+fn support(polygon: &[Vec2], direction: Vec2) -> Vec2 {
+    //! The corner furthest along `direction`, or the middle of the
+    //! edge that is, if two corners are equally far.
+    let furthest = project(polygon, direction).1;
+    let nearby: Vec<Vec2> = polygon
+        .iter()
+        .copied()
+        .filter(|vertex| vertex.inner_product(&direction) >= furthest - CONTACT_TOLERANCE)
+        .collect();
+    let sum = nearby.iter().fold(Vec2::ZERO, |sum, &vertex| sum + vertex);
+    (1.0 / nearby.len() as f64) * sum
+}
+
+fn centroid(polygon: &[Vec2]) -> Vec2 {
+    let sum = polygon.iter().fold(Vec2::ZERO, |sum, &vertex| sum + vertex);
+    (1.0 / polygon.len() as f64) * sum
+}
+
+fn polygons_contact(polygon_a: &[Vec2], polygon_b: &[Vec2]) -> Option<Contact> {
+    //! The separating axis theorem: two convex polygons are apart exactly when
+    //! their shadows on one of the edge normals do not overlap. If every shadow
+    //! overlaps, the axis with the smallest overlap is the way out.
+    let mut best: Option<(f64, Vec2, bool)> = None;
+    for (polygon, from_a) in [(polygon_a, true), (polygon_b, false)] {
+        for (_, axis) in edges(polygon) {
+            let (min_a, max_a) = project(polygon_a, axis);
+            let (min_b, max_b) = project(polygon_b, axis);
+            let overlap = (max_a - min_b).min(max_b - min_a);
+            if overlap <= 0.0 {
+                return None;
+            }
+            if best.is_none_or(|(smallest, _, _)| overlap < smallest) {
+                best = Some((overlap, axis, from_a));
+            }
+        }
+    }
+    let (depth, mut normal, from_a) = best?;
+    if (centroid(polygon_b) - centroid(polygon_a)).inner_product(&normal) < 0.0 {
+        normal = -normal;
+    }
+    // The deepest corners of the other polygon have gone into the one with the edge
+    let point = if from_a {
+        support(polygon_b, -normal) + (depth / 2.0) * normal
+    } else {
+        support(polygon_a, normal) - (depth / 2.0) * normal
+    };
+    Some(Contact {
+        normal,
+        penetration_depth: depth,
+        point,
+    })
+}
+
+fn closest_on_segment(point: Vec2, start: Vec2, end: Vec2) -> Vec2 {
+    let along = end - start;
+    let length_squared = along.inner_product(&along);
+    if length_squared == 0.0 {
+        return start;
+    }
+    let t = ((point - start).inner_product(&along) / length_squared).clamp(0.0, 1.0);
+    start + t * along
+}
+
+fn segments(core: &[Vec2]) -> Vec<(Vec2, Vec2)> {
+    //! A point as a segment of no length, a segment, or the edges of a polygon.
+    match core {
+        [] => Vec::new(),
+        [point] => vec![(*point, *point)],
+        [start, end] => vec![(*start, *end)],
+        polygon => (0..polygon.len())
+            .map(|index| (polygon[index], polygon[(index + 1) % polygon.len()]))
+            .collect(),
+    }
+}
+
+fn closest_points(core_a: &[Vec2], core_b: &[Vec2]) -> Option<(Vec2, Vec2)> {
+    //! The closest pair of points on the outlines of two points, segments or
+    //! polygons that do not overlap. When two segments do not cross, one
+    //! of the points of the closest pair is at the end of a segment.
+    let mut candidates = Vec::new();
+    for (start_a, end_a) in segments(core_a) {
+        for (start_b, end_b) in segments(core_b) {
+            candidates.extend([
+                (start_a, closest_on_segment(start_a, start_b, end_b)),
+                (end_a, closest_on_segment(end_a, start_b, end_b)),
+                (closest_on_segment(start_b, start_a, end_a), start_b),
+                (closest_on_segment(end_b, start_a, end_a), end_b),
+            ]);
+        }
+    }
+    candidates
+        .into_iter()
+        .min_by(|(a1, b1), (a2, b2)| a1.distance(b1).total_cmp(&a2.distance(b2)))
+}
+
+fn rounded_contact(
+    core_a: &[Vec2],
+    radius_a: f64,
+    core_b: &[Vec2],
+    radius_b: f64,
+) -> Option<Contact> {
+    //! The contact between two outlines that are each a point, segment or polygon
+    //! with everything within a radius of it added, such as a capsule.
+    if core_a.len() > 1 && core_b.len() > 1 {
+        if let Some(contact) = polygons_contact(core_a, core_b) {
+            // The cores overlap already, and the radii only make it deeper
+            return Some(Contact {
+                penetration_depth: contact.penetration_depth + radius_a + radius_b,
+                point: contact.point + ((radius_a - radius_b) / 2.0) * contact.normal,
+                ..contact
+            });
+        }
+    }
+    let (closest_a, closest_b) = closest_points(core_a, core_b)?;
+    circles_contact(closest_a, radius_a, closest_b, radius_b)
+}
+
+fn polygon_circle_contact(polygon: &[Vec2], center: Vec2, radius: f64) -> Option<Contact> {
+    //! The contact between a polygon and a circle, with the normal pointing into the circle.
+    let (_, normal, separation) = edges(polygon)
+        .map(|(start, normal)| (start, normal, (center - start).inner_product(&normal)))
+        .max_by(|a, b| a.2.total_cmp(&b.2))?;
+    if separation > radius {
+        return None;
+    }
+    if separation <= 0.0 {
+        // The center is inside, so the circle leaves through the nearest edge
+        let depth = radius - separation;
+        return Some(Contact {
+            normal,
+            penetration_depth: depth,
+            point: center - ((separation + radius) / 2.0) * normal,
+        });
+    }
+    let closest = edges(polygon)
+        .enumerate()
+        .map(|(index, (start, _))| {
+            closest_on_segment(center, start, polygon[(index + 1) % polygon.len()])
+        })
+        .min_by(|a, b| a.distance(&center).total_cmp(&b.distance(&center)))?;
+    let distance = closest.distance(&center);
+    if distance >= radius {
+        return None;
+    }
+    let normal = (center - closest).normalize();
+    let depth = radius - distance;
+    Some(Contact {
+        normal,
+        penetration_depth: depth,
+        point: closest - (depth / 2.0) * normal,
+    })
+}
+
+pub fn collision_react(
+    a: &Particle,
+    body_a: Option<&Body>,
+    b: &Particle,
+    body_b: Option<&Body>,
+    contact: &Contact,
+) -> ((Vec2, f64), (Vec2, f64)) {
+    //! The new velocities and angular velocities of two bodies after an elastic
+    //! collision at `contact`. The impulse acts at the contact point, so an
+    //! off-center hit turns the bodies as well as pushing them apart.
+    //! Bodies whose contact points are already moving apart are left alone.
+    let spin = |body: Option<&Body>| body.map_or(0.0, |body| body.angular_velocity);
+    let inverse_mass = |particle: &Particle| 1.0 / particle.effective_mass();
+    let inverse_inertia = |particle: &Particle, body: Option<&Body>| {
+        body.map_or(0.0, |body| body.inverse_inertia(particle))
+    };
+    let (spin_a, spin_b) = (spin(body_a), spin(body_b));
+    let unchanged = ((a.velocity, spin_a), (b.velocity, spin_b));
+
+    let normal = contact.normal;
+    let (offset_a, offset_b) = (contact.point - a.position, contact.point - b.position);
+    let relative =
+        (b.velocity + spin_b * offset_b.perp()) - (a.velocity + spin_a * offset_a.perp());
+    let approach = relative.inner_product(&normal);
+    if approach >= 0.0 {
+        return unchanged;
+    }
+
+    let (mass_a, mass_b) = (inverse_mass(a), inverse_mass(b));
+    let (inertia_a, inertia_b) = (inverse_inertia(a, body_a), inverse_inertia(b, body_b));
+    let (lever_a, lever_b) = (offset_a.cross(&normal), offset_b.cross(&normal));
+    let resistance = mass_a + mass_b + lever_a.powi(2) * inertia_a + lever_b.powi(2) * inertia_b;
+    if resistance <= 0.0 {
+        // Neither can be moved, so both carry on as they were
+        return unchanged;
+    }
+    let impulse = -2.0 * approach / resistance;
+    (
+        (
+            a.velocity - (impulse * mass_a) * normal,
+            spin_a - impulse * lever_a * inertia_a,
+        ),
+        (
+            b.velocity + (impulse * mass_b) * normal,
+            spin_b + impulse * lever_b * inertia_b,
+        ),
+    )
+}
+
+/// The shape given to random particles, fitted inside the circle of the sampled radius.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SpawnShape {
+    #[default]
+    Circle,
+    /// A square, at a random angle.
+    Box,
+    /// A regular polygon with this many sides, at a random angle.
+    Polygon(usize),
+    /// A capsule twice as long as it is wide, at a random angle.
+    Capsule,
+}
+
+impl SpawnShape {
+    pub fn body(&self, radius: f64, rng: &mut impl Rng) -> Option<Body> {
+        //! The body for a random particle of `radius`, or `None` for a plain circle.
+        let shape = match self {
+            SpawnShape::Circle => return None,
+            SpawnShape::Box => Shape::Box {
+                half_width: radius / 2f64.sqrt(),
+                half_height: radius / 2f64.sqrt(),
+            },
+            SpawnShape::Polygon(sides) => Shape::regular_polygon(*sides, radius),
+            SpawnShape::Capsule => Shape::Capsule {
+                half_length: radius / 2.0,
+                radius: radius / 2.0,
+            },
+        };
+        Some(Body {
+            angle: rng.gen::<f64>() * 2.0 * PI,
+            ..Body::new(shape)
+        })
+    }
+}
+
+impl FromStr for SpawnShape {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        //! Parse `circle`, `box`, `capsule` or `polygon:<sides>`, with at least 3 sides.
+        match value.split_once(':') {
+            None if value == "circle" => Ok(SpawnShape::Circle),
+            None if value == "box" => Ok(SpawnShape::Box),
+            None if value == "capsule" => Ok(SpawnShape::Capsule),
+            Some(("polygon", sides)) => match sides.trim().parse::<usize>() {
+                Ok(sides) if sides >= 3 => Ok(SpawnShape::Polygon(sides)),
+                _ => Err(SimError::InvalidArgument {
+                    name: "shape".to_string(),
+                    value: value.to_string(),
+                }),
+            },
+            _ => Err(SimError::InvalidArgument {
+                name: "shape".to_string(),
+                value: value.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(half_size: f64) -> Body {
+        Body::new(Shape::Box {
+            half_width: half_size,
+            half_height: half_size,
+        })
+    }
+
+    #[test]
+    fn test_separating_axis_finds_the_shallowest_overlap() {
+        let body = square(10.0);
+        let a = body.particle(1, Vec2(100.0, 100.0), Vec2::ZERO);
+        let b = body.particle(2, Vec2(118.0, 105.0), Vec2::ZERO);
+        let found = contact(&a, Some(&body), &b, Some(&body)).unwrap();
+        assert_eq!(found.normal, Vec2(1.0, 0.0));
+        assert!((found.penetration_depth - 2.0).abs() < 1e-9);
+        // Halfway into the overlap, in the middle of the edge that went in
+        assert!(found.point.distance(&Vec2(109.0, 105.0)) < 1e-9);
+
+        // Turned by 45 degrees, the second box's bounding box still overlaps
+        // the first one, but the box itself does not
+        let diamond = Body {
+            angle: PI / 4.0,
+            ..square(10.0)
+        };
+        let c = diamond.particle(3, Vec2(122.0, 122.0), Vec2::ZERO);
+        assert!(contact(&a, Some(&body), &c, Some(&diamond)).is_none());
+
+        // A circle without a body against the box, pointing from the circle
+        let ball = Particle::new(4, 5.0, Vec2(100.0, 88.0), Vec2::ZERO);
+        let found = contact(&ball, None, &a, Some(&body)).unwrap();
+        assert!(found.normal.distance(&Vec2(0.0, 1.0)) < 1e-12);
+        assert!((found.penetration_depth - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_off_center_hit_conserves_momentum_and_energy() {
+        let body = square(10.0);
+        let a = body.particle(1, Vec2(100.0, 100.0), Vec2(3.0, 0.0));
+        let b = body.particle(2, Vec2(119.0, 112.0), Vec2(-1.0, 0.5));
+        let found = contact(&a, Some(&body), &b, Some(&body)).unwrap();
+        let ((va, wa), (vb, wb)) = collision_react(&a, Some(&body), &b, Some(&body), &found);
+        // The corner of the first box hit the side of the second, which sets it spinning
+        assert!(wa != 0.0);
+        assert_eq!(wb, 0.0);
+
+        let momentum = |va: Vec2, vb: Vec2| a.mass * va + b.mass * vb;
+        let before = momentum(a.velocity, b.velocity);
+        assert!(before.distance(&momentum(va, vb)) < 1e-9 * a.mass);
+        let inertia = body.shape.inertia(a.mass);
+        let energy = |va: Vec2, wa: f64, vb: Vec2, wb: f64| {
+            a.mass * (va.inner_product(&va) + vb.inner_product(&vb))
+                + inertia * (wa.powi(2) + wb.powi(2))
+        };
+        let energy_before = energy(a.velocity, 0.0, b.velocity, 0.0);
+        assert!((energy(va, wa, vb, wb) - energy_before).abs() < 1e-9 * energy_before);
+    }
+
+    #[test]
+    fn test_box_tumbles_off_of_the_floor() {
+        let mut body = Body {
+            angle: 0.3,
+            ..square(10.0)
+        };
+        let mut particle = body.particle(1, Vec2(500.0, 14.0), Vec2(0.0, -5.0));
+        body.update_with_walls(&mut particle, 1.0, &Walls::default());
+        // Landing on a corner bounces the box up and sets it turning
+        assert!(particle.velocity.1 > 0.0);
+        assert!(body.angular_velocity != 0.0);
+        assert!(body
+            .vertices(particle.position)
+            .iter()
+            .all(|v| v.1 >= -1e-9));
+        // An elastic floor keeps the energy
+        let energy = particle.velocity.inner_product(&particle.velocity)
+            + body.shape.inertia(1.0) * body.angular_velocity.powi(2);
+        assert!((energy - 25.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_capsule_contacts() {
+        let rod = Body::new(Shape::Capsule {
+            half_length: 20.0,
+            radius: 5.0,
+        });
+        let a = rod.particle(1, Vec2(100.0, 100.0), Vec2::ZERO);
+        assert_eq!(a.radius, 25.0);
+
+        // A circle touching the middle of the side is pushed straight out
+        let ball = Particle::new(2, 5.0, Vec2(110.0, 108.0), Vec2::ZERO);
+        let found = contact(&a, Some(&rod), &ball, None).unwrap();
+        assert!(found.normal.distance(&Vec2(0.0, 1.0)) < 1e-12);
+        assert!((found.penetration_depth - 2.0).abs() < 1e-9);
+        // Past the round end it is pushed away from the end of the segment
+        let ball = Particle::new(3, 6.0, Vec2(126.0, 108.0), Vec2::ZERO);
+        let found = contact(&ball, None, &a, Some(&rod)).unwrap();
+        assert!(found.normal.distance(&Vec2(-0.6, -0.8)) < 1e-12);
+
+        // Crossing rods overlap by more than their radii
+        let crossing = Body {
+            angle: PI / 2.0,
+            ..rod.clone()
+        };
+        let b = crossing.particle(4, Vec2(105.0, 100.0), Vec2::ZERO);
+        let found = contact(&a, Some(&rod), &b, Some(&crossing)).unwrap();
+        assert!(found.penetration_depth > 10.0);
+        assert!(found.normal.inner_product(&(b.position - a.position)) > 0.0);
+
+        // Side by side rods touch along their length, and end to end ones not at all
+        let b = rod.particle(5, Vec2(95.0, 109.0), Vec2::ZERO);
+        let found = contact(&a, Some(&rod), &b, Some(&rod)).unwrap();
+        assert!(found.normal.distance(&Vec2(0.0, 1.0)) < 1e-12);
+        let b = rod.particle(6, Vec2(151.0, 100.0), Vec2::ZERO);
+        assert!(contact(&a, Some(&rod), &b, Some(&rod)).is_none());
+    }
+
+    #[test]
+    fn test_parse_spawn_shape() {
+        assert_eq!("box".parse::<SpawnShape>().unwrap(), SpawnShape::Box);
+        assert_eq!(
+            "polygon:5".parse::<SpawnShape>().unwrap(),
+            SpawnShape::Polygon(5)
+        );
+        assert!("polygon:2".parse::<SpawnShape>().is_err());
+        assert_eq!(
+            "capsule".parse::<SpawnShape>().unwrap(),
+            SpawnShape::Capsule
+        );
+        assert!("star".parse::<SpawnShape>().is_err());
+        let area = Shape::regular_polygon(4, 2f64.sqrt()).area();
+        assert!((area - 4.0).abs() < 1e-9);
+    }
+}
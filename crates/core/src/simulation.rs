@@ -0,0 +1,1195 @@
+use crate::checkpoint::{Checkpoint, SavedEntity};
+use crate::components::{Appearance, Charge, GravityScale, Lifetime, Species};
+use crate::error::{SimError, SimResult};
+use crate::events::{ContactCache, Event};
+use crate::export::{self, Exporter};
+use crate::gravity;
+use crate::invariants;
+use crate::obstacles::Obstacle;
+use crate::particle::{Particle, HEIGHT, WIDTH};
+use crate::patterns::{self, SpawnPattern};
+use crate::portals::Portal;
+use crate::profiler::{Phase, Profiler};
+use crate::settings::PhysicsSettings;
+use crate::shapes::Body;
+use crate::spawn::SpawnSettings;
+use crate::species::SpeciesTable;
+use crate::sweep_prune::Divergence;
+use crate::systems;
+use crate::time_regions::TimeRegion;
+use crate::utils::{self, Vec2};
+use hecs::{Entity, EntityBuilder, World};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// The simulation owns every entity in an ECS `World`.
+/// Each step runs the systems in a fixed order, timing each of them,
+/// and then hands the world to every exporter.
+/// All randomness comes from the simulation's own seedable RNG,
+/// so that a run can be reproduced from its seed or a checkpoint.
+pub struct Simulation {
+    world: World,
+    settings: PhysicsSettings,
+    spawn_settings: SpawnSettings,
+    species: SpeciesTable,
+    obstacles: Vec<Obstacle>,
+    portals: Vec<Portal>,
+    time_regions: Vec<TimeRegion>,
+    profiler: Profiler,
+    exporters: Vec<Box<dyn Exporter>>,
+    events: Vec<Event>,
+    /// Only kept up to date while someone is looking, see `set_track_contacts`.
+    contacts: ContactCache,
+    track_contacts: bool,
+    /// The pressure on the walls during the last step.
+    pressure: f64,
+    frame: u64,
+    next_id: u64,
+    rng: ChaCha8Rng,
+    paranoid: bool,
+    /// The broadphase divergences found so far, if they are being looked for.
+    divergences: Option<u64>,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Simulation::new()
+    }
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        //! An empty simulation, with an RNG seeded from the operating system.
+        Simulation::with_rng(ChaCha8Rng::from_entropy())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        //! An empty simulation whose random particles are the same on every run.
+        Simulation::with_rng(ChaCha8Rng::seed_from_u64(seed))
+    }
+
+    fn with_rng(rng: ChaCha8Rng) -> Self {
+        Simulation {
+            world: World::new(),
+            settings: PhysicsSettings::default(),
+            spawn_settings: SpawnSettings::default(),
+            species: SpeciesTable::new(),
+            obstacles: Vec::new(),
+            portals: Vec::new(),
+            time_regions: Vec::new(),
+            profiler: Profiler::new(),
+            exporters: Vec::new(),
+            events: Vec::new(),
+            contacts: ContactCache::new(),
+            pressure: 0.0,
+            frame: 0,
+            next_id: 0,
+            rng,
+            paranoid: false,
+            track_contacts: false,
+            divergences: None,
+        }
+    }
+
+    pub fn from_checkpoint(checkpoint: Checkpoint) -> Self {
+        //! Restore a simulation saved with `checkpoint`. Entities are
+        //! spawned in their saved order, so stepping continues exactly
+        //! as it would have in the original run.
+        let mut simulation = Simulation::with_rng(checkpoint.rng);
+        simulation.settings = checkpoint.settings;
+        simulation.spawn_settings = checkpoint.spawn_settings;
+        simulation.species = checkpoint.species;
+        simulation.obstacles = checkpoint.obstacles;
+        simulation.portals = checkpoint.portals;
+        simulation.time_regions = checkpoint.time_regions;
+        simulation.frame = checkpoint.frame;
+        simulation.next_id = checkpoint.next_id;
+        for saved in checkpoint.entities {
+            let mut builder = EntityBuilder::new();
+            builder.add(saved.particle);
+            if let Some(charge) = saved.charge {
+                builder.add(charge);
+            }
+            if let Some(species) = saved.species {
+                builder.add(species);
+            }
+            if let Some(gravity_scale) = saved.gravity_scale {
+                builder.add(gravity_scale);
+            }
+            if let Some(lifetime) = saved.lifetime {
+                builder.add(lifetime);
+            }
+            if let Some(appearance) = saved.appearance {
+                builder.add(appearance);
+            }
+            if let Some(body) = saved.body {
+                builder.add(body);
+            }
+            simulation.world.spawn(builder.build());
+        }
+        simulation
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        //! Capture everything needed to carry on from this frame: the entities,
+        //! settings, frame counter and RNG state. Exporters and the profiler
+        //! are not part of a checkpoint.
+        let entities = self
+            .world
+            .iter()
+            .filter_map(|entity| {
+                Some(SavedEntity {
+                    particle: *entity.get::<&Particle>()?,
+                    charge: entity.get::<&Charge>().map(|charge| *charge),
+                    species: entity.get::<&Species>().map(|species| *species),
+                    gravity_scale: entity.get::<&GravityScale>().map(|scale| *scale),
+                    lifetime: entity.get::<&Lifetime>().map(|lifetime| *lifetime),
+                    appearance: entity.get::<&Appearance>().map(|appearance| *appearance),
+                    body: entity.get::<&Body>().map(|body| (*body).clone()),
+                })
+            })
+            .collect();
+        Checkpoint {
+            frame: self.frame,
+            next_id: self.next_id,
+            settings: self.settings,
+            spawn_settings: self.spawn_settings,
+            species: self.species.clone(),
+            obstacles: self.obstacles.clone(),
+            portals: self.portals.clone(),
+            time_regions: self.time_regions.clone(),
+            rng: self.rng.clone(),
+            entities,
+        }
+    }
+
+    pub fn with_random_particles(count: usize) -> Self {
+        //! Create a simulation populated by `count` random particles,
+        //! each given a random color to be rendered with.
+        let mut simulation = Simulation::new();
+        simulation.spawn_random(count);
+        simulation
+    }
+
+    pub fn spawn_random(&mut self, count: usize) {
+        //! Add `count` random particles, with unused ids and random colors.
+        //! Their sizes and shapes are chosen according to the `SpawnSettings`.
+        for _ in 0..count {
+            let particle = self
+                .spawn_settings
+                .random_particle(self.next_id, &mut self.rng);
+            self.next_id += 1;
+            self.spawn_colored(particle);
+        }
+    }
+
+    fn spawn_colored(&mut self, mut particle: Particle) {
+        //! Spawn a random particle with a random color, and a body if the
+        //! spawn settings ask for a shape other than a circle. The body keeps
+        //! the density of the particle. Particles are split between species by id
+        //! if the spawn settings ask for several.
+        let appearance = Appearance {
+            color: utils::get_random_color(&mut self.rng),
+        };
+        let body = self
+            .spawn_settings
+            .shape
+            .body(particle.radius, &mut self.rng);
+        let species = self.spawn_settings.species_of(particle.id);
+        let entity = match body {
+            Some(body) => {
+                particle.mass = particle.density() * body.shape.area();
+                self.world.spawn((particle, appearance, body))
+            }
+            None => self.world.spawn((particle, appearance)),
+        };
+        if let Some(species) = species {
+            self.world
+                .insert_one(entity, species)
+                .expect("the entity was just spawned");
+        }
+    }
+
+    pub fn spawn_pattern(&mut self, pattern: SpawnPattern, count: usize) -> SimResult<()> {
+        //! Like `spawn_random`, but with the particles placed in `pattern`.
+        //! Nothing is spawned if they do not fit, or for the `Orbits` pattern
+        //! if there is no mutual gravity to orbit by.
+        if pattern == SpawnPattern::Random {
+            self.spawn_random(count);
+            return Ok(());
+        }
+        let mut particles: Vec<Particle> = (0..count as u64)
+            .map(|offset| {
+                self.spawn_settings
+                    .random_particle(self.next_id + offset, &mut self.rng)
+            })
+            .collect();
+        pattern.arrange(&mut particles, &mut self.rng)?;
+        if pattern == SpawnPattern::Orbits {
+            let constant = self
+                .settings
+                .mutual_gravity
+                .ok_or(SimError::InvalidArgument {
+                    name: "mutual gravity for the orbits pattern".to_string(),
+                    value: "none".to_string(),
+                })?;
+            patterns::launch_orbits(&mut particles, constant);
+        }
+        self.next_id += count as u64;
+        for particle in particles {
+            self.spawn_colored(particle);
+        }
+        Ok(())
+    }
+
+    pub fn allocate_id(&mut self) -> u64 {
+        //! Reserve an id that no particle has had yet, for one made outside the simulation.
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn spawn(&mut self, particle: Particle) -> Entity {
+        //! Add a bare particle to the simulation. Optional components
+        //! can be attached afterwards through `world_mut`.
+        self.next_id = self.next_id.max(particle.id + 1);
+        self.world.spawn((particle,))
+    }
+
+    pub fn spawn_body(&mut self, particle: Particle, body: Body) -> Entity {
+        //! Add a particle with a shape, usually made with `Body::particle`.
+        self.next_id = self.next_id.max(particle.id + 1);
+        self.world.spawn((particle, body))
+    }
+
+    pub fn add_exporter(&mut self, mut exporter: Box<dyn Exporter>) -> SimResult<()> {
+        //! Attach an exporter, which immediately records the current state.
+        exporter.record(self.frame, &self.world)?;
+        self.exporters.push(exporter);
+        Ok(())
+    }
+
+    pub fn finish_exports(&mut self) -> SimResult<()> {
+        //! Flush every exporter, call this once the simulation is done.
+        for exporter in self.exporters.iter_mut() {
+            exporter.finish()?;
+        }
+        Ok(())
+    }
+
+    pub fn set_paranoid(&mut self, paranoid: bool) {
+        //! Check the invariants in `invariants.rs` after every step, and fail with
+        //! `SimError::InvariantViolated` instead of recovering from a bad state.
+        self.paranoid = paranoid;
+    }
+
+    pub fn set_track_contacts(&mut self, track: bool) {
+        //! Keep count of the pairs in contact and how long they have been, for
+        //! `contacts`. It costs a map rebuilt every step, so it is off by default.
+        self.track_contacts = track;
+        if !track {
+            self.contacts = ContactCache::new();
+        }
+    }
+
+    pub fn set_verify_broadphase(&mut self, verify: bool) {
+        //! Also find the possible collisions of every step by checking every pair,
+        //! and warn about each pair that sweep and prune disagrees on. This is
+        //! O(n²), for hunting pruning bugs rather than for normal runs.
+        self.divergences = verify.then_some(self.divergences.unwrap_or(0));
+    }
+
+    pub fn broadphase_divergences(&self) -> Option<u64> {
+        //! How many pairs the two broadphases have disagreed on so far, or `None`
+        //! unless `set_verify_broadphase` is on.
+        self.divergences
+    }
+
+    pub fn frame(&self) -> u64 {
+        //! The number of steps taken so far.
+        self.frame
+    }
+
+    pub fn events(&self) -> &[Event] {
+        //! Everything that happened during the latest step, in the order it happened.
+        &self.events
+    }
+
+    pub fn contacts(&self) -> &ContactCache {
+        //! The pairs in contact during the latest step, and for how long they have
+        //! been. Empty unless `set_track_contacts` turned the tracking on.
+        &self.contacts
+    }
+
+    pub fn state_hash(&self) -> u64 {
+        //! A 64 bit FNV-1a hash of the frame counter and every particle, in id order.
+        //! The exact bits of every float are hashed, so any change in the physics,
+        //! however small, changes the hash. Used by the golden replay tests.
+        const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0100_0000_01b3;
+        let mut hash = FNV_OFFSET;
+        let mut feed = |value: u64| {
+            for byte in value.to_le_bytes() {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        };
+        feed(self.frame);
+        for particle in export::sorted_particles(&self.world) {
+            feed(particle.id);
+            for value in [
+                particle.radius,
+                particle.mass,
+                particle.position.0,
+                particle.position.1,
+                particle.velocity.0,
+                particle.velocity.1,
+            ] {
+                feed(value.to_bits());
+            }
+        }
+        hash
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.world.query::<&Particle>().iter().count()
+    }
+
+    pub fn particle(&self, id: u64) -> Option<Particle> {
+        //! A copy of the particle with this id, if it still exists.
+        self.world
+            .query::<&Particle>()
+            .iter()
+            .find(|(_, particle)| particle.id == id)
+            .map(|(_, particle)| *particle)
+    }
+
+    pub fn particle_mut(&mut self, id: u64) -> Option<&mut Particle> {
+        //! The particle with this id, to be changed in place between steps.
+        self.world
+            .query_mut::<&mut Particle>()
+            .into_iter()
+            .find(|(_, particle)| particle.id == id)
+            .map(|(_, particle)| particle)
+    }
+
+    pub fn particle_at(&self, point: Vec2) -> Option<u64> {
+        //! The id of the particle covering `point`, or of the smallest one if
+        //! several overlap there. Shapes are picked by their bounding circle.
+        self.world
+            .query::<&Particle>()
+            .iter()
+            .filter(|(_, particle)| particle.position.distance(&point) <= particle.radius)
+            .min_by(|(_, a), (_, b)| a.radius.total_cmp(&b.radius))
+            .map(|(_, particle)| particle.id)
+    }
+
+    pub fn remove_particle(&mut self, id: u64) -> bool {
+        //! Remove the particle with this id, and whatever else its entity holds.
+        //! Returns whether it existed.
+        !self.remove_where(|particle| particle.id == id).is_empty()
+    }
+
+    pub fn remove_within(&mut self, corner: Vec2, opposite: Vec2) -> Vec<u64> {
+        //! Remove every particle whose center lies in the rectangle between two
+        //! opposite corners, given in any order. Returns the ids of those removed.
+        let (min, max) = (
+            Vec2(corner.0.min(opposite.0), corner.1.min(opposite.1)),
+            Vec2(corner.0.max(opposite.0), corner.1.max(opposite.1)),
+        );
+        self.remove_where(|particle| {
+            (min.0..=max.0).contains(&particle.position.0)
+                && (min.1..=max.1).contains(&particle.position.1)
+        })
+    }
+
+    fn remove_where(&mut self, mut condition: impl FnMut(&Particle) -> bool) -> Vec<u64> {
+        let removed: Vec<(Entity, u64)> = self
+            .world
+            .query::<&Particle>()
+            .iter()
+            .filter(|(_, particle)| condition(particle))
+            .map(|(entity, particle)| (entity, particle.id))
+            .collect();
+        for &(entity, id) in &removed {
+            self.world.despawn(entity).ok();
+            self.contacts.forget(id);
+        }
+        if !removed.is_empty() {
+            tracing::info!(count = removed.len(), "removed particles");
+        }
+        removed.into_iter().map(|(_, id)| id).collect()
+    }
+
+    pub fn spawn_burst(&mut self, center: Vec2, count: usize) {
+        //! Add `count` random particles around `center`, each heading straight away
+        //! from it at the speed the spawn settings give it. Every particle starts
+        //! its own radius out along its heading, so that none of them share a
+        //! center, and they overlap only while flying apart.
+        for _ in 0..count {
+            let mut particle = self
+                .spawn_settings
+                .random_particle(self.next_id, &mut self.rng);
+            self.next_id += 1;
+            let angle = self.rng.gen::<f64>() * 2.0 * std::f64::consts::PI;
+            let heading = Vec2(angle.cos(), angle.sin());
+            let position = center + particle.radius * heading;
+            particle.position = Vec2(
+                position.0.max(particle.radius).min(WIDTH - particle.radius),
+                position
+                    .1
+                    .max(particle.radius)
+                    .min(HEIGHT - particle.radius),
+            );
+            particle.velocity = particle.velocity.length() * heading;
+            self.spawn_colored(particle);
+        }
+    }
+
+    pub fn explode(&mut self, center: Vec2, strength: f64) -> usize {
+        //! Push every moving particle straight away from `center` with an impulse
+        //! of `strength / r` at a distance `r`, so light and nearby particles fly
+        //! off the fastest. Distances are counted from no closer than a particle's
+        //! own radius, so one sitting on `center` gets a large but finite kick, and
+        //! a particle exactly at `center` has no direction to go and is left alone.
+        //! Returns how many particles were pushed.
+        let mut pushed = 0;
+        for (_, particle) in self.world.query_mut::<&mut Particle>() {
+            let offset = particle.position - center;
+            let distance = offset.length();
+            if particle.fixed || particle.mass.is_infinite() || distance == 0.0 {
+                continue;
+            }
+            let impulse = strength / distance.max(particle.radius);
+            particle.velocity += (impulse / (particle.mass * distance)) * offset;
+            pushed += 1;
+        }
+        tracing::info!(x = center.0, y = center.1, strength, pushed, "explosion");
+        pushed
+    }
+
+    pub fn predict_path(&self, id: u64, steps: usize) -> Vec<Vec2> {
+        //! Where the particle with this id is headed over the next `steps` steps
+        //! of 1, under gravity and the mutual gravity of every particle alone.
+        //! Collisions, walls and everything else are left out, so the prediction
+        //! only holds for particles flying freely, such as planets in orbit.
+        let mut particles: Vec<Particle> = self
+            .world
+            .query::<&Particle>()
+            .iter()
+            .map(|(_, particle)| *particle)
+            .collect();
+        let Some(index) = particles.iter().position(|particle| particle.id == id) else {
+            return Vec::new();
+        };
+        let mut path = Vec::with_capacity(steps);
+        for _ in 0..steps {
+            let accelerations = match self.settings.mutual_gravity {
+                Some(constant) => gravity::accelerations(&particles, constant),
+                None => vec![Vec2::ZERO; particles.len()],
+            };
+            for (particle, acceleration) in particles.iter_mut().zip(accelerations) {
+                if particle.fixed || particle.mass.is_infinite() {
+                    continue;
+                }
+                particle.velocity += acceleration;
+                particle.velocity += self.settings.gravity;
+                particle.position += particle.velocity;
+            }
+            path.push(particles[index].position);
+        }
+        path
+    }
+
+    pub fn kinetic_energy(&self) -> f64 {
+        //! The total kinetic energy of every particle, including the spin of
+        //! shaped ones. Fixed and massive particles are left out.
+        self.world
+            .query::<(&Particle, Option<&Body>)>()
+            .iter()
+            .filter(|(_, (particle, _))| !particle.fixed && particle.mass.is_finite())
+            .map(|(_, (particle, body))| {
+                let spin = body.map_or(0.0, |body| {
+                    0.5 * body.shape.inertia(particle.mass) * body.angular_velocity.powi(2)
+                });
+                particle.kinetic_energy() + spin
+            })
+            .sum()
+    }
+
+    pub fn momentum(&self) -> Vec2 {
+        //! The total momentum of the moving particles. Fixed and massive particles
+        //! are left out, as they soak up momentum like walls.
+        self.world
+            .query::<&Particle>()
+            .iter()
+            .filter(|(_, particle)| !particle.fixed && particle.mass.is_finite())
+            .fold(Vec2::ZERO, |momentum, (_, particle)| {
+                momentum + particle.mass * particle.velocity
+            })
+    }
+
+    pub fn center_of_mass(&self) -> Option<(Vec2, Vec2)> {
+        //! Where the center of mass of the moving particles is, and its velocity,
+        //! which is their total momentum over their total mass. Without walls
+        //! or forces from outside, collisions leave the velocity unchanged.
+        let (mass, moment) = self
+            .world
+            .query::<&Particle>()
+            .iter()
+            .filter(|(_, particle)| !particle.fixed && particle.mass.is_finite())
+            .fold((0.0, Vec2::ZERO), |(mass, moment), (_, particle)| {
+                (
+                    mass + particle.mass,
+                    moment + particle.mass * particle.position,
+                )
+            });
+        (mass > 0.0).then(|| ((1.0 / mass) * moment, (1.0 / mass) * self.momentum()))
+    }
+
+    pub fn temperature(&self) -> f64 {
+        //! The mean kinetic energy of the moving particles' motion (not spin), which in
+        //! two dimensions is the temperature of the gas with a Boltzmann constant of 1.
+        let (energy, count) = self
+            .world
+            .query::<&Particle>()
+            .iter()
+            .filter(|(_, particle)| !particle.fixed && particle.mass.is_finite())
+            .fold((0.0, 0), |(energy, count), (_, particle)| {
+                (energy + particle.kinetic_energy(), count + 1)
+            });
+        if count == 0 {
+            0.0
+        } else {
+            energy / count as f64
+        }
+    }
+
+    pub fn pressure(&self) -> f64 {
+        //! The force per unit of length that the particles pushed the walls with
+        //! during the last step, averaged over all four walls. It comes and goes
+        //! with individual bounces, so it is only meaningful averaged over time.
+        self.pressure
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn settings(&self) -> &PhysicsSettings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut PhysicsSettings {
+        &mut self.settings
+    }
+
+    pub fn spawn_settings(&self) -> &SpawnSettings {
+        &self.spawn_settings
+    }
+
+    pub fn spawn_settings_mut(&mut self) -> &mut SpawnSettings {
+        &mut self.spawn_settings
+    }
+
+    pub fn obstacles(&self) -> &[Obstacle] {
+        &self.obstacles
+    }
+
+    pub fn set_obstacles(&mut self, obstacles: Vec<Obstacle>) {
+        //! Replace the static boxes that particles bounce off of.
+        self.obstacles = obstacles;
+    }
+
+    pub fn portals(&self) -> &[Portal] {
+        &self.portals
+    }
+
+    pub fn set_portals(&mut self, portals: Vec<Portal>) {
+        //! Replace the pairs of regions that particles are teleported between.
+        self.portals = portals;
+    }
+
+    pub fn species(&self) -> &SpeciesTable {
+        &self.species
+    }
+
+    pub fn species_mut(&mut self) -> &mut SpeciesTable {
+        //! The properties of each species, such as how strongly gravity pulls on it.
+        &mut self.species
+    }
+
+    pub fn time_regions(&self) -> &[TimeRegion] {
+        &self.time_regions
+    }
+
+    pub fn set_time_regions(&mut self, time_regions: Vec<TimeRegion>) {
+        //! Replace the regions where time runs at a different rate.
+        self.time_regions = time_regions;
+    }
+
+    pub fn profiler(&self) -> &Profiler {
+        &self.profiler
+    }
+
+    pub fn profiler_mut(&mut self) -> &mut Profiler {
+        &mut self.profiler
+    }
+
+    pub fn profiler_and_world(&mut self) -> (&mut Profiler, &World) {
+        //! Borrow the profiler mutably while reading the world, so that
+        //! code outside of the simulation (rendering) can be timed.
+        (&mut self.profiler, &self.world)
+    }
+
+    pub fn step(&mut self, dt: f64) -> SimResult<()> {
+        //! Advance the simulation by `dt`.
+        self.events.clear();
+        let wall_impulse = self.profiler.time(Phase::Integration, || {
+            if let Some(constant) = self.settings.mutual_gravity {
+                systems::attract(&mut self.world, constant, &self.time_regions, dt);
+            }
+            systems::integrate(
+                &mut self.world,
+                &self.settings,
+                &self.species,
+                &self.time_regions,
+                dt,
+                &mut self.rng,
+            )
+        });
+        self.pressure = if dt > 0.0 {
+            wall_impulse / (dt * 2.0 * (WIDTH + HEIGHT))
+        } else {
+            0.0
+        };
+        systems::collide_obstacles(&mut self.world, &self.obstacles);
+        systems::teleport(&mut self.world, &self.portals, &self.time_regions, dt);
+        systems::expire_lifetimes(&mut self.world, &self.time_regions, dt);
+        let momentum_before = self
+            .paranoid
+            .then(|| invariants::total_momentum(&self.world))
+            .flatten();
+        if let Some(count) = &mut self.divergences {
+            let divergences = systems::verify_broadphase(&mut self.world, self.settings.sweep_axis);
+            for divergence in &divergences {
+                report_divergence(self.frame + 1, divergence);
+            }
+            *count += divergences.len() as u64;
+        }
+        systems::resolve_collisions(
+            &mut self.world,
+            self.settings.sweep_axis,
+            &mut self.profiler,
+            &mut self.events,
+        )?;
+        if self.track_contacts {
+            self.contacts.update(&self.events);
+        }
+        self.frame += 1;
+
+        if self.paranoid {
+            if let (Some(before), Some(after)) =
+                (momentum_before, invariants::total_momentum(&self.world))
+            {
+                invariants::check_momentum(before, after)
+                    .map_err(|reason| invariants::violation(self.frame, reason, &self.world))?;
+            }
+            invariants::check_step(&self.world, dt, self.frame)?;
+        }
+        systems::sanitize(&mut self.world, self.settings.non_finite);
+        systems::limit_speeds(&mut self.world, self.settings.max_speed);
+        systems::recover_escaped(&mut self.world, self.settings.out_of_bounds);
+
+        for exporter in self.exporters.iter_mut() {
+            exporter.record(self.frame, &self.world)?;
+        }
+        Ok(())
+    }
+}
+
+fn report_divergence(frame: u64, divergence: &Divergence) {
+    let (a, b) = divergence.pair;
+    if divergence.only_sweep_and_prune {
+        tracing::warn!(frame, a, b, "only sweep and prune found this pair");
+    } else {
+        tracing::warn!(frame, a, b, "sweep and prune missed this pair");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Lifetime;
+    use crate::patterns::CradleOutcome;
+    use crate::settings::{Medium, NonFinitePolicy, OutOfBoundsPolicy, Side};
+    use crate::shapes::SpawnShape;
+    use std::f64::consts::PI;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_step_moves_particles() {
+        let mut simulation = Simulation::new();
+        let entity = simulation.spawn(Particle::new(1, 1.0, Vec2(1.0, 1.0), Vec2(2.5, 3.5)));
+        simulation.step(1.0).unwrap();
+
+        let particle = simulation.world().get::<&Particle>(entity).unwrap();
+        assert_eq!(particle.position.0, 3.5);
+        assert_eq!(particle.position.1, 4.5);
+    }
+
+    #[test]
+    fn test_bounces_push_on_the_walls() {
+        let mut simulation = Simulation::new();
+        let entity = simulation.spawn(Particle::new(
+            1,
+            5.0,
+            Vec2(WIDTH - 6.0, 500.0),
+            Vec2(2.0, 0.0),
+        ));
+        simulation.step(1.0).unwrap();
+        let mass = simulation.world().get::<&Particle>(entity).unwrap().mass;
+        // The velocity across the wall went from 2 to -2
+        assert_eq!(simulation.pressure(), 4.0 * mass / (2.0 * (WIDTH + HEIGHT)));
+        simulation.step(1.0).unwrap();
+        assert_eq!(simulation.pressure(), 0.0);
+    }
+
+    #[test]
+    fn test_thermal_walls_bring_the_gas_to_their_temperature() {
+        let mut simulation = Simulation::with_seed(3);
+        simulation.spawn_random(30);
+        let walls = &mut simulation.settings_mut().walls;
+        for side in [Side::Left, Side::Right, Side::Bottom, Side::Top] {
+            walls.side_mut(side).temperature = Some(500.0);
+        }
+        for _ in 0..3000 {
+            simulation.step(1.0).unwrap();
+        }
+        let mut average = 0.0;
+        for _ in 0..3000 {
+            simulation.step(1.0).unwrap();
+            average += simulation.temperature() / 3000.0;
+        }
+        assert!((400.0..600.0).contains(&average), "{}", average);
+    }
+
+    #[test]
+    fn test_gravity_accelerates_particles() {
+        let mut simulation = Simulation::new();
+        simulation.settings_mut().gravity = Vec2(0.0, -0.5);
+        let entity = simulation.spawn(Particle::new(1, 1.0, Vec2(500.0, 500.0), Vec2(0.0, 0.0)));
+        simulation.step(1.0).unwrap();
+        simulation.step(1.0).unwrap();
+
+        let particle = simulation.world().get::<&Particle>(entity).unwrap();
+        assert_eq!(particle.velocity.1, -1.0);
+        assert_eq!(particle.position.1, 498.5);
+    }
+
+    #[test]
+    fn test_species_fall_at_their_own_rates() {
+        let mut simulation = Simulation::new();
+        simulation.settings_mut().gravity = Vec2(0.0, -0.5);
+        simulation.species_mut().set_gravity_scale(Species(1), -2.0);
+        let plain = simulation.spawn(Particle::new(1, 5.0, Vec2(200.0, 500.0), Vec2::ZERO));
+        let floating = simulation.spawn(Particle::new(2, 5.0, Vec2(500.0, 500.0), Vec2::ZERO));
+        let own = simulation.spawn(Particle::new(3, 5.0, Vec2(800.0, 500.0), Vec2::ZERO));
+        let world = simulation.world_mut();
+        world.insert_one(floating, Species(1)).unwrap();
+        world.insert(own, (Species(1), GravityScale(0.5))).unwrap();
+        simulation.step(1.0).unwrap();
+
+        let velocity = |entity| {
+            simulation
+                .world()
+                .get::<&Particle>(entity)
+                .unwrap()
+                .velocity
+        };
+        assert_eq!(velocity(plain), Vec2(0.0, -0.5));
+        assert_eq!(velocity(floating), Vec2(0.0, 1.0));
+        assert_eq!(velocity(own), Vec2(0.0, -0.25));
+
+        // Random particles take turns between the species
+        let mut simulation = Simulation::new();
+        simulation.spawn_settings_mut().species = 3;
+        simulation.spawn_random(6);
+        let mut species: Vec<u32> = simulation
+            .world()
+            .query::<&Species>()
+            .iter()
+            .map(|(_, species)| species.0)
+            .collect();
+        species.sort();
+        assert_eq!(species, [0, 0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_time_regions_slow_particles_down() {
+        let mut simulation = Simulation::new();
+        simulation.set_time_regions(vec!["0,0,500,1000,0.25".parse().unwrap()]);
+        let slow = simulation.spawn(Particle::new(1, 5.0, Vec2(100.0, 500.0), Vec2(4.0, 0.0)));
+        let normal = simulation.spawn(Particle::new(2, 5.0, Vec2(700.0, 500.0), Vec2(4.0, 0.0)));
+        simulation.step(1.0).unwrap();
+
+        let position = |entity| {
+            simulation
+                .world()
+                .get::<&Particle>(entity)
+                .unwrap()
+                .position
+        };
+        assert_eq!(position(slow), Vec2(101.0, 500.0));
+        assert_eq!(position(normal), Vec2(704.0, 500.0));
+    }
+
+    #[test]
+    fn test_bursts_fly_out_from_the_center() {
+        let mut simulation = Simulation::with_seed(2);
+        let center = Vec2(500.0, 500.0);
+        simulation.spawn_burst(center, 50);
+        assert_eq!(simulation.particle_count(), 50);
+        for (_, particle) in simulation.world().query::<&Particle>().iter() {
+            let offset = particle.position - center;
+            assert!((offset.length() - particle.radius).abs() < 1e-9);
+            // Heading the same way as the offset, at any speed
+            assert!(offset.perp().inner_product(&particle.velocity).abs() < 1e-6);
+            assert!(offset.inner_product(&particle.velocity) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn test_removed_particles_are_gone_with_their_contacts() {
+        let mut simulation = Simulation::new();
+        for (id, x) in [(1, 100.0), (2, 115.0), (3, 400.0), (4, 600.0)] {
+            simulation.spawn(Particle::new(id, 10.0, Vec2(x, 500.0), Vec2::ZERO));
+        }
+        simulation.step(1.0).unwrap();
+        assert!(simulation.contacts().is_empty());
+        simulation.set_track_contacts(true);
+        simulation.step(1.0).unwrap();
+        assert_eq!(simulation.contacts().steps_in_contact(1, 2), 1);
+
+        assert!(simulation.remove_particle(2));
+        assert!(!simulation.remove_particle(2));
+        assert_eq!(simulation.contacts().steps_in_contact(1, 2), 0);
+        let mut removed = simulation.remove_within(Vec2(700.0, 400.0), Vec2(300.0, 600.0));
+        removed.sort();
+        assert_eq!(removed, [3, 4]);
+        assert_eq!(simulation.particle_count(), 1);
+        assert!(simulation.particle(1).is_some());
+    }
+
+    #[test]
+    fn test_explosions_fall_off_with_distance() {
+        let mut simulation = Simulation::new();
+        let near = simulation.spawn(Particle::new(1, 1.0, Vec2(510.0, 500.0), Vec2::ZERO));
+        let far = simulation.spawn(Particle::new(2, 1.0, Vec2(500.0, 480.0), Vec2::ZERO));
+        let pinned =
+            simulation.spawn(Particle::new(3, 1.0, Vec2(490.0, 500.0), Vec2::ZERO).pinned());
+        assert_eq!(simulation.explode(Vec2(500.0, 500.0), 10.0 * PI), 2);
+
+        let velocity = |entity| {
+            simulation
+                .world()
+                .get::<&Particle>(entity)
+                .unwrap()
+                .velocity
+        };
+        assert!(velocity(near).distance(&Vec2(1.0, 0.0)) < 1e-12);
+        assert!(velocity(far).distance(&Vec2(0.0, -0.5)) < 1e-12);
+        assert_eq!(velocity(pinned), Vec2::ZERO);
+    }
+
+    #[test]
+    fn test_light_particles_rise_in_a_medium() {
+        let mut simulation = Simulation::new();
+        simulation.settings_mut().gravity = Vec2(0.0, -0.5);
+        simulation.settings_mut().medium = Some(Medium {
+            floor_density: 3.0,
+            ceiling_density: 1.0,
+        });
+        // The medium has a density of 2 halfway up
+        let light = simulation.spawn(Particle::new(1, 5.0, Vec2(200.0, 500.0), Vec2::ZERO));
+        let neutral = simulation
+            .spawn(Particle::new(2, 5.0, Vec2(500.0, 500.0), Vec2::ZERO).with_density(2.0));
+        let heavy = simulation
+            .spawn(Particle::new(3, 5.0, Vec2(800.0, 500.0), Vec2::ZERO).with_density(4.0));
+        simulation.step(1.0).unwrap();
+
+        let velocity = |entity| {
+            simulation
+                .world()
+                .get::<&Particle>(entity)
+                .unwrap()
+                .velocity
+        };
+        assert_eq!(velocity(light), Vec2(0.0, 0.5));
+        assert_eq!(velocity(neutral), Vec2::ZERO);
+        assert_eq!(velocity(heavy), Vec2(0.0, -0.25));
+    }
+
+    #[test]
+    fn test_random_particles_get_unique_ids() {
+        let mut simulation = Simulation::new();
+        simulation.spawn(Particle::new(10, 1.0, Vec2(500.0, 500.0), Vec2(0.0, 0.0)));
+        simulation.spawn_random(5);
+
+        let mut ids: Vec<u64> = simulation
+            .world()
+            .query::<&Particle>()
+            .iter()
+            .map(|(_, particle)| particle.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec![10, 11, 12, 13, 14, 15]);
+    }
+
+    #[test]
+    fn test_exporters_see_every_frame() {
+        struct FrameLog(Arc<Mutex<Vec<u64>>>);
+        impl Exporter for FrameLog {
+            fn record(&mut self, frame: u64, _world: &World) -> SimResult<()> {
+                self.0.lock().unwrap().push(frame);
+                Ok(())
+            }
+        }
+
+        let frames = Arc::new(Mutex::new(Vec::new()));
+        let mut simulation = Simulation::new();
+        simulation
+            .add_exporter(Box::new(FrameLog(Arc::clone(&frames))))
+            .unwrap();
+        simulation.step(1.0).unwrap();
+        simulation.step(1.0).unwrap();
+        assert_eq!(*frames.lock().unwrap(), vec![0, 1, 2]);
+        assert_eq!(simulation.frame(), 2);
+    }
+
+    #[test]
+    fn test_non_finite_particles_are_recovered() {
+        let mut simulation = Simulation::new();
+        let broken_velocity =
+            simulation.spawn(Particle::new(1, 1.0, Vec2(10.0, 10.0), Vec2(f64::NAN, 1.0)));
+        let broken_mass = simulation.spawn(Particle::new(
+            2,
+            f64::INFINITY,
+            Vec2(50.0, 50.0),
+            Vec2(0.0, 0.0),
+        ));
+        simulation.step(1.0).unwrap();
+
+        let particle = *simulation
+            .world()
+            .get::<&Particle>(broken_velocity)
+            .unwrap();
+        assert!(particle.is_finite());
+        assert_eq!(particle.velocity.0, 0.0);
+        assert!(!simulation.world().contains(broken_mass));
+
+        simulation.settings_mut().non_finite = NonFinitePolicy::Remove;
+        simulation
+            .world_mut()
+            .get::<&mut Particle>(broken_velocity)
+            .unwrap()
+            .position
+            .1 = f64::NAN;
+        simulation.step(1.0).unwrap();
+        assert_eq!(simulation.particle_count(), 0);
+    }
+
+    #[test]
+    fn test_speed_limit_and_escaped_particles() {
+        let mut simulation = Simulation::new();
+        simulation.settings_mut().max_speed = Some(10.0);
+        let fast = simulation.spawn(Particle::new(1, 5.0, Vec2(500.0, 500.0), Vec2(30.0, 40.0)));
+        // Already outside, so the wall does not bounce it back
+        let escaped = simulation.spawn(Particle::new(2, 5.0, Vec2(-50.0, 500.0), Vec2(3.0, 0.0)));
+        simulation.step(1.0).unwrap();
+
+        let particle = *simulation.world().get::<&Particle>(fast).unwrap();
+        assert!((particle.velocity.length() - 10.0).abs() < 1e-9);
+        assert!((particle.velocity.0 / particle.velocity.1 - 0.75).abs() < 1e-9);
+        let particle = *simulation.world().get::<&Particle>(escaped).unwrap();
+        assert_eq!(particle.position.0, 5.0);
+        assert!(particle.velocity.0 > 0.0);
+
+        simulation.settings_mut().out_of_bounds = OutOfBoundsPolicy::Remove;
+        simulation
+            .world_mut()
+            .get::<&mut Particle>(escaped)
+            .unwrap()
+            .position
+            .1 = 2000.0;
+        simulation.step(1.0).unwrap();
+        assert_eq!(simulation.particle_count(), 1);
+    }
+
+    #[test]
+    fn test_lifetime_expires() {
+        let mut simulation = Simulation::new();
+        let mortal = simulation.spawn(Particle::new(1, 1.0, Vec2(100.0, 100.0), Vec2(0.0, 0.0)));
+        let immortal = simulation.spawn(Particle::new(2, 1.0, Vec2(500.0, 500.0), Vec2(0.0, 0.0)));
+        simulation
+            .world_mut()
+            .insert_one(mortal, Lifetime(1.5))
+            .unwrap();
+
+        simulation.step(1.0).unwrap();
+        assert!(simulation.world().contains(mortal));
+        simulation.step(1.0).unwrap();
+        assert!(!simulation.world().contains(mortal));
+        assert!(simulation.world().contains(immortal));
+    }
+
+    #[test]
+    fn test_particles_are_found_by_id_and_position() {
+        let mut simulation = Simulation::new();
+        simulation.spawn(Particle::new(1, 30.0, Vec2(100.0, 100.0), Vec2::ZERO));
+        simulation.spawn(Particle::new(2, 10.0, Vec2(120.0, 100.0), Vec2::ZERO));
+        // The smaller particle wins where the two overlap
+        assert_eq!(simulation.particle_at(Vec2(125.0, 100.0)), Some(2));
+        assert_eq!(simulation.particle_at(Vec2(80.0, 100.0)), Some(1));
+        assert_eq!(simulation.particle_at(Vec2(500.0, 500.0)), None);
+
+        simulation.particle_mut(2).unwrap().velocity = Vec2(1.0, 2.0);
+        assert_eq!(simulation.particle(2).unwrap().velocity, Vec2(1.0, 2.0));
+        assert!(simulation.particle(3).is_none());
+    }
+
+    #[test]
+    fn test_energy_and_temperature_leave_out_fixed_particles() {
+        let mut simulation = Simulation::new();
+        simulation.spawn(Particle::new(1, 1.0, Vec2(100.0, 100.0), Vec2(3.0, 4.0)));
+        simulation.spawn(Particle::new(2, 1.0, Vec2(300.0, 100.0), Vec2(0.0, 0.0)));
+        simulation.spawn(Particle::new(3, 1.0, Vec2(500.0, 100.0), Vec2(1.0, 0.0)).pinned());
+        simulation.spawn(Particle::new(4, 1.0, Vec2(700.0, 100.0), Vec2(1.0, 0.0)).massive());
+        let energy = 0.5 * PI * 25.0;
+        assert!((simulation.kinetic_energy() - energy).abs() < 1e-9);
+        assert!((simulation.temperature() - energy / 2.0).abs() < 1e-9);
+        assert_eq!(Simulation::new().temperature(), 0.0);
+    }
+
+    #[test]
+    fn test_head_on_collision_swaps_velocities() {
+        // Two equal particles moving straight at each other along the x axis
+        // should simply exchange velocities in an elastic collision.
+        let mut simulation = Simulation::new();
+        let left = simulation.spawn(Particle::new(1, 10.0, Vec2(100.0, 500.0), Vec2(5.0, 0.0)));
+        let right = simulation.spawn(Particle::new(2, 10.0, Vec2(125.0, 500.0), Vec2(-5.0, 0.0)));
+        simulation.step(1.0).unwrap();
+
+        let left = simulation.world().get::<&Particle>(left).unwrap();
+        let right = simulation.world().get::<&Particle>(right).unwrap();
+        assert!((left.velocity.0 + 5.0).abs() < 1e-9);
+        assert!((right.velocity.0 - 5.0).abs() < 1e-9);
+        // The collision is reported, with the normal pointing from left to right
+        match simulation.events() {
+            [Event::Contact {
+                a: 1,
+                b: 2,
+                contact,
+                impulse,
+            }] => {
+                assert_eq!(contact.normal, Vec2(1.0, 0.0));
+                assert_eq!(contact.penetration_depth, 5.0);
+                // Each velocity changed by 10
+                assert!((impulse - 1000.0 * PI).abs() < 1e-6);
+            }
+            events => panic!("unexpected events {:?}", events),
+        }
+    }
+
+    #[test]
+    fn test_newtons_cradle_passes_the_strike_along() {
+        let mut simulation = Simulation::with_seed(7);
+        simulation
+            .spawn_pattern(SpawnPattern::Cradle { speed: 6.0 }, 5)
+            .unwrap();
+        let outcome = (0..100)
+            .find_map(|_| {
+                simulation.step(1.0).unwrap();
+                CradleOutcome::measure(simulation.world())
+            })
+            .unwrap();
+        assert!(outcome.is_correct(6.0), "{:?}", outcome);
+    }
+
+    #[test]
+    fn test_planets_follow_their_predicted_orbits() {
+        let mut simulation = Simulation::with_seed(2);
+        assert!(simulation.spawn_pattern(SpawnPattern::Orbits, 3).is_err());
+        simulation.settings_mut().mutual_gravity = Some(0.005);
+        simulation.spawn_pattern(SpawnPattern::Orbits, 3).unwrap();
+
+        let star = simulation.particle(0).unwrap().position;
+        let planet = simulation.particle(1).unwrap();
+        let radius = planet.position.distance(&star);
+        let path = simulation.predict_path(1, 50);
+        for _ in 0..50 {
+            simulation.step(1.0).unwrap();
+        }
+        // The star wobbles too, so the orbit is measured from where it is now
+        let star = simulation.particle(0).unwrap().position;
+        let moved = simulation.particle(1).unwrap().position;
+        assert!(moved.distance(&planet.position) > 10.0);
+        assert!((moved.distance(&star) - radius).abs() < 0.05 * radius);
+        assert!(path[49].distance(&moved) < 1e-6);
+    }
+
+    #[test]
+    fn test_collisions_keep_the_center_of_mass_on_course() {
+        let mut simulation = Simulation::new();
+        simulation.spawn(Particle::new(1, 10.0, Vec2(400.0, 500.0), Vec2(3.0, 0.0)));
+        simulation.spawn(Particle::new(2, 20.0, Vec2(500.0, 505.0), Vec2(-1.0, 0.0)));
+        simulation.spawn(Particle::new(3, 5.0, Vec2(100.0, 100.0), Vec2::ZERO).massive());
+        let (start, velocity) = simulation.center_of_mass().unwrap();
+        let momentum = simulation.momentum();
+        for _ in 0..40 {
+            simulation.step(1.0).unwrap();
+        }
+        // They have collided
+        assert!(simulation.particle(2).unwrap().velocity.0 > 0.0);
+        let (center, _) = simulation.center_of_mass().unwrap();
+        assert!(center.distance(&(start + 40.0 * velocity)) < 1e-9);
+        assert!(simulation.momentum().distance(&momentum) < 1e-9);
+        assert!(Simulation::new().center_of_mass().is_none());
+    }
+
+    #[test]
+    fn test_obstacles_block_particles() {
+        let mut simulation = Simulation::new();
+        simulation.set_obstacles(vec![Obstacle::new(500.0, 0.0, 600.0, 1000.0)]);
+        let entity = simulation.spawn(Particle::new(1, 10.0, Vec2(480.0, 500.0), Vec2(15.0, 0.0)));
+        simulation.step(1.0).unwrap();
+
+        let particle = simulation.world().get::<&Particle>(entity).unwrap();
+        assert_eq!(particle.position.0, 490.0);
+        assert_eq!(particle.velocity.0, -15.0);
+    }
+
+    #[test]
+    fn test_tumbling_boxes_keep_their_energy() {
+        let mut simulation = Simulation::with_seed(5);
+        simulation.spawn_settings_mut().shape = SpawnShape::Box;
+        simulation.spawn_settings_mut().max_radius = 20.0;
+        simulation.spawn_random(60);
+        let before = simulation.kinetic_energy();
+        for _ in 0..500 {
+            simulation.step(1.0).unwrap();
+        }
+        assert!((simulation.kinetic_energy() - before).abs() < 1e-6 * before);
+        let spinning = simulation
+            .world()
+            .query::<&Body>()
+            .iter()
+            .filter(|(_, body)| body.angular_velocity != 0.0)
+            .count();
+        assert!(spinning > 0);
+    }
+}
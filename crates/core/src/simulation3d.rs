@@ -0,0 +1,174 @@
+use crate::particle::{HEIGHT, WIDTH};
+use crate::particle3d::{Particle3d, DEPTH};
+use crate::spawn::SpawnSettings;
+use crate::utils::Vec3;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// The size of the 3D box.
+pub const BOX_3D: Vec3 = Vec3(WIDTH, HEIGHT, DEPTH);
+
+/// A gas of spheres in a `WIDTH` × `HEIGHT` × `DEPTH` box. This is a much
+/// smaller simulation than the 2D `Simulation`: the particles are kept in a plain
+/// `Vec`, and only gravity and elastic collisions with each other and the walls
+/// are simulated.
+pub struct Simulation3d {
+    particles: Vec<Particle3d>,
+    spawn_settings: SpawnSettings,
+    gravity: Vec3,
+    frame: u64,
+    next_id: u64,
+    rng: ChaCha8Rng,
+}
+
+impl Default for Simulation3d {
+    fn default() -> Self {
+        Simulation3d::new()
+    }
+}
+
+impl Simulation3d {
+    pub fn new() -> Self {
+        //! An empty simulation, with an RNG seeded from the operating system.
+        Simulation3d::with_rng(ChaCha8Rng::from_entropy())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        //! An empty simulation whose random particles are the same on every run.
+        Simulation3d::with_rng(ChaCha8Rng::seed_from_u64(seed))
+    }
+
+    fn with_rng(rng: ChaCha8Rng) -> Self {
+        Simulation3d {
+            particles: Vec::new(),
+            spawn_settings: SpawnSettings::default(),
+            gravity: Vec3::default(),
+            frame: 0,
+            next_id: 0,
+            rng,
+        }
+    }
+
+    pub fn spawn_random(&mut self, count: usize) {
+        //! Add `count` spheres anywhere in the box, drawn from the spawn settings.
+        for _ in 0..count {
+            let particle = self
+                .spawn_settings
+                .random_particle3d(self.next_id, &mut self.rng);
+            self.spawn(particle);
+        }
+    }
+
+    pub fn spawn(&mut self, particle: Particle3d) {
+        self.next_id = self.next_id.max(particle.id + 1);
+        self.particles.push(particle);
+    }
+
+    pub fn particles(&self) -> &[Particle3d] {
+        &self.particles
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn gravity(&self) -> Vec3 {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vec3) {
+        self.gravity = gravity;
+    }
+
+    pub fn spawn_settings(&self) -> &SpawnSettings {
+        &self.spawn_settings
+    }
+
+    pub fn spawn_settings_mut(&mut self) -> &mut SpawnSettings {
+        &mut self.spawn_settings
+    }
+
+    pub fn kinetic_energy(&self) -> f64 {
+        self.particles.iter().map(Particle3d::kinetic_energy).sum()
+    }
+
+    pub fn step(&mut self, dt: f64) {
+        //! Advance the simulation by `dt`.
+        let _span = tracing::debug_span!("step_3d").entered();
+        for particle in self.particles.iter_mut() {
+            particle.velocity = particle.velocity + dt * self.gravity;
+            particle.update(dt, BOX_3D);
+        }
+        self.resolve_collisions();
+        self.frame += 1;
+    }
+
+    fn resolve_collisions(&mut self) {
+        //! Sweep and prune along the x axis, exactly as in 2D: once the spheres
+        //! are sorted by their leftmost point, each one only needs to be checked
+        //! against the following spheres that start before it ends.
+        let mut order: Vec<usize> = (0..self.particles.len()).collect();
+        let left = |particle: &Particle3d| particle.position.0 - particle.radius;
+        order.sort_by(|&a, &b| left(&self.particles[a]).total_cmp(&left(&self.particles[b])));
+
+        for (i, &a) in order.iter().enumerate() {
+            for &b in &order[i + 1..] {
+                let (first, second) = (self.particles[a], self.particles[b]);
+                if left(&second) > first.position.0 + first.radius {
+                    break;
+                }
+                if first.check_pairwise_collision(&second) {
+                    let (velocity_a, velocity_b) = first.collision_react(&second);
+                    self.particles[a].velocity = velocity_a;
+                    self.particles[b].velocity = velocity_b;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spheres_stay_in_the_box() {
+        let mut simulation = Simulation3d::with_seed(7);
+        simulation.spawn_random(100);
+        let energy = simulation.kinetic_energy();
+        for _ in 0..200 {
+            simulation.step(1.0);
+        }
+        for particle in simulation.particles() {
+            for (position, size) in [
+                (particle.position.0, WIDTH),
+                (particle.position.1, HEIGHT),
+                (particle.position.2, DEPTH),
+            ] {
+                assert!(position >= -particle.radius && position <= size + particle.radius);
+            }
+        }
+        assert!((simulation.kinetic_energy() - energy).abs() < 1e-6 * energy);
+    }
+
+    #[test]
+    fn test_head_on_collision_swaps_velocities() {
+        let mut simulation = Simulation3d::with_seed(0);
+        let velocity = Vec3(0.0, 0.0, 5.0);
+        simulation.spawn(Particle3d::new(
+            1,
+            10.0,
+            Vec3(500.0, 500.0, 100.0),
+            velocity,
+        ));
+        simulation.spawn(Particle3d::new(
+            2,
+            10.0,
+            Vec3(500.0, 500.0, 125.0),
+            -1.0 * velocity,
+        ));
+        simulation.step(1.0);
+        assert_eq!(simulation.particles()[0].velocity, -1.0 * velocity);
+        assert_eq!(simulation.particles()[1].velocity, velocity);
+    }
+}
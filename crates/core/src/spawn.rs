@@ -0,0 +1,459 @@
+use crate::components::Species;
+use crate::error::{SimError, SimResult};
+use crate::particle::{Particle, HEIGHT, RADIUS_UPPER_BOUND, WIDTH};
+use crate::particle3d::{Particle3d, DEPTH};
+use crate::shapes::SpawnShape;
+use crate::utils::{Vec2, Vec3};
+use rand::Rng;
+use rand_distr::{Distribution, LogNormal, Normal};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+const VELOCITY_UPPER_BOUND: f64 = 25.0;
+
+/// How the radii of random particles are chosen. Every distribution is limited
+/// to the `min_radius..=max_radius` range of the `SpawnSettings` it is part of.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum RadiusDistribution {
+    /// Every radius in the range is equally likely.
+    #[default]
+    Uniform,
+    /// A bell curve around `mean`.
+    Normal { mean: f64, std_dev: f64 },
+    /// The logarithm of the radius is normally distributed, giving
+    /// many small particles and a long tail of large ones.
+    LogNormal { mu: f64, sigma: f64 },
+    /// The likelihood of a radius `r` is proportional to `r^-exponent`.
+    PowerLaw { exponent: f64 },
+}
+
+/// How the velocities of random particles are chosen. The direction of
+/// travel is always uniformly random.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SpeedDistribution {
+    /// Every speed between `min` and `max` is equally likely.
+    Uniform { min: f64, max: f64 },
+    /// The Maxwell-Boltzmann distribution of a gas at `temperature`, with the
+    /// Boltzmann constant taken as 1. Each velocity component is normally
+    /// distributed with variance `temperature / mass`, so heavy particles are
+    /// slower and the average kinetic energy per particle is `temperature`.
+    Thermal { temperature: f64 },
+}
+
+impl Default for SpeedDistribution {
+    fn default() -> Self {
+        SpeedDistribution::Uniform {
+            min: 0.0,
+            max: VELOCITY_UPPER_BOUND,
+        }
+    }
+}
+
+/// Everything that decides what a random particle looks like.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpawnSettings {
+    pub min_radius: f64,
+    pub max_radius: f64,
+    pub radius_distribution: RadiusDistribution,
+    pub speed_distribution: SpeedDistribution,
+    /// Random particles are fitted inside the circle of their radius,
+    /// only the 2D simulation has shapes other than circles.
+    pub shape: SpawnShape,
+    /// When set, every random particle gets a density drawn uniformly from this
+    /// range instead of a density of 1, which matters for buoyancy in a `Medium`.
+    pub densities: Option<(f64, f64)>,
+    /// How many species random particles are split between, taking turns by id.
+    /// With 1 they get no `Species` component at all.
+    pub species: u32,
+}
+
+impl Default for SpawnSettings {
+    fn default() -> Self {
+        SpawnSettings {
+            // Anything smaller is hard to see and numerically fragile in collisions
+            min_radius: 5.0,
+            max_radius: RADIUS_UPPER_BOUND,
+            radius_distribution: RadiusDistribution::Uniform,
+            speed_distribution: SpeedDistribution::default(),
+            shape: SpawnShape::Circle,
+            densities: None,
+            species: 1,
+        }
+    }
+}
+
+fn invalid(name: &str, value: impl fmt::Display) -> SimError {
+    SimError::InvalidArgument {
+        name: name.to_string(),
+        value: value.to_string(),
+    }
+}
+
+impl SpawnSettings {
+    pub fn validate(&self) -> SimResult<()> {
+        //! The radius range must be positive, ordered, and within `RADIUS_UPPER_BOUND`,
+        //! which the broadphase relies on to stop searching early. Every parameter
+        //! of the distributions must be finite.
+        if self.min_radius.is_nan() || self.min_radius <= 0.0 {
+            return Err(invalid("minimum radius", self.min_radius));
+        }
+        if self.max_radius.is_nan()
+            || self.max_radius < self.min_radius
+            || self.max_radius > RADIUS_UPPER_BOUND
+        {
+            return Err(invalid("maximum radius", self.max_radius));
+        }
+        let valid = match self.radius_distribution {
+            RadiusDistribution::Uniform => true,
+            RadiusDistribution::Normal { mean, std_dev } => {
+                mean.is_finite() && std_dev.is_finite() && std_dev >= 0.0
+            }
+            RadiusDistribution::LogNormal { mu, sigma } => {
+                mu.is_finite() && sigma.is_finite() && sigma >= 0.0
+            }
+            RadiusDistribution::PowerLaw { exponent } => exponent.is_finite(),
+        };
+        if !valid {
+            return Err(invalid(
+                "radius distribution",
+                format!("{:?}", self.radius_distribution),
+            ));
+        }
+        let valid = match self.speed_distribution {
+            SpeedDistribution::Uniform { min, max } => min >= 0.0 && max >= min && max.is_finite(),
+            SpeedDistribution::Thermal { temperature } => {
+                temperature.is_finite() && temperature >= 0.0
+            }
+        };
+        if !valid {
+            return Err(invalid(
+                "speed distribution",
+                format!("{:?}", self.speed_distribution),
+            ));
+        }
+        if let Some((min, max)) = self.densities {
+            if !(min > 0.0 && max >= min && max.is_finite()) {
+                return Err(invalid("density range", format!("{}:{}", min, max)));
+            }
+        }
+        if self.species == 0 {
+            return Err(invalid("species count", self.species));
+        }
+        Ok(())
+    }
+
+    pub fn species_of(&self, id: u64) -> Option<Species> {
+        //! The species a random particle with this id belongs to, if there are several.
+        (self.species > 1).then(|| Species((id % self.species as u64) as u32))
+    }
+
+    pub fn sample_radius(&self, rng: &mut impl Rng) -> f64 {
+        //! Draw a radius from the distribution, clamped to the allowed range.
+        let (min, max) = (self.min_radius, self.max_radius);
+        let radius = match self.radius_distribution {
+            RadiusDistribution::Uniform => min + rng.gen::<f64>() * (max - min),
+            RadiusDistribution::Normal { mean, std_dev } => Normal::new(mean, std_dev)
+                .map(|normal| normal.sample(rng))
+                .unwrap_or(mean),
+            RadiusDistribution::LogNormal { mu, sigma } => LogNormal::new(mu, sigma)
+                .map(|log_normal| log_normal.sample(rng))
+                .unwrap_or(mu.exp()),
+            RadiusDistribution::PowerLaw { exponent } => {
+                // Inverse transform sampling of the truncated power law
+                let u = rng.gen::<f64>();
+                let power = 1.0 - exponent;
+                if power.abs() < f64::EPSILON {
+                    min * (max / min).powf(u)
+                } else {
+                    (min.powf(power) + u * (max.powf(power) - min.powf(power))).powf(1.0 / power)
+                }
+            }
+        };
+        radius.clamp(min, max)
+    }
+
+    pub fn sample_velocity(&self, mass: f64, rng: &mut impl Rng) -> Vec2 {
+        //! Draw a velocity for a particle of `mass`, heading in any direction.
+        match self.speed_distribution {
+            SpeedDistribution::Uniform { min, max } => {
+                let speed = min + rng.gen::<f64>() * (max - min);
+                let angle = rng.gen::<f64>() * 2.0 * PI;
+                Vec2(speed * angle.cos(), speed * angle.sin())
+            }
+            SpeedDistribution::Thermal { temperature } => {
+                // Independent normal components are already isotropic
+                match Normal::new(0.0, (temperature / mass).sqrt()) {
+                    Ok(normal) => Vec2(normal.sample(rng), normal.sample(rng)),
+                    Err(_) => Vec2::ZERO,
+                }
+            }
+        }
+    }
+
+    pub fn random_particle(&self, id: u64, rng: &mut impl Rng) -> Particle {
+        //! A particle anywhere in the box, with a radius, density and velocity
+        //! from these settings.
+        let radius = self.sample_radius(rng);
+        let position = Vec2(rng.gen::<f64>() * WIDTH, rng.gen::<f64>() * HEIGHT);
+        let mut particle = Particle::new(id, radius, position, Vec2::ZERO);
+        if let Some((min, max)) = self.densities {
+            particle = particle.with_density(min + rng.gen::<f64>() * (max - min));
+        }
+        particle.velocity = self.sample_velocity(particle.mass, rng);
+        particle
+    }
+
+    pub fn sample_velocity3d(&self, mass: f64, rng: &mut impl Rng) -> Vec3 {
+        //! Like `sample_velocity`, but heading in any direction in 3D.
+        match self.speed_distribution {
+            SpeedDistribution::Uniform { min, max } => {
+                let speed = min + rng.gen::<f64>() * (max - min);
+                // Uniform on the sphere: uniform height, uniform angle around it
+                let z = 2.0 * rng.gen::<f64>() - 1.0;
+                let angle = rng.gen::<f64>() * 2.0 * PI;
+                let ring = (1.0 - z * z).sqrt();
+                speed * Vec3(ring * angle.cos(), ring * angle.sin(), z)
+            }
+            SpeedDistribution::Thermal { temperature } => {
+                match Normal::new(0.0, (temperature / mass).sqrt()) {
+                    Ok(normal) => Vec3(normal.sample(rng), normal.sample(rng), normal.sample(rng)),
+                    Err(_) => Vec3::default(),
+                }
+            }
+        }
+    }
+
+    pub fn random_particle3d(&self, id: u64, rng: &mut impl Rng) -> Particle3d {
+        //! A sphere anywhere in the 3D box, with a radius and velocity from these settings.
+        let radius = self.sample_radius(rng);
+        let position = Vec3(
+            rng.gen::<f64>() * WIDTH,
+            rng.gen::<f64>() * HEIGHT,
+            rng.gen::<f64>() * DEPTH,
+        );
+        let mut particle = Particle3d::new(id, radius, position, Vec3::default());
+        particle.velocity = self.sample_velocity3d(particle.mass, rng);
+        particle
+    }
+}
+
+impl FromStr for RadiusDistribution {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        //! Parse `uniform`, `normal:<mean>,<std_dev>`, `log-normal:<mu>,<sigma>`
+        //! or `power-law:<exponent>`.
+        let (name, params) = value.split_once(':').unwrap_or((value, ""));
+        let params: Vec<f64> = params
+            .split(',')
+            .filter(|param| !param.trim().is_empty())
+            .map(|param| param.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid("radius distribution", value))?;
+        match (name, params.as_slice()) {
+            ("uniform", []) => Ok(RadiusDistribution::Uniform),
+            ("normal", [mean, std_dev]) => Ok(RadiusDistribution::Normal {
+                mean: *mean,
+                std_dev: *std_dev,
+            }),
+            ("log-normal", [mu, sigma]) => Ok(RadiusDistribution::LogNormal {
+                mu: *mu,
+                sigma: *sigma,
+            }),
+            ("power-law", [exponent]) => Ok(RadiusDistribution::PowerLaw {
+                exponent: *exponent,
+            }),
+            _ => Err(invalid("radius distribution", value)),
+        }
+    }
+}
+
+impl FromStr for SpeedDistribution {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        //! Parse `uniform`, `uniform:<min>,<max>` or `thermal:<temperature>`.
+        let (name, params) = value.split_once(':').unwrap_or((value, ""));
+        let params: Vec<f64> = params
+            .split(',')
+            .filter(|param| !param.trim().is_empty())
+            .map(|param| param.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid("speed distribution", value))?;
+        match (name, params.as_slice()) {
+            ("uniform", []) => Ok(SpeedDistribution::default()),
+            ("uniform", [min, max]) => Ok(SpeedDistribution::Uniform {
+                min: *min,
+                max: *max,
+            }),
+            ("thermal", [temperature]) => Ok(SpeedDistribution::Thermal {
+                temperature: *temperature,
+            }),
+            _ => Err(invalid("speed distribution", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_radii_stay_in_range() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for distribution in [
+            RadiusDistribution::Uniform,
+            RadiusDistribution::Normal {
+                mean: 10.0,
+                std_dev: 20.0,
+            },
+            RadiusDistribution::LogNormal {
+                mu: 2.0,
+                sigma: 1.0,
+            },
+            RadiusDistribution::PowerLaw { exponent: 2.5 },
+            RadiusDistribution::PowerLaw { exponent: 1.0 },
+        ] {
+            let settings = SpawnSettings {
+                min_radius: 3.0,
+                max_radius: 30.0,
+                radius_distribution: distribution,
+                ..SpawnSettings::default()
+            };
+            settings.validate().unwrap();
+            for _ in 0..1000 {
+                let radius = settings.sample_radius(&mut rng);
+                assert!(
+                    (3.0..=30.0).contains(&radius),
+                    "{:?} gave {}",
+                    distribution,
+                    radius
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_directions_cover_every_quadrant() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let settings = SpawnSettings::default();
+        let mut quadrants = [0; 4];
+        for id in 0..400 {
+            let particle = settings.random_particle(id, &mut rng);
+            let quadrant = match (particle.velocity.0 >= 0.0, particle.velocity.1 >= 0.0) {
+                (true, true) => 0,
+                (false, true) => 1,
+                (false, false) => 2,
+                (true, false) => 3,
+            };
+            quadrants[quadrant] += 1;
+        }
+        assert!(quadrants.iter().all(|&count| count > 50), "{:?}", quadrants);
+    }
+
+    #[test]
+    fn test_thermal_energy_matches_temperature() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let settings = SpawnSettings {
+            speed_distribution: SpeedDistribution::Thermal {
+                temperature: 1000.0,
+            },
+            ..SpawnSettings::default()
+        };
+        let samples = 20_000;
+        let total_energy: f64 = (0..samples)
+            .map(|id| {
+                let particle = settings.random_particle(id, &mut rng);
+                0.5 * particle.mass * (particle.velocity.0.powi(2) + particle.velocity.1.powi(2))
+            })
+            .sum();
+        // In two dimensions the mean kinetic energy is k * T, with k = 1
+        let mean_energy = total_energy / samples as f64;
+        assert!((mean_energy - 1000.0).abs() < 30.0, "{}", mean_energy);
+    }
+
+    #[test]
+    fn test_parse_distributions() {
+        let parse = |value: &str| value.parse::<RadiusDistribution>().unwrap();
+        assert_eq!(parse("uniform"), RadiusDistribution::Uniform);
+        assert_eq!(
+            parse("normal:20,5"),
+            RadiusDistribution::Normal {
+                mean: 20.0,
+                std_dev: 5.0
+            }
+        );
+        assert_eq!(
+            parse("power-law:2.5"),
+            RadiusDistribution::PowerLaw { exponent: 2.5 }
+        );
+        assert!("normal:20".parse::<RadiusDistribution>().is_err());
+        assert!("triangle".parse::<RadiusDistribution>().is_err());
+        assert_eq!(
+            "thermal:300".parse::<SpeedDistribution>().unwrap(),
+            SpeedDistribution::Thermal { temperature: 300.0 }
+        );
+        assert_eq!(
+            "uniform:5,10".parse::<SpeedDistribution>().unwrap(),
+            SpeedDistribution::Uniform {
+                min: 5.0,
+                max: 10.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_ranges_are_rejected() {
+        let inverted = SpawnSettings {
+            min_radius: 20.0,
+            max_radius: 10.0,
+            ..SpawnSettings::default()
+        };
+        assert!(inverted.validate().is_err());
+        let too_large = SpawnSettings {
+            max_radius: RADIUS_UPPER_BOUND * 2.0,
+            ..SpawnSettings::default()
+        };
+        assert!(too_large.validate().is_err());
+
+        let infinite = [
+            RadiusDistribution::Normal {
+                mean: f64::NAN,
+                std_dev: 1.0,
+            },
+            RadiusDistribution::Normal {
+                mean: 10.0,
+                std_dev: f64::INFINITY,
+            },
+            RadiusDistribution::LogNormal {
+                mu: f64::INFINITY,
+                sigma: 0.5,
+            },
+            RadiusDistribution::LogNormal {
+                mu: 2.0,
+                sigma: f64::NAN,
+            },
+            RadiusDistribution::PowerLaw {
+                exponent: f64::NEG_INFINITY,
+            },
+        ];
+        for distribution in infinite {
+            let settings = SpawnSettings {
+                radius_distribution: distribution,
+                ..SpawnSettings::default()
+            };
+            assert!(settings.validate().is_err(), "{:?}", distribution);
+        }
+        let endless = SpawnSettings {
+            speed_distribution: SpeedDistribution::Thermal {
+                temperature: f64::INFINITY,
+            },
+            ..SpawnSettings::default()
+        };
+        assert!(endless.validate().is_err());
+    }
+}
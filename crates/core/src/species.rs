@@ -0,0 +1,53 @@
+use crate::components::{GravityScale, Species};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Properties shared by every particle of a species. A species without an entry
+/// keeps the defaults, and so do particles without a `Species` component, which
+/// count as species 0.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpeciesTable {
+    gravity_scales: BTreeMap<u32, f64>,
+}
+
+impl SpeciesTable {
+    pub fn new() -> Self {
+        SpeciesTable::default()
+    }
+
+    pub fn set_gravity_scale(&mut self, species: Species, scale: f64) {
+        //! Multiply gravity by `scale` for every particle of `species`. A negative
+        //! scale makes them fall upwards.
+        self.gravity_scales.insert(species.0, scale);
+    }
+
+    pub fn gravity_scale(&self, species: Option<&Species>) -> f64 {
+        //! 1 unless the species has been given a scale of its own.
+        let species = species.map_or(0, |species| species.0);
+        self.gravity_scales.get(&species).copied().unwrap_or(1.0)
+    }
+
+    pub fn gravity_scale_of(&self, species: Option<&Species>, own: Option<&GravityScale>) -> f64 {
+        //! The scale for one particle: its own `GravityScale` if it has one,
+        //! otherwise that of its species.
+        own.map_or_else(|| self.gravity_scale(species), |own| own.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_particles_override_their_species() {
+        let mut table = SpeciesTable::new();
+        table.set_gravity_scale(Species(1), -0.5);
+        assert_eq!(table.gravity_scale(None), 1.0);
+        assert_eq!(table.gravity_scale(Some(&Species(1))), -0.5);
+        assert_eq!(table.gravity_scale_of(Some(&Species(1)), None), -0.5);
+        assert_eq!(
+            table.gravity_scale_of(Some(&Species(1)), Some(&GravityScale(2.0))),
+            2.0
+        );
+    }
+}
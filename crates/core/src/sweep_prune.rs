@@ -0,0 +1,670 @@
+use crate::error::{SimError, SimResult};
+use crate::events::Event;
+use crate::particle::Particle;
+use crate::profiler::{Phase, Profiler};
+use crate::shapes::{self, Body, Contact};
+use ordered_float::OrderedFloat;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// The axis that sweep and prune sorts the particles along. Sorting along an axis
+/// the particles are bunched up on, such as x for a horizontal band, prunes almost
+/// nothing, as they all overlap along it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SweepAxis {
+    #[default]
+    X,
+    Y,
+    /// Whichever axis the particles are spread out the most along, picked every step.
+    Auto,
+}
+
+impl SweepAxis {
+    pub fn name(&self) -> &'static str {
+        //! The name of the broadphase sweeping along this axis.
+        match self {
+            SweepAxis::X => "sweep-and-prune (x)",
+            SweepAxis::Y => "sweep-and-prune (y)",
+            SweepAxis::Auto => "sweep-and-prune (auto)",
+        }
+    }
+
+    fn index(self, particles: &[Collider]) -> usize {
+        //! The index of the axis into a `Vec2`. The automatic choice goes
+        //! by the variance of the positions along each axis.
+        match self {
+            SweepAxis::X => 0,
+            SweepAxis::Y => 1,
+            SweepAxis::Auto => {
+                let variance = |axis: usize| {
+                    let count = particles.len().max(1) as f64;
+                    let mean = particles
+                        .iter()
+                        .map(|collider| collider.particle.position[axis])
+                        .sum::<f64>()
+                        / count;
+                    particles
+                        .iter()
+                        .map(|collider| (collider.particle.position[axis] - mean).powi(2))
+                        .sum::<f64>()
+                        / count
+                };
+                if variance(1) > variance(0) {
+                    1
+                } else {
+                    0
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for SweepAxis {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        //! Parse `x`, `y` or `auto`.
+        match value {
+            "x" => Ok(SweepAxis::X),
+            "y" => Ok(SweepAxis::Y),
+            "auto" => Ok(SweepAxis::Auto),
+            _ => Err(SimError::InvalidArgument {
+                name: "sweep axis".to_string(),
+                value: value.to_string(),
+            }),
+        }
+    }
+}
+
+/// With the `parallel` feature, collisions are split into islands and solved on
+/// several threads from this many on. Below it, the bookkeeping costs more than it saves.
+const PARALLEL_COLLISIONS: usize = 128;
+
+/// A particle taking part in collisions, with its `Body` if it is not a plain circle.
+pub struct Collider<'a> {
+    pub particle: &'a mut Particle,
+    pub body: Option<&'a mut Body>,
+}
+
+impl<'a> From<&'a mut Particle> for Collider<'a> {
+    fn from(particle: &'a mut Particle) -> Self {
+        Collider {
+            particle,
+            body: None,
+        }
+    }
+}
+
+fn sweep_and_prune(particles: &[Collider], axis: usize) -> Vec<(usize, usize)> {
+    //!Apply the sweep_and_prune algorithm to check for potential collisions
+    //! The particles must already be sorted along `axis`, we then check for a potential overlap
+    //! Pairs that overlap along the sweep axis are pruned a second time along the other
+    //! axis, which is much cheaper than sending them to the narrow phase.
+    //! Returns tuple pairs of the positions of possible collisions
+    let other = 1 - axis;
+    // The largest particle this step, whatever the limits of random particles are
+    let max_radius = particles
+        .iter()
+        .map(|collider| collider.particle.radius)
+        .fold(0.0, f64::max);
+
+    //First, we sort the list_of_particles along an axis (the x axis)
+    // We cannot sort the vector using the build in method because f64 does not
+    // implement Ord
+
+    // initialize an empty vectors that will store tuple pairs or the index of
+    //potential collisions that we have to check more thoroughly
+    let mut potential_collisions = Vec::new();
+
+    //We now iterate over the list of particles,
+    let mut outer_counter = 0;
+    while outer_counter < particles.len() {
+        let mut inner_counter = outer_counter + 1;
+        while inner_counter < particles.len() {
+            // Both counters are bounded by the length of the slice, so indexing is safe
+            let particle_1 = &particles[outer_counter].particle;
+            let particle_2 = &particles[inner_counter].particle;
+
+            // If the two particles overlap on both axes, then there may be a collision to check
+            if particle_1.position[axis] + particle_1.radius
+                > particle_2.position[axis] - particle_2.radius
+                && (particle_1.position[other] - particle_2.position[other]).abs()
+                    < particle_1.radius + particle_2.radius
+            {
+                potential_collisions.push((outer_counter, inner_counter));
+            }
+
+            //One important optimization is that if the farthest right point
+            //of particle_a is further from the center of particle_b than the radius of the
+            // largest particle, then because the particles are sorted, we know no particles
+            //further in the list can possibly collide with the current particle, so we break early.
+            if particle_2.position[axis] - (particle_1.position[axis] + particle_1.radius)
+                > max_radius
+            {
+                break;
+            }
+
+            inner_counter += 1;
+        }
+        outer_counter += 1;
+    }
+
+    potential_collisions
+}
+
+fn brute_force(particles: &[Collider]) -> Vec<(usize, usize)> {
+    //! The reference broadphase: every pair whose extents overlap along both axes,
+    //! found by checking all of them. It finds the same pairs as `sweep_and_prune`
+    //! in any order of the particles, only much more slowly.
+    let mut potential_collisions = Vec::new();
+    for (index_a, a) in particles.iter().enumerate() {
+        for (index_b, b) in particles.iter().enumerate().skip(index_a + 1) {
+            let reach = a.particle.radius + b.particle.radius;
+            let offset = b.particle.position - a.particle.position;
+            if offset.0.abs() < reach && offset.1.abs() < reach {
+                potential_collisions.push((index_a, index_b));
+            }
+        }
+    }
+    potential_collisions
+}
+
+/// A pair of particles that only one of the two broadphases picked as a possible
+/// collision, found by `verify_broadphase`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    /// The ids of the two particles, the smaller first.
+    pub pair: (u64, u64),
+    /// Whether sweep and prune found the pair and the reference did not, rather
+    /// than the other way round.
+    pub only_sweep_and_prune: bool,
+}
+
+fn divergences(
+    particles: &[Collider],
+    found: &[(usize, usize)],
+    reference: &[(usize, usize)],
+) -> Vec<Divergence> {
+    //! Every pair of indices in one list but not the other, by particle id.
+    let ids = |pairs: &[(usize, usize)]| -> HashSet<(u64, u64)> {
+        pairs
+            .iter()
+            .map(|&(index_a, index_b)| {
+                let (a, b) = (
+                    particles[index_a].particle.id,
+                    particles[index_b].particle.id,
+                );
+                (a.min(b), a.max(b))
+            })
+            .collect()
+    };
+    let (found, reference) = (ids(found), ids(reference));
+    let mut divergences: Vec<Divergence> = found
+        .difference(&reference)
+        .map(|&pair| Divergence {
+            pair,
+            only_sweep_and_prune: true,
+        })
+        .chain(reference.difference(&found).map(|&pair| Divergence {
+            pair,
+            only_sweep_and_prune: false,
+        }))
+        .collect();
+    divergences.sort_by_key(|divergence| divergence.pair);
+    divergences
+}
+
+pub fn verify_broadphase(particles: &mut [Collider], sweep_axis: SweepAxis) -> Vec<Divergence> {
+    //! Run sweep and prune and the brute force reference on the same particles,
+    //! and report every pair that only one of them found. Nothing is changed but
+    //! the order of the slice.
+    let axis = sweep_axis.index(particles);
+    particles.sort_by_key(|collider| OrderedFloat(collider.particle.position[axis]));
+    divergences(
+        particles,
+        &sweep_and_prune(particles, axis),
+        &brute_force(particles),
+    )
+}
+
+fn narrow_phase(
+    particles: &[Collider],
+    potential_collisions: Vec<(usize, usize)>,
+) -> Vec<(usize, usize, Contact)> {
+    //! Keep only the potential collisions where the shapes really overlap,
+    //! along with where and how deeply they do.
+    potential_collisions
+        .into_iter()
+        .filter_map(|(index_a, index_b)| {
+            let (a, b) = (&particles[index_a], &particles[index_b]);
+            let contact = match (&a.body, &b.body) {
+                (None, None) => a.particle.contact(b.particle),
+                (body_a, body_b) => {
+                    shapes::contact(a.particle, body_a.as_deref(), b.particle, body_b.as_deref())
+                }
+            }?;
+            Some((index_a, index_b, contact))
+        })
+        .collect()
+}
+
+fn apply_collision_updates(
+    particles: &mut [Collider],
+    actual_collisions: Vec<(usize, usize, Contact)>,
+    events: &mut Vec<Event>,
+) -> SimResult<()> {
+    //!Due to borrowing rules, we take each particle mutably one at a time.
+    //! There is a nightly method to mutably borrow multiple at a time, but that is not used
+    //! here.
+
+    // At this point, actual_collisions contains all of the index pairs of collisions
+    // Now we just need to iterate one by one, and apply the collision updates
+
+    for (index_a, index_b, contact) in actual_collisions.iter() {
+        //First, we immutably borrow both particles
+        let particle_a = particles
+            .get(*index_a)
+            .ok_or(SimError::MissingParticle(*index_a))?;
+        let particle_b = particles
+            .get(*index_b)
+            .ok_or(SimError::MissingParticle(*index_b))?;
+        //Obtain the required updates to the two particles. Plain circles keep
+        // the simpler formula, shaped bodies also spin.
+        let ((update_a, spin_a), (update_b, spin_b)) = match (&particle_a.body, &particle_b.body) {
+            (None, None) => {
+                let (update_a, update_b) = particle_a
+                    .particle
+                    .collision_react(particle_b.particle, contact);
+                ((update_a, 0.0), (update_b, 0.0))
+            }
+            (body_a, body_b) => shapes::collision_react(
+                particle_a.particle,
+                body_a.as_deref(),
+                particle_b.particle,
+                body_b.as_deref(),
+                contact,
+            ),
+        };
+        // Coincident centers or massless particles divide by zero, and the
+        // resulting NaN would spread to everything the pair touches afterwards
+        if !update_a.is_finite()
+            || !update_b.is_finite()
+            || !spin_a.is_finite()
+            || !spin_b.is_finite()
+        {
+            tracing::warn!(
+                a = particle_a.particle.id,
+                b = particle_b.particle.id,
+                "collision produced non-finite velocities, leaving the pair unchanged"
+            );
+            continue;
+        }
+        // Measured on whichever side has a finite mass, the other may not move
+        let impulse = [(particle_a, update_a), (particle_b, update_b)]
+            .into_iter()
+            .map(|(collider, update)| {
+                collider.particle.effective_mass() * (update - collider.particle.velocity).length()
+            })
+            .filter(|impulse| impulse.is_finite())
+            .fold(0.0, f64::max);
+        events.push(Event::Contact {
+            a: particle_a.particle.id,
+            b: particle_b.particle.id,
+            contact: *contact,
+            impulse,
+        });
+
+        // Now we can borrow mutably one at a time without issue.
+        for (index, update, spin) in [(*index_a, update_a, spin_a), (*index_b, update_b, spin_b)] {
+            let collider = particles
+                .get_mut(index)
+                .ok_or(SimError::MissingParticle(index))?;
+            collider.particle.velocity = update;
+            if let Some(body) = collider.body.as_deref_mut() {
+                body.angular_velocity = spin;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn islands(count: usize, collisions: &[(usize, usize, Contact)]) -> (Vec<Option<usize>>, usize) {
+    //! Split the particles into islands: groups that are connected by collisions,
+    //! directly or through each other, so that no collision in one island changes
+    //! a particle in another. Returns the island of every particle (None for those
+    //! that collide with nothing), numbered in the order of their first collision,
+    //! and the number of islands.
+    let mut parents: Vec<usize> = (0..count).collect();
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+    for (index_a, index_b, _) in collisions {
+        let (root_a, root_b) = (root(&mut parents, *index_a), root(&mut parents, *index_b));
+        parents[root_a.max(root_b)] = root_a.min(root_b);
+    }
+
+    let mut island_of_root = vec![None; count];
+    let mut island_count = 0;
+    for (index_a, _, _) in collisions {
+        let root = root(&mut parents, *index_a);
+        if island_of_root[root].is_none() {
+            island_of_root[root] = Some(island_count);
+            island_count += 1;
+        }
+    }
+    let mut involved = vec![false; count];
+    for (index_a, index_b, _) in collisions {
+        involved[*index_a] = true;
+        involved[*index_b] = true;
+    }
+    let islands = (0..count)
+        .map(|index| {
+            involved[index]
+                .then(|| island_of_root[root(&mut parents, index)])
+                .flatten()
+        })
+        .collect();
+    (islands, island_count)
+}
+
+fn solve_islands(
+    particles: &mut [Collider],
+    actual_collisions: Vec<(usize, usize, Contact)>,
+    events: &mut Vec<Event>,
+) -> SimResult<()> {
+    //! Apply the collision updates of every island separately, in parallel with the
+    //! `parallel` feature. Every island sees its collisions in the same order as a
+    //! single pass would, so the velocities come out exactly the same, but the events
+    //! are grouped by island.
+    if let Some(&(index_a, index_b, _)) = actual_collisions
+        .iter()
+        .find(|(index_a, index_b, _)| (*index_a).max(*index_b) >= particles.len())
+    {
+        return Err(SimError::MissingParticle(index_a.max(index_b)));
+    }
+    let (island_of, island_count) = islands(particles.len(), &actual_collisions);
+
+    // Hand every island its own particles, so that they can be borrowed mutably at once
+    let mut members: Vec<Vec<Collider>> = (0..island_count).map(|_| Vec::new()).collect();
+    let mut local_index = vec![0; particles.len()];
+    for (index, collider) in particles.iter_mut().enumerate() {
+        if let Some(island) = island_of[index] {
+            local_index[index] = members[island].len();
+            members[island].push(Collider {
+                particle: &mut *collider.particle,
+                body: collider.body.as_deref_mut(),
+            });
+        }
+    }
+    let mut collisions: Vec<Vec<(usize, usize, Contact)>> =
+        (0..island_count).map(|_| Vec::new()).collect();
+    for (index_a, index_b, contact) in actual_collisions {
+        if let Some(island) = island_of[index_a] {
+            collisions[island].push((local_index[index_a], local_index[index_b], contact));
+        }
+    }
+
+    let solve = |(particles, collisions): (&mut Vec<Collider>, Vec<(usize, usize, Contact)>)| {
+        let mut island_events = Vec::new();
+        apply_collision_updates(particles, collisions, &mut island_events).map(|_| island_events)
+    };
+    #[cfg(feature = "parallel")]
+    let solved: Vec<SimResult<Vec<Event>>> =
+        members.par_iter_mut().zip(collisions).map(solve).collect();
+    #[cfg(not(feature = "parallel"))]
+    let solved: Vec<SimResult<Vec<Event>>> =
+        members.iter_mut().zip(collisions).map(solve).collect();
+    for island_events in solved {
+        events.extend(island_events?);
+    }
+    Ok(())
+}
+
+pub fn detect_and_apply_collisions(
+    particles: &mut [Collider],
+    sweep_axis: SweepAxis,
+    profiler: &mut Profiler,
+    events: &mut Vec<Event>,
+) -> SimResult<()> {
+    //! Applies sweep and prune algorithm to detect collisions.
+    //! Then calculates new velocities for the collided pairs,
+    //! adding an `Event::Contact` for each of them to `events`.
+
+    let potential_collisions = {
+        let _span = tracing::debug_span!("broadphase", particles = particles.len()).entered();
+        //First, we sort the list_of_particles along an axis (the x axis unless told otherwise)
+        // We cannot sort the vector using the built in method because f64 does not
+        // implement Ord
+        let axis = profiler.time(Phase::Sort, || {
+            let axis = sweep_axis.index(particles);
+            particles.sort_by_key(|collider| OrderedFloat(collider.particle.position[axis]));
+            axis
+        });
+        // Find Collisions, this is the most computationally expensive part of this function.
+        profiler.time(Phase::SweepAndPrune, || sweep_and_prune(particles, axis))
+    };
+
+    let confirmed_collisions = {
+        let _span =
+            tracing::debug_span!("narrowphase", candidates = potential_collisions.len()).entered();
+        profiler.time(Phase::NarrowPhase, || {
+            narrow_phase(particles, potential_collisions)
+        })
+    };
+    tracing::trace!(
+        collisions = confirmed_collisions.len(),
+        "resolving collisions"
+    );
+
+    //Apply the physics of a collision to particles that have collided.
+    profiler.time(Phase::CollisionResponse, || {
+        if cfg!(feature = "parallel") && confirmed_collisions.len() >= PARALLEL_COLLISIONS {
+            solve_islands(particles, confirmed_collisions, events)
+        } else {
+            apply_collision_updates(particles, confirmed_collisions, events)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Vec2;
+
+    #[test]
+    fn test_sort() {
+        // Test that radix sort does the job
+        let mut t1 = [
+            Particle::new(1, 1.0, Vec2(1.0, 1.0), Vec2(1.0, 1.0)),
+            Particle::new(1, 0.5, Vec2(0.5, 0.5), Vec2(0.5, 0.5)),
+            Particle::new(1, 3.2, Vec2(3.2, 3.2), Vec2(3.2, 3.2)),
+        ];
+        t1.sort_by_key(|particle| OrderedFloat(particle.position.0));
+
+        let expected_sort = vec![0.5, 1.0, 3.2];
+        let actual_sort: Vec<f64> = t1.iter().map(|circle| circle.position.0).collect();
+
+        assert_eq!(expected_sort, actual_sort);
+    }
+
+    #[test]
+    fn test_narrow_phase_filters_x_overlaps() {
+        // All three overlap along x, but the last one is far away along y
+        let mut p1 = Particle::new(1, 2.0, Vec2(10.0, 10.0), Vec2(0.0, 0.0));
+        let mut p2 = Particle::new(2, 2.0, Vec2(12.0, 10.0), Vec2(0.0, 0.0));
+        let mut p3 = Particle::new(3, 2.0, Vec2(13.0, 500.0), Vec2(0.0, 0.0));
+        let particles = [
+            Collider::from(&mut p1),
+            Collider::from(&mut p2),
+            Collider::from(&mut p3),
+        ];
+
+        // The second pass along y already drops the last one
+        let potential = sweep_and_prune(&particles, 0);
+        assert_eq!(potential, vec![(0, 1)]);
+        let confirmed = narrow_phase(&particles, vec![(0, 1), (0, 2), (1, 2)]);
+        assert_eq!(confirmed.len(), 1);
+        let (index_a, index_b, contact) = confirmed[0];
+        assert_eq!((index_a, index_b), (0, 1));
+        assert_eq!(contact.normal, Vec2(1.0, 0.0));
+        assert_eq!(contact.penetration_depth, 2.0);
+        assert_eq!(contact.point, Vec2(11.0, 10.0));
+    }
+
+    #[test]
+    fn test_auto_axis_sweeps_along_the_spread() {
+        // A vertical column, which every particle overlaps along x
+        let mut column: Vec<Particle> = (0..10)
+            .map(|i| Particle::new(i, 5.0, Vec2(500.0, 50.0 + 100.0 * i as f64), Vec2::ZERO))
+            .collect();
+        let mut particles: Vec<Collider> = column.iter_mut().map(Collider::from).collect();
+        assert_eq!(SweepAxis::Auto.index(&particles), 1);
+        assert_eq!(SweepAxis::X.index(&particles), 0);
+
+        let mut events = Vec::new();
+        let mut profiler = Profiler::new();
+        detect_and_apply_collisions(&mut particles, SweepAxis::Auto, &mut profiler, &mut events)
+            .unwrap();
+        assert!(events.is_empty());
+        assert_eq!("auto".parse::<SweepAxis>().unwrap(), SweepAxis::Auto);
+        assert!("z".parse::<SweepAxis>().is_err());
+    }
+
+    #[test]
+    fn test_large_particles_are_not_pruned_early() {
+        // The gap between the two small particles is wider than the largest
+        // random radius, but the huge one behind them still reaches the first
+        let mut p1 = Particle::new(1, 1.0, Vec2(0.0, 0.0), Vec2::ZERO);
+        let mut p2 = Particle::new(2, 1.0, Vec2(60.0, 0.0), Vec2::ZERO);
+        let mut p3 = Particle::new(3, 150.0, Vec2(150.0, 0.0), Vec2::ZERO);
+        let particles = [
+            Collider::from(&mut p1),
+            Collider::from(&mut p2),
+            Collider::from(&mut p3),
+        ];
+        assert_eq!(sweep_and_prune(&particles, 0), vec![(0, 2), (1, 2)]);
+    }
+
+    #[test]
+    fn test_brute_force_finds_what_sweep_and_prune_finds() {
+        // The large particle starts left of the small one's extent, while its
+        // center lies to the right
+        let mut particles = vec![
+            Particle::new(1, 5.0, Vec2(100.0, 100.0), Vec2::ZERO),
+            Particle::new(2, 40.0, Vec2(110.0, 120.0), Vec2::ZERO),
+            Particle::new(3, 5.0, Vec2(300.0, 100.0), Vec2::ZERO),
+            Particle::new(4, 5.0, Vec2(306.0, 500.0), Vec2::ZERO),
+        ];
+        for id in 5..60 {
+            let position = Vec2((id * 37 % 900) as f64, (id * 53 % 900) as f64);
+            particles.push(Particle::new(
+                id,
+                3.0 + (id % 20) as f64,
+                position,
+                Vec2::ZERO,
+            ));
+        }
+        let mut colliders: Vec<Collider> = particles.iter_mut().map(Collider::from).collect();
+        for sweep_axis in [SweepAxis::X, SweepAxis::Y, SweepAxis::Auto] {
+            assert_eq!(verify_broadphase(&mut colliders, sweep_axis), vec![]);
+        }
+        let reference = brute_force(&colliders);
+        assert!(reference.len() > 1);
+
+        // A pair missed by sweep and prune, and then one only it found
+        let (a, b) = reference[0];
+        let pair = (colliders[a].particle.id, colliders[b].particle.id);
+        let pair = (pair.0.min(pair.1), pair.0.max(pair.1));
+        let missed = Divergence {
+            pair,
+            only_sweep_and_prune: false,
+        };
+        assert_eq!(
+            divergences(&colliders, &reference[1..], &reference),
+            vec![missed]
+        );
+        let extra = Divergence {
+            only_sweep_and_prune: true,
+            ..missed
+        };
+        assert_eq!(
+            divergences(&colliders, &reference, &reference[1..]),
+            vec![extra]
+        );
+    }
+
+    #[test]
+    fn test_islands_give_the_same_velocities_as_one_pass() {
+        // Two chains of three, far apart, and a lone particle
+        let positions = [0.0, 15.0, 30.0, 500.0, 515.0, 530.0, 900.0];
+        let spawn = || -> Vec<Particle> {
+            positions
+                .iter()
+                .enumerate()
+                .map(|(id, x)| {
+                    let velocity = Vec2(if id % 2 == 0 { 3.0 } else { -2.0 }, 0.5);
+                    Particle::new(id as u64, 10.0, Vec2(*x, 100.0), velocity)
+                })
+                .collect()
+        };
+        let mut one_pass = spawn();
+        let mut by_island = spawn();
+        let collisions =
+            |particles: &[Collider]| narrow_phase(particles, sweep_and_prune(particles, 0));
+
+        let mut particles: Vec<Collider> = one_pass.iter_mut().map(Collider::from).collect();
+        let confirmed = collisions(&particles);
+        assert_eq!(confirmed.len(), 4);
+        let (island_of, island_count) = islands(particles.len(), &confirmed);
+        assert_eq!(island_count, 2);
+        assert_eq!(
+            island_of,
+            [Some(0), Some(0), Some(0), Some(1), Some(1), Some(1), None]
+        );
+        let mut events = Vec::new();
+        apply_collision_updates(&mut particles, confirmed, &mut events).unwrap();
+
+        let mut particles: Vec<Collider> = by_island.iter_mut().map(Collider::from).collect();
+        let confirmed = collisions(&particles);
+        let mut island_events = Vec::new();
+        solve_islands(&mut particles, confirmed, &mut island_events).unwrap();
+        assert_eq!(island_events.len(), events.len());
+        for (a, b) in one_pass.iter().zip(&by_island) {
+            assert_eq!(a.velocity, b.velocity);
+        }
+    }
+
+    #[test]
+    fn test_missing_particle_is_an_error() {
+        // A collision pair pointing past the end of the slice must be reported,
+        // not panic.
+        let mut p1 = Particle::new(1, 1.0, Vec2(1.0, 1.0), Vec2(1.0, 1.0));
+        let mut p2 = Particle::new(2, 1.0, Vec2(1.5, 1.0), Vec2(-1.0, 1.0));
+        let contact = p1.contact(&p2).unwrap();
+        let mut particles = [Collider::from(&mut p1), Collider::from(&mut p2)];
+        let result =
+            apply_collision_updates(&mut particles, vec![(0, 5, contact)], &mut Vec::new());
+        assert!(matches!(result, Err(SimError::MissingParticle(5))));
+    }
+
+    #[test]
+    fn test_coincident_particles_keep_finite_velocities() {
+        let mut p1 = Particle::new(1, 1.0, Vec2(5.0, 5.0), Vec2(1.0, 0.0));
+        let mut p2 = Particle::new(2, 1.0, Vec2(5.0, 5.0), Vec2(-1.0, 0.0));
+        let contact = p1.contact(&p2).unwrap();
+        let mut particles = [Collider::from(&mut p1), Collider::from(&mut p2)];
+        let mut events = Vec::new();
+        apply_collision_updates(&mut particles, vec![(0, 1, contact)], &mut events).unwrap();
+        assert_eq!(particles[0].particle.velocity.0, 1.0);
+        assert_eq!(particles[1].particle.velocity.0, -1.0);
+        // A collision that was not applied is not reported either
+        assert!(events.is_empty());
+    }
+}
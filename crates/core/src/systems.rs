@@ -0,0 +1,273 @@
+use crate::components::{GravityScale, Lifetime, Species};
+use crate::error::SimResult;
+use crate::events::Event;
+use crate::gravity;
+use crate::obstacles::Obstacle;
+use crate::particle::{Particle, HEIGHT, WIDTH};
+use crate::portals::Portal;
+use crate::profiler::Profiler;
+use crate::settings::{NonFinitePolicy, OutOfBoundsPolicy, PhysicsSettings};
+use crate::shapes::Body;
+use crate::species::SpeciesTable;
+use crate::sweep_prune::{self, detect_and_apply_collisions, Collider, Divergence, SweepAxis};
+use crate::time_regions::{self, TimeRegion};
+use crate::utils::Vec2;
+use hecs::{Entity, World};
+use rand::Rng;
+
+pub fn integrate(
+    world: &mut World,
+    settings: &PhysicsSettings,
+    species: &SpeciesTable,
+    time_regions: &[TimeRegion],
+    dt: f64,
+    rng: &mut impl Rng,
+) -> f64 {
+    //! Accelerate every particle by gravity, scaled for its species and less the
+    //! buoyancy of the medium if there is one, then move it forward by `dt`, bouncing off of the walls.
+    //! Shaped bodies also turn, and bounce off of the walls with their corners.
+    //! Particles inside a time region step by their own scaled `dt`. Particles leaving a thermal wall get a
+    //! new velocity drawn from `rng`. Returns the total impulse the walls gave
+    //! the particles.
+    let _span = tracing::debug_span!("integration").entered();
+    let mut wall_impulse = 0.0;
+    let query = world.query_mut::<(
+        &mut Particle,
+        Option<&mut Body>,
+        Option<&Species>,
+        Option<&GravityScale>,
+    )>();
+    for (_, (particle, body, kind, own_scale)) in query {
+        if particle.fixed {
+            continue;
+        }
+        let dt = time_regions::local_dt(time_regions, particle.position, dt);
+        let gravity = species.gravity_scale_of(kind, own_scale) * settings.gravity;
+        match settings.medium {
+            Some(medium) => {
+                let density = match &body {
+                    Some(body) => particle.mass / body.shape.area(),
+                    None => particle.density(),
+                };
+                let share = medium.gravity_share(particle.position.1, density);
+                particle.velocity += (dt * share) * gravity;
+            }
+            None => particle.velocity += dt * gravity,
+        }
+        let velocity = particle.velocity;
+        let bounced_off = match body {
+            Some(body) => body.update_with_walls(particle, dt, &settings.walls),
+            None => particle.update_with_walls(dt, &settings.walls),
+        };
+        for side in bounced_off.into_iter().flatten() {
+            let wall = settings.walls.side(side);
+            if let Some(velocity) = wall.thermalize(particle.mass, side.inward(), rng) {
+                particle.velocity = velocity;
+            }
+        }
+        // Only a bounce changes the velocity here
+        if particle.mass.is_finite() {
+            wall_impulse += particle.mass * (particle.velocity - velocity).length();
+        }
+    }
+    wall_impulse
+}
+
+pub fn attract(world: &mut World, constant: f64, time_regions: &[TimeRegion], dt: f64) {
+    //! Accelerate every moving particle towards all of the others by their
+    //! mutual gravity, see `gravity::accelerations`.
+    let mut query = world.query::<&mut Particle>();
+    let mut particles: Vec<&mut Particle> = query.iter().map(|(_, particle)| particle).collect();
+    let snapshot: Vec<Particle> = particles.iter().map(|particle| **particle).collect();
+    let accelerations = gravity::accelerations(&snapshot, constant);
+    for (particle, acceleration) in particles.iter_mut().zip(accelerations) {
+        if particle.fixed || particle.mass.is_infinite() {
+            continue;
+        }
+        let dt = time_regions::local_dt(time_regions, particle.position, dt);
+        particle.velocity += dt * acceleration;
+    }
+}
+
+pub fn collide_obstacles(world: &mut World, obstacles: &[Obstacle]) {
+    //! Push every particle out of any obstacle it has moved into.
+    if obstacles.is_empty() {
+        return;
+    }
+    for (_, particle) in world.query_mut::<&mut Particle>() {
+        if particle.fixed {
+            continue;
+        }
+        for obstacle in obstacles {
+            obstacle.push_out(particle);
+        }
+    }
+}
+
+pub fn teleport(world: &mut World, portals: &[Portal], time_regions: &[TimeRegion], dt: f64) {
+    //! Send every particle that moved into a portal out of its other end.
+    //! A particle goes through at most one portal per step.
+    if portals.is_empty() {
+        return;
+    }
+    for (_, particle) in world.query_mut::<&mut Particle>() {
+        if particle.fixed {
+            continue;
+        }
+        let dt = time_regions::local_dt(time_regions, particle.position, dt);
+        for portal in portals {
+            if portal.teleport(particle, dt) {
+                break;
+            }
+        }
+    }
+}
+
+pub fn expire_lifetimes(world: &mut World, time_regions: &[TimeRegion], dt: f64) {
+    //! Count down every `Lifetime` by `dt`, scaled for particles inside a time
+    //! region, and despawn the entities whose time has run out.
+    let mut expired: Vec<Entity> = Vec::new();
+    for (entity, (lifetime, particle)) in world.query_mut::<(&mut Lifetime, Option<&Particle>)>() {
+        lifetime.0 -= match particle {
+            Some(particle) => time_regions::local_dt(time_regions, particle.position, dt),
+            None => dt,
+        };
+        if lifetime.0 <= 0.0 {
+            expired.push(entity);
+        }
+    }
+
+    // Entities cannot be despawned while the world is borrowed by the query
+    for entity in expired {
+        tracing::debug!(?entity, "lifetime expired");
+        world.despawn(entity).ok();
+    }
+}
+
+pub fn sanitize(world: &mut World, policy: NonFinitePolicy) -> usize {
+    //! Find every particle with a NaN or infinite value, log it, and then
+    //! clamp or remove it according to `policy`. Returns how many were found.
+    let mut removed: Vec<Entity> = Vec::new();
+    let mut found = 0;
+    for (entity, particle) in world.query_mut::<&mut Particle>() {
+        if particle.is_finite() {
+            continue;
+        }
+        found += 1;
+        tracing::warn!(?particle, ?policy, "particle has non-finite values");
+
+        let repairable = particle.radius.is_finite()
+            && !particle.mass.is_nan()
+            && particle.radius > 0.0
+            && particle.mass > 0.0;
+        if policy == NonFinitePolicy::Remove || !repairable {
+            removed.push(entity);
+            continue;
+        }
+        if !particle.velocity.is_finite() {
+            particle.velocity = Vec2::ZERO;
+        }
+        particle.position.0 = clamp_coordinate(particle.position.0, WIDTH);
+        particle.position.1 = clamp_coordinate(particle.position.1, HEIGHT);
+    }
+
+    for entity in removed {
+        world.despawn(entity).ok();
+    }
+    found
+}
+
+pub fn limit_speeds(world: &mut World, max_speed: Option<f64>) -> usize {
+    //! Slow every particle that moves faster than `max_speed` down to it,
+    //! keeping its direction. Returns how many were slowed.
+    let Some(max_speed) = max_speed else {
+        return 0;
+    };
+    let mut clamped = 0;
+    for (_, particle) in world.query_mut::<&mut Particle>() {
+        let speed = particle.velocity.length();
+        if speed > max_speed {
+            particle.velocity = (max_speed / speed) * particle.velocity;
+            clamped += 1;
+        }
+    }
+    if clamped > 0 {
+        tracing::debug!(clamped, max_speed, "particles slowed to the speed limit");
+    }
+    clamped
+}
+
+pub fn recover_escaped(world: &mut World, policy: OutOfBoundsPolicy) -> usize {
+    //! Find every moving particle whose center has left the box, log it, and then
+    //! bring it back inside or remove it according to `policy`. A particle that is
+    //! brought back sits against the wall it went through, moving away from it.
+    //! Returns how many were found.
+    let mut removed: Vec<Entity> = Vec::new();
+    let mut found = 0;
+    for (entity, particle) in world.query_mut::<&mut Particle>() {
+        let (x, y) = (particle.position.0, particle.position.1);
+        if particle.fixed || ((0.0..=WIDTH).contains(&x) && (0.0..=HEIGHT).contains(&y)) {
+            continue;
+        }
+        found += 1;
+        tracing::warn!(?particle, ?policy, "particle escaped the box");
+        if policy == OutOfBoundsPolicy::Remove {
+            removed.push(entity);
+            continue;
+        }
+        for (axis, size) in [(0, WIDTH), (1, HEIGHT)] {
+            let inside = particle.radius.min(size / 2.0);
+            if particle.position[axis] < 0.0 {
+                particle.position[axis] = inside;
+                particle.velocity[axis] = particle.velocity[axis].abs();
+            } else if particle.position[axis] > size {
+                particle.position[axis] = size - inside;
+                particle.velocity[axis] = -particle.velocity[axis].abs();
+            }
+        }
+    }
+
+    for entity in removed {
+        world.despawn(entity).ok();
+    }
+    found
+}
+
+fn clamp_coordinate(value: f64, size: f64) -> f64 {
+    //! Infinities are clamped to the edges of the box, NaN goes to the middle.
+    if value.is_nan() {
+        size / 2.0
+    } else {
+        value.clamp(0.0, size)
+    }
+}
+
+pub fn resolve_collisions(
+    world: &mut World,
+    sweep_axis: SweepAxis,
+    profiler: &mut Profiler,
+    events: &mut Vec<Event>,
+) -> SimResult<()> {
+    //! Detect collisions between all particles in the world, apply the new
+    //! velocities to the colliding pairs, and report their contacts in `events`.
+
+    // Sweep and prune needs a sortable slice, so we gather mutable
+    // references to every particle component in the world, and its body if any.
+    let mut particles: Vec<Collider> = world
+        .query_mut::<(&mut Particle, Option<&mut Body>)>()
+        .into_iter()
+        .map(|(_, (particle, body))| Collider { particle, body })
+        .collect();
+    detect_and_apply_collisions(&mut particles, sweep_axis, profiler, events)
+}
+
+pub fn verify_broadphase(world: &mut World, sweep_axis: SweepAxis) -> Vec<Divergence> {
+    //! Compare sweep and prune with the brute force broadphase on every particle
+    //! in the world, without resolving any collisions.
+    let mut particles: Vec<Collider> = world
+        .query_mut::<(&mut Particle, Option<&mut Body>)>()
+        .into_iter()
+        .map(|(_, (particle, body))| Collider { particle, body })
+        .collect();
+    sweep_prune::verify_broadphase(&mut particles, sweep_axis)
+}
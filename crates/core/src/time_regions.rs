@@ -0,0 +1,78 @@
+use crate::error::{SimError, SimResult};
+use crate::utils::Vec2;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// An axis aligned rectangle of the box where time runs at `scale` times its
+/// normal rate: a particle whose center is inside moves, falls and ages that much
+/// more slowly or quickly. Collisions are resolved the same as anywhere else, so
+/// a fast particle crossing into a slow region piles up behind the slow ones.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeRegion {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub scale: f64,
+}
+
+impl TimeRegion {
+    pub fn contains(&self, point: Vec2) -> bool {
+        (self.min.0..self.max.0).contains(&point.0) && (self.min.1..self.max.1).contains(&point.1)
+    }
+}
+
+pub fn local_dt(regions: &[TimeRegion], position: Vec2, dt: f64) -> f64 {
+    //! The step a particle at `position` takes instead of `dt`. Where regions
+    //! overlap, the first of them that holds the particle wins.
+    regions
+        .iter()
+        .find(|region| region.contains(position))
+        .map_or(dt, |region| region.scale * dt)
+}
+
+impl FromStr for TimeRegion {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        //! Parse `<min_x>,<min_y>,<max_x>,<max_y>,<scale>`, where a scale of 0
+        //! freezes the particles inside.
+        let invalid = || SimError::InvalidArgument {
+            name: "time region".to_string(),
+            value: value.to_string(),
+        };
+        let params: Vec<f64> = value
+            .split(',')
+            .map(|param| param.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+        let [min_x, min_y, max_x, max_y, scale] = params.as_slice() else {
+            return Err(invalid());
+        };
+        if !(min_x < max_x && min_y < max_y && scale.is_finite() && *scale >= 0.0) {
+            return Err(invalid());
+        }
+        Ok(TimeRegion {
+            min: Vec2(*min_x, *min_y),
+            max: Vec2(*max_x, *max_y),
+            scale: *scale,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_region_sets_the_step() {
+        let regions: Vec<TimeRegion> = ["0,0,100,100,0.5", "50,50,200,200,2"]
+            .iter()
+            .map(|region| region.parse().unwrap())
+            .collect();
+        assert_eq!(local_dt(&regions, Vec2(60.0, 60.0), 1.0), 0.5);
+        assert_eq!(local_dt(&regions, Vec2(150.0, 60.0), 1.0), 2.0);
+        assert_eq!(local_dt(&regions, Vec2(500.0, 500.0), 1.0), 1.0);
+        assert!("0,0,100,100".parse::<TimeRegion>().is_err());
+        assert!("0,0,100,100,-1".parse::<TimeRegion>().is_err());
+        assert!("100,0,0,100,1".parse::<TimeRegion>().is_err());
+    }
+}
@@ -0,0 +1,319 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::{Add, AddAssign, Index, IndexMut, Mul, Neg, Sub, SubAssign};
+
+pub fn get_random_color(rng: &mut impl Rng) -> [f32; 4] {
+    //!Utility to get a Random opaque RGBA Color for rendering purposes
+    [rng.gen::<f32>(), rng.gen::<f32>(), rng.gen::<f32>(), 1.0]
+}
+
+/// A 2D vector for much more readable vectorized operations.
+/// Positions, velocities and forces are all `Vec2`s, with the
+/// x component in `.0` (or `[0]`) and the y component in `.1` (or `[1]`).
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+pub struct Vec2(pub f64, pub f64);
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2(0.0, 0.0);
+
+    pub fn inner_product(&self, other: &Self) -> f64 {
+        // We are in the Real domain only, so we can ignore
+        // the inner_product requirements for complex vectors.
+        // This isnt't Quantum Mechanics!
+        backend::dot(*self, *other)
+    }
+
+    pub fn cross(&self, other: &Self) -> f64 {
+        //! The z component of the cross product of the two vectors as if they
+        //! were in 3D, positive when `other` is anticlockwise from `self`.
+        self.0 * other.1 - self.1 * other.0
+    }
+
+    pub fn length(&self) -> f64 {
+        f64::sqrt(self.inner_product(self))
+    }
+
+    pub fn normalize(&self) -> Self {
+        //! Returns a vector of length 1 in the same direction,
+        //! or the zero vector if this one has no direction.
+        let length = self.length();
+        if length > 0.0 {
+            self.scale(1.0 / length)
+        } else {
+            Vec2::ZERO
+        }
+    }
+
+    pub fn perp(&self) -> Self {
+        //! Returns the vector turned anticlockwise by a right angle
+        Vec2(-self.1, self.0)
+    }
+
+    pub fn rotate(&self, angle: f64) -> Self {
+        //! Returns the vector rotated anticlockwise by `angle` radians
+        backend::rotate(*self, angle)
+    }
+
+    pub fn scale(&self, factor: f64) -> Self {
+        backend::scale(*self, factor)
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.0.is_finite() && self.1.is_finite()
+    }
+
+    pub fn distance(&self, other: &Self) -> f64 {
+        //! Returns the distance between two Vec2s
+        backend::distance(*self, *other)
+    }
+}
+
+/// The arithmetic behind `Vec2`, written out by hand.
+#[cfg(not(feature = "glam"))]
+mod backend {
+    use super::Vec2;
+
+    pub fn dot(a: Vec2, b: Vec2) -> f64 {
+        a.0 * b.0 + a.1 * b.1
+    }
+
+    pub fn add(a: Vec2, b: Vec2) -> Vec2 {
+        Vec2(a.0 + b.0, a.1 + b.1)
+    }
+
+    pub fn sub(a: Vec2, b: Vec2) -> Vec2 {
+        Vec2(a.0 - b.0, a.1 - b.1)
+    }
+
+    pub fn scale(a: Vec2, factor: f64) -> Vec2 {
+        Vec2(a.0 * factor, a.1 * factor)
+    }
+
+    pub fn rotate(a: Vec2, angle: f64) -> Vec2 {
+        let (sin, cos) = angle.sin_cos();
+        Vec2(a.0 * cos - a.1 * sin, a.0 * sin + a.1 * cos)
+    }
+
+    pub fn distance(a: Vec2, b: Vec2) -> f64 {
+        f64::sqrt((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2))
+    }
+}
+
+/// The arithmetic behind `Vec2`, handed to `glam::DVec2`. Both backends
+/// do the same floating point operations, so results do not depend on the feature.
+#[cfg(feature = "glam")]
+mod backend {
+    use super::Vec2;
+    use glam::DVec2;
+
+    pub fn dot(a: Vec2, b: Vec2) -> f64 {
+        DVec2::from(a).dot(b.into())
+    }
+
+    pub fn add(a: Vec2, b: Vec2) -> Vec2 {
+        (DVec2::from(a) + DVec2::from(b)).into()
+    }
+
+    pub fn sub(a: Vec2, b: Vec2) -> Vec2 {
+        (DVec2::from(a) - DVec2::from(b)).into()
+    }
+
+    pub fn scale(a: Vec2, factor: f64) -> Vec2 {
+        (DVec2::from(a) * factor).into()
+    }
+
+    pub fn rotate(a: Vec2, angle: f64) -> Vec2 {
+        DVec2::from_angle(angle).rotate(a.into()).into()
+    }
+
+    pub fn distance(a: Vec2, b: Vec2) -> f64 {
+        DVec2::from(a).distance(b.into())
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<glam::DVec2> for Vec2 {
+    fn from(vector: glam::DVec2) -> Self {
+        Vec2(vector.x, vector.y)
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<Vec2> for glam::DVec2 {
+    fn from(vector: Vec2) -> Self {
+        glam::DVec2::new(vector.0, vector.1)
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        backend::add(self, other)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, other: Self) {
+        *self = backend::add(*self, other);
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        backend::sub(self, other)
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, other: Self) {
+        *self = backend::sub(*self, other);
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0, -self.1)
+    }
+}
+
+impl Mul<Vec2> for f64 {
+    type Output = Vec2;
+
+    fn mul(self, rhs: Vec2) -> Vec2 {
+        backend::scale(rhs, self)
+    }
+}
+
+impl Index<usize> for Vec2 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.0,
+            1 => &self.1,
+            _ => panic!("Vec2 has no axis {}", axis),
+        }
+    }
+}
+
+impl IndexMut<usize> for Vec2 {
+    fn index_mut(&mut self, axis: usize) -> &mut f64 {
+        match axis {
+            0 => &mut self.0,
+            1 => &mut self.1,
+            _ => panic!("Vec2 has no axis {}", axis),
+        }
+    }
+}
+
+impl fmt::Display for Vec2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.0, self.1)
+    }
+}
+
+/// The three dimensional counterpart of `Vec2`, for the 3D simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Vec3(pub f64, pub f64, pub f64);
+
+impl Vec3 {
+    pub fn inner_product(&self, other: &Self) -> f64 {
+        self.0 * other.0 + self.1 * other.1 + self.2 * other.2
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.0.is_finite() && self.1.is_finite() && self.2.is_finite()
+    }
+
+    pub fn distance(&self, other: &Self) -> f64 {
+        //! Returns the distance between two Vec3s
+        (*self - *other).inner_product(&(*self - *other)).sqrt()
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self(self.0 + other.0, self.1 + other.1, self.2 + other.2)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self(self.0 - other.0, self.1 - other.1, self.2 - other.2)
+    }
+}
+
+impl Mul<Vec3> for f64 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Vec3) -> Vec3 {
+        Vec3(self * rhs.0, self * rhs.1, self * rhs.2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_random_color() {
+        let rand_color = get_random_color(&mut rand::thread_rng());
+        println!("{:?}", rand_color);
+    }
+
+    #[test]
+    fn test_add_subtract_vec2() {
+        let v1 = Vec2(5.0, 3.2);
+        let v2 = Vec2(4.7, -2.6);
+        //Unfortunately, float equality is rather fickle to test.
+        assert!((v1 + v2) - Vec2(9.7, 0.6) < Vec2(0.00001, 0.00001));
+        assert!((v1 - v2) - Vec2(0.3, 5.8) < Vec2(0.00001, 0.00001));
+    }
+
+    #[test]
+    fn test_inner_product() {
+        let v1 = Vec2(5.0, 4.0);
+        let v2 = Vec2(3.0, -7.0);
+        assert_eq!(v1.inner_product(&v2), -13.0);
+    }
+
+    #[test]
+    fn test_scalar_multiply() {
+        let v1 = Vec2(5.0, 4.0);
+        assert_eq!(3.0 * v1, Vec2(15.0, 12.0));
+    }
+
+    #[test]
+    fn test_length_normalize_and_perp() {
+        let v = Vec2(3.0, 4.0);
+        assert_eq!(v.length(), 5.0);
+        assert!((v.normalize().length() - 1.0).abs() < 1e-12);
+        assert_eq!(Vec2(0.0, -2.0).normalize(), Vec2(0.0, -1.0));
+        assert_eq!(Vec2::ZERO.normalize(), Vec2::ZERO);
+        assert_eq!(v.perp(), Vec2(-4.0, 3.0));
+        assert_eq!(v.perp().inner_product(&v), 0.0);
+        assert_eq!(v.cross(&v.perp()), 25.0);
+        assert_eq!(-v, Vec2(-3.0, -4.0));
+        assert_eq!((v[0], v[1]), (3.0, 4.0));
+        assert_eq!(v.to_string(), "(3, 4)");
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn test_glam_round_trip() {
+        let v = Vec2(1.5, -2.0);
+        let converted: glam::DVec2 = v.into();
+        assert_eq!(converted, glam::DVec2::new(1.5, -2.0));
+        assert_eq!(Vec2::from(converted), v);
+    }
+}
@@ -0,0 +1,80 @@
+//! Property based tests for `Particle::contact` and `Particle::collision_react`: for
+//! any pair of overlapping particles, the collision must conserve momentum and kinetic
+//! energy, and must leave the pair moving apart (or at least not towards each other)
+//! along the line between their centers.
+use particle_core::particle::{Particle, RADIUS_UPPER_BOUND};
+use particle_core::utils::Vec2;
+use proptest::prelude::*;
+use std::f64::consts::PI;
+
+const SPEED: f64 = 25.0;
+// Relative tolerance, the formula is evaluated in a different order than the checks
+const EPSILON: f64 = 1e-9;
+
+/// Two particles whose circles overlap, at any angle, with any velocities.
+fn colliding_pair() -> impl Strategy<Value = (Particle, Particle)> {
+    (
+        (1.0..RADIUS_UPPER_BOUND, 1.0..RADIUS_UPPER_BOUND),
+        (100.0..900.0, 100.0..900.0),
+        (0.0..2.0 * PI, 0.05..0.99),
+        (-SPEED..SPEED, -SPEED..SPEED, -SPEED..SPEED, -SPEED..SPEED),
+    )
+        .prop_map(
+            |((r1, r2), (x, y), (angle, overlap), (vx1, vy1, vx2, vy2))| {
+                let distance = overlap * (r1 + r2);
+                let first = Particle::new(0, r1, Vec2(x, y), Vec2(vx1, vy1));
+                let second = Particle::new(
+                    1,
+                    r2,
+                    Vec2(x + distance * angle.cos(), y + distance * angle.sin()),
+                    Vec2(vx2, vy2),
+                );
+                (first, second)
+            },
+        )
+}
+
+fn react(first: &Particle, second: &Particle) -> (Vec2, Vec2) {
+    let contact = first
+        .contact(second)
+        .expect("the pair is generated overlapping");
+    first.collision_react(second, &contact)
+}
+
+fn momentum(particle: &Particle, velocity: Vec2) -> Vec2 {
+    particle.mass * velocity
+}
+
+fn kinetic_energy(particle: &Particle, velocity: Vec2) -> f64 {
+    0.5 * particle.mass * velocity.inner_product(&velocity)
+}
+
+proptest! {
+    #[test]
+    fn test_momentum_is_conserved((first, second) in colliding_pair()) {
+        let (v1, v2) = react(&first, &second);
+        let before = momentum(&first, first.velocity) + momentum(&second, second.velocity);
+        let after = momentum(&first, v1) + momentum(&second, v2);
+        let scale = (first.mass + second.mass) * SPEED;
+        prop_assert!((after.0 - before.0).abs() <= EPSILON * scale, "{:?} != {:?}", after, before);
+        prop_assert!((after.1 - before.1).abs() <= EPSILON * scale, "{:?} != {:?}", after, before);
+    }
+
+    #[test]
+    fn test_kinetic_energy_is_conserved((first, second) in colliding_pair()) {
+        let (v1, v2) = react(&first, &second);
+        let before = kinetic_energy(&first, first.velocity) + kinetic_energy(&second, second.velocity);
+        let after = kinetic_energy(&first, v1) + kinetic_energy(&second, v2);
+        prop_assert!((after - before).abs() <= EPSILON * before.max(1.0), "{} != {}", after, before);
+    }
+
+    #[test]
+    fn test_pair_is_not_approaching_afterwards((first, second) in colliding_pair()) {
+        let (v1, v2) = react(&first, &second);
+        let normal = first.position - second.position;
+        // Positive when the particles are separating along the normal
+        let separation_speed = (v1 - v2).inner_product(&normal);
+        let scale = SPEED * normal.inner_product(&normal).sqrt();
+        prop_assert!(separation_speed >= -EPSILON * scale, "still approaching at {}", separation_speed);
+    }
+}
@@ -0,0 +1,39 @@
+//! A long run that checks the total kinetic energy of a closed box of particles.
+//! Walls and collisions are both elastic, so the energy may only drift by rounding
+//! error. A solver or integrator bug that loses or gains a little energy per
+//! collision stays hidden over the few hundred steps of the other tests, but adds up
+//! over a hundred thousand of them.
+//!
+//! The run takes a while, so it is ignored by default:
+//!
+//! ```text
+//! cargo test -p particle-core --release --test energy_drift -- --ignored
+//! ```
+use particle_core::simulation::Simulation;
+
+const SEED: u64 = 7;
+const PARTICLES: usize = 50;
+const STEPS: u64 = 100_000;
+/// The largest relative change in energy allowed over the whole run.
+const TOLERANCE: f64 = 1e-9;
+
+#[test]
+#[ignore = "runs 100k steps, use --ignored"]
+fn test_energy_does_not_drift_over_a_long_run() {
+    let mut simulation = Simulation::with_seed(SEED);
+    simulation.set_paranoid(true);
+    simulation.spawn_random(PARTICLES);
+    let before = simulation.kinetic_energy();
+    for step in 1..=STEPS {
+        simulation.step(1.0).unwrap();
+        if step % 10_000 == 0 {
+            let drift = (simulation.kinetic_energy() - before).abs() / before;
+            assert!(
+                drift < TOLERANCE,
+                "energy drifted by {:e} after {} steps",
+                drift,
+                step
+            );
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Golden replay tests: every scenario runs a seeded simulation for a fixed number of
+//! steps and compares a hash of the final state against `golden/replay.txt`.
+//!
+//! A refactor that should not change the physics (a new broadphase, a faster solver)
+//! must leave every hash untouched. When a change is meant to alter the behavior,
+//! regenerate the golden file and commit it alongside the change:
+//!
+//! ```text
+//! UPDATE_GOLDEN=1 cargo test -p particle-core --test golden_replay
+//! ```
+use particle_core::simulation::Simulation;
+use particle_core::utils::Vec2;
+use std::fs;
+use std::path::PathBuf;
+
+struct Scenario {
+    name: &'static str,
+    seed: u64,
+    particles: usize,
+    gravity: Vec2,
+    steps: u64,
+}
+
+const SCENARIOS: &[Scenario] = &[
+    Scenario {
+        name: "sparse",
+        seed: 1,
+        particles: 20,
+        gravity: Vec2::ZERO,
+        steps: 1000,
+    },
+    Scenario {
+        name: "crowded",
+        seed: 2,
+        particles: 300,
+        gravity: Vec2::ZERO,
+        steps: 200,
+    },
+    Scenario {
+        name: "gravity",
+        seed: 3,
+        particles: 50,
+        gravity: Vec2(0.0, -0.2),
+        steps: 500,
+    },
+];
+
+fn golden_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden/replay.txt")
+}
+
+fn replay(scenario: &Scenario) -> u64 {
+    let mut simulation = Simulation::with_seed(scenario.seed);
+    simulation.settings_mut().gravity = scenario.gravity;
+    simulation.spawn_random(scenario.particles);
+    for _ in 0..scenario.steps {
+        simulation.step(1.0).unwrap();
+    }
+    simulation.state_hash()
+}
+
+fn render(hashes: &[(&str, u64)]) -> String {
+    let mut golden = String::from("# scenario hash, regenerate with UPDATE_GOLDEN=1\n");
+    for (name, hash) in hashes {
+        golden.push_str(&format!("{} {:016x}\n", name, hash));
+    }
+    golden
+}
+
+#[test]
+fn test_replays_match_golden_hashes() {
+    let hashes: Vec<(&str, u64)> = SCENARIOS
+        .iter()
+        .map(|scenario| (scenario.name, replay(scenario)))
+        .collect();
+    let actual = render(&hashes);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(golden_path(), actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(golden_path()).unwrap();
+    assert_eq!(
+        actual, expected,
+        "the simulation no longer replays the golden scenarios, \
+         rerun with UPDATE_GOLDEN=1 if the change in behavior is intended"
+    );
+}
+
+#[test]
+fn test_replay_is_deterministic() {
+    let scenario = &SCENARIOS[0];
+    assert_eq!(replay(scenario), replay(scenario));
+}
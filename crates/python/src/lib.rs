@@ -0,0 +1,210 @@
+//! Python bindings for the simulation core. Build and install them into the
+//! current virtualenv with `maturin develop -m crates/python/Cargo.toml`.
+use numpy::ndarray::Array2;
+use numpy::{IntoPyArray, PyArray1, PyArray2};
+use particle_core::particle::Particle as CoreParticle;
+use particle_core::simulation::Simulation as CoreSimulation;
+use particle_core::utils::Vec2;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+
+/// A single particle. Changing it does not affect any simulation it was read from,
+/// use `Simulation.add_particle` to put particles into a simulation. Unless one is
+/// given, a new particle has no id until it is added.
+#[pyclass(name = "Particle", from_py_object)]
+#[derive(Clone, Copy)]
+pub struct PyParticle(CoreParticle, Option<u64>);
+
+impl PyParticle {
+    fn read(particle: CoreParticle) -> Self {
+        //! A particle read from a simulation, which always has an id.
+        PyParticle(particle, Some(particle.id))
+    }
+}
+
+#[pymethods]
+impl PyParticle {
+    #[new]
+    #[pyo3(signature = (radius, x, y, vx=0.0, vy=0.0, id=None, fixed=false))]
+    fn new(radius: f64, x: f64, y: f64, vx: f64, vy: f64, id: Option<u64>, fixed: bool) -> Self {
+        let particle = CoreParticle::new(id.unwrap_or(0), radius, Vec2(x, y), Vec2(vx, vy));
+        PyParticle(if fixed { particle.pinned() } else { particle }, id)
+    }
+
+    #[getter]
+    fn id(&self) -> Option<u64> {
+        self.1
+    }
+
+    #[getter]
+    fn radius(&self) -> f64 {
+        self.0.radius
+    }
+
+    #[getter]
+    fn mass(&self) -> f64 {
+        self.0.mass
+    }
+
+    #[getter]
+    fn x(&self) -> f64 {
+        self.0.position.0
+    }
+
+    #[getter]
+    fn y(&self) -> f64 {
+        self.0.position.1
+    }
+
+    #[getter]
+    fn vx(&self) -> f64 {
+        self.0.velocity.0
+    }
+
+    #[getter]
+    fn vy(&self) -> f64 {
+        self.0.velocity.1
+    }
+
+    #[getter]
+    fn fixed(&self) -> bool {
+        self.0.fixed
+    }
+
+    fn __repr__(&self) -> String {
+        let id = self.1.map_or("None".to_string(), |id| id.to_string());
+        format!(
+            "Particle(id={}, radius={}, x={}, y={}, vx={}, vy={})",
+            id,
+            self.0.radius,
+            self.0.position.0,
+            self.0.position.1,
+            self.0.velocity.0,
+            self.0.velocity.1
+        )
+    }
+}
+
+/// A running simulation. The arrays returned by `ids`, `positions` and
+/// `velocities` all list the particles in the same order.
+#[pyclass(name = "Simulation", unsendable)]
+pub struct PySimulation(CoreSimulation);
+
+#[pymethods]
+impl PySimulation {
+    #[new]
+    #[pyo3(signature = (particle_count=0))]
+    fn new(particle_count: usize) -> Self {
+        PySimulation(CoreSimulation::with_random_particles(particle_count))
+    }
+
+    fn add_particle(&mut self, particle: PyParticle) -> u64 {
+        //! Put a copy of `particle` into the simulation and return its id, a new
+        //! one unless the particle was given an id.
+        let mut added = particle.0;
+        added.id = particle.1.unwrap_or_else(|| self.0.allocate_id());
+        self.0.spawn(added);
+        added.id
+    }
+
+    #[pyo3(signature = (dt=1.0, steps=1))]
+    fn step(&mut self, dt: f64, steps: usize) -> PyResult<()> {
+        //! Advance the simulation `steps` times by `dt`.
+        for _ in 0..steps {
+            self.0
+                .step(dt)
+                .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.particle_count()
+    }
+
+    fn particles(&self) -> Vec<PyParticle> {
+        collect_particles(&self.0)
+            .into_iter()
+            .map(PyParticle::read)
+            .collect()
+    }
+
+    fn ids<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<u64>> {
+        let ids: Vec<u64> = collect_particles(&self.0).iter().map(|p| p.id).collect();
+        ids.into_pyarray(py)
+    }
+
+    fn positions<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f64>> {
+        //! An `(n, 2)` array of particle positions.
+        columns(&self.0, |p| [p.position.0, p.position.1]).into_pyarray(py)
+    }
+
+    fn velocities<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f64>> {
+        //! An `(n, 2)` array of particle velocities.
+        columns(&self.0, |p| [p.velocity.0, p.velocity.1]).into_pyarray(py)
+    }
+}
+
+fn collect_particles(simulation: &CoreSimulation) -> Vec<CoreParticle> {
+    simulation
+        .world()
+        .query::<&CoreParticle>()
+        .iter()
+        .map(|(_, particle)| *particle)
+        .collect()
+}
+
+fn columns(simulation: &CoreSimulation, f: impl Fn(&CoreParticle) -> [f64; 2]) -> Array2<f64> {
+    //! One row of two values per particle.
+    let particles = collect_particles(simulation);
+    let values: Vec<f64> = particles.iter().flat_map(f).collect();
+    Array2::from_shape_vec((particles.len(), 2), values)
+        .expect("two values were collected for every particle")
+}
+
+#[pymodule]
+#[pyo3(name = "particle_simulator")]
+fn particle_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyParticle>()?;
+    m.add_class::<PySimulation>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns_match_particle_order() {
+        let mut simulation = CoreSimulation::new();
+        simulation.spawn(CoreParticle::new(1, 1.0, Vec2(10.0, 20.0), Vec2(1.0, 2.0)));
+        simulation.spawn(CoreParticle::new(2, 1.0, Vec2(30.0, 40.0), Vec2(3.0, 4.0)));
+
+        let positions = columns(&simulation, |p| [p.position.0, p.position.1]);
+        let ids: Vec<u64> = collect_particles(&simulation)
+            .iter()
+            .map(|p| p.id)
+            .collect();
+        assert_eq!(positions.shape(), &[2, 2]);
+        for (row, id) in ids.iter().enumerate() {
+            let expected = if *id == 1 { [10.0, 20.0] } else { [30.0, 40.0] };
+            assert_eq!(positions[[row, 0]], expected[0]);
+            assert_eq!(positions[[row, 1]], expected[1]);
+        }
+    }
+
+    #[test]
+    fn test_added_particles_get_ids_of_their_own() {
+        let mut simulation = PySimulation::new(3);
+        let particle = PyParticle::new(1.0, 100.0, 100.0, 0.0, 0.0, None, false);
+        assert_eq!(particle.id(), None);
+        let first = simulation.add_particle(particle);
+        let second = simulation.add_particle(particle);
+        assert_ne!(first, second);
+
+        let mut ids: Vec<u64> = simulation.particles().iter().map(|p| p.0.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 5);
+    }
+}
@@ -0,0 +1,29 @@
+"""Run with `maturin develop -m crates/python/Cargo.toml && python -m pytest crates/python/tests`."""
+import numpy as np
+import particle_simulator as ps
+
+
+def test_step_moves_particle():
+    sim = ps.Simulation()
+    sim.add_particle(ps.Particle(radius=1.0, x=1.0, y=1.0, vx=2.5, vy=3.5))
+    sim.step(dt=1.0)
+    np.testing.assert_allclose(sim.positions(), [[3.5, 4.5]])
+    np.testing.assert_allclose(sim.velocities(), [[2.5, 3.5]])
+
+
+def test_random_population_shapes():
+    sim = ps.Simulation(25)
+    sim.step(steps=10)
+    assert len(sim) == 25
+    assert sim.positions().shape == (25, 2)
+    assert sim.ids().shape == (25,)
+
+
+def test_added_particles_get_ids_of_their_own():
+    sim = ps.Simulation(3)
+    particle = ps.Particle(radius=1.0, x=100.0, y=100.0)
+    assert particle.id is None
+    first = sim.add_particle(particle)
+    second = sim.add_particle(particle)
+    assert first != second
+    assert len(set(sim.ids())) == 5
@@ -0,0 +1,96 @@
+//! A browser front-end for the simulator. Build it with
+//! `wasm-pack build crates/web --target web` and serve `crates/web/www`.
+use particle_core::components::Appearance;
+use particle_core::particle::Particle;
+use particle_core::simulation::Simulation;
+use std::f64::consts::PI;
+use wasm_bindgen::prelude::*;
+use web_sys::CanvasRenderingContext2d;
+
+/// The simulation works in a 1000 by 1000 world.
+const WORLD_SIZE: f64 = 1000.0;
+
+/// A simulation that can be stepped and drawn from JavaScript.
+#[wasm_bindgen]
+pub struct WebSimulation {
+    simulation: Simulation,
+}
+
+#[wasm_bindgen]
+impl WebSimulation {
+    #[wasm_bindgen(constructor)]
+    pub fn new(particle_count: usize) -> WebSimulation {
+        WebSimulation {
+            simulation: Simulation::with_random_particles(particle_count),
+        }
+    }
+
+    pub fn step(&mut self, dt: f64) -> Result<(), JsValue> {
+        self.simulation
+            .step(dt)
+            .map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.simulation.particle_count()
+    }
+
+    /// Draw every particle onto a canvas of `width` by `height` pixels.
+    pub fn render(&self, context: &CanvasRenderingContext2d, width: f64, height: f64) {
+        context.set_fill_style_str("black");
+        context.fill_rect(0.0, 0.0, width, height);
+
+        for (_, (particle, appearance)) in self
+            .simulation
+            .world()
+            .query::<(&Particle, &Appearance)>()
+            .iter()
+        {
+            let (x, y, radius) = world_to_canvas(particle, width, height);
+            context.set_fill_style_str(&css_color(appearance.color));
+            context.begin_path();
+            context.arc(x, y, radius, 0.0, 2.0 * PI).ok();
+            context.fill();
+        }
+    }
+}
+
+fn world_to_canvas(particle: &Particle, width: f64, height: f64) -> (f64, f64, f64) {
+    //! The simulation has y pointing up, while the canvas has y pointing down.
+    let scale = (width / WORLD_SIZE).min(height / WORLD_SIZE);
+    (
+        particle.position.0 * scale,
+        height - particle.position.1 * scale,
+        particle.radius * scale,
+    )
+}
+
+fn css_color([r, g, b, a]: [f32; 4]) -> String {
+    format!(
+        "rgba({}, {}, {}, {})",
+        (r * 255.0).round(),
+        (g * 255.0).round(),
+        (b * 255.0).round(),
+        a
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use particle_core::utils::Vec2;
+
+    #[test]
+    fn test_world_to_canvas_flips_y() {
+        let particle = Particle::new(1, 10.0, Vec2(250.0, 750.0), Vec2(0.0, 0.0));
+        assert_eq!(
+            world_to_canvas(&particle, 500.0, 500.0),
+            (125.0, 125.0, 5.0)
+        );
+    }
+
+    #[test]
+    fn test_css_color() {
+        assert_eq!(css_color([1.0, 0.0, 0.5, 1.0]), "rgba(255, 0, 128, 1)");
+    }
+}
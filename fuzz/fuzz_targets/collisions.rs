@@ -0,0 +1,121 @@
+//! Feeds arbitrary sets of particles, with or without shapes, through the whole
+//! collision pipeline. Detection and response must never panic or fail, and
+//! finite particles must come out with finite velocities, however degenerate
+//! they are: coincident centers, zero radii and huge velocities included.
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use particle_core::particle::Particle;
+use particle_core::profiler::Profiler;
+use particle_core::shapes::{Body, Shape};
+use particle_core::sweep_prune::{detect_and_apply_collisions, Collider, SweepAxis};
+use particle_core::utils::Vec2;
+
+#[derive(Debug, Arbitrary)]
+enum FuzzShape {
+    Circle,
+    Box { half_width: f64, half_height: f64 },
+    Polygon { sides: u8 },
+    Capsule { half_length: f64 },
+}
+
+#[derive(Debug, Arbitrary)]
+struct FuzzParticle {
+    radius: f64,
+    position: (f64, f64),
+    velocity: (f64, f64),
+    fixed: bool,
+    massive: bool,
+    shape: Option<(FuzzShape, f64, f64)>,
+}
+
+impl FuzzParticle {
+    fn build(&self, id: u64) -> (Particle, Option<Body>) {
+        let radius = self.radius.abs();
+        let body = self.shape.as_ref().map(|(shape, angle, angular_velocity)| {
+            let shape = match shape {
+                FuzzShape::Circle => Shape::Circle { radius },
+                FuzzShape::Box {
+                    half_width,
+                    half_height,
+                } => Shape::Box {
+                    half_width: half_width.abs(),
+                    half_height: half_height.abs(),
+                },
+                FuzzShape::Polygon { sides } => {
+                    Shape::regular_polygon(3 + usize::from(*sides % 8), radius)
+                }
+                FuzzShape::Capsule { half_length } => Shape::Capsule {
+                    half_length: half_length.abs(),
+                    radius,
+                },
+            };
+            Body {
+                angle: *angle,
+                angular_velocity: *angular_velocity,
+                ..Body::new(shape)
+            }
+        });
+        let position = Vec2(self.position.0, self.position.1);
+        let velocity = Vec2(self.velocity.0, self.velocity.1);
+        let mut particle = match &body {
+            Some(body) => body.particle(id, position, velocity),
+            None => Particle::new(id, radius, position, velocity),
+        };
+        if self.fixed {
+            particle = particle.pinned();
+        }
+        if self.massive {
+            particle = particle.massive();
+        }
+        (particle, body)
+    }
+}
+
+fuzz_target!(|input: Vec<FuzzParticle>| {
+    let (mut particles, mut bodies): (Vec<Particle>, Vec<Option<Body>>) = input
+        .iter()
+        .zip(0..)
+        .map(|(particle, id)| particle.build(id))
+        .unzip();
+    // Only particles that start out finite have to stay finite
+    let finite: Vec<bool> = particles
+        .iter()
+        .zip(&bodies)
+        .map(|(particle, body)| {
+            particle.is_finite()
+                && body
+                    .as_ref()
+                    .is_none_or(|body| body.angle.is_finite() && body.angular_velocity.is_finite())
+        })
+        .collect();
+
+    let mut colliders: Vec<Collider> = particles
+        .iter_mut()
+        .zip(bodies.iter_mut())
+        .map(|(particle, body)| Collider {
+            particle,
+            body: body.as_mut(),
+        })
+        .collect();
+    let mut events = Vec::new();
+    // Picking the axis looks at every position, infinite and NaN ones included
+    detect_and_apply_collisions(
+        &mut colliders,
+        SweepAxis::Auto,
+        &mut Profiler::new(),
+        &mut events,
+    )
+        .expect("every collision pair refers to a particle that exists");
+    drop(colliders);
+
+    for ((particle, body), finite) in particles.iter().zip(&bodies).zip(finite) {
+        if finite {
+            assert!(particle.velocity.is_finite(), "{:?}", particle);
+            if let Some(body) = body {
+                assert!(body.angular_velocity.is_finite(), "{:?}", body);
+            }
+        }
+    }
+});
@@ -0,0 +1,125 @@
+//! `--autotune`: time this machine before the window opens and pick the settings
+//! that keep up with the requested frame rate.
+//!
+//! Every broadphase is timed on the requested particles, and the fastest is kept.
+//! With it, the particle count is ramped up like in `--stress` until the steps of
+//! a frame no longer fit in the half of it that the physics may use, which becomes
+//! the particle budget. The steps that fit in a frame at the resulting count are
+//! how many the clock may take to catch up after a slow frame.
+use crate::clock::{MAX_STEPS_PER_FRAME, STEPS_PER_SECOND};
+use crate::stress::{self, StressOptions};
+use particle_core::error::SimResult;
+use particle_core::sweep_prune::SweepAxis;
+use std::time::Duration;
+
+/// What the calibration picked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuning {
+    pub sweep_axis: SweepAxis,
+    /// The most particles whose steps fit in a frame, or `None` if not even one
+    /// particle does.
+    pub particle_budget: Option<usize>,
+    /// The most steps taken in one frame to catch up.
+    pub catch_up_steps: u32,
+    /// The average step time of the chosen broadphase at the requested count.
+    pub step_time: Duration,
+}
+
+impl Tuning {
+    pub fn lines(&self, requested: usize) -> Vec<String> {
+        //! What was chosen, for printing before the simulation starts.
+        let budget = match self.particle_budget {
+            Some(budget) if budget < requested => {
+                format!(
+                    "{} particles (fewer than the {} requested)",
+                    budget, requested
+                )
+            }
+            Some(budget) => format!("{} particles", budget),
+            None => "none, even 1 particle is too slow".to_string(),
+        };
+        vec![
+            format!(
+                "broadphase: {} ({:.3} ms per step)",
+                self.sweep_axis.name(),
+                self.step_time.as_secs_f64() * 1000.0
+            ),
+            format!("particle budget: {}", budget),
+            format!("catch-up steps per frame: {}", self.catch_up_steps),
+        ]
+    }
+}
+
+pub fn steps_per_frame(time_scale: f64, frame_interval: Duration) -> f64 {
+    //! How many steps the clock takes in an average frame, at least 1.
+    (STEPS_PER_SECOND * time_scale * frame_interval.as_secs_f64()).max(1.0)
+}
+
+pub fn catch_up_steps(physics_budget: Duration, step_time: Duration) -> u32 {
+    //! How many steps of `step_time` fit in the physics budget of a frame,
+    //! between 1 and the clock's own limit.
+    let fit = physics_budget.as_secs_f64() / step_time.as_secs_f64().max(f64::MIN_POSITIVE);
+    (fit.floor() as u32).clamp(1, MAX_STEPS_PER_FRAME)
+}
+
+pub fn run(options: &StressOptions, steps_per_frame: f64) -> SimResult<Tuning> {
+    //! Calibrate with the requested particles, `options.start`, whose physics may
+    //! take `options.budget` per frame at `steps_per_frame` steps a frame.
+    let requested = options.start.max(1);
+    let mut fastest: Option<(SweepAxis, Duration)> = None;
+    for sweep_axis in [SweepAxis::X, SweepAxis::Y, SweepAxis::Auto] {
+        let options = StressOptions {
+            sweep_axis,
+            ..options.clone()
+        };
+        let step_time = stress::time_steps(requested, &options)?;
+        tracing::debug!(broadphase = sweep_axis.name(), ?step_time, "autotune");
+        if fastest.is_none_or(|(_, fastest)| step_time < fastest) {
+            fastest = Some((sweep_axis, step_time));
+        }
+    }
+    let (sweep_axis, step_time) = fastest.unwrap_or_default();
+
+    let options = StressOptions {
+        sweep_axis,
+        ..options.clone()
+    };
+    let step_budget = options.budget.div_f64(steps_per_frame);
+    let fits = |count: usize| -> SimResult<bool> {
+        Ok(stress::time_steps(count, &options)? <= step_budget)
+    };
+    // Ramping up from the requested count never finds a smaller budget
+    let particle_budget = match stress::max_stable(requested, options.limit, fits)? {
+        Some(budget) => Some(budget),
+        None => stress::max_stable(1, requested, fits)?,
+    };
+    // Catching up is timed at the count that will actually run
+    let running = particle_budget.map_or(1, |budget| budget.min(requested));
+    let running_step_time = if running == requested {
+        step_time
+    } else {
+        stress::time_steps(running, &options)?
+    };
+    Ok(Tuning {
+        sweep_axis,
+        particle_budget,
+        catch_up_steps: catch_up_steps(options.budget, running_step_time),
+        step_time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catch_up_steps_fit_the_budget() {
+        let ms = Duration::from_millis;
+        assert_eq!(catch_up_steps(ms(10), ms(3)), 3);
+        assert_eq!(catch_up_steps(ms(10), ms(30)), 1);
+        assert_eq!(catch_up_steps(ms(10), Duration::ZERO), MAX_STEPS_PER_FRAME);
+        let frame = Duration::from_secs_f64(1.0 / STEPS_PER_SECOND);
+        assert_eq!(steps_per_frame(0.5, frame), 1.0);
+        assert!((steps_per_frame(4.0, frame) - 4.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,130 @@
+//! `--motion-blur ALPHA`: leave fading copies of the last few frames behind the
+//! particles, as if each frame were painted over the one before with a background
+//! only `ALPHA` opaque. flo_draw redraws its layers from their drawing commands
+//! rather than keeping their pixels, so the fading frames are drawn again every
+//! frame, each `1 - ALPHA` as opaque as the one after it, until they are too
+//! faint to see.
+use crate::coloring::Coloring;
+use flo_canvas::*;
+use hecs::World;
+use particle_core::components::{Appearance, Charge, Species};
+use particle_core::particle::Particle;
+use particle_core::utils::Vec2;
+use std::collections::{BTreeMap, VecDeque};
+
+/// A frame this faint, as a share of its original opacity, is no longer drawn.
+const FAINTEST: f64 = 1.0 / 32.0;
+/// The most frames kept, however little each one fades.
+const MAX_FRAMES: usize = 30;
+/// Each color channel is rounded to one of this many levels, so that the
+/// circles of a frame can be filled in a few paths.
+const COLOR_LEVELS: f32 = 8.0;
+
+/// The particles of the last few frames, the latest first.
+#[derive(Debug, Clone)]
+pub struct MotionBlur {
+    fade: f64,
+    length: usize,
+    frames: VecDeque<Vec<(Vec2, f64, [f32; 4])>>,
+}
+
+impl MotionBlur {
+    pub fn new(fade: f64) -> Self {
+        //! Fade each frame by `fade` of its opacity, from 0 to 1, every frame.
+        let length = (1..=MAX_FRAMES)
+            .take_while(|age| (1.0 - fade).powi(*age as i32) >= FAINTEST)
+            .count();
+        MotionBlur {
+            fade,
+            length: length.max(1),
+            frames: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, world: &World, coloring: &Coloring) {
+        //! Keep the frame that has just been drawn, to fade from the next one on.
+        let frame = world
+            .query::<(&Particle, &Appearance, Option<&Charge>, Option<&Species>)>()
+            .iter()
+            .map(|(_, (particle, appearance, charge, species))| {
+                let color = coloring.color(particle, appearance, charge, species);
+                (particle.position, particle.radius, color)
+            })
+            .collect();
+        self.frames.push_front(frame);
+        self.frames.truncate(self.length);
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    pub fn draw(&self, gc: &mut impl GraphicsContext) {
+        //! Draw the kept frames, the oldest and faintest first.
+        for (age, frame) in self.frames.iter().enumerate().rev() {
+            let opacity = (1.0 - self.fade).powi(age as i32 + 1) as f32;
+            let mut paths: BTreeMap<[u8; 4], Vec<(Vec2, f64)>> = BTreeMap::new();
+            for (position, radius, color) in frame {
+                let mut color = *color;
+                color[3] *= opacity;
+                let level = color
+                    .map(|channel| (channel.clamp(0.0, 1.0) * (COLOR_LEVELS - 1.0)).round() as u8);
+                paths.entry(level).or_default().push((*position, *radius));
+            }
+            for (level, circles) in paths {
+                if level[3] == 0 {
+                    continue;
+                }
+                gc.new_path();
+                for (position, radius) in circles {
+                    gc.circle(position.0 as f32, position.1 as f32, radius as f32);
+                }
+                let [r, g, b, a] = level.map(|level| level as f32 / (COLOR_LEVELS - 1.0));
+                gc.fill_color(Color::Rgba(r, g, b, a));
+                gc.fill();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coloring::{ColorBy, Palette};
+
+    #[test]
+    fn test_frames_fade_until_they_are_too_faint() {
+        let mut blur = MotionBlur::new(0.5);
+        // Half as opaque each frame, and gone after five
+        assert_eq!(blur.length, 5);
+
+        let mut world = World::new();
+        let appearance = Appearance {
+            color: [1.0, 1.0, 1.0, 1.0],
+        };
+        world.spawn((
+            Particle::new(0, 5.0, Vec2(100.0, 100.0), Vec2::ZERO),
+            appearance,
+        ));
+        let coloring = Coloring::new(ColorBy::Random, Palette::Viridis);
+        for _ in 0..10 {
+            blur.record(&world, &coloring);
+        }
+        assert_eq!(blur.frames.len(), 5);
+
+        let mut drawing: Vec<Draw> = vec![];
+        blur.draw(&mut drawing);
+        let opacities: Vec<f32> = drawing
+            .iter()
+            .filter_map(|draw| match draw {
+                Draw::FillColor(color) => Some(color.to_rgba_components().3),
+                _ => None,
+            })
+            .collect();
+        // Rounded to one of eight levels, which leaves out the faintest
+        assert_eq!(opacities, [1.0 / 7.0, 2.0 / 7.0, 4.0 / 7.0]);
+
+        blur.clear();
+        assert!(blur.frames.is_empty());
+    }
+}
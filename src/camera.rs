@@ -0,0 +1,183 @@
+use flo_canvas::Transform2D;
+use particle_core::particle::{HEIGHT, WIDTH};
+use particle_core::utils::Vec2;
+use serde::Deserialize;
+
+/// The closest and farthest the camera zooms, as multiples of the whole box
+/// filling the window.
+const MIN_ZOOM: f64 = 0.5;
+const MAX_ZOOM: f64 = 8.0;
+
+/// Which part of the box the window shows: the point of the box in the middle of
+/// the window, and how far the box is magnified around it. The window is always
+/// 1000 by 1000 canvas units, the same as the box at a zoom of 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub center: Vec2,
+    pub zoom: f64,
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        //! The whole box, as the window shows it without a camera.
+        Camera {
+            center: Vec2(WIDTH / 2.0, HEIGHT / 2.0),
+            zoom: 1.0,
+        }
+    }
+
+    pub fn pan(&mut self, offset: Vec2) {
+        //! Move the view by `offset` in window units, so that a pan covers the same
+        //! distance on screen at any zoom. The center never leaves the box.
+        self.look_at(self.center + offset.scale(1.0 / self.zoom));
+    }
+
+    pub fn look_at(&mut self, point: Vec2) {
+        //! Center the view on `point`, kept inside the box.
+        self.center = Vec2(point.0.clamp(0.0, WIDTH), point.1.clamp(0.0, HEIGHT));
+    }
+
+    pub fn zoom_by(&mut self, factor: f64) {
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    pub fn transform(&self) -> Transform2D {
+        //! From positions in the box to canvas units in the window.
+        let zoom = self.zoom as f32;
+        Transform2D::translate(WIDTH as f32 / 2.0, HEIGHT as f32 / 2.0)
+            * Transform2D::scale(zoom, zoom)
+            * Transform2D::translate(-self.center.0 as f32, -self.center.1 as f32)
+    }
+
+    pub fn to_world(&self, point: Vec2) -> Vec2 {
+        //! The position in the box under a point of the window, such as the pointer.
+        (point - Vec2(WIDTH / 2.0, HEIGHT / 2.0)).scale(1.0 / self.zoom) + self.center
+    }
+
+    pub fn to_window(&self, point: Vec2) -> Vec2 {
+        //! Where a position in the box shows in the window, the other way from `to_world`.
+        (point - self.center).scale(self.zoom) + Vec2(WIDTH / 2.0, HEIGHT / 2.0)
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera::new()
+    }
+}
+
+fn whole_box() -> f64 {
+    1.0
+}
+
+/// Where a scripted camera is at a moment of simulated time, given in the
+/// `camera` list of a `--config` file, such as `{"at": 5, "x": 300, "y": 700, "zoom": 3}`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keyframe {
+    /// Seconds of simulated time from the start.
+    pub at: f64,
+    pub x: f64,
+    pub y: f64,
+    #[serde(default = "whole_box")]
+    pub zoom: f64,
+}
+
+/// A camera that moves through keyframes on its own, for recording videos. It
+/// eases in and out of every keyframe, and zooms by the same factor every moment
+/// of the way between two of them, which looks even where a steady change of
+/// the zoom itself would seem to speed up as it closes in.
+#[derive(Debug, Clone, Default)]
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+    finished: bool,
+}
+
+impl CameraPath {
+    pub fn new(keyframes: &[Keyframe]) -> Self {
+        let mut keyframes = keyframes.to_vec();
+        keyframes.sort_by(|a, b| a.at.total_cmp(&b.at));
+        CameraPath {
+            finished: keyframes.is_empty(),
+            keyframes,
+        }
+    }
+
+    pub fn camera_at(&self, seconds: f64) -> Option<Camera> {
+        //! The camera at `seconds`, held at the first keyframe before it and the
+        //! last one after it.
+        let next = self.keyframes.iter().position(|key| key.at > seconds);
+        let (from, to, share) = match next {
+            None => (self.keyframes.last()?, self.keyframes.last()?, 0.0),
+            Some(0) => (&self.keyframes[0], &self.keyframes[0], 0.0),
+            Some(next) => {
+                let (from, to) = (&self.keyframes[next - 1], &self.keyframes[next]);
+                let t = (seconds - from.at) / (to.at - from.at);
+                (from, to, t * t * (3.0 - 2.0 * t))
+            }
+        };
+        let center = Vec2(from.x, from.y) + (Vec2(to.x, to.y) - Vec2(from.x, from.y)).scale(share);
+        let zoom = from.zoom * (to.zoom / from.zoom).powf(share);
+        Some(Camera {
+            center: Vec2(center.0.clamp(0.0, WIDTH), center.1.clamp(0.0, HEIGHT)),
+            zoom: zoom.clamp(MIN_ZOOM, MAX_ZOOM),
+        })
+    }
+
+    pub fn update(&mut self, seconds: f64) -> Option<Camera> {
+        //! The camera at `seconds` while the path lasts. Once it has reached the
+        //! last keyframe, it lets go, and the camera is free to move by hand again.
+        if self.finished {
+            return None;
+        }
+        self.finished = self.keyframes.last().is_some_and(|key| seconds >= key.at);
+        self.camera_at(seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pointer_lands_where_the_box_is_drawn() {
+        let mut camera = Camera::new();
+        assert_eq!(camera.to_world(Vec2(100.0, 900.0)), Vec2(100.0, 900.0));
+        camera.zoom_by(2.0);
+        camera.pan(Vec2(200.0, 0.0));
+        assert_eq!(camera.center, Vec2(600.0, 500.0));
+        let world = camera.to_world(Vec2(700.0, 500.0));
+        assert_eq!(world, Vec2(700.0, 500.0));
+        let (x, y) = camera
+            .transform()
+            .transform_point(world.0 as f32, world.1 as f32);
+        assert_eq!((x, y), (700.0, 500.0));
+        assert_eq!(camera.to_window(world), Vec2(700.0, 500.0));
+
+        camera.zoom_by(100.0);
+        assert_eq!(camera.zoom, MAX_ZOOM);
+        camera.pan(Vec2(-1e6, 0.0));
+        assert_eq!(camera.center.0, 0.0);
+    }
+
+    #[test]
+    fn test_camera_paths_ease_between_keyframes() {
+        let keyframes: Vec<Keyframe> = serde_json::from_str(
+            r#"[{"at": 4, "x": 800, "y": 500, "zoom": 4}, {"at": 2, "x": 200, "y": 500}]"#,
+        )
+        .unwrap();
+        let mut path = CameraPath::new(&keyframes);
+        assert_eq!(path.update(0.0).unwrap().center, Vec2(200.0, 500.0));
+        // Halfway in time is halfway across, and zoomed in halfway to four times
+        let halfway = path.update(3.0).unwrap();
+        assert_eq!(halfway.center, Vec2(500.0, 500.0));
+        assert!((halfway.zoom - 2.0).abs() < 1e-12);
+        // Slow to leave a keyframe
+        let leaving = path.camera_at(2.2).unwrap();
+        assert!(leaving.center.0 - 200.0 < 0.1 * 600.0);
+
+        assert_eq!(path.update(5.0).unwrap().zoom, 4.0);
+        assert_eq!(path.update(6.0), None);
+        assert_eq!(CameraPath::new(&[]).update(0.0), None);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::hud::FONT;
+use flo_canvas::*;
+use std::collections::VecDeque;
+
+/// How many frames of history each chart shows, about ten seconds of the window.
+const HISTORY: usize = 450;
+const MARGIN: f32 = 40.0;
+/// The charts window uses the same 1000 by 1000 canvas as the simulation.
+const SIZE: f32 = 1000.0;
+
+/// A rolling time series, dropping the oldest value once it is full.
+#[derive(Debug, Clone)]
+pub struct Series {
+    label: &'static str,
+    values: VecDeque<f64>,
+}
+
+impl Series {
+    pub fn new(label: &'static str) -> Self {
+        Series {
+            label,
+            values: VecDeque::with_capacity(HISTORY),
+        }
+    }
+
+    pub fn push(&mut self, value: f64) {
+        if self.values.len() == HISTORY {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    pub fn latest(&self) -> Option<f64> {
+        self.values.back().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    fn range(&self) -> (f64, f64) {
+        //! The smallest and largest finite values, widened if they are equal
+        //! so that a flat series is drawn across the middle of its chart.
+        let (min, max) = self
+            .values
+            .iter()
+            .filter(|value| value.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+                (min.min(*value), max.max(*value))
+            });
+        if min > max {
+            (0.0, 1.0)
+        } else if min == max {
+            (min - 0.5, max + 0.5)
+        } else {
+            (min, max)
+        }
+    }
+
+    fn points(&self, left: f32, bottom: f32, width: f32, height: f32) -> Vec<(f32, f32)> {
+        //! The series scaled into the given rectangle, the oldest value on the left
+        //! edge and the newest wherever the history has filled up to.
+        let (min, max) = self.range();
+        let step = width / (HISTORY - 1) as f32;
+        self.values
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| value.is_finite())
+            .map(|(index, value)| {
+                let y = ((value - min) / (max - min)) as f32;
+                (left + index as f32 * step, bottom + y * height)
+            })
+            .collect()
+    }
+}
+
+/// Line charts of the total kinetic energy, the temperature and the collision rate,
+/// stacked on top of each other in a window of their own.
+pub struct Charts {
+    series: [Series; 3],
+}
+
+impl Charts {
+    pub fn new() -> Self {
+        Charts {
+            series: [
+                Series::new("Kinetic energy"),
+                Series::new("Temperature"),
+                Series::new("Collisions per second"),
+            ],
+        }
+    }
+
+    pub fn record(&mut self, energy: f64, temperature: f64, collisions_per_second: f64) {
+        for (series, value) in
+            self.series
+                .iter_mut()
+                .zip([energy, temperature, collisions_per_second])
+        {
+            series.push(value);
+        }
+    }
+
+    pub fn draw(&self, gc: &mut impl GraphicsContext) {
+        //! Redraw every chart. The font must already have been loaded
+        //! into the canvas with `Hud::load_font`.
+        gc.layer(LayerId(0));
+        gc.clear_layer();
+        gc.canvas_height(SIZE);
+        gc.center_region(0.0, 0.0, SIZE, SIZE);
+        self.draw_between(gc, 0.0, SIZE);
+    }
+
+    pub fn draw_between(&self, gc: &mut impl GraphicsContext, bottom: f32, top: f32) {
+        //! Draw the charts across the full width of the canvas, stacked between
+        //! the heights `bottom` and `top`, on whatever layer is current.
+        let panel_height = (top - bottom) / self.series.len() as f32;
+        let width = SIZE - 2.0 * MARGIN;
+        let height = panel_height - 2.0 * MARGIN;
+        // The first series goes at the top
+        let base = bottom;
+        for (row, series) in self.series.iter().rev().enumerate() {
+            let bottom = base + row as f32 * panel_height + MARGIN;
+
+            gc.new_path();
+            gc.rect(MARGIN, bottom, MARGIN + width, bottom + height);
+            gc.line_width(1.0);
+            gc.stroke_color(Color::Rgba(0.4, 0.4, 0.4, 1.0));
+            gc.stroke();
+
+            if let Some((first, rest)) = series.points(MARGIN, bottom, width, height).split_first()
+            {
+                gc.new_path();
+                gc.move_to(first.0, first.1);
+                for point in rest {
+                    gc.line_to(point.0, point.1);
+                }
+                gc.line_width(2.0);
+                gc.stroke_color(Color::Rgba(0.3, 0.8, 1.0, 1.0));
+                gc.stroke();
+            }
+
+            let (min, max) = series.range();
+            let label = match series.latest() {
+                Some(latest) => format!("{}: {:.1}", series.label, latest),
+                None => series.label.to_string(),
+            };
+            gc.fill_color(Color::Rgba(0.9, 0.9, 0.9, 0.9));
+            gc.draw_text(FONT, label, MARGIN, bottom + height + 8.0);
+            gc.draw_text(
+                FONT,
+                format!("{:.1} to {:.1}", min, max),
+                SIZE - MARGIN - 150.0,
+                bottom + height + 8.0,
+            );
+        }
+    }
+}
+
+impl Default for Charts {
+    fn default() -> Self {
+        Charts::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_series_keeps_a_rolling_window() {
+        let mut series = Series::new("test");
+        for value in 0..HISTORY + 10 {
+            series.push(value as f64);
+        }
+        assert_eq!(series.len(), HISTORY);
+        assert_eq!(series.values.front(), Some(&10.0));
+        assert_eq!(series.latest(), Some((HISTORY + 9) as f64));
+    }
+
+    #[test]
+    fn test_points_fill_the_chart() {
+        let mut series = Series::new("test");
+        for value in [2.0, f64::NAN, 4.0, 3.0] {
+            series.push(value);
+        }
+        let points = series.points(0.0, 100.0, (HISTORY - 1) as f32, 50.0);
+        // The non-finite value is skipped, and the others span the full height
+        assert_eq!(points, vec![(0.0, 100.0), (2.0, 150.0), (3.0, 125.0)]);
+
+        let mut flat = Series::new("flat");
+        flat.push(7.0);
+        assert_eq!(flat.points(0.0, 0.0, 10.0, 10.0), vec![(0.0, 5.0)]);
+    }
+}
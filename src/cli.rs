@@ -0,0 +1,1016 @@
+use crate::camera::Keyframe;
+use crate::clock::FrameRate;
+use crate::coloring::{ColorBy, Palette};
+use crate::compare::Override;
+use crate::lockstep::Peer;
+use crate::midi::NoteRange;
+use crate::renderer::Backend;
+use crate::server::StreamFormat;
+use crate::sweep::{Metric, ParamRange};
+use crate::theme::Theme;
+use crate::timeline::TimedEvent;
+use crate::utils::{
+    parse_densities, parse_gravity, parse_max_speed, parse_medium, parse_midi_channel,
+    parse_motion_blur, parse_mutual_gravity, parse_osc_target, parse_particle_count,
+    parse_rdf_range, parse_species_gravity, parse_step_length, parse_thermal_wall,
+    parse_time_scale,
+};
+use clap::{Parser, Subcommand};
+use particle_core::patterns::SpawnPattern;
+use particle_core::portals::Portal;
+use particle_core::settings::{Medium, OutOfBoundsPolicy, Side};
+use particle_core::shapes::SpawnShape;
+use particle_core::spawn::{RadiusDistribution, SpeedDistribution};
+use particle_core::sweep_prune::SweepAxis;
+use particle_core::time_regions::TimeRegion;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+/// Command line options for the simulator.
+#[derive(Parser, Debug, Clone)]
+#[command(about = "A 2D simulator of colliding particles")]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Number of particles in the simulation
+    #[arg(default_value = "20", value_parser = parse_particle_count)]
+    pub particles: usize,
+
+    /// Optional safety limit, the particle count is capped at this value
+    #[arg(long, value_parser = parse_particle_count)]
+    pub max_particles: Option<usize>,
+
+    /// Where the particles start: `random`, `grid`, `ring`, `disk`,
+    /// `two-clusters[:<speed>]` for two groups on a collision course, or
+    /// `cradle[:<speed>]` for a Newton's cradle, or `orbits` for planets around a
+    /// heavy star, which needs --mutual-gravity
+    #[arg(long, value_name = "PATTERN", default_value = "random")]
+    pub pattern: SpawnPattern,
+
+    /// Smallest radius of a random particle
+    #[arg(long, value_name = "RADIUS", default_value_t = 5.0)]
+    pub min_radius: f64,
+
+    /// Largest radius of a random particle (at most 50)
+    #[arg(long, value_name = "RADIUS", default_value_t = 50.0)]
+    pub max_radius: f64,
+
+    /// How random radii are distributed: `uniform`, `normal:<mean>,<std_dev>`,
+    /// `log-normal:<mu>,<sigma>` or `power-law:<exponent>`
+    #[arg(long, value_name = "DISTRIBUTION", default_value = "uniform")]
+    pub radius_distribution: RadiusDistribution,
+
+    /// How random speeds are distributed, in a random direction: `uniform` (0 to 25),
+    /// `uniform:<min>,<max>` or `thermal:<temperature>` for a Maxwell-Boltzmann gas
+    #[arg(long, value_name = "DISTRIBUTION", default_value = "uniform")]
+    pub speed_distribution: SpeedDistribution,
+
+    /// Shape of the random particles: `circle`, `box`, `capsule`, or `polygon:<sides>`
+    /// for a regular polygon. Shapes other than circles spin when hit off center
+    #[arg(long, value_name = "SHAPE", default_value = "circle")]
+    pub shape: SpawnShape,
+
+    /// Minimum level of log messages to print (error, warn, info, debug, trace),
+    /// or any `tracing` filter directive such as `particle_core=debug`
+    #[arg(long, default_value = "warn")]
+    pub log_level: String,
+
+    /// Print log messages as JSON lines instead of human readable text
+    #[arg(long)]
+    pub log_json: bool,
+
+    /// A JSON file of settings that take the place of the flags of the same name,
+    /// such as `{"gravity": 0.1, "palette": "plasma"}`. The window applies the
+    /// physics, time scale and colors again whenever the file is saved, and the
+    /// rest when R reloads the scenario
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Run the simulation without opening a window
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Open a second window with live charts of the total kinetic energy,
+    /// the temperature and the number of collisions per second
+    #[arg(long, conflicts_with_all = ["headless", "serve", "http", "metrics", "lockstep"])]
+    pub charts: bool,
+
+    /// Open a statistics dashboard in a second window, drawn on its own thread: the
+    /// charts of --charts, a histogram of the particle speeds and a heatmap of where
+    /// collisions happen
+    #[arg(long, conflicts_with_all = ["headless", "serve", "http", "metrics", "lockstep", "charts"])]
+    pub dashboard: bool,
+
+    /// Show a second copy of the simulation on the right, started from the same seed
+    /// and stepped in lockstep, with a setting changed: `<parameter>=<value>` for any
+    /// parameter `sweep` takes but `particles` (can be repeated)
+    #[arg(long, value_name = "PARAMETER=VALUE", conflicts_with_all = ["headless", "serve", "http", "metrics", "lockstep", "charts", "dashboard"])]
+    pub compare: Vec<Override>,
+
+    /// Start with a grid every 100 units drawn behind the particles, with the
+    /// coordinates along the edges. G turns it on and off in the window
+    #[arg(long)]
+    pub grid: bool,
+
+    /// What the color of a particle shows: its random color, speed, mass, charge,
+    /// species, or how recently it collided. C steps through them in the window
+    #[arg(long, value_enum, value_name = "ATTRIBUTE", default_value = "random")]
+    pub color_by: ColorBy,
+
+    /// Colors for --color-by: the sequential viridis or plasma, or the colorblind
+    /// safe okabe-ito or tol-bright. P steps through them in the window
+    #[arg(long, value_enum, default_value = "viridis")]
+    pub palette: Palette,
+
+    /// How the window looks: dark, light, neon or wireframe. T steps through
+    /// them in the window
+    #[arg(long, value_enum, default_value = "dark")]
+    pub theme: Theme,
+
+    /// Paint particles with the PNG images in this directory: particle-ID.png for
+    /// one particle, species-N.png for a species and particle.png for the rest
+    #[arg(long, value_name = "DIR")]
+    pub textures: Option<PathBuf>,
+
+    /// Draw the particles as one fluid surface, in which particles that come
+    /// close together merge, instead of as separate circles
+    #[arg(long)]
+    pub metaballs: bool,
+
+    /// Leave fading copies of the last frames behind the particles, as if each
+    /// frame were painted over the last with a background this opaque, from 0 to 1
+    #[arg(long, value_name = "ALPHA", value_parser = parse_motion_blur)]
+    pub motion_blur: Option<f64>,
+
+    /// Draw the window with another backend, which must have been built in. Only
+    /// flo_draw, the default, has the interactive window: pixels and terminal
+    /// show the particles and a few lines of text
+    #[arg(long, value_enum, conflicts_with_all = ["headless", "serve", "http", "metrics", "lockstep", "event_driven", "compare"])]
+    pub renderer: Option<Backend>,
+
+    /// How fast the simulation runs in the window compared to real time, for
+    /// example 0.5 for slow motion. Steps always stay the same size, so a faster
+    /// simulation takes more of them per frame
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0, value_parser = parse_time_scale)]
+    pub time_scale: f64,
+
+    /// How many frames the window draws per second (45 by default), or `uncapped`
+    /// to draw as fast as possible, for benchmarking the renderer. Frames that
+    /// fall behind are skipped, and the physics keeps to the wall clock either way
+    #[arg(long, value_name = "FPS", default_value = "45")]
+    pub fps: FrameRate,
+
+    /// Keep every visual and physics setting as it is even when the physics cannot
+    /// keep up, instead of turning them down one by one and saying so in the HUD
+    #[arg(long)]
+    pub fixed_quality: bool,
+
+    /// Above this many particles, counting more of them the further the window is
+    /// zoomed out, draw them as plain squares without any effects, and above four
+    /// times as many as points, so that crowded boxes still draw quickly
+    #[arg(long, value_name = "PARTICLES", default_value_t = 20_000)]
+    pub lod_threshold: usize,
+
+    /// Time this machine before starting, and pick the fastest broadphase, the most
+    /// particles that keep up with --fps (fewer are simulated if more were asked for)
+    /// and how many steps a frame may take to catch up. Prints what it picked
+    #[arg(long, conflicts_with = "sweep_axis")]
+    pub autotune: bool,
+
+    /// Start with the id of every particle written next to it, to match up log
+    /// messages with what is on screen. L turns them on and off in the window
+    #[arg(long)]
+    pub labels: bool,
+
+    /// Make particles glow brighter the harder and more often they have collided
+    /// lately, to show where the collisions are concentrated. E turns it on and off
+    #[arg(long)]
+    pub heat: bool,
+
+    /// Mark the center of mass of the particles with a cross and their total
+    /// momentum with an arrow, which should stay put and keep its length while
+    /// nothing but collisions acts on them. M turns them on and off
+    #[arg(long)]
+    pub center_of_mass: bool,
+
+    /// How many particles B adds at the pointer in the window, flying outwards
+    #[arg(long, value_name = "COUNT", default_value = "50", value_parser = parse_particle_count)]
+    pub burst: usize,
+
+    /// Treat the left button as a finger on a touchscreen: tap empty space to add
+    /// a particle, drag a particle to fling it, and pinch with two fingers to zoom
+    #[arg(long)]
+    pub touch: bool,
+
+    /// Play a drone in the window whose pitch follows the temperature and whose
+    /// volume follows the collision rate. Needs the `sound` feature
+    #[arg(long)]
+    pub sonify: bool,
+
+    /// Play every collision in the window as a note on the first MIDI output port
+    /// whose name contains PORT. Needs the `midi` feature
+    #[arg(long, value_name = "PORT")]
+    pub midi: Option<String>,
+
+    /// The notes of the heaviest and the lightest particles, as MIDI note numbers
+    #[arg(long, value_name = "HEAVIEST:LIGHTEST", default_value_t = NoteRange::default(), requires = "midi")]
+    pub midi_notes: NoteRange,
+
+    /// The MIDI channel to play on, from 1 to 16
+    #[arg(long, value_name = "CHANNEL", default_value = "1", value_parser = parse_midi_channel, requires = "midi")]
+    pub midi_channel: u8,
+
+    /// Simulate spheres in a 1000x1000x1000 box instead of circles in a square.
+    /// The 3D simulation only has elastic walls and random placement
+    #[arg(long = "3d", conflicts_with_all = [
+        "pattern", "shape", "paranoid", "verify_broadphase", "config", "wall_restitution",
+        "wall_friction", "floor_restitution", "floor_friction", "thermal_walls", "max_speed",
+        "gravity", "medium", "densities", "species", "species_gravity", "mutual_gravity",
+        "out_of_bounds", "map", "portals", "time_regions", "resume", "checkpoint_every", "profile",
+        "profile_csv", "sweep_axis", "export_trajectories", "export_msd", "export_rdf", "probes",
+        "free_paths", "event_driven", "cross_validate", "serve", "http", "metrics", "lockstep",
+        "charts", "dashboard", "compare", "grid", "labels", "heat", "center_of_mass", "burst",
+        "touch", "sonify", "midi", "osc", "shm", "svg", "svg_trails", "color_by", "palette",
+        "theme", "textures", "metaballs", "motion_blur", "lod_threshold", "renderer",
+        "fixed_quality", "autotune",
+    ])]
+    pub three_d: bool,
+
+    /// Number of steps to run before exiting in headless mode (runs forever if omitted).
+    /// A resumed run counts the steps taken before the checkpoint.
+    /// With --stress, the number of steps timed at each particle count (20 by default)
+    #[arg(long)]
+    pub steps: Option<u64>,
+
+    /// Find the most particles that can be simulated within --frame-budget per step,
+    /// starting from the requested count and doubling it. Combine with --paranoid
+    /// to also hunt for broken invariants in crowded boxes
+    #[arg(long, conflicts_with_all = [
+        "three_d", "pattern", "wall_restitution", "wall_friction", "floor_restitution",
+        "floor_friction", "thermal_walls", "max_speed", "gravity", "medium", "mutual_gravity",
+        "out_of_bounds", "map", "portals", "time_regions", "resume", "checkpoint_every",
+        "profile_csv", "export_trajectories", "export_msd", "export_rdf", "probes", "free_paths",
+        "serve", "http", "metrics", "lockstep", "event_driven", "cross_validate", "charts",
+        "dashboard", "compare", "grid", "labels", "heat", "center_of_mass", "burst", "touch",
+        "sonify", "midi", "osc", "shm", "svg", "svg_trails", "color_by", "palette", "theme",
+        "textures", "metaballs", "motion_blur", "lod_threshold", "renderer", "time_scale",
+        "fixed_quality", "autotune", "verify_broadphase",
+    ])]
+    pub stress: bool,
+
+    /// The longest a step may take on average in --stress mode, in milliseconds
+    /// (by default the time between two frames of the window)
+    #[arg(long, value_name = "MS", requires = "stress")]
+    pub frame_budget: Option<f64>,
+
+    /// The axis the broadphase sorts particles along: `x`, `y`, or `auto` to pick
+    /// whichever they are spread out the most along, every step
+    #[arg(long, value_name = "AXIS", default_value = "x")]
+    pub sweep_axis: SweepAxis,
+
+    /// Check the simulation's invariants after every step (slow), stopping with
+    /// a dump of every particle as soon as one is broken
+    #[arg(long)]
+    pub paranoid: bool,
+
+    /// Also find the possible collisions of every step by checking every pair of
+    /// particles (slow), and warn about each pair sweep and prune disagrees on
+    #[arg(long)]
+    pub verify_broadphase: bool,
+
+    /// Share of the speed into a wall that is kept after bouncing off of it (1 is perfectly elastic)
+    #[arg(long, value_name = "COEFFICIENT", default_value_t = 1.0)]
+    pub wall_restitution: f64,
+
+    /// Friction coefficient of the walls, slowing particles that slide along them
+    #[arg(long, value_name = "COEFFICIENT", default_value_t = 0.0)]
+    pub wall_friction: f64,
+
+    /// Restitution of the floor, if it should differ from the other walls
+    #[arg(long, value_name = "COEFFICIENT")]
+    pub floor_restitution: Option<f64>,
+
+    /// Friction of the floor, if it should differ from the other walls
+    #[arg(long, value_name = "COEFFICIENT")]
+    pub floor_friction: Option<f64>,
+
+    /// Make a side of the box a thermal wall, `<side>:<temperature>`, which sends every
+    /// particle that hits it off with a velocity drawn from a gas at that temperature
+    /// (can be repeated, e.g. `--thermal-wall left:1000000 --thermal-wall right:100000`)
+    #[arg(long = "thermal-wall", value_name = "SIDE:TEMPERATURE", value_parser = parse_thermal_wall)]
+    pub thermal_walls: Vec<(Side, f64)>,
+
+    /// The fastest a particle may move, in units per step. Particles that a violent
+    /// collision sends faster are slowed down to it (no limit by default)
+    #[arg(long, value_name = "SPEED", value_parser = parse_max_speed)]
+    pub max_speed: Option<f64>,
+
+    /// How fast particles fall, in units per step per step (no gravity by default)
+    #[arg(long, value_name = "ACCELERATION", value_parser = parse_gravity)]
+    pub gravity: Option<f64>,
+
+    /// Fill the box with a fluid of density `<floor>:<ceiling>`, changing linearly with
+    /// height (or one density throughout), in which particles lighter than the fluid
+    /// around them float up under --gravity and heavier ones sink
+    #[arg(long, value_name = "FLOOR:CEILING", value_parser = parse_medium)]
+    pub medium: Option<Medium>,
+
+    /// Give every random particle a density drawn uniformly from `<min>:<max>`,
+    /// instead of a density of 1
+    #[arg(long, value_name = "MIN:MAX", value_parser = parse_densities)]
+    pub densities: Option<(f64, f64)>,
+
+    /// Split the random particles between this many species, numbered from 0,
+    /// taking turns by id. `--color-by species` tells them apart
+    #[arg(long, value_name = "COUNT", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub species: u32,
+
+    /// Multiply gravity for one species, `<species>:<scale>`. A negative scale makes
+    /// the species rise while the others fall (can be repeated)
+    #[arg(long = "species-gravity", value_name = "SPECIES:SCALE", value_parser = parse_species_gravity)]
+    pub species_gravity: Vec<(u32, f64)>,
+
+    /// Make every particle pull on every other with Newtonian gravity, with this
+    /// gravitational constant. Every pair is visited, so keep to a few hundred particles
+    #[arg(long, value_name = "G", value_parser = parse_mutual_gravity)]
+    pub mutual_gravity: Option<f64>,
+
+    /// What happens to a particle that escapes the box: `teleport` it back inside,
+    /// next to the wall it went through, or `remove` it. Either way a warning is logged
+    #[arg(long, value_name = "POLICY", default_value = "teleport")]
+    pub out_of_bounds: OutOfBoundsPolicy,
+
+    /// Obstacles to bounce off of: a text file where `#` is a wall,
+    /// or a PNG where dark pixels are walls, stretched over the whole box
+    #[arg(long, value_name = "FILE")]
+    pub map: Option<PathBuf>,
+
+    /// A pair of linked circles, `<ax>,<ay>,<bx>,<by>,<radius>[,<degrees>]`.
+    /// A particle entering one leaves the other, turned by the given angle.
+    /// Can be given more than once
+    #[arg(long = "portal", value_name = "PORTAL")]
+    pub portals: Vec<Portal>,
+
+    /// A rectangle where time runs at a different rate, `<min_x>,<min_y>,<max_x>,<max_y>,<scale>`.
+    /// Particles inside move and age `scale` times as fast, so 0.5 is slow motion
+    /// and 0 freezes them. Can be given more than once
+    #[arg(long = "time-region", value_name = "REGION")]
+    pub time_regions: Vec<TimeRegion>,
+
+    /// Seed for the random particles, so that a run can be reproduced
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Save a checkpoint every this many frames in headless mode
+    #[arg(long, value_name = "FRAMES", value_parser = clap::value_parser!(u64).range(1..))]
+    pub checkpoint_every: Option<u64>,
+
+    /// Directory that checkpoints are saved to and resumed from
+    #[arg(long, value_name = "DIR", default_value = "checkpoints")]
+    pub checkpoint_dir: PathBuf,
+
+    /// Carry on from the newest checkpoint in --checkpoint-dir, if there is one
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Show the time spent in each phase of the frame in the HUD
+    /// (or print a summary at the end of a headless run)
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Write the time spent in each phase of every frame to a CSV file
+    #[arg(long, value_name = "FILE")]
+    pub profile_csv: Option<PathBuf>,
+
+    /// Append `frame,id,x,y,vx,vy` rows for every particle to this CSV file
+    #[arg(long, value_name = "FILE")]
+    pub export_trajectories: Option<PathBuf>,
+
+    /// Append `frame,msd` rows with the mean squared displacement of the particles from
+    /// where they started to this CSV file, ending with the fitted diffusion coefficient
+    #[arg(long, value_name = "FILE")]
+    pub export_msd: Option<PathBuf>,
+
+    /// Append the radial distribution function g(r) of the particles to this CSV file,
+    /// as `frame,r,g` rows
+    #[arg(long, value_name = "FILE")]
+    pub export_rdf: Option<PathBuf>,
+
+    /// The largest distance g(r) is computed out to
+    #[arg(long, value_name = "DISTANCE", default_value_t = 100.0, value_parser = parse_rdf_range)]
+    #[arg(requires = "export_rdf")]
+    pub rdf_range: f64,
+
+    /// A JSON file of named rectangular regions to measure, such as
+    /// `[{"name": "hot", "min_x": 0, "min_y": 0, "max_x": 200, "max_y": 1000}]`
+    #[arg(long, value_name = "FILE", requires = "export_probes")]
+    pub probes: Option<PathBuf>,
+
+    /// Append the particle count, density, mean speed and temperature inside every
+    /// probe region to this CSV file
+    #[arg(long, value_name = "FILE", requires = "probes")]
+    pub export_probes: Option<PathBuf>,
+
+    /// Follow every particle from one collision to the next in headless mode, writing
+    /// the time and distance of each free flight to this CSV file and printing the mean
+    /// free path next to the prediction of kinetic theory at the end
+    #[arg(long, value_name = "FILE", requires = "headless")]
+    pub free_paths: Option<PathBuf>,
+
+    /// Run headless with the exact event-driven simulation, which jumps from one
+    /// collision to the next, instead of taking steps. --steps counts units of time.
+    /// Only elastic circles in an empty box are supported
+    #[arg(long, requires = "headless", conflicts_with_all = ["serve", "http", "metrics", "free_paths", "svg"])]
+    pub event_driven: bool,
+
+    /// Save the last frame of a headless run to this SVG file, with every particle
+    /// a vector circle, for figures that stay sharp at any size
+    #[arg(long, value_name = "FILE", requires = "headless", conflicts_with_all = ["serve", "http"])]
+    pub svg: Option<PathBuf>,
+
+    /// Draw where every particle was over this many frames as a line behind it in
+    /// SVG snapshots, both --svg and those F9 saves in the window
+    #[arg(long, value_name = "FRAMES")]
+    pub svg_trails: Option<usize>,
+
+    /// With --event-driven, also step the simulation from the same start with steps of
+    /// this length, and report every 10 units of time how far apart the two have drifted
+    #[arg(long, value_name = "DT", requires = "event_driven", value_parser = parse_step_length)]
+    pub cross_validate: Option<f64>,
+
+    /// Only export trajectories, displacements, g(r) and probes every this many frames
+    #[arg(long, value_name = "FRAMES", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub export_every: u64,
+
+    /// Run headless, streaming every frame over WebSocket on this port
+    #[arg(long, value_name = "PORT")]
+    pub serve: Option<u16>,
+
+    /// Run headless, answering REST requests on this port (can be combined with --serve)
+    #[arg(long, value_name = "PORT")]
+    pub http: Option<u16>,
+
+    /// Run headless, serving Prometheus metrics at /metrics on this port (can be
+    /// combined with --serve and --http)
+    #[arg(long, value_name = "PORT")]
+    pub metrics: Option<u16>,
+
+    /// Run headless in deterministic lockstep with a second instance, comparing
+    /// state hashes after every step: a port to wait for it on, or the
+    /// `<host>:<port>` of one that is waiting. The seed of the waiting one is used
+    #[arg(long, value_name = "PORT|HOST:PORT", conflicts_with_all = ["serve", "http", "event_driven"])]
+    pub lockstep: Option<Peer>,
+
+    /// The address --serve, --http, --metrics and a waiting --lockstep listen on.
+    /// Anyone who can reach them can control the simulation, so only this machine
+    /// can unless told otherwise
+    #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1")]
+    pub bind: IpAddr,
+
+    /// Encoding of the frames streamed by --serve
+    #[arg(long, value_enum, default_value = "json")]
+    pub stream_format: StreamFormat,
+
+    /// Stream every frame and collision as OSC over UDP to this address, in the
+    /// window or headless
+    #[arg(long, value_name = "HOST:PORT", value_parser = parse_osc_target, conflicts_with_all = ["serve", "http", "event_driven", "compare"])]
+    pub osc: Option<SocketAddr>,
+
+    /// Publish every frame to a ring buffer in this file for other processes to
+    /// map, such as /dev/shm/particles, in the window or headless. Each frame has
+    /// room for --max-particles particles, or else the starting count
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["serve", "http", "event_driven", "compare"])]
+    pub shm: Option<PathBuf>,
+
+    /// The timed events of the scenario, which only a config file can give
+    #[arg(skip)]
+    pub events: Vec<TimedEvent>,
+
+    /// The keyframes the window's camera moves through, also only from a config file
+    #[arg(skip)]
+    pub camera_path: Vec<Keyframe>,
+
+    /// The most steps a late frame catches up on, as picked by --autotune
+    #[arg(skip)]
+    pub catch_up_steps: Option<u32>,
+}
+
+/// Tools that run instead of the simulator itself.
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Run every simulation listed in a JSON manifest, headless, writing the
+    /// statistics of each into a directory of its own
+    Experiments(ExperimentsArgs),
+    /// Run the same headless simulation for every value of a parameter, and
+    /// write a CSV of a metric against it
+    Sweep(SweepArgs),
+    /// Run many replicas of the same scenario with different seeds, and report
+    /// the mean and variance of their temperature, pressure and mixing time
+    Ensemble(EnsembleArgs),
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ExperimentsArgs {
+    /// The manifest listing the runs
+    pub manifest: PathBuf,
+
+    /// Directory the output directories of the runs are created in
+    #[arg(long, value_name = "DIR", default_value = "experiments")]
+    pub out: PathBuf,
+
+    /// How many runs to execute at once, each in a process of its own
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub jobs: u64,
+
+    /// Execute only the run at this position in the manifest
+    #[arg(long, value_name = "INDEX", hide = true)]
+    pub only: Option<usize>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct SweepArgs {
+    /// The parameter and its values, `<name>=<start>:<end>:<step>`, where the name
+    /// is `restitution`, `friction`, `gravity`, `max_speed` or `particles`
+    #[arg(long, value_name = "RANGE")]
+    pub param: ParamRange,
+
+    /// What to measure from each run
+    #[arg(long, value_enum, default_value = "energy_drift")]
+    pub metric: Metric,
+
+    /// Number of particles in each run
+    #[arg(long, default_value = "20", value_parser = parse_particle_count)]
+    pub particles: usize,
+
+    /// How many steps each run takes
+    #[arg(long, default_value_t = 1000)]
+    pub steps: u64,
+
+    /// Seed shared by every run, so that only the parameter differs
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Where the particles start, as for the simulator itself
+    #[arg(long, value_name = "PATTERN", default_value = "random")]
+    pub pattern: SpawnPattern,
+
+    /// Write the CSV to this file rather than to standard output
+    #[arg(long, value_name = "FILE")]
+    pub out: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct EnsembleArgs {
+    /// How many replicas to run
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u64).range(1..))]
+    pub replicas: u64,
+
+    /// Seed of the first replica, the others count up from it
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Number of particles in each replica
+    #[arg(long, default_value = "20", value_parser = parse_particle_count)]
+    pub particles: usize,
+
+    /// How many steps each replica takes
+    #[arg(long, default_value_t = 2000)]
+    pub steps: u64,
+
+    /// Where the particles start, as for the simulator itself
+    #[arg(long, value_name = "PATTERN", default_value = "random")]
+    pub pattern: SpawnPattern,
+
+    /// How many replicas run at once, by default one per core
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub threads: Option<u64>,
+
+    /// Also write the statistics of the ensemble and of every replica to this JSON file
+    #[arg(long, value_name = "FILE")]
+    pub out: Option<PathBuf>,
+}
+
+impl Args {
+    pub fn particle_count(&self) -> usize {
+        //! The requested number of particles, capped by `--max-particles` if it was given.
+        match self.max_particles {
+            Some(max) if self.particles > max => {
+                tracing::warn!(
+                    requested = self.particles,
+                    max,
+                    "capping the particle count at --max-particles"
+                );
+                max
+            }
+            _ => self.particles,
+        }
+    }
+
+    pub fn numbered_outputs(&self, run: u32) -> Args {
+        //! The same arguments with `-<run>` added to the name of every file the
+        //! simulation writes to, so that a restarted run leaves the files of the
+        //! ones before it alone.
+        let mut args = self.clone();
+        let outputs = [
+            &mut args.profile_csv,
+            &mut args.export_trajectories,
+            &mut args.export_msd,
+            &mut args.export_rdf,
+            &mut args.export_probes,
+        ];
+        for path in outputs.into_iter().flatten() {
+            let mut name = path.file_stem().unwrap_or_default().to_os_string();
+            name.push(format!("-{}", run));
+            if let Some(extension) = path.extension() {
+                name.push(".");
+                name.push(extension);
+            }
+            path.set_file_name(name);
+        }
+        args
+    }
+
+    pub fn finishes_exports(&self) -> bool {
+        //! Whether the run comes to an end of its own and finishes its exports,
+        //! which only the headless loop does. The window and the servers run until
+        //! the process is stopped.
+        self.serve.is_none()
+            && self.http.is_none()
+            && !self.event_driven
+            && (self.headless || self.metrics.is_some() || self.lockstep.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let args = Args::try_parse_from(["particle-simulator"]).unwrap();
+        assert_eq!(args.particles, 20);
+        assert_eq!(args.log_level, "warn");
+        assert!(!args.log_json);
+        assert!(!args.headless);
+        assert!(!args.charts);
+        assert!(!args.grid);
+        assert!(!args.labels);
+        assert_eq!(args.color_by, ColorBy::Random);
+        assert_eq!(args.theme, Theme::Dark);
+        assert_eq!(args.time_scale, 1.0);
+        assert_eq!(args.fps, FrameRate::Capped(45.0));
+        assert!(!args.fixed_quality);
+        assert_eq!(args.steps, None);
+        assert!(!args.profile);
+        assert_eq!(args.profile_csv, None);
+    }
+
+    #[test]
+    fn test_headless_flags() {
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "30",
+            "--headless",
+            "--steps",
+            "100",
+            "--log-level",
+            "debug",
+            "--log-json",
+        ])
+        .unwrap();
+        assert_eq!(args.particles, 30);
+        assert!(args.headless);
+        assert_eq!(args.steps, Some(100));
+        assert_eq!(args.log_level, "debug");
+        assert!(args.log_json);
+        assert!(Args::try_parse_from(["particle-simulator", "--headless", "--charts"]).is_err());
+    }
+
+    #[test]
+    fn test_experiments_subcommand() {
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "experiments",
+            "runs.json",
+            "--jobs",
+            "4",
+        ])
+        .unwrap();
+        let Some(Command::Experiments(experiments)) = args.command else {
+            panic!("expected the experiments subcommand");
+        };
+        assert_eq!(experiments.manifest, PathBuf::from("runs.json"));
+        assert_eq!(experiments.out, PathBuf::from("experiments"));
+        assert_eq!(experiments.jobs, 4);
+        assert_eq!(experiments.only, None);
+        assert!(Args::try_parse_from(["particle-simulator", "experiments"]).is_err());
+        assert!(Args::try_parse_from(["particle-simulator", "50"])
+            .unwrap()
+            .command
+            .is_none());
+    }
+
+    #[test]
+    fn test_sweep_subcommand() {
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "sweep",
+            "--param",
+            "restitution=0.1:1.0:0.1",
+            "--metric",
+            "final_temperature",
+        ])
+        .unwrap();
+        let Some(Command::Sweep(sweep)) = args.command else {
+            panic!("expected the sweep subcommand");
+        };
+        assert_eq!(sweep.param.start, 0.1);
+        assert_eq!(sweep.metric, Metric::FinalTemperature);
+        assert_eq!(sweep.steps, 1000);
+        assert!(Args::try_parse_from(["particle-simulator", "sweep"]).is_err());
+        assert!(
+            Args::try_parse_from(["particle-simulator", "sweep", "--param", "restitution=1"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_ensemble_subcommand() {
+        let args =
+            Args::try_parse_from(["particle-simulator", "ensemble", "--replicas", "32"]).unwrap();
+        let Some(Command::Ensemble(ensemble)) = args.command else {
+            panic!("expected the ensemble subcommand");
+        };
+        assert_eq!(ensemble.replicas, 32);
+        assert_eq!(ensemble.threads, None);
+        assert!(
+            Args::try_parse_from(["particle-simulator", "ensemble", "--replicas", "0"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_3d_flag() {
+        let args = Args::try_parse_from(["particle-simulator", "--3d", "--seed", "4"]).unwrap();
+        assert!(args.three_d);
+        assert!(Args::try_parse_from(["particle-simulator", "--3d", "--serve", "9001"]).is_err());
+    }
+
+    #[test]
+    fn test_stress_flags() {
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "--stress",
+            "--frame-budget",
+            "8.5",
+            "--sweep-axis",
+            "auto",
+        ])
+        .unwrap();
+        assert!(args.stress);
+        assert_eq!(args.frame_budget, Some(8.5));
+        assert_eq!(args.sweep_axis, SweepAxis::Auto);
+        assert!(Args::try_parse_from(["particle-simulator", "--frame-budget", "8"]).is_err());
+        assert!(Args::try_parse_from(["particle-simulator", "--stress", "--3d"]).is_err());
+    }
+
+    #[test]
+    fn test_color_flags() {
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "--color-by",
+            "collision-recency",
+            "--palette",
+            "okabe-ito",
+        ])
+        .unwrap();
+        assert_eq!(args.color_by, ColorBy::CollisionRecency);
+        assert_eq!(args.palette, Palette::OkabeIto);
+        let args = Args::try_parse_from(["particle-simulator", "--theme", "wireframe"]).unwrap();
+        assert_eq!(args.theme, Theme::Wireframe);
+        let args = Args::try_parse_from(["particle-simulator", "--fps", "uncapped"]).unwrap();
+        assert_eq!(args.fps, FrameRate::Uncapped);
+        assert!(Args::try_parse_from(["particle-simulator", "--fps", "-30"]).is_err());
+        assert!(Args::try_parse_from(["particle-simulator", "--palette", "rainbow"]).is_err());
+    }
+
+    #[test]
+    fn test_serve_flags() {
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "--serve",
+            "9001",
+            "--stream-format",
+            "binary",
+            "--http",
+            "8080",
+        ])
+        .unwrap();
+        assert_eq!(args.serve, Some(9001));
+        assert_eq!(args.http, Some(8080));
+        assert_eq!(args.stream_format, StreamFormat::Binary);
+        assert!(args.bind.is_loopback());
+        let args =
+            Args::try_parse_from(["particle-simulator", "--serve", "9001", "--bind", "0.0.0.0"])
+                .unwrap();
+        assert!(args.bind.is_unspecified());
+    }
+
+    #[test]
+    fn test_export_flags() {
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "--export-trajectories",
+            "out.csv",
+            "--export-every",
+            "10",
+        ])
+        .unwrap();
+        assert_eq!(args.export_trajectories, Some(PathBuf::from("out.csv")));
+        assert_eq!(args.export_every, 10);
+        assert!(!args.finishes_exports());
+        let args = Args::try_parse_from(["particle-simulator", "--headless"]).unwrap();
+        assert!(args.finishes_exports());
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "--export-trajectories",
+            "runs/out.csv",
+            "--profile-csv",
+            "profile",
+        ])
+        .unwrap()
+        .numbered_outputs(2);
+        assert_eq!(
+            args.export_trajectories,
+            Some(PathBuf::from("runs/out-2.csv"))
+        );
+        assert_eq!(args.profile_csv, Some(PathBuf::from("profile-2")));
+        assert_eq!(args.export_msd, None);
+        let args = Args::try_parse_from(["particle-simulator", "--export-msd", "msd.csv"]).unwrap();
+        assert_eq!(args.export_msd, Some(PathBuf::from("msd.csv")));
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "--export-rdf",
+            "rdf.csv",
+            "--rdf-range",
+            "60",
+        ])
+        .unwrap();
+        assert_eq!(args.rdf_range, 60.0);
+        assert!(Args::try_parse_from(["particle-simulator", "--rdf-range", "60"]).is_err());
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "--headless",
+            "--free-paths",
+            "flights.csv",
+        ])
+        .unwrap();
+        assert_eq!(args.free_paths, Some(PathBuf::from("flights.csv")));
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "--probes",
+            "regions.json",
+            "--export-probes",
+            "probes.csv",
+        ])
+        .unwrap();
+        assert_eq!(args.probes, Some(PathBuf::from("regions.json")));
+        assert!(Args::try_parse_from(["particle-simulator", "--probes", "regions.json"]).is_err());
+        assert!(
+            Args::try_parse_from(["particle-simulator", "--free-paths", "flights.csv"]).is_err()
+        );
+        assert!(Args::try_parse_from([
+            "particle-simulator",
+            "--export-rdf",
+            "rdf.csv",
+            "--rdf-range",
+            "0"
+        ])
+        .is_err());
+        assert!(Args::try_parse_from(["particle-simulator", "--export-every", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_checkpoint_flags() {
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "--headless",
+            "--seed",
+            "42",
+            "--checkpoint-every",
+            "1000",
+            "--checkpoint-dir",
+            "runs/a",
+            "--resume",
+            "--paranoid",
+            "--verify-broadphase",
+        ])
+        .unwrap();
+        assert_eq!(args.seed, Some(42));
+        assert_eq!(args.checkpoint_every, Some(1000));
+        assert_eq!(args.checkpoint_dir, PathBuf::from("runs/a"));
+        assert!(args.resume);
+        assert!(args.paranoid);
+        assert!(args.verify_broadphase);
+
+        let defaults = Args::try_parse_from(["particle-simulator"]).unwrap();
+        assert_eq!(defaults.checkpoint_dir, PathBuf::from("checkpoints"));
+        assert!(!defaults.resume);
+        assert!(!defaults.paranoid);
+        assert!(!defaults.verify_broadphase);
+    }
+
+    #[test]
+    fn test_radius_flags() {
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "--min-radius",
+            "2",
+            "--max-radius",
+            "20",
+            "--radius-distribution",
+            "log-normal:2,0.5",
+            "--speed-distribution",
+            "thermal:5000",
+            "--pattern",
+            "two-clusters:4",
+            "--shape",
+            "polygon:6",
+        ])
+        .unwrap();
+        assert_eq!(args.min_radius, 2.0);
+        assert_eq!(args.max_radius, 20.0);
+        assert_eq!(
+            args.radius_distribution,
+            RadiusDistribution::LogNormal {
+                mu: 2.0,
+                sigma: 0.5
+            }
+        );
+        assert_eq!(
+            args.speed_distribution,
+            SpeedDistribution::Thermal {
+                temperature: 5000.0
+            }
+        );
+        assert_eq!(args.pattern, SpawnPattern::TwoClusters { speed: 4.0 });
+        assert_eq!(args.shape, SpawnShape::Polygon(6));
+        assert!(
+            Args::try_parse_from(["particle-simulator", "--radius-distribution", "normal"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_wall_flags() {
+        let args = Args::try_parse_from([
+            "particle-simulator",
+            "--wall-restitution",
+            "0.9",
+            "--floor-friction",
+            "0.3",
+            "--map",
+            "maze.txt",
+            "--portal",
+            "100,500,900,500,30",
+            "--portal",
+            "500,100,500,900,30,180",
+            "--max-speed",
+            "40",
+            "--out-of-bounds",
+            "remove",
+            "--thermal-wall",
+            "left:20",
+        ])
+        .unwrap();
+        assert_eq!(args.wall_restitution, 0.9);
+        assert_eq!(args.wall_friction, 0.0);
+        assert_eq!(args.thermal_walls, [(Side::Left, 20.0)]);
+        assert_eq!(args.floor_restitution, None);
+        assert_eq!(args.floor_friction, Some(0.3));
+        assert_eq!(args.max_speed, Some(40.0));
+        assert_eq!(args.out_of_bounds, OutOfBoundsPolicy::Remove);
+        assert_eq!(args.map, Some(PathBuf::from("maze.txt")));
+        assert_eq!(args.portals.len(), 2);
+    }
+
+    #[test]
+    fn test_max_particles_caps_the_count() {
+        let uncapped = Args::try_parse_from(["particle-simulator", "5000"]).unwrap();
+        assert_eq!(uncapped.particle_count(), 5000);
+
+        let capped =
+            Args::try_parse_from(["particle-simulator", "5000", "--max-particles", "100"]).unwrap();
+        assert_eq!(capped.particle_count(), 100);
+
+        let under_cap =
+            Args::try_parse_from(["particle-simulator", "50", "--max-particles", "100"]).unwrap();
+        assert_eq!(under_cap.particle_count(), 50);
+    }
+
+    #[test]
+    fn test_bad_particle_count_is_rejected() {
+        assert!(Args::try_parse_from(["particle-simulator", "fifty"]).is_err());
+    }
+}
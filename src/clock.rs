@@ -0,0 +1,257 @@
+use particle_core::error::{SimError, SimResult};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The physics always advances in steps of `dt = 1.0`, this many per second of
+/// real time at a time scale of 1.
+pub const STEPS_PER_SECOND: f64 = 45.0;
+/// The most steps taken to catch up in a single frame. After a long hitch, such as
+/// the window being dragged, the rest of the missed time is dropped rather than
+/// stepped through, which would only make the next frame late as well.
+pub const MAX_STEPS_PER_FRAME: u32 = 8;
+
+/// How many frames the window draws per second unless `--fps` says otherwise.
+pub const DEFAULT_FPS: f64 = 45.0;
+/// The most frames in a row that the scheduler skips drawing when it falls behind,
+/// so that the window still shows something while the physics is too slow.
+const MAX_SKIPPED_FRAMES: u32 = 4;
+
+/// How often frames are drawn: a target number per second, or as fast as
+/// possible, which is meant for benchmarking the renderer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameRate {
+    Capped(f64),
+    Uncapped,
+}
+
+impl FrameRate {
+    pub fn interval(self) -> Option<Duration> {
+        //! The time between the starts of two frames, if there is a cap.
+        match self {
+            FrameRate::Capped(fps) => Some(Duration::from_secs_f64(1.0 / fps)),
+            FrameRate::Uncapped => None,
+        }
+    }
+}
+
+impl Default for FrameRate {
+    fn default() -> Self {
+        FrameRate::Capped(DEFAULT_FPS)
+    }
+}
+
+impl FromStr for FrameRate {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        //! Parse a positive number of frames per second, or `uncapped`.
+        match value {
+            "uncapped" => Ok(FrameRate::Uncapped),
+            _ => match value.parse::<f64>() {
+                Ok(fps) if fps.is_finite() && fps > 0.0 => Ok(FrameRate::Capped(fps)),
+                _ => Err(SimError::InvalidArgument {
+                    name: "frame rate".to_string(),
+                    value: value.to_string(),
+                }),
+            },
+        }
+    }
+}
+
+/// Keeps the frame loop at its frame rate. Each frame has a deadline, one interval
+/// after the last, and `wait` sleeps until it. Sleeping until a deadline rather than
+/// for a fixed time absorbs the time the frame itself took, and a frame whose work
+/// has already run past its deadline is better off not drawn at all.
+#[derive(Debug, Clone)]
+pub struct FrameScheduler {
+    interval: Option<Duration>,
+    deadline: Instant,
+    skipped: u32,
+}
+
+impl FrameScheduler {
+    pub fn new(frame_rate: FrameRate) -> Self {
+        let interval = frame_rate.interval();
+        FrameScheduler {
+            interval,
+            deadline: Instant::now() + interval.unwrap_or_default(),
+            skipped: 0,
+        }
+    }
+
+    pub fn should_render(&mut self) -> bool {
+        //! Whether to draw the current frame: not if it is already late, unless
+        //! too many frames in a row have been skipped.
+        self.should_render_at(Instant::now())
+    }
+
+    fn should_render_at(&mut self, now: Instant) -> bool {
+        let late = self.interval.is_some() && now > self.deadline;
+        if late && self.skipped < MAX_SKIPPED_FRAMES {
+            self.skipped += 1;
+            false
+        } else {
+            self.skipped = 0;
+            true
+        }
+    }
+
+    pub fn lag(&self) -> Duration {
+        //! How far the current frame has run past its deadline, zero if it has not.
+        self.lag_at(Instant::now())
+    }
+
+    fn lag_at(&self, now: Instant) -> Duration {
+        match self.interval {
+            Some(_) => now.saturating_duration_since(self.deadline),
+            None => Duration::ZERO,
+        }
+    }
+
+    pub fn wait(&mut self) {
+        //! Sleep until the end of the current frame, and start the next one.
+        if let Some(pause) = self.next_deadline(Instant::now()) {
+            thread::sleep(pause);
+        }
+    }
+
+    fn next_deadline(&mut self, now: Instant) -> Option<Duration> {
+        //! Move on to the next deadline, returning how long to sleep first. A
+        //! scheduler that has fallen more than a frame behind starts afresh
+        //! from now, rather than rushing through frames to catch up.
+        let interval = self.interval?;
+        let pause = self.deadline.saturating_duration_since(now);
+        self.deadline += interval;
+        if self.deadline < now {
+            self.deadline = now + interval;
+        }
+        Some(pause)
+    }
+}
+
+/// Turns the wall time that passes between frames into a number of fixed physics
+/// steps. Leftover time is kept for the next frame, so the simulation runs at the
+/// same speed however fast the machine draws and however unevenly frames arrive.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    last: Instant,
+    /// Time that has passed but not been stepped through yet, in steps.
+    accumulator: f64,
+    time_scale: f64,
+    max_steps: u32,
+}
+
+impl Clock {
+    pub fn new(time_scale: f64) -> Self {
+        Clock {
+            last: Instant::now(),
+            accumulator: 0.0,
+            time_scale,
+            max_steps: MAX_STEPS_PER_FRAME,
+        }
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale;
+    }
+
+    pub fn set_max_steps(&mut self, max_steps: Option<u32>) {
+        //! Limit the steps taken to catch up in a single frame, or go back to the default.
+        self.max_steps = max_steps.unwrap_or(MAX_STEPS_PER_FRAME).max(1);
+    }
+
+    pub fn tick(&mut self) -> u32 {
+        //! How many steps to take this frame, for the time since the last tick.
+        let now = Instant::now();
+        let elapsed = now - self.last;
+        self.last = now;
+        self.advance(elapsed)
+    }
+
+    fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed.as_secs_f64() * STEPS_PER_SECOND * self.time_scale;
+        let steps = (self.accumulator.floor() as u32).min(self.max_steps);
+        self.accumulator -= steps as f64;
+        if steps == self.max_steps {
+            self.accumulator = self.accumulator.min(1.0);
+        }
+        steps
+    }
+}
+
+pub fn simulated_seconds(frame: u64) -> f64 {
+    //! The simulated time once `frame` steps have been taken, which only runs
+    //! while the simulation does, however fast or slow that is in real time.
+    frame as f64 / STEPS_PER_SECOND
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leftover_time_carries_over() {
+        let mut clock = Clock::new(1.0);
+        // Four and a half steps, then the half left over makes five more
+        assert_eq!(clock.advance(Duration::from_millis(100)), 4);
+        assert_eq!(clock.advance(Duration::from_millis(100)), 5);
+        assert_eq!(clock.advance(Duration::from_millis(10)), 0);
+
+        let mut halved = Clock::new(0.5);
+        assert_eq!(halved.advance(Duration::from_millis(100)), 2);
+    }
+
+    #[test]
+    fn test_frame_rates_are_parsed() {
+        assert_eq!("60".parse::<FrameRate>().unwrap(), FrameRate::Capped(60.0));
+        assert_eq!(
+            "uncapped".parse::<FrameRate>().unwrap(),
+            FrameRate::Uncapped
+        );
+        assert!("0".parse::<FrameRate>().is_err());
+        assert!("fast".parse::<FrameRate>().is_err());
+        assert_eq!(FrameRate::Uncapped.interval(), None);
+    }
+
+    #[test]
+    fn test_scheduler_sleeps_until_the_deadline() {
+        let mut scheduler = FrameScheduler::new(FrameRate::Capped(10.0));
+        let start = scheduler.deadline - Duration::from_millis(100);
+        // 30ms into the frame there are 70ms left to sleep
+        let pause = scheduler.next_deadline(start + Duration::from_millis(30));
+        assert_eq!(pause, Some(Duration::from_millis(70)));
+        assert_eq!(scheduler.deadline, start + Duration::from_millis(200));
+
+        // Late frames are skipped, but never too many in a row
+        let late = scheduler.deadline + Duration::from_millis(1);
+        assert_eq!(scheduler.lag_at(late), Duration::from_millis(1));
+        for _ in 0..MAX_SKIPPED_FRAMES {
+            assert!(!scheduler.should_render_at(late));
+        }
+        assert!(scheduler.should_render_at(late));
+        // A scheduler that is far behind starts again from now
+        let behind = scheduler.deadline + Duration::from_secs(1);
+        assert_eq!(scheduler.next_deadline(behind), Some(Duration::ZERO));
+        assert_eq!(scheduler.deadline, behind + Duration::from_millis(100));
+
+        let mut uncapped = FrameScheduler::new(FrameRate::Uncapped);
+        assert!(uncapped.should_render_at(Instant::now() + Duration::from_secs(1)));
+        assert_eq!(uncapped.next_deadline(Instant::now()), None);
+    }
+
+    #[test]
+    fn test_long_hitches_are_dropped() {
+        let mut clock = Clock::new(1.0);
+        assert_eq!(clock.advance(Duration::from_secs(10)), MAX_STEPS_PER_FRAME);
+        // Only the fraction of a step left over survives
+        assert!(clock.accumulator <= 1.0);
+        assert!(clock.advance(Duration::ZERO) <= 1);
+        clock.set_max_steps(Some(2));
+        assert_eq!(clock.advance(Duration::from_secs(1)), 2);
+    }
+}
@@ -0,0 +1,311 @@
+use clap::ValueEnum;
+use hecs::World;
+use particle_core::components::{Appearance, Charge, Species};
+use particle_core::events::Event;
+use particle_core::particle::Particle;
+use std::collections::HashMap;
+
+/// How many frames a collision takes to fade out when coloring by collision recency.
+const RECENCY_FRAMES: f64 = 45.0;
+
+/// What the color of a particle shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorBy {
+    /// The random color every particle is spawned with
+    Random,
+    Speed,
+    Mass,
+    Charge,
+    Species,
+    /// How recently the particle last collided
+    CollisionRecency,
+}
+
+/// The colors an attribute is mapped onto. The qualitative palettes are safe for
+/// colorblind viewers, and viridis and plasma are perceptually uniform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Palette {
+    Viridis,
+    Plasma,
+    /// The Okabe-Ito palette, without its black
+    OkabeIto,
+    /// Paul Tol's bright palette
+    TolBright,
+}
+
+const VIRIDIS: &[[u8; 3]] = &[
+    [0x44, 0x01, 0x54],
+    [0x47, 0x2d, 0x7b],
+    [0x3b, 0x52, 0x8b],
+    [0x2c, 0x72, 0x8e],
+    [0x21, 0x91, 0x8c],
+    [0x28, 0xae, 0x80],
+    [0x5e, 0xc9, 0x62],
+    [0xad, 0xdc, 0x30],
+    [0xfd, 0xe7, 0x25],
+];
+const PLASMA: &[[u8; 3]] = &[
+    [0x0d, 0x08, 0x87],
+    [0x4c, 0x02, 0xa1],
+    [0x7e, 0x03, 0xa8],
+    [0xa9, 0x23, 0x95],
+    [0xcc, 0x47, 0x78],
+    [0xe5, 0x6b, 0x5d],
+    [0xf8, 0x94, 0x41],
+    [0xfd, 0xc3, 0x28],
+    [0xf0, 0xf9, 0x21],
+];
+const OKABE_ITO: &[[u8; 3]] = &[
+    [0xe6, 0x9f, 0x00],
+    [0x56, 0xb4, 0xe9],
+    [0x00, 0x9e, 0x73],
+    [0xf0, 0xe4, 0x42],
+    [0x00, 0x72, 0xb2],
+    [0xd5, 0x5e, 0x00],
+    [0xcc, 0x79, 0xa7],
+];
+const TOL_BRIGHT: &[[u8; 3]] = &[
+    [0x44, 0x77, 0xaa],
+    [0xee, 0x66, 0x77],
+    [0x22, 0x88, 0x33],
+    [0xcc, 0xbb, 0x44],
+    [0x66, 0xcc, 0xee],
+    [0xaa, 0x33, 0x77],
+    [0xbb, 0xbb, 0xbb],
+];
+
+fn rgba([r, g, b]: [f64; 3]) -> [f32; 4] {
+    [
+        (r / 255.0) as f32,
+        (g / 255.0) as f32,
+        (b / 255.0) as f32,
+        1.0,
+    ]
+}
+
+impl Palette {
+    fn colors(self) -> &'static [[u8; 3]] {
+        match self {
+            Palette::Viridis => VIRIDIS,
+            Palette::Plasma => PLASMA,
+            Palette::OkabeIto => OKABE_ITO,
+            Palette::TolBright => TOL_BRIGHT,
+        }
+    }
+
+    fn is_qualitative(self) -> bool {
+        matches!(self, Palette::OkabeIto | Palette::TolBright)
+    }
+
+    pub fn sample(self, t: f64) -> [f32; 4] {
+        //! The color for a value scaled to between 0 and 1. Sequential palettes
+        //! blend between their colors, qualitative ones are split into bands.
+        let colors = self.colors();
+        let t = if t.is_finite() {
+            t.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let last = colors.len() - 1;
+        if self.is_qualitative() {
+            let band = ((t * colors.len() as f64) as usize).min(last);
+            return rgba(colors[band].map(f64::from));
+        }
+        let position = t * last as f64;
+        let below = (position.floor() as usize).min(last - 1);
+        let blend = position - below as f64;
+        let [low, high] = [colors[below], colors[below + 1]].map(|color| color.map(f64::from));
+        rgba([0, 1, 2].map(|channel| low[channel] + (high[channel] - low[channel]) * blend))
+    }
+
+    pub fn category(self, index: u32) -> [f32; 4] {
+        //! A color for one of several kinds of particle. Qualitative palettes
+        //! go through their colors in order, sequential ones spread eight
+        //! kinds from one end to the other.
+        let colors = self.colors();
+        if self.is_qualitative() {
+            rgba(colors[index as usize % colors.len()].map(f64::from))
+        } else {
+            self.sample((index % 8) as f64 / 7.0)
+        }
+    }
+}
+
+fn next<T: ValueEnum + PartialEq>(current: &T) -> T {
+    //! The variant after `current`, wrapping around to the first.
+    let variants = T::value_variants();
+    let index = variants
+        .iter()
+        .position(|variant| variant == current)
+        .unwrap_or(0);
+    variants[(index + 1) % variants.len()].clone()
+}
+
+/// Picks the color of every particle according to the chosen attribute and
+/// palette. Continuous attributes are scaled to the range they cover in the
+/// current frame, so `update` has to be called once per frame, before drawing.
+#[derive(Debug, Clone)]
+pub struct Coloring {
+    pub by: ColorBy,
+    pub palette: Palette,
+    frame: u64,
+    /// The frame each particle last collided in, by id.
+    last_collision: HashMap<u64, u64>,
+    /// The smallest and largest value of the attribute in the current frame.
+    range: (f64, f64),
+}
+
+impl Coloring {
+    pub fn new(by: ColorBy, palette: Palette) -> Self {
+        Coloring {
+            by,
+            palette,
+            frame: 0,
+            last_collision: HashMap::new(),
+            range: (0.0, 1.0),
+        }
+    }
+
+    pub fn cycle_attribute(&mut self) {
+        self.by = next(&self.by);
+    }
+
+    pub fn cycle_palette(&mut self) {
+        self.palette = next(&self.palette);
+    }
+
+    fn value(&self, particle: &Particle, charge: Option<&Charge>) -> Option<f64> {
+        //! The continuous attribute being shown, if it is one.
+        match self.by {
+            ColorBy::Speed => Some(particle.velocity.length()),
+            ColorBy::Mass => Some(particle.mass).filter(|mass| mass.is_finite()),
+            ColorBy::Charge => Some(charge.map_or(0.0, |charge| charge.0)),
+            ColorBy::Random | ColorBy::Species | ColorBy::CollisionRecency => None,
+        }
+    }
+
+    pub fn forget(&mut self, id: u64) {
+        //! Drop what is remembered about a particle that has been removed.
+        self.last_collision.remove(&id);
+    }
+
+    pub fn update(&mut self, frame: u64, events: &[Event], world: &World) {
+        //! Note the collisions of the latest step, and find the range of the
+        //! attribute over every particle.
+        self.frame = frame;
+        for event in events {
+            match event {
+                Event::Contact { a, b, .. } => {
+                    self.last_collision.insert(*a, frame);
+                    self.last_collision.insert(*b, frame);
+                }
+            }
+        }
+        let (min, max) = world
+            .query::<(&Particle, Option<&Charge>)>()
+            .iter()
+            .filter_map(|(_, (particle, charge))| self.value(particle, charge))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+                (min.min(value), max.max(value))
+            });
+        self.range = match self.by {
+            // Zero charge always sits in the middle of the palette
+            ColorBy::Charge => {
+                let largest = min.abs().max(max.abs());
+                (-largest, largest)
+            }
+            _ => (min, max),
+        };
+    }
+
+    pub fn color(
+        &self,
+        particle: &Particle,
+        appearance: &Appearance,
+        charge: Option<&Charge>,
+        species: Option<&Species>,
+    ) -> [f32; 4] {
+        match self.by {
+            ColorBy::Random => appearance.color,
+            ColorBy::Species => self
+                .palette
+                .category(species.map_or(0, |species| species.0)),
+            ColorBy::CollisionRecency => {
+                let age = self
+                    .last_collision
+                    .get(&particle.id)
+                    .map_or(f64::INFINITY, |frame| (self.frame - frame) as f64);
+                self.palette.sample(1.0 - age / RECENCY_FRAMES)
+            }
+            ColorBy::Speed | ColorBy::Mass | ColorBy::Charge => {
+                let (min, max) = self.range;
+                let t = match self.value(particle, charge) {
+                    Some(value) if max > min => (value - min) / (max - min),
+                    Some(_) => 0.5,
+                    None => 1.0,
+                };
+                self.palette.sample(t)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use particle_core::utils::Vec2;
+
+    #[test]
+    fn test_palettes_span_their_colors() {
+        assert_eq!(Palette::Viridis.sample(0.0), rgba([68.0, 1.0, 84.0]));
+        assert_eq!(Palette::Viridis.sample(1.0), rgba([253.0, 231.0, 37.0]));
+        assert_eq!(
+            Palette::Plasma.sample(f64::NAN),
+            Palette::Plasma.sample(0.0)
+        );
+        // Halfway between the first two of nine colors
+        let between = Palette::Viridis.sample(1.0 / 16.0);
+        assert_eq!(between, rgba([69.5, 23.0, 103.5]));
+        assert_eq!(
+            Palette::OkabeIto.sample(0.99),
+            Palette::OkabeIto.category(6)
+        );
+        assert_eq!(
+            Palette::TolBright.category(7),
+            Palette::TolBright.category(0)
+        );
+    }
+
+    #[test]
+    fn test_speed_is_scaled_to_the_current_range() {
+        let mut world = World::new();
+        let appearance = Appearance {
+            color: [0.5, 0.5, 0.5, 1.0],
+        };
+        let slow = Particle::new(1, 5.0, Vec2::ZERO, Vec2(1.0, 0.0));
+        let fast = Particle::new(2, 5.0, Vec2::ZERO, Vec2(0.0, 3.0));
+        world.spawn((slow, appearance));
+        world.spawn((fast, appearance));
+
+        let mut coloring = Coloring::new(ColorBy::Speed, Palette::Plasma);
+        coloring.update(1, &[], &world);
+        assert_eq!(
+            coloring.color(&slow, &appearance, None, None),
+            Palette::Plasma.sample(0.0)
+        );
+        assert_eq!(
+            coloring.color(&fast, &appearance, None, None),
+            Palette::Plasma.sample(1.0)
+        );
+
+        coloring.cycle_attribute();
+        assert_eq!(coloring.by, ColorBy::Mass);
+        coloring.by = ColorBy::Random;
+        assert_eq!(
+            coloring.color(&slow, &appearance, None, None),
+            appearance.color
+        );
+        coloring.cycle_palette();
+        assert_eq!(coloring.palette, Palette::OkabeIto);
+    }
+}
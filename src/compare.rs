@@ -0,0 +1,86 @@
+//! `--compare`: run two copies of the simulation from the same seed side by side,
+//! the right one with some settings changed, to see what difference they make.
+use crate::sweep::Parameter;
+use particle_core::error::{SimError, SimResult};
+use particle_core::settings::{PhysicsSettings, Side};
+use particle_core::utils::Vec2;
+use std::fmt;
+use std::str::FromStr;
+
+/// A setting that differs between the two sides, written `<parameter>=<value>`
+/// with any parameter the `sweep` subcommand takes except `particles`, which
+/// would make the two sides start differently.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Override {
+    pub parameter: Parameter,
+    pub value: f64,
+}
+
+impl Override {
+    pub fn apply(&self, settings: &mut PhysicsSettings) {
+        match self.parameter {
+            Parameter::Restitution | Parameter::Friction => {
+                for side in [Side::Left, Side::Right, Side::Bottom, Side::Top] {
+                    let wall = settings.walls.side_mut(side);
+                    if self.parameter == Parameter::Restitution {
+                        wall.restitution = self.value;
+                    } else {
+                        wall.friction = self.value;
+                    }
+                }
+            }
+            Parameter::Gravity => settings.gravity = Vec2(0.0, -self.value),
+            Parameter::MaxSpeed => settings.max_speed = Some(self.value),
+            // Refused when parsing
+            Parameter::Particles => {}
+        }
+    }
+}
+
+impl fmt::Display for Override {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.parameter.name(), self.value)
+    }
+}
+
+impl FromStr for Override {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        let invalid = || SimError::InvalidArgument {
+            name: "compared setting".to_string(),
+            value: value.to_string(),
+        };
+        let (parameter, setting) = value.split_once('=').ok_or_else(invalid)?;
+        let parameter: Parameter = parameter.trim().parse()?;
+        match setting.trim().parse::<f64>() {
+            Ok(setting) if setting.is_finite() && parameter != Parameter::Particles => {
+                Ok(Override {
+                    parameter,
+                    value: setting,
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_change_only_their_setting() {
+        let restitution: Override = "restitution=0.8".parse().unwrap();
+        assert_eq!(restitution.to_string(), "restitution=0.8");
+        let mut settings = PhysicsSettings::default();
+        restitution.apply(&mut settings);
+        assert_eq!(settings.walls.top.restitution, 0.8);
+        assert_eq!(settings.walls.left.friction, 0.0);
+        assert_eq!(settings.gravity, Vec2::ZERO);
+
+        assert!("particles=10".parse::<Override>().is_err());
+        assert!("restitution".parse::<Override>().is_err());
+        assert!("bounciness=1".parse::<Override>().is_err());
+    }
+}
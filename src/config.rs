@@ -0,0 +1,239 @@
+//! `--config`: a JSON file of settings that take the place of the command line
+//! flags of the same name, such as
+//!
+//! ```json
+//! { "gravity": 0.1, "wall_restitution": 0.9, "palette": "plasma", "particles": 300 }
+//! ```
+//!
+//! The window watches the file. Saving it applies the physics settings, the time
+//! scale and the colors straight away, while the settings that need a fresh start,
+//! the particles and how they are spawned, wait for R to reload the scenario.
+use crate::camera::Keyframe;
+use crate::cli::Args;
+use crate::coloring::{ColorBy, Palette};
+use crate::theme::Theme;
+use crate::timeline::TimedEvent;
+use crate::utils::{parse_gravity, parse_max_speed, parse_time_scale};
+use clap::ValueEnum;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use particle_core::error::{SimError, SimResult};
+use particle_core::patterns::SpawnPattern;
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+/// The settings of a config file. Anything left out keeps the value given on the
+/// command line.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Applied as soon as the file is saved
+    pub gravity: Option<f64>,
+    pub wall_restitution: Option<f64>,
+    pub wall_friction: Option<f64>,
+    pub floor_restitution: Option<f64>,
+    pub floor_friction: Option<f64>,
+    pub max_speed: Option<f64>,
+    pub time_scale: Option<f64>,
+    pub color_by: Option<String>,
+    pub palette: Option<String>,
+    pub theme: Option<String>,
+    // Applied when the scenario is reloaded
+    pub particles: Option<usize>,
+    /// Anything `--pattern` accepts, such as `grid` or `two-clusters:15`.
+    pub pattern: Option<String>,
+    pub seed: Option<u64>,
+    pub min_radius: Option<f64>,
+    pub max_radius: Option<f64>,
+    pub map: Option<PathBuf>,
+    /// Actions to take at given times, see `timeline`.
+    pub events: Option<Vec<TimedEvent>>,
+    /// Keyframes for the camera to move through on its own, see `CameraPath`.
+    pub camera: Option<Vec<Keyframe>>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> SimResult<Config> {
+        Ok(serde_json::from_slice(&fs::read(path)?).map_err(io::Error::from)?)
+    }
+
+    pub fn apply(&self, args: &mut Args) -> SimResult<()> {
+        //! Override the flags with every setting in the file, checking them the way
+        //! the flags are checked.
+        fn value_enum<T: ValueEnum>(value: &str, name: &str) -> SimResult<T> {
+            T::from_str(value, true).map_err(|_| SimError::InvalidArgument {
+                name: name.to_string(),
+                value: value.to_string(),
+            })
+        }
+        if let Some(gravity) = self.gravity {
+            args.gravity = Some(parse_gravity(&gravity.to_string())?);
+        }
+        if let Some(restitution) = self.wall_restitution {
+            args.wall_restitution = restitution;
+        }
+        if let Some(friction) = self.wall_friction {
+            args.wall_friction = friction;
+        }
+        if let Some(restitution) = self.floor_restitution {
+            args.floor_restitution = Some(restitution);
+        }
+        if let Some(friction) = self.floor_friction {
+            args.floor_friction = Some(friction);
+        }
+        if let Some(max_speed) = self.max_speed {
+            args.max_speed = Some(parse_max_speed(&max_speed.to_string())?);
+        }
+        if let Some(time_scale) = self.time_scale {
+            args.time_scale = parse_time_scale(&time_scale.to_string())?;
+        }
+        if let Some(color_by) = &self.color_by {
+            args.color_by = value_enum::<ColorBy>(color_by, "color attribute")?;
+        }
+        if let Some(palette) = &self.palette {
+            args.palette = value_enum::<Palette>(palette, "palette")?;
+        }
+        if let Some(theme) = &self.theme {
+            args.theme = value_enum::<Theme>(theme, "theme")?;
+        }
+        if let Some(particles) = self.particles {
+            args.particles = particles;
+        }
+        if let Some(pattern) = &self.pattern {
+            args.pattern = pattern.parse::<SpawnPattern>()?;
+        }
+        if let Some(seed) = self.seed {
+            args.seed = Some(seed);
+        }
+        if let Some(radius) = self.min_radius {
+            args.min_radius = radius;
+        }
+        if let Some(radius) = self.max_radius {
+            args.max_radius = radius;
+        }
+        if let Some(map) = &self.map {
+            args.map = Some(map.clone());
+        }
+        if let Some(events) = &self.events {
+            if let Some(event) = events
+                .iter()
+                .find(|event| !(event.at.is_finite() && event.at >= 0.0))
+            {
+                return Err(SimError::InvalidArgument {
+                    name: "event time".to_string(),
+                    value: event.at.to_string(),
+                });
+            }
+            args.events = events.clone();
+        }
+        if let Some(keyframes) = &self.camera {
+            if let Some(key) = keyframes.iter().find(|key| {
+                !(key.at.is_finite() && key.at >= 0.0 && key.zoom.is_finite() && key.zoom > 0.0)
+            }) {
+                return Err(SimError::InvalidArgument {
+                    name: "camera keyframe".to_string(),
+                    value: format!("{:?}", key),
+                });
+            }
+            args.camera_path = keyframes.clone();
+        }
+        Ok(())
+    }
+}
+
+/// Watches a config file for changes, to apply it again over the command line it
+/// was first applied to.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    command_line: Args,
+    changes: Receiver<notify::Result<notify::Event>>,
+    // Notifications stop once this is dropped
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path, command_line: Args) -> SimResult<Self> {
+        //! Start watching. Many editors save by writing a new file in place of the
+        //! old one, which a watch on the file itself would not survive, so the
+        //! directory is watched for changes to a file of that name instead.
+        let (sender, changes) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .map_err(io::Error::other)?;
+        Ok(ConfigWatcher {
+            path: path.to_path_buf(),
+            command_line,
+            changes,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn poll(&self) -> Option<SimResult<Args>> {
+        //! The command line with the file applied again, if the file has changed
+        //! since the last poll.
+        let mut changed = false;
+        for event in self.changes.try_iter() {
+            match event {
+                Ok(event) => {
+                    changed |= (event.kind.is_create() || event.kind.is_modify())
+                        && event
+                            .paths
+                            .iter()
+                            .any(|path| path.file_name() == self.path.file_name());
+                }
+                Err(err) => tracing::warn!(%err, "could not watch the config file"),
+            }
+        }
+        changed.then(|| self.load())
+    }
+
+    pub fn load(&self) -> SimResult<Args> {
+        let mut args = self.command_line.clone();
+        Config::load(&self.path)?.apply(&mut args)?;
+        Ok(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_config_overrides_only_what_it_sets() {
+        let mut args =
+            Args::try_parse_from(["particle-simulator", "50", "--gravity", "0.2"]).unwrap();
+        let config: Config = serde_json::from_str(
+            r#"{"wall_restitution": 0.8, "palette": "okabe-ito", "pattern": "grid"}"#,
+        )
+        .unwrap();
+        config.apply(&mut args).unwrap();
+        assert_eq!(args.particles, 50);
+        assert_eq!(args.gravity, Some(0.2));
+        assert_eq!(args.wall_restitution, 0.8);
+        assert_eq!(args.palette, Palette::OkabeIto);
+        assert_eq!(args.pattern, SpawnPattern::Grid);
+
+        assert!(serde_json::from_str::<Config>(r#"{"gravty": 1}"#).is_err());
+        let config: Config = serde_json::from_str(r#"{"theme": "sepia"}"#).unwrap();
+        assert!(config.apply(&mut args).is_err());
+        let config: Config = serde_json::from_str(r#"{"max_speed": -1}"#).unwrap();
+        assert!(config.apply(&mut args).is_err());
+        let config: Config = serde_json::from_str(
+            r#"{"events": [{"at": -1, "action": "explode", "x": 0, "y": 0, "strength": 1}]}"#,
+        )
+        .unwrap();
+        assert!(config.apply(&mut args).is_err());
+    }
+}
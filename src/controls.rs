@@ -0,0 +1,162 @@
+use crate::clock::{Clock, STEPS_PER_SECOND};
+use particle_core::settings::Side;
+use particle_core::simulation::Simulation;
+
+/// How much a nudge changes the time scale by, up or down.
+const TIME_SCALE_FACTOR: f64 = 1.25;
+
+/// A setting of the running simulation that can be nudged from the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Gravity,
+    /// Of every wall, the floor included.
+    Restitution,
+    TimeScale,
+    /// How many random particles are added per second of simulated time.
+    SpawnRate,
+}
+
+impl Control {
+    const ALL: [Control; 4] = [
+        Control::Gravity,
+        Control::Restitution,
+        Control::TimeScale,
+        Control::SpawnRate,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Control::Gravity => "gravity",
+            Control::Restitution => "restitution",
+            Control::TimeScale => "time scale",
+            Control::SpawnRate => "spawn rate",
+        }
+    }
+}
+
+/// The settings Tab, `,` and `.` change while the window runs. Gravity, restitution
+/// and the time scale live in the simulation and the clock, so that they stay in
+/// step with anything else that changes them, and only the spawn rate is kept here.
+#[derive(Debug, Clone)]
+pub struct Controls {
+    selected: Control,
+    spawn_rate: f64,
+    /// Particles owed by the spawn rate but not added yet, less than one.
+    owed: f64,
+}
+
+impl Controls {
+    pub fn new() -> Self {
+        Controls {
+            selected: Control::Gravity,
+            spawn_rate: 0.0,
+            owed: 0.0,
+        }
+    }
+
+    pub fn selected(&self) -> Control {
+        self.selected
+    }
+
+    pub fn select_next(&mut self) {
+        //! Move the selection to the next setting, wrapping around.
+        let index = Control::ALL
+            .iter()
+            .position(|control| *control == self.selected)
+            .unwrap_or(0);
+        self.selected = Control::ALL[(index + 1) % Control::ALL.len()];
+    }
+
+    pub fn nudge(&mut self, simulation: &mut Simulation, clock: &mut Clock, steps: f64) {
+        //! Change the selected setting by `steps` nudges, down if negative.
+        match self.selected {
+            Control::Gravity => simulation.settings_mut().gravity.1 -= 0.01 * steps,
+            Control::Restitution => {
+                let walls = &mut simulation.settings_mut().walls;
+                let restitution = (walls.bottom.restitution + 0.05 * steps).clamp(0.0, 1.0);
+                for side in [Side::Left, Side::Right, Side::Bottom, Side::Top] {
+                    walls.side_mut(side).restitution = restitution;
+                }
+            }
+            Control::TimeScale => {
+                clock.set_time_scale(clock.time_scale() * TIME_SCALE_FACTOR.powf(steps));
+            }
+            Control::SpawnRate => self.spawn_rate = (self.spawn_rate + 5.0 * steps).max(0.0),
+        }
+    }
+
+    pub fn spawn(&mut self, simulation: &mut Simulation, steps: u32, limit: Option<usize>) {
+        //! Add the random particles the spawn rate owes for `steps` steps, as long
+        //! as there are fewer than `limit`.
+        self.owed += self.spawn_rate * steps as f64 / STEPS_PER_SECOND;
+        let mut count = self.owed.floor() as usize;
+        self.owed -= count as f64;
+        if let Some(limit) = limit {
+            count = count.min(limit.saturating_sub(simulation.particle_count()));
+        }
+        simulation.spawn_random(count);
+    }
+
+    pub fn lines(&self, simulation: &Simulation, clock: &Clock) -> Vec<String> {
+        //! The current value of every setting, the selected one marked with an
+        //! arrow, and where to find the rest of the keys.
+        let settings = simulation.settings();
+        let mut lines: Vec<String> = Control::ALL
+            .iter()
+            .map(|&control| {
+                let value = match control {
+                    Control::Gravity => format!("{:.2}", -settings.gravity.1),
+                    Control::Restitution => format!("{:.2}", settings.walls.bottom.restitution),
+                    Control::TimeScale => format!("x{:.2}", clock.time_scale()),
+                    Control::SpawnRate => format!("{} per second", self.spawn_rate),
+                };
+                let marker = if control == self.selected { ">" } else { " " };
+                format!("{} {}: {}", marker, control.label(), value)
+            })
+            .collect();
+        lines.push("F1 or H: help".to_string());
+        lines
+    }
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Controls::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use particle_core::particle::Particle;
+    use particle_core::utils::Vec2;
+
+    #[test]
+    fn test_nudges_change_the_selected_setting() {
+        let mut simulation = Simulation::with_seed(1);
+        simulation.spawn(Particle::new(0, 10.0, Vec2(500.0, 500.0), Vec2::ZERO));
+        let mut clock = Clock::new(1.0);
+        let mut controls = Controls::new();
+        controls.nudge(&mut simulation, &mut clock, 10.0);
+        assert!((simulation.settings().gravity.1 + 0.1).abs() < 1e-12);
+
+        controls.select_next();
+        controls.nudge(&mut simulation, &mut clock, -4.0);
+        assert_eq!(simulation.settings().walls.top.restitution, 0.8);
+        controls.select_next();
+        controls.nudge(&mut simulation, &mut clock, 1.0);
+        assert_eq!(clock.time_scale(), TIME_SCALE_FACTOR);
+
+        // 10 particles a second is one every 4.5 steps, up to the limit
+        controls.select_next();
+        controls.nudge(&mut simulation, &mut clock, 2.0);
+        controls.spawn(&mut simulation, 9, None);
+        assert_eq!(simulation.particle_count(), 3);
+        controls.spawn(&mut simulation, 45, Some(5));
+        assert_eq!(simulation.particle_count(), 5);
+        assert_eq!(
+            controls.lines(&simulation, &clock)[3],
+            "> spawn rate: 10 per second"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use crate::charts::Charts;
+use crate::hud::FONT;
+use flo_canvas::*;
+use particle_core::events::Event;
+use particle_core::particle::{Particle, HEIGHT, WIDTH};
+use particle_core::simulation::Simulation;
+use std::sync::{Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+
+/// The dashboard window uses the same 1000 by 1000 canvas as the simulation.
+const SIZE: f32 = 1000.0;
+const MARGIN: f32 = 40.0;
+/// How many bars the speed histogram has.
+const SPEED_BINS: usize = 30;
+/// How many cells the collision heatmap has along each side of the box.
+const HEATMAP_CELLS: usize = 25;
+/// How much of its collisions a heatmap cell still shows a frame later, so that
+/// the map fades over a few seconds.
+const HEATMAP_COOLING: f64 = 0.97;
+/// How often the dashboard window is redrawn. It changes slowly, so there is no
+/// point in drawing it as often as the simulation.
+const DASHBOARD_FPS: f64 = 10.0;
+
+/// How many particles move at each speed, from zero to the fastest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    counts: [usize; SPEED_BINS],
+    max_speed: f64,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Histogram {
+            counts: [0; SPEED_BINS],
+            max_speed: 0.0,
+        }
+    }
+
+    pub fn measure(&mut self, speeds: &[f64]) {
+        //! Replace the counts with those of these speeds.
+        self.counts = [0; SPEED_BINS];
+        self.max_speed = speeds
+            .iter()
+            .copied()
+            .filter(|speed| speed.is_finite())
+            .fold(0.0, f64::max);
+        for &speed in speeds.iter().filter(|speed| speed.is_finite()) {
+            let bin = if self.max_speed > 0.0 {
+                (speed / self.max_speed * SPEED_BINS as f64) as usize
+            } else {
+                0
+            };
+            self.counts[bin.min(SPEED_BINS - 1)] += 1;
+        }
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram::new()
+    }
+}
+
+/// Where in the box collisions have happened lately, on a coarse grid whose
+/// counts fade away as time goes on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heatmap {
+    cells: Vec<f64>,
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Heatmap {
+            cells: vec![0.0; HEATMAP_CELLS * HEATMAP_CELLS],
+        }
+    }
+
+    fn cell(x: f64, y: f64) -> usize {
+        let index = |position: f64, size: f64| {
+            ((position / size * HEATMAP_CELLS as f64).max(0.0) as usize).min(HEATMAP_CELLS - 1)
+        };
+        index(y, HEIGHT) * HEATMAP_CELLS + index(x, WIDTH)
+    }
+
+    pub fn record(&mut self, events: &[Event]) {
+        //! Count every contact in the cell of its contact point.
+        for event in events {
+            match event {
+                Event::Contact { contact, .. } => {
+                    self.cells[Heatmap::cell(contact.point.0, contact.point.1)] += 1.0;
+                }
+            }
+        }
+    }
+
+    pub fn cool(&mut self) {
+        for cell in &mut self.cells {
+            *cell *= HEATMAP_COOLING;
+        }
+    }
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Heatmap::new()
+    }
+}
+
+/// Statistics of a running simulation: the charts of `--charts`, a histogram of
+/// the particle speeds and a heatmap of where collisions happen. The frame loop
+/// feeds it, and the dashboard window draws it in a render loop of its own.
+#[derive(Default)]
+pub struct Dashboard {
+    charts: Charts,
+    speeds: Histogram,
+    heatmap: Heatmap,
+}
+
+impl Dashboard {
+    pub fn new() -> Self {
+        Dashboard::default()
+    }
+
+    pub fn record_step(&mut self, events: &[Event]) {
+        //! Add the collisions of a step to the heatmap.
+        self.heatmap.record(events);
+    }
+
+    pub fn record_frame(&mut self, simulation: &Simulation, collisions_per_second: f64) {
+        //! Take the measurements that are only updated once per frame.
+        self.charts.record(
+            simulation.kinetic_energy(),
+            simulation.temperature(),
+            collisions_per_second,
+        );
+        let speeds: Vec<f64> = simulation
+            .world()
+            .query::<&Particle>()
+            .iter()
+            .filter(|(_, particle)| !particle.fixed)
+            .map(|(_, particle)| particle.velocity.length())
+            .collect();
+        self.speeds.measure(&speeds);
+        self.heatmap.cool();
+    }
+
+    pub fn draw(&self, gc: &mut impl GraphicsContext) {
+        //! Redraw the whole dashboard: the charts along the top, the histogram at
+        //! the bottom left and the heatmap at the bottom right. The font must
+        //! already have been loaded into the canvas with `Hud::load_font`.
+        gc.layer(LayerId(0));
+        gc.clear_layer();
+        gc.canvas_height(SIZE);
+        gc.center_region(0.0, 0.0, SIZE, SIZE);
+        self.charts.draw_between(gc, SIZE / 2.0, SIZE);
+        let half = SIZE / 2.0;
+        self.draw_histogram(gc, MARGIN, MARGIN, half - 2.0 * MARGIN);
+        self.draw_heatmap(gc, half + MARGIN, MARGIN, half - 2.0 * MARGIN);
+    }
+
+    fn draw_histogram(&self, gc: &mut impl GraphicsContext, left: f32, bottom: f32, size: f32) {
+        let tallest = self.speeds.counts.iter().copied().max().unwrap_or(0).max(1);
+        let width = size / SPEED_BINS as f32;
+        gc.new_path();
+        for (bin, &count) in self.speeds.counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let x = left + bin as f32 * width;
+            let height = count as f32 / tallest as f32 * size;
+            gc.rect(x, bottom, x + width - 1.0, bottom + height);
+        }
+        gc.fill_color(Color::Rgba(0.3, 0.8, 1.0, 1.0));
+        gc.fill();
+        gc.fill_color(Color::Rgba(0.9, 0.9, 0.9, 0.9));
+        gc.draw_text(
+            FONT,
+            format!("Speeds, 0 to {:.1}", self.speeds.max_speed),
+            left,
+            bottom + size + 8.0,
+        );
+    }
+
+    fn draw_heatmap(&self, gc: &mut impl GraphicsContext, left: f32, bottom: f32, size: f32) {
+        let hottest = self.heatmap.cells.iter().copied().fold(0.0, f64::max);
+        let cell = size / HEATMAP_CELLS as f32;
+        for (index, &heat) in self.heatmap.cells.iter().enumerate() {
+            if heat <= 0.0 {
+                continue;
+            }
+            let x = left + (index % HEATMAP_CELLS) as f32 * cell;
+            let y = bottom + (index / HEATMAP_CELLS) as f32 * cell;
+            gc.new_path();
+            gc.rect(x, y, x + cell, y + cell);
+            gc.fill_color(Color::Rgba(1.0, 0.4, 0.1, (heat / hottest) as f32));
+            gc.fill();
+        }
+        gc.new_path();
+        gc.rect(left, bottom, left + size, bottom + size);
+        gc.line_width(1.0);
+        gc.stroke_color(Color::Rgba(0.4, 0.4, 0.4, 1.0));
+        gc.stroke();
+        gc.fill_color(Color::Rgba(0.9, 0.9, 0.9, 0.9));
+        gc.draw_text(FONT, "Collisions".to_string(), left, bottom + size + 8.0);
+    }
+}
+
+pub fn spawn_render_loop(dashboard: Weak<Mutex<Dashboard>>, canvas: DrawingTarget) {
+    //! Redraw the dashboard on a thread of its own, `DASHBOARD_FPS` times a
+    //! second, until the frame loop that feeds it has dropped it.
+    thread::spawn(move || {
+        while let Some(dashboard) = dashboard.upgrade() {
+            canvas.draw(|gc| dashboard.lock().unwrap().draw(gc));
+            drop(dashboard);
+            thread::sleep(Duration::from_secs_f64(1.0 / DASHBOARD_FPS));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use particle_core::shapes::Contact;
+    use particle_core::utils::Vec2;
+
+    #[test]
+    fn test_histogram_spans_zero_to_the_fastest() {
+        let mut histogram = Histogram::new();
+        histogram.measure(&[0.0, 1.0, 2.9, 3.0, f64::NAN]);
+        assert_eq!(histogram.max_speed, 3.0);
+        assert_eq!(histogram.counts.iter().sum::<usize>(), 4);
+        assert_eq!(histogram.counts[0], 1);
+        assert_eq!(histogram.counts[SPEED_BINS - 1], 2);
+    }
+
+    #[test]
+    fn test_heatmap_counts_contacts_where_they_happen() {
+        let contact = |x, y| Event::Contact {
+            a: 0,
+            b: 1,
+            contact: Contact {
+                normal: Vec2(1.0, 0.0),
+                penetration_depth: 0.0,
+                point: Vec2(x, y),
+            },
+            impulse: 1.0,
+        };
+        let mut heatmap = Heatmap::new();
+        heatmap.record(&[contact(1.0, 1.0), contact(999.0, 1.0), contact(1200.0, 1.0)]);
+        assert_eq!(heatmap.cells[0], 1.0);
+        assert_eq!(heatmap.cells[HEATMAP_CELLS - 1], 2.0);
+        heatmap.cool();
+        assert_eq!(heatmap.cells[0], HEATMAP_COOLING);
+    }
+}
@@ -0,0 +1,114 @@
+//! State dumps for bug reports: pressing F5 in the window writes everything
+//! behind the current frame to a JSON file in the working directory, named
+//! `state-dump-<unix time>-frame-<frame>.json`. It holds
+//!
+//! - the command line the simulator was started with,
+//! - the collision events of the last few steps, newest last,
+//! - a full checkpoint: settings, obstacles, portals, the RNG and every entity.
+use crate::utils;
+use particle_core::checkpoint::Checkpoint;
+use particle_core::error::SimResult;
+use particle_core::events::Event;
+use particle_core::simulation::Simulation;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How many steps of events are kept for the next dump, about a second.
+const RECENT_FRAMES: usize = 45;
+
+/// The events of one step.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FrameEvents {
+    pub frame: u64,
+    pub events: Vec<Event>,
+}
+
+/// The events of the last few steps, kept around in case a dump is asked for.
+#[derive(Debug, Clone, Default)]
+pub struct EventHistory {
+    frames: VecDeque<FrameEvents>,
+}
+
+impl EventHistory {
+    pub fn new() -> Self {
+        EventHistory::default()
+    }
+
+    pub fn record(&mut self, simulation: &Simulation) {
+        //! Remember the events of the step that was just taken.
+        if self.frames.len() == RECENT_FRAMES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(FrameEvents {
+            frame: simulation.frame(),
+            events: simulation.events().to_vec(),
+        });
+    }
+}
+
+#[derive(Serialize)]
+struct StateDump<'a> {
+    frame: u64,
+    command_line: &'a [String],
+    recent_events: &'a VecDeque<FrameEvents>,
+    checkpoint: Checkpoint,
+}
+
+pub fn write_dump(
+    dir: &Path,
+    simulation: &Simulation,
+    command_line: &[String],
+    history: &EventHistory,
+) -> SimResult<PathBuf> {
+    //! Write the dump into `dir`, returning the path of the new file.
+    let dump = StateDump {
+        frame: simulation.frame(),
+        command_line,
+        recent_events: &history.frames,
+        checkpoint: simulation.checkpoint(),
+    };
+    let path = dir.join(format!(
+        "state-dump-{}-frame-{}.json",
+        utils::unix_time(),
+        simulation.frame()
+    ));
+    let json = serde_json::to_vec_pretty(&dump).map_err(io::Error::from)?;
+    fs::write(&path, json)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use particle_core::particle::Particle;
+    use particle_core::utils::Vec2;
+    use serde_json::Value;
+
+    #[test]
+    fn test_dump_holds_the_state_and_recent_events() {
+        let mut simulation = Simulation::with_seed(1);
+        simulation.spawn(Particle::new(1, 10.0, Vec2(100.0, 500.0), Vec2(5.0, 0.0)));
+        simulation.spawn(Particle::new(2, 10.0, Vec2(125.0, 500.0), Vec2(-5.0, 0.0)));
+        let mut history = EventHistory::new();
+        for _ in 0..RECENT_FRAMES + 5 {
+            simulation.step(1.0).unwrap();
+            history.record(&simulation);
+        }
+        assert_eq!(history.frames.len(), RECENT_FRAMES);
+
+        let dir = std::env::temp_dir().join(format!("state-dumps-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let command_line = vec!["particle-simulator".to_string(), "2".to_string()];
+        let path = write_dump(&dir, &simulation, &command_line, &history).unwrap();
+        let dump: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(dump["frame"], RECENT_FRAMES as u64 + 5);
+        assert_eq!(dump["command_line"][1], "2");
+        assert_eq!(dump["recent_events"][0]["frame"], 6);
+        assert_eq!(dump["checkpoint"]["entities"].as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,203 @@
+//! The `ensemble` subcommand: run many replicas of the same scenario that differ
+//! only in their seed, and report the mean and variance of what they measure.
+use crate::experiments::RunSpec;
+use particle_core::error::SimResult;
+use particle_core::particle::{Particle, WIDTH};
+use particle_core::simulation::Simulation;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// A replica counts as mixed once the particles that started in the left half are
+/// spread over the box this close to evenly, as a share of how unevenly they
+/// started out.
+const MIXING_TOLERANCE: f64 = 0.1;
+
+/// What a single replica measured.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ReplicaStats {
+    pub seed: u64,
+    /// The temperature averaged over every step.
+    pub temperature: f64,
+    /// The pressure on the walls averaged over every step.
+    pub pressure: f64,
+    /// The first frame the particles were mixed in, if they ever were.
+    pub mixing_time: Option<u64>,
+}
+
+/// The mean and sample variance of a quantity over the replicas that measured it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Summary {
+    pub mean: f64,
+    pub variance: f64,
+    pub count: usize,
+}
+
+impl Summary {
+    pub fn of(values: &[f64]) -> Self {
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / count.max(1) as f64;
+        let variance = if count > 1 {
+            values
+                .iter()
+                .map(|value| (value - mean).powi(2))
+                .sum::<f64>()
+                / (count - 1) as f64
+        } else {
+            0.0
+        };
+        Summary {
+            mean,
+            variance,
+            count,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnsembleReport {
+    pub temperature: Summary,
+    pub pressure: Summary,
+    /// Only over the replicas that mixed within their steps.
+    pub mixing_time: Summary,
+    pub replicas: Vec<ReplicaStats>,
+}
+
+impl EnsembleReport {
+    pub fn lines(&self) -> Vec<String> {
+        //! A plain text table of the ensemble statistics.
+        let row = |name: &str, summary: &Summary| {
+            format!(
+                "{:<12} mean {:>12.5}  variance {:>12.5e}  ({} replicas)",
+                name, summary.mean, summary.variance, summary.count
+            )
+        };
+        vec![
+            row("temperature", &self.temperature),
+            row("pressure", &self.pressure),
+            row("mixing time", &self.mixing_time),
+        ]
+    }
+}
+
+fn segregation(simulation: &Simulation, left_starters: &HashSet<u64>) -> f64 {
+    //! How much more of the particles that started in the left half are still
+    //! there than of the particles overall. 0 when they are perfectly mixed.
+    let (mut in_left, mut starters_in_left, mut count) = (0, 0, 0);
+    for (_, particle) in simulation.world().query::<&Particle>().iter() {
+        count += 1;
+        if particle.position.0 < WIDTH / 2.0 {
+            in_left += 1;
+            starters_in_left += left_starters.contains(&particle.id) as usize;
+        }
+    }
+    if count == 0 || left_starters.is_empty() {
+        return 0.0;
+    }
+    (starters_in_left as f64 / left_starters.len() as f64 - in_left as f64 / count as f64).abs()
+}
+
+pub fn run_replica(spec: &RunSpec) -> SimResult<ReplicaStats> {
+    //! Run one replica to the end, measuring as it goes.
+    let mut simulation = spec.simulation()?;
+    let left_starters: HashSet<u64> = simulation
+        .world()
+        .query::<&Particle>()
+        .iter()
+        .filter(|(_, particle)| particle.position.0 < WIDTH / 2.0)
+        .map(|(_, particle)| particle.id)
+        .collect();
+    let initial_segregation = segregation(&simulation, &left_starters);
+
+    let (mut temperature, mut pressure, mut mixing_time) = (0.0, 0.0, None);
+    while simulation.frame() < spec.steps {
+        simulation.step(1.0)?;
+        temperature += simulation.temperature();
+        pressure += simulation.pressure();
+        if mixing_time.is_none()
+            && segregation(&simulation, &left_starters) <= MIXING_TOLERANCE * initial_segregation
+        {
+            mixing_time = Some(simulation.frame());
+        }
+    }
+    let steps = spec.steps.max(1) as f64;
+    Ok(ReplicaStats {
+        seed: spec.seed,
+        temperature: temperature / steps,
+        pressure: pressure / steps,
+        mixing_time,
+    })
+}
+
+pub fn run(base: &RunSpec, replicas: usize, threads: usize) -> SimResult<EnsembleReport> {
+    //! Run `replicas` copies of `base`, seeded one after the other from its
+    //! seed, spread over `threads` threads.
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..replicas).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, replicas.max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= replicas {
+                    break;
+                }
+                let spec = RunSpec {
+                    seed: base.seed + index as u64,
+                    ..base.clone()
+                };
+                let stats = run_replica(&spec);
+                results.lock().unwrap()[index] = Some(stats);
+            });
+        }
+    });
+
+    let replicas = results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect::<SimResult<Vec<_>>>()?;
+    let summary = |measure: fn(&ReplicaStats) -> Option<f64>| {
+        Summary::of(&replicas.iter().filter_map(measure).collect::<Vec<_>>())
+    };
+    Ok(EnsembleReport {
+        temperature: summary(|stats| Some(stats.temperature)),
+        pressure: summary(|stats| Some(stats.pressure)),
+        mixing_time: summary(|stats| stats.mixing_time.map(|frame| frame as f64)),
+        replicas,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_uses_the_sample_variance() {
+        let summary = Summary::of(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(summary.mean, 2.5);
+        assert!((summary.variance - 5.0 / 3.0).abs() < 1e-12);
+        assert_eq!(Summary::of(&[7.0]).variance, 0.0);
+        assert_eq!(Summary::of(&[]).count, 0);
+    }
+
+    #[test]
+    fn test_replicas_do_not_depend_on_the_threads() {
+        let base = RunSpec {
+            seed: 5,
+            particles: 30,
+            steps: 300,
+            ..RunSpec::default()
+        };
+        let single = run(&base, 4, 1).unwrap();
+        let parallel = run(&base, 4, 3).unwrap();
+        assert_eq!(single, parallel);
+        assert_eq!(single.replicas[2].seed, 7);
+        assert!(single.temperature.mean > 0.0);
+        assert!(single.pressure.mean > 0.0);
+        // Different seeds give different gases
+        assert!(single.temperature.variance > 0.0);
+    }
+}
@@ -0,0 +1,342 @@
+//! The `experiments` subcommand: run a batch of headless simulations listed in a
+//! JSON manifest, and write the statistics of each into a directory of its own.
+//!
+//! ```json
+//! {
+//!   "runs": [
+//!     { "name": "cold", "seed": 1, "particles": 200, "steps": 5000 },
+//!     { "name": "falling", "seed": 2, "particles": 200, "steps": 5000,
+//!       "gravity": [0, -0.1], "wall_restitution": 0.8, "pattern": "grid" }
+//!   ]
+//! }
+//! ```
+//!
+//! Every run writes `stats.json`, a summary of the whole run, and `series.csv`,
+//! the energy, temperature and collisions sampled along the way. Runs with
+//! `probes` also write `probes.csv`, what was measured inside each of them. With more than
+//! one job the runs are shared out between child processes of the simulator,
+//! which each run a single entry of the manifest.
+use particle_core::error::{SimError, SimResult};
+use particle_core::export::{Probe, ProbeExporter};
+use particle_core::patterns::SpawnPattern;
+use particle_core::settings::{Wall, Walls};
+use particle_core::simulation::Simulation;
+use particle_core::utils::Vec2;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::Instant;
+
+/// How often `series.csv` gets a row, in steps.
+const SAMPLE_EVERY: u64 = 100;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest {
+    pub runs: Vec<RunSpec>,
+}
+
+/// One simulation of the manifest. Anything left out keeps the default of the
+/// command line flag of the same name.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunSpec {
+    /// The name of the output directory, `run-<index>` if not given.
+    pub name: Option<String>,
+    pub seed: u64,
+    pub particles: usize,
+    pub steps: u64,
+    pub gravity: [f64; 2],
+    pub wall_restitution: f64,
+    pub wall_friction: f64,
+    pub max_speed: Option<f64>,
+    /// Anything `--pattern` accepts, such as `grid` or `two-clusters:15`.
+    pub pattern: String,
+    /// Regions measured every hundred steps into `probes.csv`, as for `--probes`.
+    pub probes: Vec<Probe>,
+}
+
+impl Default for RunSpec {
+    fn default() -> Self {
+        RunSpec {
+            name: None,
+            seed: 0,
+            particles: 20,
+            steps: 1000,
+            gravity: [0.0, 0.0],
+            wall_restitution: 1.0,
+            wall_friction: 0.0,
+            max_speed: None,
+            pattern: "random".to_string(),
+            probes: Vec::new(),
+        }
+    }
+}
+
+impl RunSpec {
+    pub fn simulation(&self) -> SimResult<Simulation> {
+        //! A new simulation set up as the run describes.
+        let mut simulation = Simulation::with_seed(self.seed);
+        let settings = simulation.settings_mut();
+        settings.gravity = Vec2(self.gravity[0], self.gravity[1]);
+        settings.walls = Walls::uniform(Wall {
+            restitution: self.wall_restitution,
+            friction: self.wall_friction,
+            ..Wall::default()
+        });
+        settings.max_speed = self.max_speed;
+        simulation.spawn_pattern(self.pattern.parse::<SpawnPattern>()?, self.particles)?;
+        Ok(simulation)
+    }
+}
+
+/// The summary of a finished run, written to `stats.json`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunStats {
+    pub name: String,
+    pub seed: u64,
+    pub steps: u64,
+    pub particles: usize,
+    pub seconds: f64,
+    pub initial_energy: f64,
+    pub final_energy: f64,
+    /// The change in kinetic energy over the run, relative to the start.
+    pub energy_drift: f64,
+    pub final_temperature: f64,
+    pub collisions: u64,
+    /// `Simulation::state_hash` at the end, to check that reruns agree.
+    pub state_hash: u64,
+}
+
+pub fn load_manifest(path: &Path) -> SimResult<Manifest> {
+    let manifest: Manifest = serde_json::from_slice(&fs::read(path)?).map_err(io::Error::from)?;
+    if manifest.runs.is_empty() {
+        return Err(SimError::InvalidArgument {
+            name: "experiment manifest".to_string(),
+            value: path.display().to_string(),
+        });
+    }
+    Ok(manifest)
+}
+
+fn run_name(spec: &RunSpec, index: usize) -> String {
+    spec.name
+        .clone()
+        .unwrap_or_else(|| format!("run-{}", index))
+}
+
+pub fn run(spec: &RunSpec, name: &str, series: impl Write) -> SimResult<RunStats> {
+    //! Run the simulation to the end, writing the sampled series to `series`
+    //! as CSV.
+    run_simulation(spec.simulation()?, spec, name, series)
+}
+
+fn run_simulation(
+    mut simulation: Simulation,
+    spec: &RunSpec,
+    name: &str,
+    mut series: impl Write,
+) -> SimResult<RunStats> {
+    writeln!(series, "frame,kinetic_energy,temperature,collisions")?;
+
+    let start = Instant::now();
+    let initial_energy = simulation.kinetic_energy();
+    let (mut collisions, mut sampled) = (0, 0);
+    writeln!(
+        series,
+        "0,{},{},0",
+        initial_energy,
+        simulation.temperature()
+    )?;
+    while simulation.frame() < spec.steps {
+        simulation.step(1.0)?;
+        collisions += simulation.events().len() as u64;
+        if simulation.frame().is_multiple_of(SAMPLE_EVERY) || simulation.frame() == spec.steps {
+            writeln!(
+                series,
+                "{},{},{},{}",
+                simulation.frame(),
+                simulation.kinetic_energy(),
+                simulation.temperature(),
+                collisions - sampled
+            )?;
+            sampled = collisions;
+        }
+    }
+    series.flush()?;
+    simulation.finish_exports()?;
+
+    let final_energy = simulation.kinetic_energy();
+    Ok(RunStats {
+        name: name.to_string(),
+        seed: spec.seed,
+        steps: spec.steps,
+        particles: simulation.particle_count(),
+        seconds: start.elapsed().as_secs_f64(),
+        initial_energy,
+        final_energy,
+        energy_drift: if initial_energy > 0.0 {
+            (final_energy - initial_energy) / initial_energy
+        } else {
+            0.0
+        },
+        final_temperature: simulation.temperature(),
+        collisions,
+        state_hash: simulation.state_hash(),
+    })
+}
+
+pub fn run_one(spec: &RunSpec, name: &str, dir: &Path) -> SimResult<RunStats> {
+    //! Run the simulation to the end, writing its statistics into `dir`.
+    fs::create_dir_all(dir)?;
+    let series = io::BufWriter::new(fs::File::create(dir.join("series.csv"))?);
+    let mut simulation = spec.simulation()?;
+    if !spec.probes.is_empty() {
+        let path = dir.join("probes.csv");
+        let probes = ProbeExporter::create(&path, SAMPLE_EVERY, spec.probes.clone())?;
+        simulation.add_exporter(Box::new(probes))?;
+    }
+    let stats = run_simulation(simulation, spec, name, series)?;
+    let json = serde_json::to_vec_pretty(&stats).map_err(io::Error::from)?;
+    fs::write(dir.join("stats.json"), json)?;
+    Ok(stats)
+}
+
+pub fn run_entry(manifest: &Manifest, index: usize, out: &Path) -> SimResult<RunStats> {
+    //! Run a single entry of the manifest, by its position.
+    let spec = manifest.runs.get(index).ok_or(SimError::InvalidArgument {
+        name: "experiment run".to_string(),
+        value: index.to_string(),
+    })?;
+    let name = run_name(spec, index);
+    let stats = run_one(spec, &name, &out.join(&name))?;
+    println!(
+        "{}: {} steps in {:.1} s, energy drift {:.3e}",
+        name, stats.steps, stats.seconds, stats.energy_drift
+    );
+    Ok(stats)
+}
+
+fn spawn_child(manifest_path: &Path, out: &Path, index: usize) -> SimResult<Child> {
+    let child = Command::new(env::current_exe()?)
+        .arg("experiments")
+        .arg(manifest_path)
+        .arg("--out")
+        .arg(out)
+        .arg("--only")
+        .arg(index.to_string())
+        .spawn()?;
+    Ok(child)
+}
+
+pub fn run_all(manifest_path: &Path, out: &Path, jobs: usize) -> SimResult<()> {
+    //! Run every entry of the manifest, one after the other in this process, or
+    //! spread over up to `jobs` child processes at a time.
+    let manifest = load_manifest(manifest_path)?;
+    fs::create_dir_all(out)?;
+    if jobs <= 1 {
+        for index in 0..manifest.runs.len() {
+            run_entry(&manifest, index, out)?;
+        }
+        return Ok(());
+    }
+
+    let mut pending = 0..manifest.runs.len();
+    let mut running: Vec<(usize, Child)> = Vec::new();
+    let mut failed: Vec<String> = Vec::new();
+    loop {
+        while running.len() < jobs {
+            match pending.next() {
+                Some(index) => running.push((index, spawn_child(manifest_path, out, index)?)),
+                None => break,
+            }
+        }
+        let Some((index, mut child)) = (!running.is_empty()).then(|| running.remove(0)) else {
+            break;
+        };
+        if !child.wait()?.success() {
+            failed.push(run_name(&manifest.runs[index], index));
+        }
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(SimError::Io(io::Error::other(format!(
+            "experiment runs failed: {}",
+            failed.join(", ")
+        ))))
+    }
+}
+
+pub fn output_dir(out: &Path, manifest: &Manifest, index: usize) -> PathBuf {
+    //! Where the results of an entry of the manifest are written.
+    out.join(run_name(&manifest.runs[index], index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_fills_in_defaults() {
+        let manifest: Manifest = serde_json::from_str(
+            r#"{"runs": [{"seed": 4, "steps": 50, "gravity": [0, -0.1]}, {"name": "grid", "pattern": "grid"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.runs[0].particles, 20);
+        assert_eq!(manifest.runs[0].gravity, [0.0, -0.1]);
+        assert_eq!(run_name(&manifest.runs[0], 0), "run-0");
+        assert_eq!(
+            output_dir(Path::new("out"), &manifest, 1),
+            Path::new("out/grid")
+        );
+        assert!(serde_json::from_str::<Manifest>(r#"{"runs": [{"sead": 4}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_run_writes_stats_and_series() {
+        let dir = env::temp_dir().join(format!("experiments-{}", std::process::id()));
+        let spec = RunSpec {
+            seed: 9,
+            particles: 10,
+            steps: 250,
+            probes: vec![Probe {
+                name: "everywhere".to_string(),
+                min_x: 0.0,
+                min_y: 0.0,
+                max_x: 1000.0,
+                max_y: 1000.0,
+            }],
+            ..RunSpec::default()
+        };
+        let stats = run_one(&spec, "test", &dir).unwrap();
+        let series = fs::read_to_string(dir.join("series.csv")).unwrap();
+        let written = fs::read(dir.join("stats.json")).unwrap();
+        let probes = fs::read_to_string(dir.join("probes.csv")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Frames 0, 100 and 200, with every particle inside the probe
+        assert_eq!(probes.lines().count(), 4);
+        assert!(probes
+            .lines()
+            .nth(3)
+            .unwrap()
+            .starts_with("200,everywhere,10,"));
+
+        assert_eq!(stats.steps, 250);
+        assert!(stats.energy_drift.abs() < 1e-9);
+        // The start, every hundred steps, and the end
+        assert_eq!(series.lines().count(), 5);
+        assert!(series.lines().last().unwrap().starts_with("250,"));
+        let written: serde_json::Value = serde_json::from_slice(&written).unwrap();
+        assert_eq!(written["state_hash"], stats.state_hash);
+        // The same run again ends in the same state
+        let mut again = spec.simulation().unwrap();
+        for _ in 0..250 {
+            again.step(1.0).unwrap();
+        }
+        assert_eq!(again.state_hash(), stats.state_hash);
+    }
+}
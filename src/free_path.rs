@@ -0,0 +1,169 @@
+use hecs::World;
+use particle_core::events::Event;
+use particle_core::particle::{Particle, HEIGHT, WIDTH};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// The time and distance a particle travelled between two of its collisions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flight {
+    pub time: f64,
+    pub distance: f64,
+}
+
+/// Follows every particle from one collision to the next, to measure the
+/// distributions of the time and distance between collisions. The first flight
+/// of each particle starts wherever it was spawned rather than at a collision,
+/// so it is left out.
+#[derive(Debug, Clone, Default)]
+pub struct FreePaths {
+    /// The flight each particle is on, by id, once it has collided at least once.
+    current: HashMap<u64, Flight>,
+    flights: Vec<Flight>,
+    /// The particle count and mean diameter, summed over every update.
+    particles: f64,
+    diameters: f64,
+    updates: u64,
+}
+
+impl FreePaths {
+    pub fn new() -> Self {
+        FreePaths::default()
+    }
+
+    pub fn update(&mut self, events: &[Event], world: &World, dt: f64) {
+        //! Move every particle on by its latest step, then end the flights of
+        //! the particles that collided in it.
+        let (mut count, mut diameters) = (0, 0.0);
+        for (_, particle) in world.query::<&Particle>().iter() {
+            count += 1;
+            diameters += 2.0 * particle.radius;
+            if let Some(flight) = self.current.get_mut(&particle.id) {
+                flight.time += dt;
+                flight.distance += particle.velocity.length() * dt;
+            }
+        }
+        if count > 0 {
+            self.particles += count as f64;
+            self.diameters += diameters / count as f64;
+            self.updates += 1;
+        }
+
+        for event in events {
+            match event {
+                Event::Contact { a, b, .. } => {
+                    for id in [*a, *b] {
+                        let started = Flight {
+                            time: 0.0,
+                            distance: 0.0,
+                        };
+                        if let Some(flight) = self.current.insert(id, started) {
+                            self.flights.push(flight);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn flights(&self) -> &[Flight] {
+        &self.flights
+    }
+
+    pub fn mean_free_path(&self) -> Option<f64> {
+        (!self.flights.is_empty()).then(|| {
+            self.flights
+                .iter()
+                .map(|flight| flight.distance)
+                .sum::<f64>()
+                / self.flights.len() as f64
+        })
+    }
+
+    pub fn mean_free_time(&self) -> Option<f64> {
+        (!self.flights.is_empty()).then(|| {
+            self.flights.iter().map(|flight| flight.time).sum::<f64>() / self.flights.len() as f64
+        })
+    }
+
+    pub fn predicted_mean_free_path(&self) -> Option<f64> {
+        //! What kinetic theory predicts for hard disks in two dimensions,
+        //! `1 / (√2 n d)` for a number density `n` and a diameter `d`, from the
+        //! average particle count and diameter.
+        if self.updates == 0 {
+            return None;
+        }
+        let density = self.particles / self.updates as f64 / (WIDTH * HEIGHT);
+        let diameter = self.diameters / self.updates as f64;
+        Some(1.0 / (2f64.sqrt() * density * diameter))
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        //! A summary of the statistics, for printing at the end of a run.
+        let Some((path, time)) = self.mean_free_path().zip(self.mean_free_time()) else {
+            return vec!["No particle collided twice, so there are no free paths".to_string()];
+        };
+        let predicted = self.predicted_mean_free_path().unwrap_or(f64::NAN);
+        vec![
+            format!("free flights:                 {}", self.flights.len()),
+            format!("mean free path:               {:.3}", path),
+            format!("predicted mean free path:     {:.3}", predicted),
+            format!("mean time between collisions: {:.3} frames", time),
+            format!("collision frequency:          {:.5} per frame", 1.0 / time),
+        ]
+    }
+
+    pub fn write_csv(&self, path: &Path) -> io::Result<()> {
+        //! One `frames,distance` row per finished flight.
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "frames,distance")?;
+        for flight in &self.flights {
+            writeln!(writer, "{},{}", flight.time, flight.distance)?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use particle_core::shapes::Contact;
+    use particle_core::utils::Vec2;
+
+    fn contact(a: u64, b: u64) -> Event {
+        Event::Contact {
+            a,
+            b,
+            contact: Contact {
+                normal: Vec2(1.0, 0.0),
+                penetration_depth: 0.0,
+                point: Vec2::ZERO,
+            },
+            impulse: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_flights_run_from_collision_to_collision() {
+        let mut world = World::new();
+        world.spawn((Particle::new(1, 5.0, Vec2(100.0, 100.0), Vec2(3.0, 4.0)),));
+        world.spawn((Particle::new(2, 5.0, Vec2(200.0, 100.0), Vec2(0.0, 1.0)),));
+        let mut free_paths = FreePaths::new();
+
+        free_paths.update(&[contact(1, 2)], &world, 1.0);
+        assert!(free_paths.flights().is_empty());
+        for _ in 0..3 {
+            free_paths.update(&[], &world, 1.0);
+        }
+        free_paths.update(&[contact(1, 2)], &world, 1.0);
+
+        // Four steps at speeds of 5 and 1
+        assert_eq!(free_paths.mean_free_time(), Some(4.0));
+        assert_eq!(free_paths.mean_free_path(), Some(12.0));
+        let density = 2.0 / (WIDTH * HEIGHT);
+        let predicted = free_paths.predicted_mean_free_path().unwrap();
+        assert!((predicted - 1.0 / (2f64.sqrt() * density * 10.0)).abs() < 1e-9);
+    }
+}
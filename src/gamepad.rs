@@ -0,0 +1,91 @@
+//! Game controller input for the window, through gilrs, behind the `gamepad`
+//! feature. The left stick pans the camera, the right trigger zooms in and the
+//! left one out, and the face buttons pause, spawn bursts and switch gravity.
+use crate::camera::Camera;
+use gilrs::{Axis, Button, EventType, Gilrs};
+use particle_core::utils::Vec2;
+use std::time::Instant;
+
+/// How far the camera pans with the stick all the way over, in window units a second.
+const PAN_SPEED: f64 = 800.0;
+/// How many times over the camera zooms in a second with a trigger fully pressed.
+const ZOOM_SPEED: f64 = 3.0;
+/// Stick positions closer to the middle than this count as centered, since
+/// sticks rarely rest at exactly zero.
+const DEAD_ZONE: f32 = 0.15;
+
+/// What a button press on the controller asks the window to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// South, A on most controllers
+    Pause,
+    /// West, X on most controllers
+    Burst,
+    /// North, Y on most controllers
+    ToggleGravity,
+    /// Select, to show the whole box again
+    ResetCamera,
+}
+
+/// Every controller that is plugged in, all driving the same window.
+pub struct Gamepads {
+    gilrs: Gilrs,
+    last_poll: Instant,
+}
+
+impl Gamepads {
+    pub fn new() -> Option<Self> {
+        //! Start listening for controllers, or `None` with a warning if the
+        //! platform has no support for them.
+        match Gilrs::new() {
+            Ok(gilrs) => Some(Gamepads {
+                gilrs,
+                last_poll: Instant::now(),
+            }),
+            Err(err) => {
+                tracing::warn!(%err, "could not open game controllers");
+                None
+            }
+        }
+    }
+
+    pub fn poll(&mut self, camera: &mut Camera) -> Vec<Action> {
+        //! Move the camera by the sticks and triggers for the time since the last
+        //! poll, and return the buttons pressed since.
+        let elapsed = self.last_poll.elapsed().as_secs_f64();
+        self.last_poll = Instant::now();
+        let mut actions = Vec::new();
+        while let Some(event) = self.gilrs.next_event() {
+            if let EventType::ButtonPressed(button, _) = event.event {
+                actions.extend(match button {
+                    Button::South => Some(Action::Pause),
+                    Button::West => Some(Action::Burst),
+                    Button::North => Some(Action::ToggleGravity),
+                    Button::Select => Some(Action::ResetCamera),
+                    _ => None,
+                });
+            }
+        }
+        for (_, gamepad) in self.gilrs.gamepads() {
+            let stick = |axis| {
+                let value = gamepad.value(axis);
+                if value.abs() < DEAD_ZONE {
+                    0.0
+                } else {
+                    value as f64
+                }
+            };
+            let trigger = |button| {
+                gamepad
+                    .button_data(button)
+                    .map_or(0.0, |data| data.value() as f64)
+            };
+            camera.pan(
+                Vec2(stick(Axis::LeftStickX), stick(Axis::LeftStickY)).scale(PAN_SPEED * elapsed),
+            );
+            let zoom = trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2);
+            camera.zoom_by(ZOOM_SPEED.powf(zoom * elapsed));
+        }
+        actions
+    }
+}
@@ -0,0 +1,109 @@
+use particle_core::events::Event;
+use std::collections::HashMap;
+
+/// The share of its heat a particle keeps from one step to the next, so that the
+/// heat of a collision fades out over about a second.
+const COOLING: f64 = 0.93;
+/// Heat below this share of a typical collision is forgotten.
+const FORGOTTEN: f64 = 0.01;
+/// How many typical collisions in quick succession make a particle glow at
+/// nearly full brightness.
+const GLOWING_COLLISIONS: f64 = 3.0;
+
+/// Collision heat: every collision warms both particles by the impulse it took,
+/// and the heat leaks away every step, so particles caught in a crush of
+/// collisions stand out from those drifting alone. Heat is measured against the
+/// average impulse of every collision seen so far, so that it means the same
+/// whatever the sizes and speeds of the particles.
+#[derive(Debug, Clone, Default)]
+pub struct Heat {
+    /// The heat of every warm particle, by id.
+    heat: HashMap<u64, f64>,
+    total_impulse: f64,
+    collisions: u64,
+}
+
+impl Heat {
+    pub fn new() -> Self {
+        Heat::default()
+    }
+
+    fn typical_impulse(&self) -> f64 {
+        if self.collisions == 0 {
+            1.0
+        } else {
+            self.total_impulse / self.collisions as f64
+        }
+    }
+
+    pub fn record(&mut self, events: &[Event]) {
+        //! Cool every particle by one step, then warm the ones that collided in it.
+        let forgotten = FORGOTTEN * self.typical_impulse();
+        self.heat.retain(|_, heat| {
+            *heat *= COOLING;
+            *heat > forgotten
+        });
+        for event in events {
+            match event {
+                Event::Contact { a, b, impulse, .. } => {
+                    *self.heat.entry(*a).or_default() += impulse;
+                    *self.heat.entry(*b).or_default() += impulse;
+                    self.total_impulse += impulse;
+                    self.collisions += 1;
+                }
+            }
+        }
+    }
+
+    pub fn forget(&mut self, id: u64) {
+        //! Drop the heat of a particle that has been removed.
+        self.heat.remove(&id);
+    }
+
+    pub fn heat(&self, id: u64) -> f64 {
+        self.heat.get(&id).copied().unwrap_or(0.0)
+    }
+
+    pub fn glow(&self, id: u64) -> f32 {
+        //! How brightly the particle should glow, from 0 when it is cold
+        //! towards 1 as it keeps colliding.
+        let heat = self.heat(id) / (GLOWING_COLLISIONS * self.typical_impulse());
+        (1.0 - (-3.0 * heat).exp()) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use particle_core::shapes::Contact;
+    use particle_core::utils::Vec2;
+
+    fn contact(a: u64, b: u64, impulse: f64) -> Event {
+        Event::Contact {
+            a,
+            b,
+            contact: Contact {
+                normal: Vec2(1.0, 0.0),
+                penetration_depth: 0.0,
+                point: Vec2::ZERO,
+            },
+            impulse,
+        }
+    }
+
+    #[test]
+    fn test_collisions_warm_and_heat_fades() {
+        let mut heat = Heat::new();
+        heat.record(&[contact(1, 2, 10.0), contact(2, 3, 30.0)]);
+        assert_eq!(heat.heat(2), 40.0);
+        assert!(heat.glow(2) > heat.glow(1));
+        assert_eq!(heat.glow(4), 0.0);
+
+        heat.record(&[]);
+        assert!((heat.heat(1) - 10.0 * COOLING).abs() < 1e-12);
+        for _ in 0..200 {
+            heat.record(&[]);
+        }
+        assert_eq!(heat.heat(2), 0.0);
+    }
+}
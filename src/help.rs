@@ -0,0 +1,139 @@
+use crate::clock::Clock;
+use crate::hud::FONT;
+use crate::layers::Layer;
+use crate::theme::Theme;
+use flo_canvas::*;
+use particle_core::simulation::Simulation;
+
+const LINE_HEIGHT: f32 = 18.0;
+const PANEL_LEFT: f32 = 200.0;
+const PANEL_WIDTH: f32 = 600.0;
+const PADDING: f32 = 20.0;
+
+/// Every key and mouse binding of the window that works at any time.
+const BINDINGS: &[&str] = &[
+    "F1 or H: show or hide this help, F2: the overlays, F3: the HUD",
+    "Tab: select a setting in the HUD, , and .: lower or raise it",
+    "Click: inspect a particle, drag: select a box of particles",
+    "Right click: delete a particle, right drag: erase a box",
+    "X: explosion under the pointer, B: burst of particles from it",
+    "G: grid, L: particle ids, E: collision heat, M: center of mass",
+    "C: color attribute, P: palette, T: theme",
+    "Space: pause, R: reload the scenario",
+    "F5: state dump, F9: SVG snapshot, F12: screenshot",
+];
+
+/// The bindings that only work while a particle is being inspected.
+const INSPECTOR_BINDINGS: &[&str] = &[
+    "Up/Down: select a field of the particle",
+    "- and =: change it",
+    "Esc or a click on empty space: stop inspecting",
+];
+
+/// The bindings that only work while some particles are selected.
+const SELECTION_BINDINGS: &[&str] = &[
+    "Z: stop the selection, V: move it together at its average velocity",
+    "S: next species, K: next color of the palette, F: pin or unpin",
+    "Delete: delete the selection, Esc or a click: select nothing",
+];
+
+pub fn lines(
+    simulation: &Simulation,
+    clock: &Clock,
+    inspecting: bool,
+    selecting: bool,
+) -> Vec<String> {
+    //! The bindings that work right now, followed by the major physics settings.
+    let mut lines = vec!["Controls".to_string()];
+    lines.extend(BINDINGS.iter().map(|line| line.to_string()));
+    if inspecting {
+        lines.extend(INSPECTOR_BINDINGS.iter().map(|line| line.to_string()));
+    }
+    if selecting {
+        lines.extend(SELECTION_BINDINGS.iter().map(|line| line.to_string()));
+    }
+    let settings = simulation.settings();
+    let walls = &settings.walls;
+    lines.extend([
+        String::new(),
+        "Physics".to_string(),
+        format!("particles: {}", simulation.particle_count()),
+        format!("gravity: {:.2}", -settings.gravity.1),
+        format!(
+            "mutual gravity: {}",
+            settings
+                .mutual_gravity
+                .map_or("off".to_string(), |constant| constant.to_string())
+        ),
+        format!(
+            "wall restitution: {:.2}, floor: {:.2}",
+            walls.top.restitution, walls.bottom.restitution
+        ),
+        format!(
+            "wall friction: {:.2}, floor: {:.2}",
+            walls.top.friction, walls.bottom.friction
+        ),
+        format!(
+            "max speed: {}",
+            settings
+                .max_speed
+                .map_or("none".to_string(), |speed| speed.to_string())
+        ),
+        format!("time scale: x{:.2}", clock.time_scale()),
+        format!("broadphase: {}", settings.sweep_axis.name()),
+    ]);
+    lines
+}
+
+pub fn draw(gc: &mut impl GraphicsContext, lines: Option<&[String]>, theme: Theme) {
+    //! Replace the overlay of the last frame with a panel of `lines` in the middle
+    //! of the window, or with nothing. The font must already have been loaded
+    //! with `Hud::load_font`.
+    Layer::Help.clear(gc);
+    let Some(lines) = lines else {
+        return;
+    };
+    let height = lines.len() as f32 * LINE_HEIGHT + 2.0 * PADDING;
+    let bottom = (1000.0 - height) / 2.0;
+    gc.new_path();
+    gc.rect(
+        PANEL_LEFT,
+        bottom,
+        PANEL_LEFT + PANEL_WIDTH,
+        bottom + height,
+    );
+    gc.fill_color(theme.background().with_alpha(0.9));
+    gc.fill();
+    gc.line_width(1.0);
+    gc.stroke_color(theme.text());
+    gc.stroke();
+    gc.fill_color(theme.text());
+    let top = bottom + height - PADDING;
+    for (row, line) in lines.iter().enumerate() {
+        gc.draw_text(
+            FONT,
+            line.clone(),
+            PANEL_LEFT + PADDING,
+            top - LINE_HEIGHT * (row as f32 + 1.0),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspector_bindings_only_while_inspecting() {
+        let mut simulation = Simulation::new();
+        simulation.settings_mut().max_speed = Some(30.0);
+        let clock = Clock::new(1.0);
+        let lines = lines(&simulation, &clock, false, false);
+        assert!(!lines.contains(&INSPECTOR_BINDINGS[0].to_string()));
+        assert!(lines.contains(&"max speed: 30".to_string()));
+        assert_eq!(
+            super::lines(&simulation, &clock, true, true).len(),
+            lines.len() + INSPECTOR_BINDINGS.len() + SELECTION_BINDINGS.len()
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! A small REST API for scripting a simulation started with `--http`.
+//!
+//! | Method | Path         | Body                                              |
+//! |--------|--------------|---------------------------------------------------|
+//! | GET    | `/state`     |                                                   |
+//! | POST   | `/particles` | `{"count": 10}` or `{"radius": 5, "x": 1, "y": 2}` |
+//! | POST   | `/pause`     | optional `{"paused": false}` to resume            |
+//! | GET    | `/config`    |                                                   |
+//! | PUT    | `/config`    | `{"gravity": [0, -0.2]}`                          |
+//! | POST   | `/explode`   | `{"x": 500, "y": 500, "strength": 1000000}`       |
+//!
+//! Every response is JSON. Errors are reported as `{"error": "..."}`. Spawning
+//! stops at `--max-particles`, and a single particle past it is refused.
+use crate::server::{Control, ServerState};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tiny_http::{Header, Response, Server};
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SpawnRequest {
+    Count {
+        count: usize,
+    },
+    Particle {
+        radius: f64,
+        x: f64,
+        y: f64,
+        #[serde(default)]
+        vx: f64,
+        #[serde(default)]
+        vy: f64,
+        #[serde(default)]
+        fixed: bool,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct PauseRequest {
+    paused: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExplodeRequest {
+    x: f64,
+    y: f64,
+    strength: f64,
+}
+
+/// The settings that can be read and changed through `/config`.
+/// Every field is optional when updating.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gravity: Option<[f64; 2]>,
+}
+
+fn current_config(state: &ServerState) -> Config {
+    let gravity = state.simulation.settings().gravity;
+    Config {
+        gravity: Some([gravity.0, gravity.1]),
+    }
+}
+
+fn error(status: u16, message: impl ToString) -> (u16, String) {
+    (status, json!({ "error": message.to_string() }).to_string())
+}
+
+fn ok(body: impl Serialize) -> (u16, String) {
+    (
+        200,
+        serde_json::to_string(&body).expect("responses are always serializable"),
+    )
+}
+
+pub fn handle(state: &mut ServerState, method: &str, url: &str, body: &str) -> (u16, String) {
+    //! Route a request, returning the status code and JSON body of the response.
+    let path = url.split('?').next().unwrap_or(url);
+    match (method, path) {
+        ("GET", "/state") => ok(state.snapshot()),
+        ("POST", "/particles") => match serde_json::from_str::<SpawnRequest>(body) {
+            Ok(SpawnRequest::Count { count }) => match state.apply(Control::Spawn { count }) {
+                Ok(()) => ok(json!({ "particles": state.simulation.particle_count() })),
+                Err(err) => error(400, err),
+            },
+            Ok(SpawnRequest::Particle {
+                radius,
+                x,
+                y,
+                vx,
+                vy,
+                fixed,
+            }) => {
+                match state.apply(Control::SpawnParticle {
+                    radius,
+                    x,
+                    y,
+                    vx,
+                    vy,
+                    fixed,
+                }) {
+                    Ok(()) => ok(json!({ "particles": state.simulation.particle_count() })),
+                    Err(err) => error(400, err),
+                }
+            }
+            Err(err) => error(400, err),
+        },
+        ("POST", "/pause") => {
+            let paused = if body.trim().is_empty() {
+                true
+            } else {
+                match serde_json::from_str::<PauseRequest>(body) {
+                    Ok(request) => request.paused,
+                    Err(err) => return error(400, err),
+                }
+            };
+            let control = if paused {
+                Control::Pause
+            } else {
+                Control::Resume
+            };
+            match state.apply(control) {
+                Ok(()) => ok(json!({ "paused": state.paused })),
+                Err(err) => error(400, err),
+            }
+        }
+        ("GET", "/config") => ok(current_config(state)),
+        ("PUT", "/config") => match serde_json::from_str::<Config>(body) {
+            Ok(config) => {
+                if let Some([x, y]) = config.gravity {
+                    if let Err(err) = state.apply(Control::SetGravity { x, y }) {
+                        return error(400, err);
+                    }
+                }
+                ok(current_config(state))
+            }
+            Err(err) => error(400, err),
+        },
+        ("POST", "/explode") => match serde_json::from_str::<ExplodeRequest>(body) {
+            Ok(ExplodeRequest { x, y, strength }) => {
+                match state.apply(Control::Explode { x, y, strength }) {
+                    Ok(()) => ok(state.snapshot()),
+                    Err(err) => error(400, err),
+                }
+            }
+            Err(err) => error(400, err),
+        },
+        (_, "/state" | "/particles" | "/pause" | "/config" | "/explode") => {
+            error(405, "method not allowed")
+        }
+        _ => error(404, format!("no such endpoint: {}", path)),
+    }
+}
+
+pub fn poll(server: &Server, state: &mut ServerState) {
+    //! Answer every request that has arrived since the last frame, without blocking.
+    while let Ok(Some(mut request)) = server.try_recv() {
+        let mut body = String::new();
+        let (status, response) = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => handle(
+                state,
+                request.method().as_str(),
+                request.url(),
+                body.as_str(),
+            ),
+            Err(err) => error(400, err),
+        };
+        tracing::debug!(method = %request.method(), url = request.url(), status, "HTTP request");
+
+        let content_type = Header::from_bytes("Content-Type", "application/json")
+            .expect("the content type header is valid");
+        let response = Response::from_string(response)
+            .with_status_code(status)
+            .with_header(content_type);
+        if let Err(err) = request.respond(response) {
+            tracing::warn!(%err, "failed to send an HTTP response");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use particle_core::simulation::Simulation;
+    use serde_json::Value;
+
+    fn call(state: &mut ServerState, method: &str, url: &str, body: &str) -> (u16, Value) {
+        let (status, response) = handle(state, method, url, body);
+        (status, serde_json::from_str(&response).unwrap())
+    }
+
+    #[test]
+    fn test_spawn_and_read_state() {
+        let mut state = ServerState::new(Simulation::new());
+        let (status, body) = call(&mut state, "POST", "/particles", r#"{"count": 2}"#);
+        assert_eq!(status, 200);
+        assert_eq!(body["particles"], 2);
+
+        let (status, _) = call(
+            &mut state,
+            "POST",
+            "/particles",
+            r#"{"radius": 5, "x": 100, "y": 200}"#,
+        );
+        assert_eq!(status, 200);
+
+        let (status, body) = call(&mut state, "GET", "/state?pretty", "");
+        assert_eq!(status, 200);
+        assert_eq!(body["particles"].as_array().unwrap().len(), 3);
+        assert_eq!(body["particles"][2]["x"], 100.0);
+
+        let (status, body) = call(
+            &mut state,
+            "POST",
+            "/explode",
+            r#"{"x": 90, "y": 200, "strength": 1000}"#,
+        );
+        assert_eq!(status, 200);
+        assert!(body["particles"][2]["vx"].as_f64().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_pause_and_config() {
+        let mut state = ServerState::new(Simulation::new());
+        let (_, body) = call(&mut state, "POST", "/pause", "");
+        assert_eq!(body["paused"], true);
+        let (_, body) = call(&mut state, "POST", "/pause", r#"{"paused": false}"#);
+        assert_eq!(body["paused"], false);
+
+        let (status, body) = call(&mut state, "PUT", "/config", r#"{"gravity": [0, -0.5]}"#);
+        assert_eq!(status, 200);
+        assert_eq!(body["gravity"][1], -0.5);
+        let (_, body) = call(&mut state, "GET", "/config", "");
+        assert_eq!(body["gravity"][1], -0.5);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut state = ServerState::new(Simulation::new());
+        assert_eq!(call(&mut state, "GET", "/nowhere", "").0, 404);
+        assert_eq!(call(&mut state, "DELETE", "/state", "").0, 405);
+        let (status, body) = call(&mut state, "POST", "/particles", "{}");
+        assert_eq!(status, 400);
+        assert!(body["error"].is_string());
+        let particle = r#"{"radius": -5, "x": 100, "y": 200}"#;
+        assert_eq!(call(&mut state, "POST", "/particles", particle).0, 400);
+
+        state.max_particles = Some(3);
+        let (status, body) = call(&mut state, "POST", "/particles", r#"{"count": 10}"#);
+        assert_eq!(status, 200);
+        assert_eq!(body["particles"], 3);
+        let particle = r#"{"radius": 5, "x": 100, "y": 200}"#;
+        assert_eq!(call(&mut state, "POST", "/particles", particle).0, 400);
+        assert_eq!(state.simulation.particle_count(), 3);
+    }
+}
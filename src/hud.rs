@@ -0,0 +1,47 @@
+use crate::layers::Layer;
+use flo_canvas::*;
+use std::sync::Arc;
+
+/// Loaded by `Hud::load_font`, and shared by anything else that draws text.
+pub(crate) const FONT: FontId = FontId(1);
+const FONT_SIZE: f32 = 14.0;
+const LINE_HEIGHT: f32 = 18.0;
+
+/// A heads up display of text lines in the top left corner of the window.
+pub struct Hud {
+    font: Arc<CanvasFontFace>,
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Hud {
+            font: CanvasFontFace::from_slice(include_bytes!("../assets/Lato-Regular.ttf")),
+        }
+    }
+
+    pub fn load_font(&self, gc: &mut impl GraphicsContext) {
+        //! The font only needs to be sent to the canvas once.
+        gc.define_font_data(FONT, Arc::clone(&self.font));
+        gc.set_font_size(FONT, FONT_SIZE);
+    }
+
+    pub fn draw(&self, gc: &mut impl GraphicsContext, lines: &[String], color: Color) {
+        //! Replace whatever the HUD showed last frame with `lines`, written in `color`.
+        Layer::Hud.clear(gc);
+        gc.fill_color(color);
+        for (row, line) in lines.iter().enumerate() {
+            gc.draw_text(
+                FONT,
+                line.clone(),
+                10.0,
+                1000.0 - LINE_HEIGHT * (row as f32 + 1.0),
+            );
+        }
+    }
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Hud::new()
+    }
+}
@@ -0,0 +1,226 @@
+use particle_core::events::Event;
+use particle_core::particle::{Particle, RADIUS_UPPER_BOUND};
+use particle_core::shapes::Body;
+use particle_core::simulation::Simulation;
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+/// How many frames count as recent for the collision count, about a second.
+const RECENT_FRAMES: usize = 45;
+
+/// A value of the inspected particle that can be nudged from the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    X,
+    Y,
+    VelocityX,
+    VelocityY,
+    Radius,
+}
+
+impl Field {
+    const ALL: [Field; 5] = [
+        Field::X,
+        Field::Y,
+        Field::VelocityX,
+        Field::VelocityY,
+        Field::Radius,
+    ];
+
+    fn step(self) -> f64 {
+        //! How much a single nudge changes the value by.
+        match self {
+            Field::X | Field::Y => 10.0,
+            Field::VelocityX | Field::VelocityY | Field::Radius => 1.0,
+        }
+    }
+
+    fn value(self, particle: &Particle) -> f64 {
+        match self {
+            Field::X => particle.position.0,
+            Field::Y => particle.position.1,
+            Field::VelocityX => particle.velocity.0,
+            Field::VelocityY => particle.velocity.1,
+            Field::Radius => particle.radius,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Field::X => "x",
+            Field::Y => "y",
+            Field::VelocityX => "vx",
+            Field::VelocityY => "vy",
+            Field::Radius => "radius",
+        }
+    }
+}
+
+/// The particle that was clicked on in the window, followed from frame to frame by
+/// its id. One of its fields is selected for editing, and nudging changes it in
+/// place between steps, to poke at a particle that misbehaves.
+#[derive(Debug, Clone)]
+pub struct Inspector {
+    id: u64,
+    field: Field,
+    /// The number of collisions of the particle in each recent frame, newest last.
+    collisions: VecDeque<usize>,
+}
+
+impl Inspector {
+    pub fn new(id: u64) -> Self {
+        Inspector {
+            id,
+            field: Field::X,
+            collisions: VecDeque::with_capacity(RECENT_FRAMES),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn field(&self) -> Field {
+        self.field
+    }
+
+    pub fn record(&mut self, events: &[Event]) {
+        //! Count the collisions of the inspected particle during the latest step.
+        let count = events
+            .iter()
+            .filter(|event| match event {
+                Event::Contact { a, b, .. } => *a == self.id || *b == self.id,
+            })
+            .count();
+        if self.collisions.len() == RECENT_FRAMES {
+            self.collisions.pop_front();
+        }
+        self.collisions.push_back(count);
+    }
+
+    pub fn recent_collisions(&self) -> usize {
+        self.collisions.iter().sum()
+    }
+
+    pub fn select_next(&mut self, forwards: bool) {
+        //! Move the selection to the next field down, or up, wrapping around.
+        let count = Field::ALL.len();
+        let index = Field::ALL
+            .iter()
+            .position(|field| *field == self.field)
+            .unwrap_or(0);
+        let next = if forwards {
+            index + 1
+        } else {
+            index + count - 1
+        };
+        self.field = Field::ALL[next % count];
+    }
+
+    pub fn nudge(&self, simulation: &mut Simulation, steps: f64) {
+        //! Change the selected field by `steps` times its step size. The radius
+        //! of a circle stays within the limits of random particles, and its mass
+        //! follows it. Shapes keep their radius, which their outline depends on.
+        let has_body = simulation
+            .world()
+            .query::<(&Particle, &Body)>()
+            .iter()
+            .any(|(_, (particle, _))| particle.id == self.id);
+        let Some(particle) = simulation.particle_mut(self.id) else {
+            return;
+        };
+        let change = steps * self.field.step();
+        match self.field {
+            Field::X => particle.position.0 += change,
+            Field::Y => particle.position.1 += change,
+            Field::VelocityX if !particle.fixed => particle.velocity.0 += change,
+            Field::VelocityY if !particle.fixed => particle.velocity.1 += change,
+            Field::Radius if !has_body => {
+                particle.radius = (particle.radius + change).clamp(1.0, RADIUS_UPPER_BOUND);
+                if particle.mass.is_finite() {
+                    particle.mass = particle.radius.powi(2) * PI;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn lines(&self, simulation: &Simulation) -> Option<Vec<String>> {
+        //! The panel describing the particle, or None once it no longer exists.
+        //! The selected field is marked with an arrow.
+        let particle = simulation.particle(self.id)?;
+        let mut lines = vec![
+            format!("Particle {}", particle.id),
+            format!("mass: {:.1}", particle.mass),
+            format!("speed: {:.2}", particle.velocity.length()),
+            format!(
+                "collisions in the last second: {}",
+                self.recent_collisions()
+            ),
+        ];
+        for field in Field::ALL {
+            let marker = if field == self.field { ">" } else { " " };
+            lines.push(format!(
+                "{} {}: {:.2}",
+                marker,
+                field.label(),
+                field.value(&particle)
+            ));
+        }
+        lines.push("Up/Down: select, -/=: change, Esc: close".to_string());
+        Some(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use particle_core::shapes::Contact;
+    use particle_core::utils::Vec2;
+
+    fn contact(a: u64, b: u64) -> Event {
+        Event::Contact {
+            a,
+            b,
+            contact: Contact {
+                normal: Vec2(1.0, 0.0),
+                penetration_depth: 1.0,
+                point: Vec2::ZERO,
+            },
+            impulse: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_recent_collisions_are_counted() {
+        let mut inspector = Inspector::new(3);
+        inspector.record(&[contact(1, 3), contact(1, 2), contact(3, 4)]);
+        assert_eq!(inspector.recent_collisions(), 2);
+        for _ in 0..RECENT_FRAMES {
+            inspector.record(&[]);
+        }
+        assert_eq!(inspector.recent_collisions(), 0);
+    }
+
+    #[test]
+    fn test_nudging_edits_the_selected_field() {
+        let mut simulation = Simulation::new();
+        simulation.spawn(Particle::new(1, 10.0, Vec2(100.0, 100.0), Vec2::ZERO));
+        let mut inspector = Inspector::new(1);
+        inspector.nudge(&mut simulation, 2.0);
+        assert_eq!(simulation.particle(1).unwrap().position, Vec2(120.0, 100.0));
+
+        // Up from the first field wraps around to the radius
+        inspector.select_next(false);
+        assert_eq!(inspector.field(), Field::Radius);
+        inspector.nudge(&mut simulation, 100.0);
+        let particle = simulation.particle(1).unwrap();
+        assert_eq!(particle.radius, RADIUS_UPPER_BOUND);
+        assert_eq!(particle.mass, RADIUS_UPPER_BOUND.powi(2) * PI);
+
+        let lines = inspector.lines(&simulation).unwrap();
+        assert_eq!(lines[0], "Particle 1");
+        assert!(lines.contains(&"> radius: 50.00".to_string()));
+        assert!(Inspector::new(2).lines(&simulation).is_none());
+    }
+}
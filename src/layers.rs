@@ -0,0 +1,83 @@
+//! The layers of the window, drawn from the bottom up. Each part of the picture
+//! is drawn on a layer of its own, so that it can be cleared and drawn again, or
+//! hidden, without touching the others. The canvas transform is shared by all
+//! of them, so a part drawn under the camera can switch layers freely.
+use flo_canvas::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Layer {
+    /// The background color, the grid, obstacles, time regions and portals.
+    Background,
+    /// What particles leave behind them, such as the frames of `--motion-blur`.
+    Trails,
+    /// The particles themselves, with their glow.
+    Particles,
+    /// Labels, rings around the inspected and selected particles, the center of
+    /// mass, the boxes being dragged out and the predicted path.
+    Debug,
+    Minimap,
+    Hud,
+    /// The help overlay, above everything else.
+    Help,
+}
+
+impl Layer {
+    /// The layers of the box, which move with the camera.
+    pub const SCENE: [Layer; 4] = [
+        Layer::Background,
+        Layer::Trails,
+        Layer::Particles,
+        Layer::Debug,
+    ];
+
+    pub const fn id(self) -> LayerId {
+        LayerId(self as u64)
+    }
+
+    pub fn clear(self, gc: &mut impl GraphicsContext) {
+        //! Empty the layer, leaving it selected to draw on.
+        gc.layer(self.id());
+        gc.clear_layer();
+    }
+}
+
+/// Which layers are shown. A hidden layer is cleared and left empty, while the
+/// others are drawn as usual.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Layers {
+    hidden: u8,
+}
+
+impl Layers {
+    pub fn new() -> Self {
+        //! Every layer shown.
+        Layers::default()
+    }
+
+    pub fn is_shown(self, layer: Layer) -> bool {
+        self.hidden & (1 << layer as u8) == 0
+    }
+
+    pub fn toggle(&mut self, layer: Layer) {
+        self.hidden ^= 1 << layer as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers_are_stacked_in_order_and_hidden_one_at_a_time() {
+        assert_eq!(Layer::Background.id(), LayerId(0));
+        assert!(Layer::Particles.id().0 < Layer::Debug.id().0);
+        assert!(Layer::Hud.id().0 < Layer::Help.id().0);
+
+        let mut layers = Layers::new();
+        layers.toggle(Layer::Debug);
+        assert!(!layers.is_shown(Layer::Debug));
+        assert!(layers.is_shown(Layer::Particles));
+        layers.toggle(Layer::Debug);
+        assert_eq!(layers, Layers::new());
+    }
+}
@@ -0,0 +1,49 @@
+pub mod autotune;
+pub mod blur;
+pub mod camera;
+pub mod charts;
+pub mod cli;
+pub mod clock;
+pub mod coloring;
+pub mod compare;
+pub mod config;
+pub mod controls;
+pub mod dashboard;
+pub mod dump;
+pub mod ensemble;
+pub mod experiments;
+pub mod free_path;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod heat;
+pub mod help;
+pub mod http_api;
+pub mod hud;
+pub mod inspector;
+pub mod layers;
+pub mod lockstep;
+pub mod lod;
+pub mod logging;
+pub mod metaballs;
+pub mod metrics;
+pub mod midi;
+pub mod minimap;
+pub mod osc;
+pub mod quality;
+pub mod render;
+pub mod renderer;
+#[cfg(feature = "flo-draw")]
+pub mod screenshot;
+pub mod selection;
+pub mod server;
+pub mod shm;
+pub mod sonify;
+pub mod sprites;
+pub mod stress;
+pub mod svg;
+pub mod sweep;
+pub mod textures;
+pub mod theme;
+pub mod timeline;
+pub mod touch;
+pub mod utils;
@@ -0,0 +1,180 @@
+//! `--lockstep`: run the same seeded simulation headless in two instances
+//! connected over TCP, taking each step together and comparing
+//! `Simulation::state_hash` after every one, to show that the physics is
+//! deterministic and to catch the first frame where it is not.
+//!
+//! One instance listens with `--lockstep <port>` and the other joins it with
+//! `--lockstep <host>:<port>`. Both start by sending a hello, the 4 bytes `PSLS`,
+//! a little endian `u32` protocol version and a `u64` seed, and both then use the
+//! seed of the one that listened. After that, every frame each side sends its
+//! frame number and state hash as two little endian `u64`s, and waits for the
+//! other's before taking the next step, so neither gets more than a frame ahead.
+use particle_core::error::{SimError, SimResult};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MAGIC: &[u8; 4] = b"PSLS";
+const VERSION: u32 = 1;
+/// How long the joining instance keeps trying to reach one that has not started
+/// listening yet.
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for the other instance's frame before giving up on it.
+const FRAME_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The other instance: a bare port to listen on for it, or `<host>:<port>` to join it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Peer {
+    Listen(u16),
+    Join(SocketAddr),
+}
+
+impl FromStr for Peer {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        if let Ok(port) = value.parse::<u16>() {
+            return Ok(Peer::Listen(port));
+        }
+        value
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .map(Peer::Join)
+            .ok_or_else(|| SimError::InvalidArgument {
+                name: "lockstep peer".to_string(),
+                value: value.to_string(),
+            })
+    }
+}
+
+/// The connection to the other instance.
+pub struct Lockstep {
+    stream: TcpStream,
+}
+
+impl Lockstep {
+    pub fn connect(peer: Peer, address: IpAddr, seed: u64) -> SimResult<(Lockstep, u64)> {
+        //! Wait for the other instance on `address` or join it, and agree on a
+        //! seed, which is returned along with the connection.
+        match peer {
+            Peer::Listen(port) => {
+                let listener = TcpListener::bind((address, port))?;
+                println!("Waiting for the other instance on port {}", port);
+                Lockstep::listen(&listener, seed)
+            }
+            Peer::Join(address) => Lockstep::join(address, seed),
+        }
+    }
+
+    fn listen(listener: &TcpListener, seed: u64) -> SimResult<(Lockstep, u64)> {
+        let (stream, address) = listener.accept()?;
+        tracing::info!(%address, "lockstep peer joined");
+        let mut lockstep = Lockstep::new(stream)?;
+        lockstep.hello(seed)?;
+        Ok((lockstep, seed))
+    }
+
+    fn join(address: SocketAddr, seed: u64) -> SimResult<(Lockstep, u64)> {
+        let started = Instant::now();
+        let stream = loop {
+            match TcpStream::connect(address) {
+                Ok(stream) => break stream,
+                Err(err)
+                    if err.kind() == ErrorKind::ConnectionRefused
+                        && started.elapsed() < JOIN_TIMEOUT =>
+                {
+                    thread::sleep(Duration::from_millis(200));
+                }
+                Err(err) => return Err(err.into()),
+            }
+        };
+        tracing::info!(%address, "joined lockstep peer");
+        let mut lockstep = Lockstep::new(stream)?;
+        let theirs = lockstep.hello(seed)?;
+        Ok((lockstep, theirs))
+    }
+
+    fn new(stream: TcpStream) -> SimResult<Lockstep> {
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(FRAME_TIMEOUT))?;
+        Ok(Lockstep { stream })
+    }
+
+    fn hello(&mut self, seed: u64) -> SimResult<u64> {
+        //! Send our hello and read theirs, returning their seed.
+        let mut hello = [0; 16];
+        hello[..4].copy_from_slice(MAGIC);
+        hello[4..8].copy_from_slice(&VERSION.to_le_bytes());
+        hello[8..].copy_from_slice(&seed.to_le_bytes());
+        self.stream.write_all(&hello)?;
+        self.read(&mut hello)?;
+        if &hello[..4] != MAGIC || hello[4..8] != VERSION.to_le_bytes() {
+            return Err(
+                io::Error::new(ErrorKind::InvalidData, "the peer does not speak lockstep").into(),
+            );
+        }
+        Ok(u64::from_le_bytes(hello[8..].try_into().unwrap()))
+    }
+
+    pub fn exchange(&mut self, frame: u64, hash: u64) -> SimResult<Option<u64>> {
+        //! Send the hash of `frame` and wait for the other instance's, returning
+        //! theirs if the two differ.
+        let mut message = [0; 16];
+        message[..8].copy_from_slice(&frame.to_le_bytes());
+        message[8..].copy_from_slice(&hash.to_le_bytes());
+        self.stream.write_all(&message)?;
+        self.read(&mut message)?;
+        let their_frame = u64::from_le_bytes(message[..8].try_into().unwrap());
+        let theirs = u64::from_le_bytes(message[8..].try_into().unwrap());
+        if their_frame != frame {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("the peer is at frame {} instead of {}", their_frame, frame),
+            )
+            .into());
+        }
+        Ok((theirs != hash).then_some(theirs))
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> SimResult<()> {
+        self.stream.read_exact(buffer).map_err(|err| {
+            if err.kind() == ErrorKind::UnexpectedEof {
+                io::Error::new(ErrorKind::UnexpectedEof, "the peer has left").into()
+            } else {
+                err.into()
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agree_on_a_seed_and_compare_hashes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host = thread::spawn(move || {
+            let (mut lockstep, seed) = Lockstep::listen(&listener, 7).unwrap();
+            assert_eq!(lockstep.exchange(0, 11).unwrap(), None);
+            assert_eq!(lockstep.exchange(1, 12).unwrap(), Some(13));
+            seed
+        });
+        let (mut lockstep, seed) = Lockstep::join(address, 9).unwrap();
+        assert_eq!(seed, 7);
+        assert_eq!(lockstep.exchange(0, 11).unwrap(), None);
+        assert_eq!(lockstep.exchange(1, 13).unwrap(), Some(12));
+        assert_eq!(host.join().unwrap(), 7);
+
+        assert_eq!("9000".parse::<Peer>().unwrap(), Peer::Listen(9000));
+        assert_eq!(
+            "127.0.0.1:9000".parse::<Peer>().unwrap(),
+            Peer::Join(([127, 0, 0, 1], 9000).into())
+        );
+        assert!("nowhere".parse::<Peer>().is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Level of detail: how much of every particle the window draws. A crowded box
+//! has too many particles for a sprite and its effects each, and seen from far
+//! out they are too small to tell apart anyway. So above `--lod-threshold`
+//! particles, counting more of them the further the camera is zoomed out, they
+//! are drawn as plain squares instead, batched into one path for each color.
+//! Four times further on, they shrink to points of a fixed size on screen.
+use flo_canvas::*;
+use particle_core::utils::Vec2;
+use std::collections::BTreeMap;
+
+/// The side of a point on screen, in canvas units.
+const POINT_SIZE: f64 = 2.0;
+/// Each color channel is rounded to one of this many levels, which bounds the
+/// number of paths a frame is drawn in.
+const COLOR_LEVELS: f32 = 8.0;
+/// How many times the threshold it takes for squares to shrink to points.
+const POINTS_FACTOR: f64 = 4.0;
+
+/// How much of each particle is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Detail {
+    /// Sprites, shapes, textures and every effect.
+    Full,
+    /// A square as wide as the particle.
+    Quads,
+    /// A square as wide as a couple of pixels, whatever the particle's size.
+    Points,
+}
+
+impl Detail {
+    pub fn choose(particles: usize, zoom: f64, threshold: usize) -> Detail {
+        //! The detail for `particles` seen at `zoom`. Zooming out to half size
+        //! fits the same particles into a quarter of the window, and so counts
+        //! them four times over.
+        let crowd = particles as f64 / (zoom * zoom);
+        if crowd > threshold as f64 * POINTS_FACTOR {
+            Detail::Points
+        } else if crowd > threshold as f64 {
+            Detail::Quads
+        } else {
+            Detail::Full
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Detail::Full => "full",
+            Detail::Quads => "squares",
+            Detail::Points => "points",
+        }
+    }
+}
+
+/// Particles gathered up to be drawn together, grouped by their rounded color.
+#[derive(Debug, Default)]
+pub struct Batch {
+    squares: BTreeMap<[u8; 4], Vec<(Vec2, f64)>>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Batch::default()
+    }
+
+    pub fn add(&mut self, detail: Detail, zoom: f64, center: Vec2, radius: f64, color: [f32; 4]) {
+        //! Gather a particle, as big as `detail` draws it at `zoom`.
+        let point = POINT_SIZE / 2.0 / zoom;
+        let half_side = match detail {
+            Detail::Points => point,
+            Detail::Full | Detail::Quads => radius.max(point),
+        };
+        let level =
+            color.map(|channel| (channel.clamp(0.0, 1.0) * (COLOR_LEVELS - 1.0)).round() as u8);
+        self.squares
+            .entry(level)
+            .or_default()
+            .push((center, half_side));
+    }
+
+    pub fn draw(&self, gc: &mut impl GraphicsContext) {
+        for (level, squares) in &self.squares {
+            gc.new_path();
+            for (center, half_side) in squares {
+                gc.rect(
+                    (center.0 - half_side) as f32,
+                    (center.1 - half_side) as f32,
+                    (center.0 + half_side) as f32,
+                    (center.1 + half_side) as f32,
+                );
+            }
+            let [r, g, b, a] = level.map(|level| level as f32 / (COLOR_LEVELS - 1.0));
+            gc.fill_color(Color::Rgba(r, g, b, a));
+            gc.fill();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detail_drops_with_crowds_and_zooming_out() {
+        assert_eq!(Detail::choose(10_000, 1.0, 20_000), Detail::Full);
+        assert_eq!(Detail::choose(30_000, 1.0, 20_000), Detail::Quads);
+        assert_eq!(Detail::choose(100_000, 1.0, 20_000), Detail::Points);
+        // Zooming in brings the detail back, and zooming out takes it away
+        assert_eq!(Detail::choose(30_000, 2.0, 20_000), Detail::Full);
+        assert_eq!(Detail::choose(10_000, 0.5, 20_000), Detail::Quads);
+    }
+
+    #[test]
+    fn test_particles_of_a_color_share_a_path() {
+        let mut batch = Batch::new();
+        for x in 0..100 {
+            batch.add(
+                Detail::Quads,
+                1.0,
+                Vec2(x as f64 * 10.0, 500.0),
+                3.0,
+                [1.0; 4],
+            );
+        }
+        batch.add(
+            Detail::Points,
+            2.0,
+            Vec2(500.0, 100.0),
+            30.0,
+            [1.0, 0.0, 0.0, 1.0],
+        );
+        let mut drawing: Vec<Draw> = vec![];
+        batch.draw(&mut drawing);
+        assert_eq!(
+            drawing.iter().filter(|draw| **draw == Draw::Fill).count(),
+            2
+        );
+        // A point is as big on screen at any zoom, whatever the particle's size
+        let red = &batch.squares[&[7, 0, 0, 7]];
+        assert_eq!(red, &[(Vec2(500.0, 100.0), 0.5)]);
+    }
+}
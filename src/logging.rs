@@ -0,0 +1,24 @@
+use particle_core::error::{SimError, SimResult};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+pub fn init(level: &str, json: bool) -> SimResult<()> {
+    //! Install the global `tracing` subscriber. `level` is either a plain level
+    //! such as `debug`, or a full filter directive. Spans are reported when they
+    //! close, along with how long they were busy for.
+    let filter = EnvFilter::try_new(level).map_err(|_| SimError::InvalidArgument {
+        name: "--log-level".to_string(),
+        value: level.to_string(),
+    })?;
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(std::io::stderr);
+    if json {
+        builder.json().init();
+    } else {
+        builder.init();
+    }
+    Ok(())
+}
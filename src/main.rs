@@ -1,63 +1,725 @@
-mod particle;
-mod sweep_prune;
-mod utils;
-use particle::Particle;
-use sweep_prune::detect_and_apply_collisions;
-
-use flo_canvas::*;
-use flo_draw::*;
+use particle_core::checkpoint::{self, Checkpoint};
+use particle_core::components::Species;
+use particle_core::error::{SimError, SimResult};
+use particle_core::event_driven::{Comparison, EventDriven};
+use particle_core::export;
+use particle_core::obstacles;
+use particle_core::patterns::{CradleOutcome, SpawnPattern};
+use particle_core::settings::{Wall, Walls};
+use particle_core::simulation::Simulation;
+use particle_core::simulation3d::Simulation3d;
+use particle_core::spawn::SpawnSettings;
+use particle_core::utils::Vec2;
+use particle_simulator::autotune;
+use particle_simulator::cli::{Args, Command, EnsembleArgs, ExperimentsArgs, SweepArgs};
+use particle_simulator::clock::DEFAULT_FPS;
+use particle_simulator::coloring::Coloring;
+use particle_simulator::config::Config;
+use particle_simulator::ensemble;
+use particle_simulator::experiments::{self, RunSpec};
+use particle_simulator::free_path::FreePaths;
+use particle_simulator::lockstep::Lockstep;
+use particle_simulator::logging;
+use particle_simulator::metrics::{Metrics, MetricsServer};
+use particle_simulator::osc::{self, OscSender};
+#[cfg(any(feature = "flo-draw", feature = "pixels", feature = "terminal"))]
+use particle_simulator::renderer::Backend;
+#[cfg(feature = "pixels")]
+use particle_simulator::renderer::PixelsRenderer;
+#[cfg(feature = "terminal")]
+use particle_simulator::renderer::TerminalRenderer;
+#[cfg(any(feature = "pixels", feature = "terminal"))]
+use particle_simulator::renderer::{self, Renderer};
+use particle_simulator::server::{self, ServeOptions};
+use particle_simulator::shm::FrameRing;
+use particle_simulator::stress::{self, StressOptions};
+use particle_simulator::svg::{self, Trails};
+use particle_simulator::sweep;
+use particle_simulator::timeline::Timeline;
+use particle_simulator::utils;
 
+use std::fs;
+use std::process;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "flo-draw")]
+mod window;
 
 fn main() {
-    let number_of_particles = utils::read_args();
+    let mut args = utils::read_args();
+    if let Err(err) = logging::init(&args.log_level, args.log_json) {
+        exit_with(err);
+    }
+    if let Some(command) = &args.command {
+        let result = match command {
+            Command::Experiments(experiments) => run_experiments(experiments),
+            Command::Sweep(sweep) => run_sweep(sweep),
+            Command::Ensemble(ensemble) => run_ensemble(ensemble),
+        };
+        if let Err(err) = result {
+            exit_with(err);
+        }
+        return;
+    }
+    if let Some(path) = args.config.clone() {
+        if let Err(err) = Config::load(&path).and_then(|config| config.apply(&mut args)) {
+            exit_with(err);
+        }
+    }
+    if args.three_d {
+        run_3d(&args);
+        return;
+    }
+    if args.stress {
+        if let Err(err) = run_stress(&args) {
+            exit_with(err);
+        }
+        return;
+    }
+
+    if args.autotune {
+        if let Err(err) = autotune(&mut args) {
+            exit_with(err);
+        }
+    }
+    if !args.compare.is_empty() {
+        // The two sides only start out the same from the same seed
+        let seed = *args.seed.get_or_insert_with(utils::unix_time);
+        println!("Comparing two simulations with seed {}", seed);
+    }
+    let lockstep = args.lockstep.map(|peer| {
+        let seed = *args.seed.get_or_insert_with(utils::unix_time);
+        match Lockstep::connect(peer, args.bind, seed) {
+            Ok((lockstep, seed)) => {
+                println!("Running in lockstep with seed {}", seed);
+                args.seed = Some(seed);
+                lockstep
+            }
+            Err(err) => exit_with(err),
+        }
+    });
+    let simulation = match start_simulation(&args) {
+        Ok(simulation) => simulation,
+        Err(err) => exit_with(err),
+    };
+
+    if args.serve.is_some() || args.http.is_some() {
+        let options = ServeOptions {
+            address: args.bind,
+            websocket_port: args.serve,
+            http_port: args.http,
+            metrics_port: args.metrics,
+            format: args.stream_format,
+            max_particles: args.max_particles,
+        };
+        if let Err(err) = server::serve(simulation, options) {
+            exit_with(err);
+        }
+    } else if args.event_driven {
+        if let Err(err) = run_event_driven(simulation, &args) {
+            exit_with(err);
+        }
+    } else if args.headless || args.metrics.is_some() || lockstep.is_some() {
+        run_headless(simulation, &args, lockstep);
+    } else if !args.compare.is_empty() {
+        let mut changed = match create_simulation(&args) {
+            Ok(simulation) => simulation,
+            Err(err) => exit_with(err),
+        };
+        changed.set_paranoid(args.paranoid);
+        changed.set_verify_broadphase(args.verify_broadphase);
+        for setting in &args.compare {
+            setting.apply(changed.settings_mut());
+        }
+        #[cfg(not(feature = "flo-draw"))]
+        exit_with(SimError::InvalidArgument {
+            name: "side by side window (built without the flo-draw feature)".to_string(),
+            value: "--compare".to_string(),
+        });
+        #[cfg(feature = "flo-draw")]
+        window::run_side_by_side(simulation, changed, args);
+    } else {
+        #[cfg(not(feature = "sound"))]
+        if args.sonify {
+            exit_with(SimError::InvalidArgument {
+                name: "sonification (built without the sound feature)".to_string(),
+                value: "--sonify".to_string(),
+            });
+        }
+        #[cfg(not(feature = "midi"))]
+        if args.midi.is_some() {
+            exit_with(SimError::InvalidArgument {
+                name: "MIDI output (built without the midi feature)".to_string(),
+                value: "--midi".to_string(),
+            });
+        }
+        match args.renderer.unwrap_or_default() {
+            #[cfg(feature = "flo-draw")]
+            Backend::Flo => window::run_windowed(simulation, args),
+            #[cfg(feature = "pixels")]
+            Backend::Pixels => run_plain(&mut PixelsRenderer::new(), simulation, &args),
+            #[cfg(feature = "terminal")]
+            Backend::Terminal => run_plain(&mut TerminalRenderer::new(), simulation, &args),
+            #[allow(unreachable_patterns)]
+            backend => exit_with(SimError::InvalidArgument {
+                name: format!("renderer (built without the {} feature)", backend.feature()),
+                value: "--renderer".to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(any(feature = "pixels", feature = "terminal"))]
+fn run_plain(renderer: &mut impl Renderer, mut simulation: Simulation, args: &Args) {
+    //! Show the plain view of `--renderer` until its window is closed.
+    let result = renderer::run(
+        renderer,
+        &mut simulation,
+        Coloring::new(args.color_by, args.palette),
+        args.theme,
+        args.time_scale,
+        args.fps,
+    );
+    if let Err(err) = result {
+        exit_with(err);
+    }
+}
+
+fn run_experiments(args: &ExperimentsArgs) -> SimResult<()> {
+    //! Run the whole manifest, or only one of its runs when this is a child
+    //! process started by a parallel batch.
+    match args.only {
+        Some(index) => {
+            let manifest = experiments::load_manifest(&args.manifest)?;
+            experiments::run_entry(&manifest, index, &args.out).map(|_| ())
+        }
+        None => experiments::run_all(&args.manifest, &args.out, args.jobs as usize),
+    }
+}
+
+fn run_sweep(args: &SweepArgs) -> SimResult<()> {
+    let base = RunSpec {
+        seed: args.seed,
+        particles: args.particles,
+        steps: args.steps,
+        pattern: args.pattern.to_string(),
+        ..RunSpec::default()
+    };
+    match &args.out {
+        Some(path) => sweep::run(&base, &args.param, args.metric, fs::File::create(path)?),
+        None => sweep::run(&base, &args.param, args.metric, std::io::stdout().lock()),
+    }
+}
+
+fn run_ensemble(args: &EnsembleArgs) -> SimResult<()> {
+    let base = RunSpec {
+        seed: args.seed,
+        particles: args.particles,
+        steps: args.steps,
+        pattern: args.pattern.to_string(),
+        ..RunSpec::default()
+    };
+    let threads = match args.threads {
+        Some(threads) => threads as usize,
+        None => thread::available_parallelism().map_or(1, |threads| threads.get()),
+    };
+    let report = ensemble::run(&base, args.replicas as usize, threads)?;
+    for line in report.lines() {
+        println!("{}", line);
+    }
+    if let Some(path) = &args.out {
+        let json = serde_json::to_vec_pretty(&report).map_err(std::io::Error::from)?;
+        fs::write(path, json)?;
+    }
+    Ok(())
+}
+
+fn start_simulation(args: &Args) -> SimResult<Simulation> {
+    //! Create the simulation, with the checks and the outputs it was asked for.
+    let mut simulation = create_simulation(args)?;
+    simulation.set_paranoid(args.paranoid);
+    simulation.set_verify_broadphase(args.verify_broadphase);
+    // Only the HUD of --profile shows the contacts
+    simulation.set_track_contacts(args.profile);
+    attach_outputs(&mut simulation, args)?;
+    Ok(simulation)
+}
+
+fn create_simulation(args: &Args) -> SimResult<Simulation> {
+    //! Resume from the newest checkpoint if asked to and one exists,
+    //! otherwise start a new simulation with random particles.
+    if args.resume {
+        if let Some(path) = checkpoint::latest_checkpoint(&args.checkpoint_dir)? {
+            let simulation = Simulation::from_checkpoint(Checkpoint::load(&path)?);
+            println!(
+                "Resuming particle simulation from frame {} with {} particles",
+                simulation.frame(),
+                simulation.particle_count()
+            );
+            tracing::info!(path = %path.display(), frame = simulation.frame(), "resuming");
+            return Ok(simulation);
+        }
+        tracing::warn!(dir = %args.checkpoint_dir.display(), "no checkpoint to resume from");
+    }
+
+    let particle_count = args.particle_count();
     println!(
         "Begginning particle simulation with {} particles",
-        number_of_particles
+        particle_count
+    );
+    tracing::info!(
+        particles = particle_count,
+        headless = args.headless
+            || args.serve.is_some()
+            || args.http.is_some()
+            || args.metrics.is_some()
+            || args.lockstep.is_some(),
+        "starting simulation"
     );
-    with_2d_graphics(move || {
-        let canvas = create_drawing_window("Particle Simulator");
+    let mut simulation = match args.seed {
+        Some(seed) => Simulation::with_seed(seed),
+        None => Simulation::new(),
+    };
+    apply_settings(&mut simulation, args);
+    simulation.settings_mut().sweep_axis = args.sweep_axis;
+    if let Some(path) = &args.map {
+        simulation.set_obstacles(obstacles::load_map(path)?);
+    }
+    simulation.set_portals(args.portals.clone());
+    simulation.set_time_regions(args.time_regions.clone());
+    *simulation.spawn_settings_mut() = spawn_settings(args);
+    simulation.spawn_settings().validate()?;
+    simulation.spawn_pattern(args.pattern, particle_count)?;
+    Ok(simulation)
+}
 
-        //Clear the canvas to set a background colour
-        canvas.draw(|gc| {
-            gc.clear_canvas(Color::Rgba(0.0, 0.0, 0.0, 1.0));
-        });
+fn apply_settings(simulation: &mut Simulation, args: &Args) {
+    //! Set up the walls, the forces and the speed limit, which can change while
+    //! the simulation runs.
+    let wall = Wall {
+        restitution: args.wall_restitution,
+        friction: args.wall_friction,
+        ..Wall::default()
+    };
+    let mut walls = Walls::uniform(wall);
+    walls.bottom = Wall {
+        restitution: args.floor_restitution.unwrap_or(wall.restitution),
+        friction: args.floor_friction.unwrap_or(wall.friction),
+        ..wall
+    };
+    for &(side, temperature) in &args.thermal_walls {
+        walls.side_mut(side).temperature = Some(temperature);
+    }
+    let settings = simulation.settings_mut();
+    settings.walls = walls;
+    settings.max_speed = args.max_speed;
+    settings.gravity = args
+        .gravity
+        .map_or(Vec2::ZERO, |gravity| Vec2(0.0, -gravity));
+    settings.medium = args.medium;
+    settings.mutual_gravity = args.mutual_gravity;
+    settings.out_of_bounds = args.out_of_bounds;
+    for &(species, scale) in &args.species_gravity {
+        simulation
+            .species_mut()
+            .set_gravity_scale(Species(species), scale);
+    }
+}
+
+fn spawn_settings(args: &Args) -> SpawnSettings {
+    SpawnSettings {
+        min_radius: args.min_radius,
+        max_radius: args.max_radius,
+        radius_distribution: args.radius_distribution,
+        speed_distribution: args.speed_distribution,
+        shape: args.shape,
+        densities: args.densities,
+        species: args.species,
+    }
+}
+
+fn attach_outputs(simulation: &mut Simulation, args: &Args) -> SimResult<()> {
+    //! Set up every file the simulation was asked to write to.
+    if let Some(path) = &args.profile_csv {
+        simulation.profiler_mut().export_csv(path)?;
+    }
+    if let Some(path) = &args.export_trajectories {
+        // A Parquet file is only readable once its footer is written at the end
+        if path.extension().is_some_and(|ext| ext == "parquet") && !args.finishes_exports() {
+            return Err(SimError::InvalidArgument {
+                name: "trajectory export (Parquet files need --headless)".to_string(),
+                value: path.display().to_string(),
+            });
+        }
+        let exporter = export::create_exporter(path, args.export_every)?;
+        simulation.add_exporter(exporter)?;
+    }
+    if let Some(path) = &args.export_msd {
+        let exporter = export::MsdExporter::create(path, args.export_every)?;
+        simulation.add_exporter(Box::new(exporter))?;
+    }
+    if let (Some(probes), Some(path)) = (&args.probes, &args.export_probes) {
+        let exporter =
+            export::ProbeExporter::create(path, args.export_every, utils::load_probes(probes)?)?;
+        simulation.add_exporter(Box::new(exporter))?;
+    }
+    if let Some(path) = &args.export_rdf {
+        let exporter = export::RdfExporter::create(path, args.export_every, args.rdf_range)?;
+        simulation.add_exporter(Box::new(exporter))?;
+    }
+    Ok(())
+}
+
+fn exit_with(err: SimError) -> ! {
+    //! Report an error to the user and stop the process.
+    tracing::error!(%err, "simulation stopped");
+    eprintln!("Error: {}", err);
+    if let SimError::InvariantViolated { frame, dump, .. } = &err {
+        let path = format!("invariant-dump-frame-{}.csv", frame);
+        match fs::write(&path, dump) {
+            Ok(()) => eprintln!("The state of every particle was written to {}", path),
+            Err(write_err) => eprintln!("Could not write the state dump: {}", write_err),
+        }
+    }
+    process::exit(1);
+}
 
-        let mut particles = Particle::particle_factory(number_of_particles);
+fn run_headless(mut simulation: Simulation, args: &Args, mut lockstep: Option<Lockstep>) {
+    //! Step the simulation as fast as possible without rendering anything,
+    //! either forever or until the requested number of steps, saving
+    //! checkpoints along the way if asked to. With `--lockstep` every step
+    //! waits for the other instance to take it too.
+    let mut free_paths = args.free_paths.as_ref().map(|_| FreePaths::new());
+    // A Newton's cradle is checked once, as soon as the strike reaches the far end
+    let mut cradle = match args.pattern {
+        SpawnPattern::Cradle { speed } => Some(speed),
+        _ => None,
+    };
+    let osc = args.osc.map(|target| match OscSender::new(target) {
+        Ok(sender) => sender,
+        Err(err) => exit_with(err),
+    });
+    let mut ring = args.shm.as_ref().map(|path| {
+        let capacity = args.max_particles.unwrap_or(args.particle_count());
+        match FrameRing::create(path, capacity) {
+            Ok(ring) => ring,
+            Err(err) => exit_with(err),
+        }
+    });
+    let metrics_server = args
+        .metrics
+        .map(|port| match MetricsServer::bind(args.bind, port) {
+            Ok(server) => server,
+            Err(err) => exit_with(err),
+        });
+    let mut metrics = Metrics::new();
+    // Only needed for --svg, but the colors can depend on the whole run
+    let mut coloring = Coloring::new(args.color_by, args.palette);
+    let mut trails = args.svg_trails.map(Trails::new);
+    let mut timeline = Timeline::new(&args.events, simulation.frame());
+    if let Some(lockstep) = &mut lockstep {
+        keep_in_step(lockstep, &simulation);
+    }
+    while args.steps.is_none_or(|steps| simulation.frame() < steps) {
+        let started = Instant::now();
+        if let Err(err) = simulation
+            .step(1.0)
+            .and_then(|_| simulation.profiler_mut().end_frame())
+            .and_then(|_| save_checkpoint(&simulation, args))
+        {
+            exit_with(err);
+        }
+        timeline.run(&mut simulation, args.max_particles);
+        if let Some(lockstep) = &mut lockstep {
+            keep_in_step(lockstep, &simulation);
+        }
+        if let Some(free_paths) = &mut free_paths {
+            free_paths.update(simulation.events(), simulation.world(), 1.0);
+        }
+        if let Some(osc) = &osc {
+            osc.send(&osc::collision_packets(simulation.events()));
+            osc.send(&osc::frame_packets(&simulation));
+        }
+        if let Some(ring) = &mut ring {
+            ring.publish(&simulation);
+        }
+        if args.svg.is_some() {
+            coloring.update(simulation.frame(), simulation.events(), simulation.world());
+            if let Some(trails) = &mut trails {
+                trails.record(simulation.world());
+            }
+        }
+        if let Some(server) = &metrics_server {
+            metrics.record_step(&simulation, started.elapsed());
+            server.poll(&metrics);
+        }
+        if let Some(speed) = cradle {
+            if let Some(outcome) = CradleOutcome::measure(simulation.world()) {
+                report_cradle(&outcome, speed, simulation.frame());
+                cradle = None;
+            }
+        }
+    }
+    tracing::info!(steps = simulation.frame(), "headless run finished");
+    if lockstep.is_some() {
+        println!(
+            "Both instances agreed on every frame up to {}",
+            simulation.frame()
+        );
+    }
+    if let Err(err) = simulation.finish_exports() {
+        exit_with(err);
+    }
+    if let Some(path) = &args.svg {
+        let snapshot = svg::render(&simulation, &coloring, args.theme, trails.as_ref());
+        if let Err(err) = fs::write(path, snapshot) {
+            exit_with(err.into());
+        }
+    }
+    if let (Some(free_paths), Some(path)) = (&free_paths, &args.free_paths) {
+        if let Err(err) = free_paths.write_csv(path) {
+            exit_with(err.into());
+        }
+        for line in free_paths.lines() {
+            println!("{}", line);
+        }
+    }
 
-        for particle in particles.iter() {
-            particle.draw(SpriteId(particle.id), &canvas, utils::get_random_color())
+    if let Some(divergences) = simulation.broadphase_divergences() {
+        println!(
+            "Broadphase: {} pairs found by only one of sweep and prune and the reference",
+            divergences
+        );
+    }
+    if args.profile {
+        for line in simulation.profiler().summary() {
+            println!("{}", line);
         }
+    }
+}
 
-        loop {
-            for particle in particles.iter_mut() {
-                particle.update(1.0);
+fn keep_in_step(lockstep: &mut Lockstep, simulation: &Simulation) {
+    //! Compare this frame with the other instance, and stop at the first one
+    //! where the two have diverged.
+    let hash = simulation.state_hash();
+    match lockstep.exchange(simulation.frame(), hash) {
+        Ok(None) => {}
+        Ok(Some(theirs)) => {
+            eprintln!(
+                "Diverged from the other instance at frame {}: state hash {:016x} here, {:016x} there",
+                simulation.frame(),
+                hash,
+                theirs
+            );
+            if simulation.frame() == 0 {
+                eprintln!("The two started out different, run both with the same arguments");
             }
+            process::exit(1);
+        }
+        Err(err) => exit_with(err),
+    }
+}
 
-            detect_and_apply_collisions(&mut particles);
-
-            // At this point, actual_collisions contains all of the index pairs of collisions
-            // Now we just need to iterate one by one, and apply the collision updates
-
-            canvas.draw(|gc| {
-                gc.layer(LayerId(0));
-                gc.clear_layer();
-                gc.canvas_height(1000.0);
-                gc.center_region(0.0, 0.0, 1000.0, 1000.0);
-
-                for particle in particles.iter() {
-                    // Render the ball's sprite at its location
-                    gc.sprite_transform(SpriteTransform::Identity);
-                    gc.sprite_transform(SpriteTransform::Translate(
-                        particle.position_x as f32,
-                        particle.position_y as f32,
-                    ));
-                    gc.draw_sprite(SpriteId(particle.id));
-                }
-            });
-            // Wait for the next frame
-            thread::sleep(Duration::from_nanos(1_000_000_123 / 45));
+fn run_event_driven(simulation: Simulation, args: &Args) -> SimResult<()> {
+    //! Advance an event-driven copy of the simulation one unit of time at a time,
+    //! and with --cross-validate step the original alongside it, reporting how
+    //! far apart the two have drifted every `CROSS_VALIDATION_INTERVAL`.
+    let mut exact = EventDriven::from_simulation(&simulation)?;
+    // Steps that do not divide 1 evenly are stretched until they do
+    let mut stepped = args
+        .cross_validate
+        .map(|dt| (simulation, (1.0 / dt).round().max(1.0) as u64));
+    let mut stepped_contacts = 0;
+    let mut elapsed: u64 = 0;
+    while args.steps.is_none_or(|steps| elapsed < steps) {
+        exact.advance(1.0);
+        elapsed += 1;
+        let Some((stepped, steps)) = &mut stepped else {
+            continue;
+        };
+        for _ in 0..*steps {
+            stepped.step(1.0 / *steps as f64)?;
+            stepped_contacts += stepped.events().len();
+        }
+        if elapsed.is_multiple_of(CROSS_VALIDATION_INTERVAL) {
+            let comparison = Comparison::measure(&exact, stepped);
+            tracing::info!(
+                time = comparison.time,
+                rms_distance = comparison.rms_distance,
+                max_distance = comparison.max_distance,
+                "cross-validation"
+            );
+            println!(
+                "t = {}: {} collisions exact, {} contacts stepped, particles apart by {:.4} on average \
+                 and {:.4} at most, energy {:.6e} exact and {:.6e} stepped",
+                comparison.time,
+                exact.collisions(),
+                stepped_contacts,
+                comparison.rms_distance,
+                comparison.max_distance,
+                comparison.exact_energy,
+                comparison.stepped_energy
+            );
         }
-    })
+    }
+    tracing::info!(
+        time = exact.time(),
+        collisions = exact.collisions(),
+        "event-driven run finished"
+    );
+    println!(
+        "Event-driven run finished at t = {}: {} collisions and {} wall bounces",
+        exact.time(),
+        exact.collisions(),
+        exact.wall_bounces()
+    );
+    Ok(())
 }
+
+fn report_cradle(outcome: &CradleOutcome, speed: f64, frame: u64) {
+    let verdict = if outcome.is_correct(speed) {
+        "correct"
+    } else {
+        "WRONG"
+    };
+    tracing::info!(
+        frame,
+        moving = outcome.moving,
+        speed = outcome.speed,
+        verdict,
+        "Newton's cradle"
+    );
+    println!(
+        "Newton's cradle at frame {}: {} particle(s) moving, the last at {:.6} (struck at {}): {}",
+        frame, outcome.moving, outcome.speed, speed, verdict
+    );
+}
+
+fn save_checkpoint(simulation: &Simulation, args: &Args) -> SimResult<()> {
+    match args.checkpoint_every {
+        Some(every) if simulation.frame().is_multiple_of(every) => {
+            let path = simulation.checkpoint().save_to_dir(&args.checkpoint_dir)?;
+            tracing::info!(path = %path.display(), "saved checkpoint");
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn autotune(args: &mut Args) -> SimResult<()> {
+    //! Calibrate this machine for the requested particles and frame rate, print
+    //! what was picked and apply it to `args`.
+    let frame_interval = args
+        .fps
+        .interval()
+        .unwrap_or(Duration::from_secs_f64(1.0 / DEFAULT_FPS));
+    let options = StressOptions {
+        // The same share of the frame that `AdaptiveQuality` allows the physics
+        budget: frame_interval / 2,
+        steps: AUTOTUNE_STEPS,
+        start: args.particle_count(),
+        limit: args.max_particles.unwrap_or(1_000_000),
+        seed: args.seed.unwrap_or(0),
+        spawn_settings: spawn_settings(args),
+        paranoid: false,
+        sweep_axis: args.sweep_axis,
+    };
+    options.spawn_settings.validate()?;
+    println!("Calibrating for {} particles...", options.start);
+    let tuning = autotune::run(
+        &options,
+        autotune::steps_per_frame(args.time_scale, frame_interval),
+    )?;
+    for line in tuning.lines(options.start) {
+        println!("  {}", line);
+    }
+    tracing::info!(?tuning, "autotuned");
+    args.sweep_axis = tuning.sweep_axis;
+    args.max_particles = Some(tuning.particle_budget.unwrap_or(1).min(options.limit));
+    args.catch_up_steps = Some(tuning.catch_up_steps);
+    Ok(())
+}
+
+fn run_stress(args: &Args) -> SimResult<()> {
+    //! Find the most particles that fit in the frame budget, and report it.
+    let options = StressOptions {
+        budget: match args.frame_budget {
+            Some(ms) => Duration::from_secs_f64(ms / 1000.0),
+            None => args
+                .fps
+                .interval()
+                .unwrap_or(Duration::from_secs_f64(1.0 / DEFAULT_FPS)),
+        },
+        steps: args.steps.unwrap_or(20),
+        start: args.particle_count(),
+        limit: args.max_particles.unwrap_or(1_000_000),
+        seed: args.seed.unwrap_or(0),
+        spawn_settings: spawn_settings(args),
+        paranoid: args.paranoid,
+        sweep_axis: args.sweep_axis,
+    };
+    options.spawn_settings.validate()?;
+    println!(
+        "Stress test with seed {}, within {:.3} ms per step",
+        options.seed,
+        options.budget.as_secs_f64() * 1000.0
+    );
+    let report = stress::run(&options)?;
+    match report.max_stable {
+        Some(count) => println!(
+            "{}: at most {} particles ({:.3} ms per step)",
+            report.broadphase,
+            count,
+            report.step_time.as_secs_f64() * 1000.0
+        ),
+        None => println!(
+            "{}: even {} particles are too slow",
+            report.broadphase, options.start
+        ),
+    }
+    Ok(())
+}
+
+fn run_3d(args: &Args) {
+    //! Simulate spheres instead of circles, in a window or headless.
+    let particle_count = args.particle_count();
+    println!(
+        "Begginning 3D particle simulation with {} particles",
+        particle_count
+    );
+    let mut simulation = match args.seed {
+        Some(seed) => Simulation3d::with_seed(seed),
+        None => Simulation3d::new(),
+    };
+    *simulation.spawn_settings_mut() = spawn_settings(args);
+    if let Err(err) = simulation.spawn_settings().validate() {
+        exit_with(err);
+    }
+    simulation.spawn_random(particle_count);
+
+    if args.headless {
+        while args.steps.is_none_or(|steps| simulation.frame() < steps) {
+            simulation.step(1.0);
+        }
+        tracing::info!(
+            steps = simulation.frame(),
+            energy = simulation.kinetic_energy(),
+            "headless 3D run finished"
+        );
+    } else {
+        #[cfg(not(feature = "flo-draw"))]
+        exit_with(SimError::InvalidArgument {
+            name: "3D window (built without the flo-draw feature)".to_string(),
+            value: "--3d".to_string(),
+        });
+        #[cfg(feature = "flo-draw")]
+        window::run_windowed_3d(simulation, args.time_scale, args.fps);
+    }
+}
+
+/// How many steps each broadphase and particle count is timed for by --autotune.
+const AUTOTUNE_STEPS: u64 = 20;
+
+/// How many units of time pass between the reports of --cross-validate.
+const CROSS_VALIDATION_INTERVAL: u64 = 10;
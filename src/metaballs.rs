@@ -0,0 +1,189 @@
+//! `--metaballs`: draw the particles as one fluid surface rather than as separate
+//! circles. Every particle spreads a soft bump of density around itself, falling
+//! to nothing at twice its radius, and the density is summed on a grid. Marching
+//! squares then fills every part of the grid above a threshold, chosen so that a
+//! lone particle is drawn at its own size, while particles that come close
+//! together merge into one blob.
+use flo_canvas::*;
+use particle_core::particle::{HEIGHT, WIDTH};
+use particle_core::utils::Vec2;
+use std::collections::BTreeMap;
+
+/// How far the density of a particle reaches, in radii.
+const REACH: f64 = 2.0;
+/// The density at the edge of the surface: that of a lone particle at one radius
+/// from its center.
+const THRESHOLD: f64 = (1.0 - 1.0 / (REACH * REACH)) * (1.0 - 1.0 / (REACH * REACH));
+/// The side of the cells of the grid, in world units.
+pub const CELL: f64 = 8.0;
+/// Each color channel of the surface is rounded to one of this many levels, so
+/// that the cells can be filled in a few paths rather than one each.
+const COLOR_LEVELS: f32 = 16.0;
+
+/// The summed density of all the particles on the nodes of a grid over the box,
+/// with the color of each node weighted by how much density each particle gave it.
+pub struct Field {
+    cell: f64,
+    columns: usize,
+    rows: usize,
+    density: Vec<f64>,
+    colors: Vec<[f32; 4]>,
+}
+
+impl Field {
+    pub fn new(cell: f64) -> Self {
+        let columns = (WIDTH / cell).ceil() as usize + 1;
+        let rows = (HEIGHT / cell).ceil() as usize + 1;
+        Field {
+            cell,
+            columns,
+            rows,
+            density: vec![0.0; columns * rows],
+            colors: vec![[0.0; 4]; columns * rows],
+        }
+    }
+
+    fn node(&self, column: usize, row: usize) -> Vec2 {
+        Vec2(column as f64 * self.cell, row as f64 * self.cell)
+    }
+
+    pub fn add(&mut self, center: Vec2, radius: f64, color: [f32; 4]) {
+        //! Spread the density of a particle over the nodes it reaches.
+        let reach = radius * REACH;
+        let span = |low: f64, high: f64, nodes: usize| {
+            let first = (low / self.cell).floor().max(0.0) as usize;
+            let last = ((high / self.cell).ceil().max(0.0) as usize).min(nodes - 1);
+            first..=last
+        };
+        let columns = span(center.0 - reach, center.0 + reach, self.columns);
+        for row in span(center.1 - reach, center.1 + reach, self.rows) {
+            for column in columns.clone() {
+                let offset = self.node(column, row) - center;
+                let distance = offset.length() / reach;
+                if distance < 1.0 {
+                    let weight = (1.0 - distance * distance).powi(2);
+                    let index = row * self.columns + column;
+                    self.density[index] += weight;
+                    for (channel, value) in self.colors[index].iter_mut().zip(color) {
+                        *channel += value * weight as f32;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn is_inside(&self, point: Vec2) -> bool {
+        //! Whether the node nearest to `point` is under the surface.
+        let column = ((point.0 / self.cell).round().max(0.0) as usize).min(self.columns - 1);
+        let row = ((point.1 / self.cell).round().max(0.0) as usize).min(self.rows - 1);
+        self.density[row * self.columns + column] >= THRESHOLD
+    }
+
+    pub fn polygons(&self) -> Vec<(Vec<Vec2>, [f32; 4])> {
+        //! The part of every cell under the surface, with its color. Going round
+        //! the corners of a cell, the polygon takes every corner that is inside,
+        //! and the point between two corners where the density crosses the
+        //! threshold whenever only one of them is inside.
+        let mut polygons = vec![];
+        for row in 0..self.rows - 1 {
+            for column in 0..self.columns - 1 {
+                let corners = [(0, 0), (1, 0), (1, 1), (0, 1)]
+                    .map(|(x, y)| (column + x, row + y))
+                    .map(|(x, y)| (self.node(x, y), y * self.columns + x));
+                let inside = corners.map(|(_, index)| self.density[index] >= THRESHOLD);
+                if !inside.contains(&true) {
+                    continue;
+                }
+
+                let mut polygon = Vec::with_capacity(8);
+                let mut color = [0.0; 4];
+                let mut weight = 0.0;
+                for corner in 0..4 {
+                    let (position, index) = corners[corner];
+                    let (next_position, next_index) = corners[(corner + 1) % 4];
+                    if inside[corner] {
+                        polygon.push(position);
+                        for (total, value) in color.iter_mut().zip(self.colors[index]) {
+                            *total += value;
+                        }
+                        weight += self.density[index] as f32;
+                    }
+                    if inside[corner] != inside[(corner + 1) % 4] {
+                        let (a, b) = (self.density[index], self.density[next_index]);
+                        let t = (THRESHOLD - a) / (b - a);
+                        polygon.push(position + (next_position - position).scale(t));
+                    }
+                }
+                polygons.push((polygon, color.map(|total| total / weight)));
+            }
+        }
+        polygons
+    }
+}
+
+pub fn draw(gc: &mut impl GraphicsContext, field: &Field) {
+    //! Fill the surface, in one path for each color it is rounded to.
+    let mut paths: BTreeMap<[u8; 4], Vec<Vec<Vec2>>> = BTreeMap::new();
+    for (polygon, color) in field.polygons() {
+        let level =
+            color.map(|channel| (channel.clamp(0.0, 1.0) * (COLOR_LEVELS - 1.0)).round() as u8);
+        paths.entry(level).or_default().push(polygon);
+    }
+    for (level, polygons) in paths {
+        gc.new_path();
+        for polygon in polygons {
+            if let Some((first, rest)) = polygon.split_first() {
+                gc.move_to(first.0 as f32, first.1 as f32);
+                for point in rest {
+                    gc.line_to(point.0 as f32, point.1 as f32);
+                }
+                gc.close_path();
+            }
+        }
+        let [r, g, b, a] = level.map(|level| level as f32 / (COLOR_LEVELS - 1.0));
+        gc.fill_color(Color::Rgba(r, g, b, a));
+        gc.fill();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_a_lone_particle_keeps_its_size() {
+        let mut field = Field::new(1.0);
+        field.add(Vec2(500.0, 500.0), 20.0, [1.0, 0.0, 0.0, 1.0]);
+        assert!(field.is_inside(Vec2(519.0, 500.0)));
+        assert!(!field.is_inside(Vec2(521.0, 500.0)));
+        let polygons = field.polygons();
+        assert!(polygons
+            .iter()
+            .all(|(_, color)| (color[0] - 1.0).abs() < 1e-5 && color[1] == 0.0));
+        // The surface crosses the edges of the cells at the radius
+        assert!(polygons
+            .iter()
+            .flat_map(|(polygon, _)| polygon)
+            .any(|point| (point.0 - 520.0).abs() < 0.1 && point.1 == 500.0));
+    }
+
+    #[test]
+    fn test_close_particles_merge_into_one_blob() {
+        let mut field = Field::new(CELL);
+        // Touching, and so joined at the middle
+        field.add(Vec2(200.0, 500.0), 20.0, [1.0; 4]);
+        field.add(Vec2(240.0, 500.0), 20.0, [1.0; 4]);
+        assert!(field.is_inside(Vec2(220.0, 500.0)));
+        // Further apart, and so still two blobs
+        field.add(Vec2(600.0, 500.0), 20.0, [1.0; 4]);
+        field.add(Vec2(660.0, 500.0), 20.0, [1.0; 4]);
+        assert!(!field.is_inside(Vec2(630.0, 500.0)));
+
+        let mut drawing: Vec<Draw> = vec![];
+        draw(&mut drawing, &field);
+        assert_eq!(
+            drawing.iter().filter(|draw| **draw == Draw::Fill).count(),
+            1
+        );
+    }
+}
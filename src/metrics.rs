@@ -0,0 +1,186 @@
+//! `--metrics PORT`: serve measurements of a headless run at `/metrics` in the
+//! Prometheus text format, so that a long run can be scraped and graphed in
+//! Grafana. The gauges are
+//!
+//! - `particle_simulator_step_duration_seconds`, the wall time of the last step,
+//! - `particle_simulator_particles`,
+//! - `particle_simulator_collision_rate`, the contacts over the last second of
+//!   simulated time,
+//! - `particle_simulator_kinetic_energy`,
+//! - `particle_simulator_frame_lag_seconds`, how far the last frame ran past its
+//!   deadline, which is always 0 for `--headless` since it keeps to no frame rate,
+//!
+//! and the counters `particle_simulator_steps_total` and
+//! `particle_simulator_collisions_total`.
+use crate::clock::STEPS_PER_SECOND;
+use particle_core::error::{SimError, SimResult};
+use particle_core::simulation::Simulation;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::io;
+use std::net::IpAddr;
+use std::time::Duration;
+use tiny_http::{Header, Response, Server};
+
+/// The prefix of every metric name.
+const NAMESPACE: &str = "particle_simulator";
+
+/// The latest measurements of a simulation, updated every step.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    steps: u64,
+    collisions: u64,
+    step_duration: Duration,
+    particles: usize,
+    kinetic_energy: f64,
+    frame_lag: Duration,
+    /// The contacts of each of the last second's worth of steps, oldest first.
+    recent: VecDeque<usize>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    pub fn record_step(&mut self, simulation: &Simulation, duration: Duration) {
+        //! Take the measurements of a step that has just finished in `duration`.
+        let contacts = simulation.events().len();
+        self.steps += 1;
+        self.collisions += contacts as u64;
+        self.step_duration = duration;
+        self.particles = simulation.particle_count();
+        self.kinetic_energy = simulation.kinetic_energy();
+        self.recent.push_back(contacts);
+        if self.recent.len() > STEPS_PER_SECOND as usize {
+            self.recent.pop_front();
+        }
+    }
+
+    pub fn record_lag(&mut self, lag: Duration) {
+        self.frame_lag = lag;
+    }
+
+    pub fn collision_rate(&self) -> f64 {
+        //! Contacts per second of simulated time, averaged over the last second.
+        if self.recent.is_empty() {
+            return 0.0;
+        }
+        self.recent.iter().sum::<usize>() as f64 * STEPS_PER_SECOND / self.recent.len() as f64
+    }
+
+    pub fn render(&self) -> String {
+        //! Every metric in the Prometheus text exposition format.
+        let mut text = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+            let name = format!("{}_{}", NAMESPACE, name);
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} {}", name, kind);
+            let _ = writeln!(text, "{} {}", name, value);
+        };
+        metric(
+            "step_duration_seconds",
+            "gauge",
+            "Wall time taken by the last step.",
+            self.step_duration.as_secs_f64(),
+        );
+        metric(
+            "steps_total",
+            "counter",
+            "Steps taken since the start of the run.",
+            self.steps as f64,
+        );
+        metric(
+            "particles",
+            "gauge",
+            "Particles in the simulation.",
+            self.particles as f64,
+        );
+        metric(
+            "collisions_total",
+            "counter",
+            "Contacts resolved since the start of the run.",
+            self.collisions as f64,
+        );
+        metric(
+            "collision_rate",
+            "gauge",
+            "Contacts per second of simulated time, over the last second.",
+            self.collision_rate(),
+        );
+        metric(
+            "kinetic_energy",
+            "gauge",
+            "Total kinetic energy of the particles.",
+            self.kinetic_energy,
+        );
+        metric(
+            "frame_lag_seconds",
+            "gauge",
+            "How far the last frame ran past its deadline.",
+            self.frame_lag.as_secs_f64(),
+        );
+        text
+    }
+}
+
+/// The HTTP server that Prometheus scrapes.
+pub struct MetricsServer {
+    server: Server,
+}
+
+impl MetricsServer {
+    pub fn bind(address: IpAddr, port: u16) -> SimResult<Self> {
+        let server = Server::http((address, port))
+            .map_err(|err| SimError::Io(io::Error::other(err.to_string())))?;
+        tracing::info!(%address, port, "serving Prometheus metrics at /metrics");
+        Ok(MetricsServer { server })
+    }
+
+    pub fn poll(&self, metrics: &Metrics) {
+        //! Answer every scrape that has arrived since the last step, without blocking.
+        while let Ok(Some(request)) = self.server.try_recv() {
+            let path = request.url().split('?').next().unwrap_or_default();
+            let response = if path == "/metrics" {
+                let content_type = Header::from_bytes("Content-Type", "text/plain; version=0.0.4")
+                    .expect("the content type header is valid");
+                Response::from_string(metrics.render()).with_header(content_type)
+            } else {
+                Response::from_string("no such endpoint, try /metrics").with_status_code(404)
+            };
+            if let Err(err) = request.respond(response) {
+                tracing::warn!(%err, "failed to send metrics");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use particle_core::particle::Particle;
+    use particle_core::utils::Vec2;
+
+    #[test]
+    fn test_render_in_the_text_format() {
+        let mut simulation = Simulation::new();
+        simulation.spawn(Particle::new(0, 5.0, Vec2(100.0, 100.0), Vec2(1.0, 0.0)));
+        simulation.spawn(Particle::new(1, 5.0, Vec2(200.0, 100.0), Vec2(0.0, 0.0)));
+        let mut metrics = Metrics::new();
+        simulation.step(1.0).unwrap();
+        metrics.record_step(&simulation, Duration::from_millis(2));
+        metrics.record_lag(Duration::from_millis(5));
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE particle_simulator_steps_total counter\n"));
+        assert!(text.contains("\nparticle_simulator_steps_total 1\n"));
+        assert!(text.contains("\nparticle_simulator_particles 2\n"));
+        assert!(text.contains("\nparticle_simulator_step_duration_seconds 0.002\n"));
+        assert!(text.contains("\nparticle_simulator_frame_lag_seconds 0.005\n"));
+        assert!(text.contains(&format!(
+            "\nparticle_simulator_kinetic_energy {}\n",
+            simulation.kinetic_energy()
+        )));
+        assert_eq!(metrics.collision_rate(), 0.0);
+    }
+}
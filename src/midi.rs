@@ -0,0 +1,176 @@
+//! `--midi`: play every collision as a MIDI note, to drive a synthesizer with the
+//! simulation as a generative music source. The mass of the heavier particle
+//! picks the pitch and the impulse picks how hard the note is struck. `Notes`
+//! works out the notes, and `MidiOut` sends them, which needs the `midi` feature.
+#[cfg(feature = "midi")]
+mod output;
+
+#[cfg(feature = "midi")]
+pub use self::output::MidiOut;
+
+use particle_core::error::{SimError, SimResult};
+use particle_core::events::Event;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// At most this many notes sound for one step, the hardest collisions first, so
+/// that a crowded step plays a chord instead of flooding the synthesizer.
+const MAX_NOTES_PER_STEP: usize = 8;
+
+/// The notes the heaviest and the lightest particles play, written
+/// `<heaviest>:<lightest>` with MIDI note numbers from 0 to 127. Masses between
+/// them are spread out on a log scale, and the heaviest may play the higher note.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteRange {
+    pub heaviest: u8,
+    pub lightest: u8,
+}
+
+impl Default for NoteRange {
+    fn default() -> Self {
+        //! Two octaves either side of middle C, with heavy particles low.
+        NoteRange {
+            heaviest: 36,
+            lightest: 84,
+        }
+    }
+}
+
+impl fmt::Display for NoteRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.heaviest, self.lightest)
+    }
+}
+
+impl FromStr for NoteRange {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        let invalid = || SimError::InvalidArgument {
+            name: "MIDI note range".to_string(),
+            value: value.to_string(),
+        };
+        let (heaviest, lightest) = value.split_once(':').ok_or_else(invalid)?;
+        let note = |note: &str| match note.trim().parse::<u8>() {
+            Ok(note) if note <= 127 => Ok(note),
+            _ => Err(invalid()),
+        };
+        Ok(NoteRange {
+            heaviest: note(heaviest)?,
+            lightest: note(lightest)?,
+        })
+    }
+}
+
+/// A note to strike, with a key and a velocity from 1 to 127.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Note {
+    pub key: u8,
+    pub velocity: u8,
+}
+
+/// Turns the collisions of each step into notes. Velocities are scaled to the
+/// hardest impulse heard so far, so that the dynamics adapt to any simulation.
+#[derive(Debug, Clone)]
+pub struct Notes {
+    range: NoteRange,
+    hardest: f64,
+}
+
+impl Notes {
+    pub fn new(range: NoteRange) -> Self {
+        Notes {
+            range,
+            hardest: 0.0,
+        }
+    }
+
+    pub fn notes(&mut self, events: &[Event], masses: &HashMap<u64, f64>) -> Vec<Note> {
+        //! The notes for the contacts among `events`, given the mass of every
+        //! particle by id. Particles of infinite mass count as the heaviest.
+        let finite = masses.values().copied().filter(|mass| mass.is_finite());
+        let (lightest, heaviest) = finite.fold((f64::INFINITY, 0.0_f64), |(low, high), mass| {
+            (low.min(mass), high.max(mass))
+        });
+        let mut contacts: Vec<(f64, f64)> = events
+            .iter()
+            .map(|event| match event {
+                Event::Contact { a, b, impulse, .. } => {
+                    let mass = |id| masses.get(id).copied().unwrap_or(heaviest);
+                    (mass(a).max(mass(b)), impulse.abs())
+                }
+            })
+            .filter(|(_, impulse)| impulse.is_finite() && *impulse > 0.0)
+            .collect();
+        contacts.sort_by(|a, b| b.1.total_cmp(&a.1));
+        contacts.truncate(MAX_NOTES_PER_STEP);
+        for &(_, impulse) in &contacts {
+            self.hardest = self.hardest.max(impulse);
+        }
+        contacts
+            .into_iter()
+            .map(|(mass, impulse)| {
+                // How light the particle is, from 0 for the heaviest to 1 for the lightest
+                let lightness = if heaviest > lightest {
+                    let mass = mass.clamp(lightest, heaviest);
+                    (heaviest.ln() - mass.ln()) / (heaviest.ln() - lightest.ln())
+                } else {
+                    0.5
+                };
+                let (low, high) = (self.range.heaviest as f64, self.range.lightest as f64);
+                Note {
+                    key: (low + (high - low) * lightness).round() as u8,
+                    velocity: (impulse / self.hardest * 127.0).round().clamp(1.0, 127.0) as u8,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use particle_core::shapes::Contact;
+    use particle_core::utils::Vec2;
+
+    #[test]
+    fn test_mass_picks_the_key_and_impulse_the_velocity() {
+        let contact = |a, b, impulse| Event::Contact {
+            a,
+            b,
+            contact: Contact {
+                normal: Vec2(1.0, 0.0),
+                penetration_depth: 0.0,
+                point: Vec2::ZERO,
+            },
+            impulse,
+        };
+        let masses = HashMap::from([(1, 1.0), (2, 10.0), (3, 100.0), (4, f64::INFINITY)]);
+        let mut notes = Notes::new("36:84".parse().unwrap());
+        let played = notes.notes(
+            &[contact(1, 1, 2.0), contact(1, 2, 4.0), contact(3, 4, 1.0)],
+            &masses,
+        );
+        assert_eq!(
+            played,
+            [
+                Note {
+                    key: 60,
+                    velocity: 127
+                },
+                Note {
+                    key: 84,
+                    velocity: 64
+                },
+                Note {
+                    key: 36,
+                    velocity: 32
+                },
+            ]
+        );
+
+        assert!("36".parse::<NoteRange>().is_err());
+        assert!("36:128".parse::<NoteRange>().is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use super::Note;
+use midir::{MidiOutput, MidiOutputConnection};
+use particle_core::error::SimResult;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// How long each note sounds before it is released.
+const NOTE_LENGTH: Duration = Duration::from_millis(150);
+const NOTE_ON: u8 = 0x90;
+const NOTE_OFF: u8 = 0x80;
+
+/// A connection to a MIDI output port, and the notes still sounding on it.
+pub struct MidiOut {
+    connection: MidiOutputConnection,
+    /// Channel 1 to 16, counted from 0.
+    channel: u8,
+    sounding: Vec<(Instant, u8)>,
+}
+
+impl MidiOut {
+    pub fn connect(port: &str, channel: u8) -> SimResult<MidiOut> {
+        //! Open the first output port whose name contains `port`, ignoring case.
+        //! If none does, the error lists the ports there are.
+        let output = MidiOutput::new("particle-simulator").map_err(io::Error::other)?;
+        let ports = output.ports();
+        let names: Vec<String> = ports
+            .iter()
+            .map(|port| output.port_name(port).unwrap_or_default())
+            .collect();
+        let Some(index) = names
+            .iter()
+            .position(|name| name.to_lowercase().contains(&port.to_lowercase()))
+        else {
+            return Err(io::Error::other(format!(
+                "no MIDI output port matches '{}', the ports are: {}",
+                port,
+                names.join(", ")
+            ))
+            .into());
+        };
+        let connection = output
+            .connect(&ports[index], "particle-simulator")
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        println!("Playing collisions on MIDI port {}", names[index]);
+        Ok(MidiOut {
+            connection,
+            channel,
+            sounding: Vec::new(),
+        })
+    }
+
+    pub fn play(&mut self, notes: &[Note]) {
+        //! Release the notes that have sounded long enough, then strike `notes`.
+        let now = Instant::now();
+        let mut released = Vec::new();
+        self.sounding.retain(|&(struck, key)| {
+            let done = now.duration_since(struck) >= NOTE_LENGTH;
+            if done {
+                released.push(key);
+            }
+            !done
+        });
+        for key in released {
+            self.send(&[NOTE_OFF | self.channel, key, 0]);
+        }
+        for note in notes {
+            self.send(&[NOTE_ON | self.channel, note.key, note.velocity]);
+            self.sounding.push((now, note.key));
+        }
+    }
+
+    fn send(&mut self, message: &[u8]) {
+        if let Err(err) = self.connection.send(message) {
+            tracing::warn!(%err, "could not send a MIDI message");
+        }
+    }
+}
+
+impl Drop for MidiOut {
+    fn drop(&mut self) {
+        //! Release every note still sounding, so that none hang on the synthesizer.
+        for (_, key) in std::mem::take(&mut self.sounding) {
+            self.send(&[NOTE_OFF | self.channel, key, 0]);
+        }
+    }
+}
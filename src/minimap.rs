@@ -0,0 +1,118 @@
+//! While the camera is zoomed in, a small map of the whole box sits in the bottom
+//! right corner of the window, with the part the window shows outlined on it.
+//! Pressing on the map moves the camera there, and dragging from it keeps moving it.
+use crate::camera::Camera;
+use crate::lod::{Batch, Detail};
+use crate::render::{to_color, Scene};
+use flo_canvas::*;
+use particle_core::components::{Appearance, Charge, Species};
+use particle_core::particle::{Particle, HEIGHT, WIDTH};
+use particle_core::utils::Vec2;
+
+/// The side of the map, in window units.
+const SIZE: f64 = 180.0;
+/// The gap between the map and the edges of the window.
+const MARGIN: f64 = 10.0;
+/// The window is 1000 units on a side, as the box is at a zoom of 1.
+const WINDOW: f64 = 1000.0;
+
+fn origin() -> Vec2 {
+    //! The bottom left corner of the map in the window.
+    Vec2(WINDOW - MARGIN - SIZE, MARGIN)
+}
+
+fn to_map(point: Vec2) -> Vec2 {
+    origin() + Vec2(point.0 * SIZE / WIDTH, point.1 * SIZE / HEIGHT)
+}
+
+pub fn is_shown(camera: &Camera) -> bool {
+    camera.zoom > 1.0
+}
+
+pub fn contains(camera: &Camera, screen: Vec2) -> bool {
+    //! Whether a point of the window is on the map, when it is shown.
+    let offset = screen - origin();
+    is_shown(camera) && (0.0..=SIZE).contains(&offset.0) && (0.0..=SIZE).contains(&offset.1)
+}
+
+pub fn to_world(screen: Vec2) -> Vec2 {
+    //! The position in the box that a point of the window is over on the map,
+    //! the nearest one for points beside the map.
+    let offset = screen - origin();
+    Vec2(
+        (offset.0 * WIDTH / SIZE).clamp(0.0, WIDTH),
+        (offset.1 * HEIGHT / SIZE).clamp(0.0, HEIGHT),
+    )
+}
+
+pub fn draw(gc: &mut impl GraphicsContext, scene: &Scene) {
+    //! Draw the map in window units, over whatever is in the corner.
+    let theme = scene.theme;
+    let (corner, far) = (origin(), origin() + Vec2(SIZE, SIZE));
+    gc.new_path();
+    gc.rect(corner.0 as f32, corner.1 as f32, far.0 as f32, far.1 as f32);
+    gc.fill_color(theme.background());
+    gc.fill();
+
+    if !scene.obstacles.is_empty() {
+        gc.new_path();
+        for obstacle in scene.obstacles {
+            let min = to_map(Vec2(obstacle.min_x, obstacle.min_y));
+            let max = to_map(Vec2(obstacle.max_x, obstacle.max_y));
+            gc.rect(min.0 as f32, min.1 as f32, max.0 as f32, max.1 as f32);
+        }
+        gc.fill_color(Color::Rgba(0.4, 0.4, 0.4, 1.0));
+        gc.fill();
+    }
+
+    let mut batch = Batch::new();
+    for (_, (particle, appearance, charge, species)) in scene
+        .world
+        .query::<(&Particle, &Appearance, Option<&Charge>, Option<&Species>)>()
+        .iter()
+    {
+        let color = scene.coloring.color(particle, appearance, charge, species);
+        let radius = particle.radius * SIZE / WIDTH;
+        batch.add(Detail::Quads, 1.0, to_map(particle.position), radius, color);
+    }
+    batch.draw(gc);
+
+    // The border, and the part of the box in the window
+    let (low, high) = (
+        scene.camera.to_world(Vec2(0.0, 0.0)),
+        scene.camera.to_world(Vec2(WINDOW, WINDOW)),
+    );
+    let clamp = |point: Vec2| Vec2(point.0.clamp(0.0, WIDTH), point.1.clamp(0.0, HEIGHT));
+    let (low, high) = (to_map(clamp(low)), to_map(clamp(high)));
+    gc.line_width(1.0);
+    gc.new_path();
+    gc.rect(corner.0 as f32, corner.1 as f32, far.0 as f32, far.1 as f32);
+    gc.stroke_color(theme.grid().0);
+    gc.stroke();
+    gc.new_path();
+    gc.rect(low.0 as f32, low.1 as f32, high.0 as f32, high.1 as f32);
+    gc.stroke_color(to_color([1.0, 0.8, 0.2, 1.0]));
+    gc.stroke();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_the_map_covers_the_whole_box() {
+        let mut camera = Camera::new();
+        let middle = origin() + Vec2(SIZE / 2.0, SIZE / 2.0);
+        // Only there while zoomed in
+        assert!(!contains(&camera, middle));
+        camera.zoom_by(2.0);
+        assert!(contains(&camera, middle));
+        assert!(!contains(&camera, Vec2(500.0, 500.0)));
+
+        assert_eq!(to_world(middle), Vec2(WIDTH / 2.0, HEIGHT / 2.0));
+        assert_eq!(to_world(origin() + Vec2(SIZE, 0.0)), Vec2(WIDTH, 0.0));
+        // Dragging off the map stops at its edge
+        assert_eq!(to_world(Vec2(0.0, WINDOW)), Vec2(0.0, HEIGHT));
+        assert_eq!(to_map(Vec2(WIDTH, HEIGHT)), origin() + Vec2(SIZE, SIZE));
+    }
+}
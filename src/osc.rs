@@ -0,0 +1,170 @@
+//! `--osc HOST:PORT`: stream the simulation as Open Sound Control over UDP, for
+//! creative coding tools such as TouchDesigner, Max/MSP and Processing. Every
+//! frame sends bundles of
+//!
+//! - `/frame` with the frame number and particle count as `i`s,
+//! - `/particle` for every particle: its id as an `i`, then `x`, `y`, `vx`, `vy`
+//!   and `radius` as `f`s,
+//!
+//! and every step sends a bundle of `/collision` for every contact: the ids of the
+//! two particles as `i`s, then the contact point and impulse as `f`s.
+use particle_core::error::SimResult;
+use particle_core::events::Event;
+use particle_core::particle::Particle;
+use particle_core::simulation::Simulation;
+use rosc::{OscBundle, OscMessage, OscPacket, OscTime, OscType};
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+/// How many messages go into one bundle, to keep each datagram well under the
+/// largest a UDP packet can carry.
+const MESSAGES_PER_BUNDLE: usize = 256;
+/// The time tag that tells a receiver to act on a bundle as soon as it arrives.
+const IMMEDIATELY: OscTime = OscTime {
+    seconds: 0,
+    fractional: 1,
+};
+
+fn message(addr: &str, args: Vec<OscType>) -> OscPacket {
+    OscPacket::Message(OscMessage {
+        addr: addr.to_string(),
+        args,
+    })
+}
+
+fn bundles(messages: Vec<OscPacket>) -> Vec<OscPacket> {
+    messages
+        .chunks(MESSAGES_PER_BUNDLE)
+        .map(|content| {
+            OscPacket::Bundle(OscBundle {
+                timetag: IMMEDIATELY,
+                content: content.to_vec(),
+            })
+        })
+        .collect()
+}
+
+pub fn frame_packets(simulation: &Simulation) -> Vec<OscPacket> {
+    //! The bundles for the current frame, `/frame` first.
+    let mut messages = vec![message(
+        "/frame",
+        vec![
+            OscType::Int(simulation.frame() as i32),
+            OscType::Int(simulation.particle_count() as i32),
+        ],
+    )];
+    messages.extend(
+        simulation
+            .world()
+            .query::<&Particle>()
+            .iter()
+            .map(|(_, particle)| {
+                message(
+                    "/particle",
+                    vec![
+                        OscType::Int(particle.id as i32),
+                        OscType::Float(particle.position.0 as f32),
+                        OscType::Float(particle.position.1 as f32),
+                        OscType::Float(particle.velocity.0 as f32),
+                        OscType::Float(particle.velocity.1 as f32),
+                        OscType::Float(particle.radius as f32),
+                    ],
+                )
+            }),
+    );
+    bundles(messages)
+}
+
+pub fn collision_packets(events: &[Event]) -> Vec<OscPacket> {
+    //! The bundles for the contacts of a step, none if there were none.
+    bundles(
+        events
+            .iter()
+            .map(|event| match event {
+                Event::Contact {
+                    a,
+                    b,
+                    contact,
+                    impulse,
+                } => message(
+                    "/collision",
+                    vec![
+                        OscType::Int(*a as i32),
+                        OscType::Int(*b as i32),
+                        OscType::Float(contact.point.0 as f32),
+                        OscType::Float(contact.point.1 as f32),
+                        OscType::Float(*impulse as f32),
+                    ],
+                ),
+            })
+            .collect(),
+    )
+}
+
+/// A UDP socket sending to the one address given to `--osc`.
+pub struct OscSender {
+    socket: UdpSocket,
+    target: SocketAddr,
+}
+
+impl OscSender {
+    pub fn new(target: SocketAddr) -> SimResult<Self> {
+        let local: SocketAddr = if target.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        Ok(OscSender {
+            socket: UdpSocket::bind(local)?,
+            target,
+        })
+    }
+
+    pub fn send(&self, packets: &[OscPacket]) {
+        //! Send each packet as a datagram of its own. Nothing checks that they
+        //! arrive, so failures are only logged.
+        for packet in packets {
+            let sent = rosc::encoder::encode(packet)
+                .map_err(io::Error::other)
+                .and_then(|datagram| self.socket.send_to(&datagram, self.target));
+            if let Err(err) = sent {
+                tracing::warn!(%err, target = %self.target, "could not send OSC");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use particle_core::utils::Vec2;
+
+    #[test]
+    fn test_frames_reach_a_receiver() {
+        let mut simulation = Simulation::new();
+        for id in 0..300 {
+            simulation.spawn(Particle::new(id, 1.0, Vec2(500.0, 500.0), Vec2(1.0, 0.0)));
+        }
+        let packets = frame_packets(&simulation);
+        // 301 messages need a second bundle
+        assert_eq!(packets.len(), 2);
+
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        OscSender::new(receiver.local_addr().unwrap())
+            .unwrap()
+            .send(&packets[..1]);
+        let mut buffer = vec![0; 65536];
+        let size = receiver.recv(&mut buffer).unwrap();
+        let (_, OscPacket::Bundle(bundle)) = rosc::decoder::decode_udp(&buffer[..size]).unwrap()
+        else {
+            panic!("expected a bundle");
+        };
+        assert_eq!(bundle.content.len(), MESSAGES_PER_BUNDLE);
+        let OscPacket::Message(frame) = &bundle.content[0] else {
+            panic!("expected a message");
+        };
+        assert_eq!(frame.addr, "/frame");
+        assert_eq!(frame.args, [OscType::Int(0), OscType::Int(300)]);
+        assert!(collision_packets(&[]).is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+/// How many frames in a row the physics has to run over its budget before
+/// the next degradation kicks in, so that a single hitch changes nothing.
+const DEGRADE_AFTER: u32 = 15;
+/// How many frames in a row the physics has to stay under half of its
+/// budget before the last degradation is undone.
+const RESTORE_AFTER: u32 = 90;
+
+/// A way of making frames cheaper, in the order they are turned on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Degradation {
+    /// No particle ids and no neon glow.
+    PlainRendering,
+    /// The broadphase sweeps along whichever axis prunes the most.
+    AutoSweepAxis,
+    /// At most two steps per frame, so the simulation slows down rather than
+    /// spending every frame catching up.
+    FewerCatchUpSteps,
+}
+
+impl Degradation {
+    const ALL: [Degradation; 3] = [
+        Degradation::PlainRendering,
+        Degradation::AutoSweepAxis,
+        Degradation::FewerCatchUpSteps,
+    ];
+
+    fn describe(self) -> &'static str {
+        match self {
+            Degradation::PlainRendering => "labels and glow off",
+            Degradation::AutoSweepAxis => "broadphase axis picked automatically",
+            Degradation::FewerCatchUpSteps => "simulation slowed down",
+        }
+    }
+}
+
+/// Watches how long the physics takes each frame, and how many collisions it
+/// resolves, and turns degradations on one at a time while it takes longer than
+/// its budget, then off again once it has room to spare.
+#[derive(Debug, Clone)]
+pub struct AdaptiveQuality {
+    budget: Duration,
+    /// How many of `Degradation::ALL` are turned on.
+    level: usize,
+    over_budget: u32,
+    under_budget: u32,
+    collisions: usize,
+}
+
+impl AdaptiveQuality {
+    pub fn new(budget: Duration) -> Self {
+        AdaptiveQuality {
+            budget,
+            level: 0,
+            over_budget: 0,
+            under_budget: 0,
+            collisions: 0,
+        }
+    }
+
+    pub fn record(&mut self, physics_time: Duration, collisions: usize) -> bool {
+        //! Note how long the physics of the latest frame took and how many
+        //! collisions it resolved. Returns whether the degradations changed.
+        self.collisions = collisions;
+        if physics_time > self.budget {
+            self.over_budget += 1;
+            self.under_budget = 0;
+        } else if physics_time < self.budget / 2 {
+            self.under_budget += 1;
+            self.over_budget = 0;
+        } else {
+            self.over_budget = 0;
+            self.under_budget = 0;
+        }
+
+        if self.over_budget >= DEGRADE_AFTER && self.level < Degradation::ALL.len() {
+            self.level += 1;
+            self.over_budget = 0;
+            tracing::info!(
+                level = self.level,
+                ?physics_time,
+                collisions,
+                "degrading quality"
+            );
+            true
+        } else if self.under_budget >= RESTORE_AFTER && self.level > 0 {
+            self.level -= 1;
+            self.under_budget = 0;
+            tracing::info!(level = self.level, "restoring quality");
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn is_active(&self, degradation: Degradation) -> bool {
+        Degradation::ALL[..self.level].contains(&degradation)
+    }
+
+    pub fn hud_lines(&self) -> Vec<String> {
+        //! A warning for the HUD while anything is degraded.
+        if self.level == 0 {
+            return Vec::new();
+        }
+        let mut lines = vec![format!(
+            "Under load ({} collisions per frame):",
+            self.collisions
+        )];
+        lines.extend(
+            Degradation::ALL[..self.level]
+                .iter()
+                .map(|degradation| format!("  {}", degradation.describe())),
+        );
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_degrades_under_load_and_recovers() {
+        let budget = Duration::from_millis(10);
+        let mut quality = AdaptiveQuality::new(budget);
+        // A single slow frame changes nothing
+        assert!(!quality.record(Duration::from_millis(30), 5000));
+        assert!(quality.hud_lines().is_empty());
+
+        for _ in 1..DEGRADE_AFTER {
+            quality.record(Duration::from_millis(30), 5000);
+        }
+        assert!(quality.is_active(Degradation::PlainRendering));
+        assert!(!quality.is_active(Degradation::AutoSweepAxis));
+        assert_eq!(
+            quality.hud_lines(),
+            [
+                "Under load (5000 collisions per frame):",
+                "  labels and glow off"
+            ]
+        );
+
+        // Frames just under the budget are not enough to recover
+        for _ in 0..RESTORE_AFTER {
+            quality.record(Duration::from_millis(8), 100);
+        }
+        assert!(quality.is_active(Degradation::PlainRendering));
+        for _ in 0..RESTORE_AFTER {
+            quality.record(Duration::from_millis(1), 100);
+        }
+        assert!(!quality.is_active(Degradation::PlainRendering));
+    }
+}
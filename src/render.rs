@@ -0,0 +1,596 @@
+use crate::blur::MotionBlur;
+use crate::camera::Camera;
+use crate::coloring::Coloring;
+use crate::heat::Heat;
+use crate::hud::FONT;
+use crate::layers::{Layer, Layers};
+use crate::lod::{Batch, Detail};
+use crate::metaballs::{self, Field};
+use crate::minimap;
+use crate::sprites::SpritePool;
+use crate::textures::Textures;
+use crate::theme::Theme;
+use flo_canvas::*;
+use hecs::World;
+use particle_core::components::{Appearance, Charge, Species};
+use particle_core::obstacles::Obstacle;
+use particle_core::particle::{Particle, HEIGHT, WIDTH};
+use particle_core::portals::Portal;
+use particle_core::shapes::Body;
+use particle_core::time_regions::TimeRegion;
+use particle_core::utils::Vec2;
+
+/// Everything in the box, drawn on the layers of `Layer::SCENE`.
+pub struct Scene<'a> {
+    pub world: &'a World,
+    pub obstacles: &'a [Obstacle],
+    pub portals: &'a [Portal],
+    pub time_regions: &'a [TimeRegion],
+    /// A particle to ring, such as the one being inspected.
+    pub highlight: Option<&'a Particle>,
+    /// Particles picked out for bulk changes, ringed more faintly.
+    pub selected: &'a [Particle],
+    /// Draw a labelled grid behind everything else.
+    pub grid: bool,
+    /// Write the id of every particle next to it, to match up log messages
+    /// about particles with what is on screen.
+    pub labels: bool,
+    /// Picks the color of every particle, after `Coloring::update` for this frame.
+    pub coloring: &'a Coloring,
+    /// Makes particles that have been colliding hard glow, when set.
+    pub heat: Option<&'a Heat>,
+    /// Where a particle is expected to go, drawn as a dashed line.
+    pub prediction: Option<&'a [Vec2]>,
+    /// The center of mass and its velocity, drawn as a cross with an arrow
+    /// along the total momentum.
+    pub center_of_mass: Option<(Vec2, Vec2)>,
+    /// Two opposite corners of a box being dragged out to erase the particles in it.
+    pub eraser: Option<(Vec2, Vec2)>,
+    /// Two opposite corners of a box being dragged out to select the particles in it.
+    pub selecting: Option<(Vec2, Vec2)>,
+    /// The part of the box to show. The side by side view always shows all of both.
+    pub camera: Camera,
+    pub theme: Theme,
+    /// Images to paint particles with instead of their colors, already defined
+    /// on the canvas.
+    pub textures: Option<&'a Textures>,
+    /// Draw the particles as one fluid surface instead of as circles.
+    pub metaballs: bool,
+    /// How much of every particle to draw. Below full detail, the effects of
+    /// the other fields on particles are left out.
+    pub detail: Detail,
+    /// The fading frames to draw behind the particles, if any.
+    pub motion_blur: Option<&'a MotionBlur>,
+    /// The layers to draw, the hidden ones left empty.
+    pub layers: Layers,
+}
+
+/// The distance between two lines of the grid, in world units.
+const GRID_SPACING: f64 = 100.0;
+/// How far the glow of the neon theme and of hot particles reaches, as a multiple
+/// of the radius.
+const GLOW_SCALE: f64 = 1.8;
+/// The space between the two boxes of `draw_side_by_side`.
+const SPLIT_GAP: f32 = 50.0;
+/// How far the hottest particles are blended towards white.
+const HEAT_BRIGHTENING: f32 = 0.6;
+/// The half width of the center of mass cross.
+const CROSS_SIZE: f32 = 10.0;
+/// How many steps of travel the momentum arrow stands for, so that a drift of a
+/// fraction of a unit per step is still easy to see.
+const MOMENTUM_ARROW_STEPS: f64 = 50.0;
+
+pub fn to_color([r, g, b, a]: [f32; 4]) -> Color {
+    Color::Rgba(r, g, b, a)
+}
+
+fn portal_color(portal: &Portal) -> [f32; 4] {
+    //! A color that tells the pairs of portals apart, derived from
+    //! where they are so that it stays the same from frame to frame.
+    let hue = ((portal.a.0 + portal.b.1) * 0.01).sin() as f32 * 0.5 + 0.5;
+    [hue, 1.0 - hue, 1.0, 1.0]
+}
+
+fn time_region_tint(region: &TimeRegion) -> Color {
+    //! Blue where time runs slow and red where it runs fast, stronger the further
+    //! the rate is from normal, and faint enough to see the particles through.
+    let strength = (region.scale.max(f64::MIN_POSITIVE).log2().abs().min(3.0) / 3.0) as f32;
+    let alpha = 0.08 + 0.17 * strength;
+    if region.scale < 1.0 {
+        Color::Rgba(0.2, 0.4, 1.0, alpha)
+    } else {
+        Color::Rgba(1.0, 0.3, 0.2, alpha)
+    }
+}
+
+fn draw_grid(gc: &mut impl GraphicsContext, theme: Theme) {
+    //! Faint lines every `GRID_SPACING` units, with the x coordinates along the
+    //! bottom of the box and the y coordinates up its left side.
+    let lines = (1..)
+        .map(|line| line as f64 * GRID_SPACING)
+        .take_while(|offset| *offset < WIDTH.max(HEIGHT));
+    gc.new_path();
+    for offset in lines.clone() {
+        let offset = offset as f32;
+        if offset < WIDTH as f32 {
+            gc.move_to(offset, 0.0);
+            gc.line_to(offset, HEIGHT as f32);
+        }
+        if offset < HEIGHT as f32 {
+            gc.move_to(0.0, offset);
+            gc.line_to(WIDTH as f32, offset);
+        }
+    }
+    let (line_color, label_color) = theme.grid();
+    gc.line_width(1.0);
+    gc.stroke_color(line_color);
+    gc.stroke();
+
+    gc.fill_color(label_color);
+    for offset in lines {
+        let label = format!("{}", offset);
+        if offset < WIDTH {
+            gc.draw_text(FONT, label.clone(), offset as f32 + 3.0, 4.0);
+        }
+        if offset < HEIGHT {
+            gc.draw_text(FONT, label, 4.0, offset as f32 + 3.0);
+        }
+    }
+}
+
+pub fn draw_scene(gc: &mut impl GraphicsContext, scene: &Scene, sprites: &mut SpritePool) {
+    //! Replace whatever the layers of the scene and the minimap showed with the
+    //! scene. Circles are drawn from `sprites`, which must only ever be used with
+    //! the same canvas.
+    for layer in Layer::SCENE.into_iter().chain([Layer::Minimap]) {
+        layer.clear(gc);
+    }
+    gc.canvas_height(1000.0);
+    gc.center_region(0.0, 0.0, 1000.0, 1000.0);
+    gc.push_state();
+    gc.transform(scene.camera.transform());
+    draw_box(gc, scene, sprites);
+    gc.pop_state();
+    if scene.layers.is_shown(Layer::Minimap) && minimap::is_shown(&scene.camera) {
+        gc.layer(Layer::Minimap.id());
+        minimap::draw(gc, scene);
+    }
+}
+
+pub fn draw_side_by_side(
+    gc: &mut impl GraphicsContext,
+    left: &Scene,
+    right: &Scene,
+    sprites: &mut SpritePool,
+) {
+    //! Like `draw_scene`, but with two boxes next to each other, `SPLIT_GAP` apart,
+    //! both shrunk to fit a square window. The right box is drawn shifted along
+    //! x, so it takes the same coordinates as the left one.
+    let width = 2.0 * WIDTH as f32 + SPLIT_GAP;
+    for layer in Layer::SCENE {
+        layer.clear(gc);
+    }
+    gc.canvas_height(width);
+    gc.center_region(0.0, 0.0, width, HEIGHT as f32);
+    draw_box(gc, left, sprites);
+    gc.push_state();
+    gc.transform(Transform2D::translate(WIDTH as f32 + SPLIT_GAP, 0.0));
+    draw_box(gc, right, sprites);
+    gc.pop_state();
+}
+
+fn draw_box(gc: &mut impl GraphicsContext, scene: &Scene, sprites: &mut SpritePool) {
+    //! Everything of the scene, over the box from the origin to `WIDTH` and `HEIGHT`,
+    //! each part on its own layer. The layers must have been cleared already.
+    for layer in Layer::SCENE {
+        if !scene.layers.is_shown(layer) {
+            continue;
+        }
+        gc.layer(layer.id());
+        match layer {
+            Layer::Background => draw_background(gc, scene),
+            Layer::Trails => {
+                if let Some(motion_blur) = scene.motion_blur {
+                    motion_blur.draw(gc);
+                }
+            }
+            Layer::Particles => draw_particles(gc, scene, sprites),
+            _ => draw_overlays(gc, scene),
+        }
+    }
+}
+
+fn draw_background(gc: &mut impl GraphicsContext, scene: &Scene) {
+    // Painted every frame, so that the theme can change at any time
+    let theme = scene.theme;
+    gc.new_path();
+    gc.rect(0.0, 0.0, WIDTH as f32, HEIGHT as f32);
+    gc.fill_color(theme.background());
+    gc.fill();
+
+    if scene.grid {
+        draw_grid(gc, theme);
+    }
+
+    if !scene.obstacles.is_empty() {
+        gc.new_path();
+        for obstacle in scene.obstacles {
+            gc.rect(
+                obstacle.min_x as f32,
+                obstacle.min_y as f32,
+                obstacle.max_x as f32,
+                obstacle.max_y as f32,
+            );
+        }
+        gc.fill_color(Color::Rgba(0.4, 0.4, 0.4, 1.0));
+        gc.fill();
+    }
+
+    for region in scene.time_regions {
+        gc.new_path();
+        gc.rect(
+            region.min.0 as f32,
+            region.min.1 as f32,
+            region.max.0 as f32,
+            region.max.1 as f32,
+        );
+        gc.fill_color(time_region_tint(region));
+        gc.fill();
+    }
+
+    // Both ends of a portal share an outline color
+    gc.line_width(3.0);
+    for portal in scene.portals {
+        gc.new_path();
+        for end in [portal.a, portal.b] {
+            gc.circle(end.0 as f32, end.1 as f32, portal.radius as f32);
+        }
+        gc.stroke_color(to_color(portal_color(portal)));
+        gc.stroke();
+    }
+}
+
+fn draw_particles(gc: &mut impl GraphicsContext, scene: &Scene, sprites: &mut SpritePool) {
+    let theme = scene.theme;
+    gc.blend_mode(theme.blend_mode());
+    let mut field = scene.metaballs.then(|| Field::new(metaballs::CELL));
+    let mut batch = Batch::new();
+    for (_, (particle, appearance, body, charge, species)) in scene
+        .world
+        .query::<(
+            &Particle,
+            &Appearance,
+            Option<&Body>,
+            Option<&Charge>,
+            Option<&Species>,
+        )>()
+        .iter()
+    {
+        let glow = scene.heat.map_or(0.0, |heat| heat.glow(particle.id));
+        let [r, g, b, a] = scene
+            .coloring
+            .color(particle, appearance, charge, species)
+            .map(|channel| channel + (1.0 - channel) * HEAT_BRIGHTENING * glow);
+        if let Some(field) = &mut field {
+            field.add(particle.position, particle.radius, [r, g, b, a]);
+            continue;
+        }
+        if scene.detail != Detail::Full {
+            let zoom = scene.camera.zoom;
+            batch.add(
+                scene.detail,
+                zoom,
+                particle.position,
+                particle.radius,
+                [r, g, b, a],
+            );
+            continue;
+        }
+        let color = Color::Rgba(r, g, b, a);
+        // Shapes turn, so they cannot share sprites
+        let vertices = body
+            .map(|body| body.vertices(particle.position))
+            .unwrap_or_default();
+        if let (Some(body), [start, end]) = (body, vertices.as_slice()) {
+            // A capsule is its segment, drawn with a thick round pen
+            gc.new_path();
+            gc.move_to(start.0 as f32, start.1 as f32);
+            gc.line_to(end.0 as f32, end.1 as f32);
+            gc.line_width(2.0 * body.shape.rounding() as f32);
+            gc.line_cap(LineCap::Round);
+            gc.stroke_color(color);
+            gc.stroke();
+            continue;
+        }
+        if let Some((first, rest)) = vertices.split_first() {
+            gc.new_path();
+            gc.move_to(first.0 as f32, first.1 as f32);
+            for vertex in rest {
+                gc.line_to(vertex.0 as f32, vertex.1 as f32);
+            }
+            gc.close_path();
+            if theme.outlines_only() {
+                gc.line_width(1.5);
+                gc.stroke_color(color);
+                gc.stroke();
+            } else {
+                gc.fill_color(color);
+                gc.fill();
+            }
+            continue;
+        }
+        if theme.outlines_only() {
+            gc.new_path();
+            gc.circle(
+                particle.position.0 as f32,
+                particle.position.1 as f32,
+                particle.radius as f32,
+            );
+            gc.line_width(1.5);
+            gc.stroke_color(color);
+            gc.stroke();
+            continue;
+        }
+        if theme.glows() || glow > 0.0 {
+            // A faint, wider disc behind the particle, stronger the hotter it is
+            let neon: f32 = if theme.glows() { 1.0 / 3.0 } else { 0.0 };
+            sprites.draw_circle(
+                gc,
+                Layer::Particles.id(),
+                (particle.position.0, particle.position.1),
+                particle.radius * GLOW_SCALE,
+                Color::Rgba(r, g, b, a * neon.max(glow / 2.0)),
+            );
+        }
+        let texture = scene
+            .textures
+            .and_then(|textures| textures.lookup(particle.id, species));
+        if let Some(texture) = texture {
+            let center = (particle.position.0, particle.position.1);
+            Textures::draw_circle(gc, texture, center, particle.radius);
+            continue;
+        }
+        // Render the ball's shared sprite at its location
+        sprites.draw_circle(
+            gc,
+            Layer::Particles.id(),
+            (particle.position.0, particle.position.1),
+            particle.radius,
+            color,
+        );
+    }
+    if let Some(field) = &field {
+        metaballs::draw(gc, field);
+    }
+    batch.draw(gc);
+    gc.blend_mode(BlendMode::SourceOver);
+}
+
+fn draw_overlays(gc: &mut impl GraphicsContext, scene: &Scene) {
+    let theme = scene.theme;
+    if scene.labels {
+        gc.fill_color(theme.text());
+        for (_, particle) in scene.world.query::<&Particle>().iter() {
+            // Just off the top right of the particle
+            let offset = (particle.radius * std::f64::consts::FRAC_1_SQRT_2) as f32 + 2.0;
+            gc.draw_text(
+                FONT,
+                particle.id.to_string(),
+                particle.position.0 as f32 + offset,
+                particle.position.1 as f32 + offset,
+            );
+        }
+    }
+
+    if let Some(particle) = scene.highlight {
+        gc.new_path();
+        gc.circle(
+            particle.position.0 as f32,
+            particle.position.1 as f32,
+            particle.radius as f32 + 4.0,
+        );
+        gc.line_width(2.0);
+        gc.stroke_color(theme.text());
+        gc.stroke();
+    }
+
+    if !scene.selected.is_empty() {
+        gc.new_path();
+        for particle in scene.selected {
+            gc.circle(
+                particle.position.0 as f32,
+                particle.position.1 as f32,
+                particle.radius as f32 + 3.0,
+            );
+        }
+        gc.line_width(1.0);
+        gc.stroke_color(theme.text().with_alpha(0.6));
+        gc.stroke();
+    }
+
+    if let Some((center, velocity)) = scene.center_of_mass {
+        draw_center_of_mass(gc, center, velocity, theme);
+    }
+
+    // The eraser is filled in, to set it apart from a selection box
+    if let Some(corners) = scene.eraser {
+        draw_drag_box(gc, corners, 0.1, theme);
+    }
+    if let Some(corners) = scene.selecting {
+        draw_drag_box(gc, corners, 0.0, theme);
+    }
+
+    if let Some(path) = scene.prediction {
+        // Every other segment, so that it reads as a forecast rather than a trail
+        gc.new_path();
+        for segment in path.windows(2).step_by(2) {
+            gc.move_to(segment[0].0 as f32, segment[0].1 as f32);
+            gc.line_to(segment[1].0 as f32, segment[1].1 as f32);
+        }
+        gc.line_width(1.5);
+        gc.stroke_color(theme.text());
+        gc.stroke();
+    }
+}
+
+fn draw_drag_box(
+    gc: &mut impl GraphicsContext,
+    (corner, opposite): (Vec2, Vec2),
+    fill: f32,
+    theme: Theme,
+) {
+    gc.new_path();
+    gc.rect(
+        corner.0 as f32,
+        corner.1 as f32,
+        opposite.0 as f32,
+        opposite.1 as f32,
+    );
+    gc.fill_color(theme.text().with_alpha(fill));
+    gc.fill();
+    gc.line_width(1.0);
+    gc.stroke_color(theme.text());
+    gc.stroke();
+}
+
+fn draw_center_of_mass(gc: &mut impl GraphicsContext, center: Vec2, velocity: Vec2, theme: Theme) {
+    //! A cross on the center of mass and an arrow from it along the velocity,
+    //! which is left out while the center of mass stands still.
+    let (x, y) = (center.0 as f32, center.1 as f32);
+    gc.new_path();
+    gc.move_to(x - CROSS_SIZE, y - CROSS_SIZE);
+    gc.line_to(x + CROSS_SIZE, y + CROSS_SIZE);
+    gc.move_to(x - CROSS_SIZE, y + CROSS_SIZE);
+    gc.line_to(x + CROSS_SIZE, y - CROSS_SIZE);
+    let arrow = MOMENTUM_ARROW_STEPS * velocity;
+    let length = arrow.length();
+    if length > 0.0 {
+        let tip = center + arrow;
+        // The head is a fixed size, turned 30 degrees either side of the shaft
+        let back = (-(CROSS_SIZE as f64) / length) * arrow;
+        let side = std::f64::consts::FRAC_PI_6.tan() * back.perp();
+        gc.move_to(x, y);
+        gc.line_to(tip.0 as f32, tip.1 as f32);
+        for head in [tip + back + side, tip + back - side] {
+            gc.move_to(tip.0 as f32, tip.1 as f32);
+            gc.line_to(head.0 as f32, head.1 as f32);
+        }
+    }
+    gc.line_width(2.0);
+    gc.stroke_color(theme.text());
+    gc.stroke();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coloring::{ColorBy, Palette};
+
+    fn scene<'a>(world: &'a World, coloring: &'a Coloring) -> Scene<'a> {
+        Scene {
+            world,
+            coloring,
+            obstacles: &[],
+            portals: &[],
+            time_regions: &[],
+            heat: None,
+            prediction: None,
+            center_of_mass: None,
+            eraser: None,
+            selecting: None,
+            camera: Camera::new(),
+            textures: None,
+            metaballs: false,
+            detail: Detail::Full,
+            motion_blur: None,
+            layers: Layers::new(),
+            highlight: None,
+            selected: &[],
+            grid: false,
+            labels: false,
+            theme: Theme::Dark,
+        }
+    }
+
+    fn texts(scene: &Scene) -> Vec<(String, f32, f32)> {
+        let mut drawing: Vec<Draw> = vec![];
+        draw_scene(&mut drawing, scene, &mut SpritePool::new());
+        drawing
+            .into_iter()
+            .filter_map(|draw| match draw {
+                Draw::DrawText(_, text, x, y) => Some((text, x, y)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_grid_is_labelled_along_both_axes() {
+        let world = World::new();
+        let coloring = Coloring::new(ColorBy::Random, Palette::Viridis);
+        let mut scene = scene(&world, &coloring);
+        assert!(texts(&scene).is_empty());
+
+        scene.grid = true;
+        let labels = texts(&scene);
+        // 100 to 900 on each axis
+        assert_eq!(labels.len(), 18);
+        assert!(labels.contains(&("500".to_string(), 503.0, 4.0)));
+        assert!(labels.contains(&("500".to_string(), 4.0, 503.0)));
+    }
+
+    #[test]
+    fn test_particles_are_labelled_with_their_ids() {
+        let mut world = World::new();
+        let appearance = Appearance {
+            color: [1.0, 0.0, 0.0, 1.0],
+        };
+        world.spawn((
+            Particle::new(7, 10.0, Vec2(100.0, 200.0), Vec2::ZERO),
+            appearance,
+        ));
+        let coloring = Coloring::new(ColorBy::Random, Palette::Viridis);
+        let mut scene = scene(&world, &coloring);
+        scene.labels = true;
+        let offset = 10.0 * std::f32::consts::FRAC_1_SQRT_2 + 2.0;
+        assert_eq!(
+            texts(&scene),
+            vec![("7".to_string(), 100.0 + offset, 200.0 + offset)]
+        );
+        // Labels are a debug overlay, and go with it
+        scene.layers.toggle(Layer::Debug);
+        assert!(texts(&scene).is_empty());
+    }
+
+    #[test]
+    fn test_themes_change_how_particles_are_painted() {
+        let mut world = World::new();
+        let appearance = Appearance {
+            color: [1.0, 0.0, 0.0, 1.0],
+        };
+        world.spawn((
+            Particle::new(1, 10.0, Vec2(100.0, 200.0), Vec2::ZERO),
+            appearance,
+        ));
+        let coloring = Coloring::new(ColorBy::Random, Palette::Viridis);
+        let mut scene = scene(&world, &coloring);
+        let sprites_drawn = |scene: &Scene| {
+            let mut drawing: Vec<Draw> = vec![];
+            draw_scene(&mut drawing, scene, &mut SpritePool::new());
+            let sprites = drawing
+                .iter()
+                .filter(|draw| matches!(draw, Draw::DrawSprite(_)))
+                .count();
+            (sprites, drawing)
+        };
+
+        assert_eq!(sprites_drawn(&scene).0, 1);
+        scene.theme = Theme::Neon;
+        let (sprites, drawing) = sprites_drawn(&scene);
+        // The glow and the particle, blended onto the background
+        assert_eq!(sprites, 2);
+        assert!(drawing.contains(&Draw::BlendMode(BlendMode::Screen)));
+        scene.theme = Theme::Wireframe;
+        assert_eq!(sprites_drawn(&scene).0, 0);
+    }
+}
@@ -0,0 +1,157 @@
+//! A small interface for drawing the simulation, so that the window can come from
+//! a backend other than flo_draw. `run` draws a plain view through it: every
+//! particle colored as in the window and a few lines of text, with Escape or
+//! closing the window to quit. The interactive window, with its overlays and key
+//! bindings, stays the default: it writes its frames and HUD through
+//! `FloRenderer`, and so do its particles once they are drawn as plain discs.
+//!
+//! Each backend is behind a cargo feature: `flo-draw`, which is on by default,
+//! for `FloRenderer`, `pixels`, for `PixelsRenderer` on pixels and winit, and
+//! `terminal`, for `TerminalRenderer` on ratatui and crossterm.
+#[cfg(feature = "flo-draw")]
+mod flo;
+#[cfg(feature = "pixels")]
+mod pixels;
+#[cfg(feature = "terminal")]
+mod terminal;
+
+#[cfg(feature = "flo-draw")]
+pub use self::flo::{window_events, FloRenderer};
+#[cfg(feature = "pixels")]
+pub use self::pixels::PixelsRenderer;
+#[cfg(feature = "terminal")]
+pub use self::terminal::TerminalRenderer;
+
+use crate::clock::{Clock, FrameRate, FrameScheduler};
+use crate::coloring::Coloring;
+use crate::theme::Theme;
+use clap::ValueEnum;
+use flo_canvas::Color;
+use hecs::World;
+use particle_core::components::{Appearance, Charge, Species};
+use particle_core::error::SimResult;
+use particle_core::particle::Particle;
+use particle_core::simulation::Simulation;
+use particle_core::utils::Vec2;
+use std::time::Instant;
+
+/// Draws frames of the simulation into a window, in world coordinates: the box
+/// runs from the origin to `WIDTH` and `HEIGHT`, with y pointing up.
+pub trait Renderer {
+    /// Open the window.
+    fn init(&mut self, title: &str) -> SimResult<()>;
+    /// Start a new frame with the box filled with `background`.
+    fn begin_frame(&mut self, background: [f32; 4]);
+    fn draw_particle(&mut self, position: Vec2, radius: f64, color: [f32; 4]);
+    /// Write `lines` in the top left corner, above the particles.
+    fn overlay_text(&mut self, lines: &[String], color: [f32; 4]);
+    /// Show the frame. Returns false once the window has been closed.
+    fn end_frame(&mut self) -> bool;
+}
+
+/// The backends `--renderer` can pick from. Only those whose feature was built
+/// in can be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// The interactive flo_draw window.
+    Flo,
+    /// The plain view, drawn in software with pixels and winit.
+    Pixels,
+    /// The plain view, drawn in the terminal with braille characters.
+    Terminal,
+}
+
+impl Backend {
+    pub fn feature(self) -> &'static str {
+        match self {
+            Backend::Flo => "flo-draw",
+            Backend::Pixels => "pixels",
+            Backend::Terminal => "terminal",
+        }
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        //! flo_draw, unless the build left it out for another backend.
+        if cfg!(feature = "flo-draw") {
+            Backend::Flo
+        } else if cfg!(feature = "pixels") {
+            Backend::Pixels
+        } else if cfg!(feature = "terminal") {
+            Backend::Terminal
+        } else {
+            Backend::Flo
+        }
+    }
+}
+
+pub fn rgba(color: Color) -> [f32; 4] {
+    let (r, g, b, a) = color.to_rgba_components();
+    [r, g, b, a]
+}
+
+pub fn draw_frame(
+    renderer: &mut impl Renderer,
+    simulation: &Simulation,
+    coloring: &Coloring,
+    background: [f32; 4],
+    text: ([f32; 4], &[String]),
+) {
+    //! Draw one frame of the plain view.
+    renderer.begin_frame(background);
+    draw_particles(renderer, simulation.world(), coloring);
+    let (color, lines) = text;
+    renderer.overlay_text(lines, color);
+}
+
+pub fn draw_particles(renderer: &mut impl Renderer, world: &World, coloring: &Coloring) {
+    //! Every particle as a plain disc, colored as in the window.
+    for (_, (particle, appearance, charge, species)) in world
+        .query::<(&Particle, &Appearance, Option<&Charge>, Option<&Species>)>()
+        .iter()
+    {
+        let color = coloring.color(particle, appearance, charge, species);
+        renderer.draw_particle(particle.position, particle.radius, color);
+    }
+}
+
+pub fn run(
+    renderer: &mut impl Renderer,
+    simulation: &mut Simulation,
+    mut coloring: Coloring,
+    theme: Theme,
+    time_scale: f64,
+    frame_rate: FrameRate,
+) -> SimResult<()> {
+    //! Step the simulation in real time and draw it through `renderer` until the
+    //! window is closed.
+    renderer.init("Particle Simulator")?;
+    let mut clock = Clock::new(time_scale);
+    let mut scheduler = FrameScheduler::new(frame_rate);
+    let mut last_frame = Instant::now();
+    loop {
+        for _ in 0..clock.tick() {
+            simulation.step(1.0)?;
+        }
+        coloring.update(simulation.frame(), simulation.events(), simulation.world());
+        let fps = 1.0 / last_frame.elapsed().as_secs_f64().max(f64::EPSILON);
+        last_frame = Instant::now();
+        let lines = [
+            format!("particles: {}", simulation.particle_count()),
+            format!("frame: {}", simulation.frame()),
+            format!("fps: {:.0}", fps),
+        ];
+        draw_frame(
+            renderer,
+            simulation,
+            &coloring,
+            rgba(theme.background()),
+            (rgba(theme.text()), &lines),
+        );
+        if !renderer.end_frame() {
+            return Ok(());
+        }
+        scheduler.wait();
+    }
+}
@@ -0,0 +1,162 @@
+use super::Renderer;
+use crate::camera::Camera;
+use crate::hud::Hud;
+use crate::layers::Layer;
+use crate::render::to_color;
+use crate::sprites::SpritePool;
+use flo_canvas::*;
+use flo_draw::*;
+use futures::executor;
+use futures::prelude::*;
+use particle_core::error::SimResult;
+use particle_core::particle::{HEIGHT, WIDTH};
+use particle_core::utils::Vec2;
+use std::mem;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+pub fn window_events(
+    events: impl 'static + Send + Stream<Item = DrawEvent>,
+) -> Receiver<DrawEvent> {
+    //! Forward the events of a window to a channel, so that the frame
+    //! loop can handle whatever arrived since the last frame without waiting.
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for event in executor::block_on_stream(events.boxed()) {
+            if sender.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Draws into a flo_draw window. Each frame is gathered up and sent to the
+/// window whole, so that it never shows half a frame. It must be used inside
+/// `with_2d_graphics`, like any flo_draw window.
+#[derive(Default)]
+pub struct FloRenderer {
+    canvas: Option<DrawingTarget>,
+    /// Only for a window the renderer opened itself, as otherwise whoever
+    /// opened it handles its events.
+    events: Option<Receiver<DrawEvent>>,
+    drawing: Vec<Draw>,
+    sprites: SpritePool,
+    hud: Hud,
+    camera: Camera,
+}
+
+impl FloRenderer {
+    pub fn new() -> Self {
+        FloRenderer::default()
+    }
+
+    pub fn with_canvas(canvas: DrawingTarget) -> Self {
+        //! Draw into a window that is already open, such as the interactive one.
+        //! `init` must not be called then.
+        let hud = Hud::new();
+        canvas.draw(|gc| hud.load_font(gc));
+        FloRenderer {
+            canvas: Some(canvas),
+            hud,
+            ..FloRenderer::default()
+        }
+    }
+
+    pub fn drawing(&mut self) -> (&mut Vec<Draw>, &mut SpritePool) {
+        //! The frame being gathered, and the sprites of the window, for drawing
+        //! what the renderer cannot draw itself.
+        (&mut self.drawing, &mut self.sprites)
+    }
+
+    pub fn begin_particles(&mut self, camera: Camera) {
+        //! Draw the particles that follow on their layer, as `camera` sees them,
+        //! for a frame that was not started with `begin_frame`.
+        self.camera = camera;
+        self.drawing.layer(Layer::Particles.id());
+    }
+}
+
+impl Renderer for FloRenderer {
+    fn init(&mut self, title: &str) -> SimResult<()> {
+        let (canvas, events) = create_drawing_window_with_events(title.to_string());
+        canvas.draw(|gc| self.hud.load_font(gc));
+        self.canvas = Some(canvas);
+        self.events = Some(window_events(events));
+        Ok(())
+    }
+
+    fn begin_frame(&mut self, background: [f32; 4]) {
+        self.camera = Camera::new();
+        let gc = &mut self.drawing;
+        Layer::Particles.clear(gc);
+        Layer::Background.clear(gc);
+        gc.canvas_height(HEIGHT as f32);
+        gc.center_region(0.0, 0.0, WIDTH as f32, HEIGHT as f32);
+        gc.new_path();
+        gc.rect(0.0, 0.0, WIDTH as f32, HEIGHT as f32);
+        gc.fill_color(to_color(background));
+        gc.fill();
+        gc.layer(Layer::Particles.id());
+    }
+
+    fn draw_particle(&mut self, position: Vec2, radius: f64, color: [f32; 4]) {
+        let center = self.camera.to_window(position);
+        self.sprites.draw_circle(
+            &mut self.drawing,
+            Layer::Particles.id(),
+            (center.0, center.1),
+            radius * self.camera.zoom,
+            to_color(color),
+        );
+    }
+
+    fn overlay_text(&mut self, lines: &[String], color: [f32; 4]) {
+        self.hud.draw(&mut self.drawing, lines, to_color(color));
+    }
+
+    fn end_frame(&mut self) -> bool {
+        let Some(canvas) = &self.canvas else {
+            return false;
+        };
+        canvas.write(mem::take(&mut self.drawing));
+        !self
+            .events
+            .iter()
+            .flat_map(Receiver::try_iter)
+            .any(|event| {
+                matches!(
+                    event,
+                    DrawEvent::Closed | DrawEvent::KeyDown(_, Some(Key::KeyEscape))
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_are_gathered_until_they_end() {
+        let mut renderer = FloRenderer::new();
+        renderer.begin_frame([0.0, 0.0, 0.0, 1.0]);
+        renderer.draw_particle(Vec2(100.0, 200.0), 5.0, [1.0, 0.0, 0.0, 1.0]);
+        renderer.overlay_text(&["particles: 1".to_string()], [1.0; 4]);
+        assert!(renderer.drawing.contains(&Draw::ClearLayer));
+        assert_eq!(
+            renderer
+                .drawing
+                .iter()
+                .filter(|draw| matches!(draw, Draw::DrawSprite(_)))
+                .count(),
+            1
+        );
+        assert!(renderer
+            .drawing
+            .iter()
+            .any(|draw| matches!(draw, Draw::DrawText(_, text, _, _) if text == "particles: 1")));
+        // Without a window there is nowhere to show the frame
+        assert!(!renderer.end_frame());
+    }
+}
@@ -0,0 +1,249 @@
+use super::Renderer;
+use fontdue::{Font, FontSettings};
+use particle_core::error::SimResult;
+use particle_core::particle::{HEIGHT, WIDTH};
+use particle_core::utils::Vec2;
+use pixels::{Pixels, SurfaceTexture};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use winit::application::ApplicationHandler;
+use winit::dpi::LogicalSize;
+use winit::event::{ElementState, KeyEvent, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, EventLoop};
+use winit::keyboard::{Key, NamedKey};
+use winit::platform::pump_events::{EventLoopExtPumpEvents, PumpStatus};
+use winit::window::{Window, WindowId};
+
+/// The side of the square frame, in pixels. The window scales it to fit.
+const SIZE: usize = 800;
+const FONT_SIZE: f32 = 14.0;
+const LINE_HEIGHT: usize = 18;
+/// The most times to wait for winit to open the window before giving up.
+const OPEN_ATTEMPTS: u32 = 100;
+
+/// The window and what it shows, which only exist once winit has resumed.
+#[derive(Default)]
+struct Surface {
+    title: String,
+    window: Option<Arc<Window>>,
+    pixels: Option<Pixels<'static>>,
+    closed: bool,
+    error: Option<String>,
+}
+
+impl ApplicationHandler for Surface {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+        let attributes = Window::default_attributes()
+            .with_title(self.title.as_str())
+            .with_inner_size(LogicalSize::new(SIZE as u32, SIZE as u32));
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => Arc::new(window),
+            Err(err) => {
+                self.error = Some(err.to_string());
+                return;
+            }
+        };
+        let size = window.inner_size();
+        let surface = SurfaceTexture::new(size.width, size.height, Arc::clone(&window));
+        match Pixels::new(SIZE as u32, SIZE as u32, surface) {
+            Ok(pixels) => self.pixels = Some(pixels),
+            Err(err) => self.error = Some(err.to_string()),
+        }
+        self.window = Some(window);
+    }
+
+    fn window_event(&mut self, _: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested
+            | WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::Escape),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.closed = true,
+            WindowEvent::Resized(size) if size.width > 0 && size.height > 0 => {
+                if let Some(pixels) = &mut self.pixels {
+                    if let Err(err) = pixels.resize_surface(size.width, size.height) {
+                        tracing::warn!(%err, "could not resize the window");
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Draws in software into an RGBA frame, which pixels then scales into a winit
+/// window. Circles get a pixel of antialiasing at the edge, and text is laid out
+/// with fontdue in the same font as the flo_draw window.
+pub struct PixelsRenderer {
+    event_loop: Option<EventLoop<()>>,
+    surface: Surface,
+    font: Font,
+    frame: Vec<u8>,
+}
+
+impl PixelsRenderer {
+    pub fn new() -> Self {
+        let font = Font::from_bytes(
+            include_bytes!("../../assets/Lato-Regular.ttf") as &[u8],
+            FontSettings::default(),
+        )
+        .expect("the bundled font is valid");
+        PixelsRenderer {
+            event_loop: None,
+            surface: Surface::default(),
+            font,
+            frame: vec![0; SIZE * SIZE * 4],
+        }
+    }
+
+    fn blend(&mut self, x: usize, y: usize, color: [f32; 4], coverage: f32) {
+        let alpha = color[3] * coverage;
+        let pixel = &mut self.frame[(y * SIZE + x) * 4..][..4];
+        for (channel, source) in pixel.iter_mut().zip(color) {
+            let blended = *channel as f32 * (1.0 - alpha) + source * 255.0 * alpha;
+            *channel = blended.round().clamp(0.0, 255.0) as u8;
+        }
+        pixel[3] = 255;
+    }
+}
+
+impl Default for PixelsRenderer {
+    fn default() -> Self {
+        PixelsRenderer::new()
+    }
+}
+
+fn to_pixels(position: Vec2) -> (f64, f64) {
+    //! Where a point of the box falls in the frame, whose rows run top down.
+    (
+        position.0 * SIZE as f64 / WIDTH,
+        (HEIGHT - position.1) * SIZE as f64 / HEIGHT,
+    )
+}
+
+impl Renderer for PixelsRenderer {
+    fn init(&mut self, title: &str) -> SimResult<()> {
+        let mut event_loop = EventLoop::new().map_err(io::Error::other)?;
+        self.surface.title = title.to_string();
+        for _ in 0..OPEN_ATTEMPTS {
+            if self.surface.pixels.is_some() || self.surface.error.is_some() {
+                break;
+            }
+            let timeout = Some(Duration::from_millis(10));
+            if let PumpStatus::Exit(_) = event_loop.pump_app_events(timeout, &mut self.surface) {
+                break;
+            }
+        }
+        if let Some(err) = self.surface.error.take() {
+            return Err(io::Error::other(err).into());
+        }
+        if self.surface.pixels.is_none() {
+            return Err(io::Error::other("the window never opened").into());
+        }
+        self.event_loop = Some(event_loop);
+        Ok(())
+    }
+
+    fn begin_frame(&mut self, background: [f32; 4]) {
+        let [r, g, b, _] = background.map(|channel| (channel.clamp(0.0, 1.0) * 255.0) as u8);
+        for pixel in self.frame.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[r, g, b, 255]);
+        }
+    }
+
+    fn draw_particle(&mut self, position: Vec2, radius: f64, color: [f32; 4]) {
+        let (x, y) = to_pixels(position);
+        let radius = (radius * SIZE as f64 / WIDTH).max(0.5);
+        let rows = (y - radius).floor().max(0.0) as usize
+            ..((y + radius).ceil().max(0.0) as usize).min(SIZE);
+        let columns = (x - radius).floor().max(0.0) as usize
+            ..((x + radius).ceil().max(0.0) as usize).min(SIZE);
+        for row in rows {
+            for column in columns.clone() {
+                let dx = column as f64 + 0.5 - x;
+                let dy = row as f64 + 0.5 - y;
+                let coverage = (radius + 0.5 - (dx * dx + dy * dy).sqrt()).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    self.blend(column, row, color, coverage as f32);
+                }
+            }
+        }
+    }
+
+    fn overlay_text(&mut self, lines: &[String], color: [f32; 4]) {
+        for (row, line) in lines.iter().enumerate() {
+            let baseline = (LINE_HEIGHT * (row + 1)) as i32;
+            let mut pen = 10.0;
+            for character in line.chars() {
+                let (metrics, coverage) = self.font.rasterize(character, FONT_SIZE);
+                let left = pen as i32 + metrics.xmin;
+                let top = baseline - metrics.height as i32 - metrics.ymin;
+                for (index, value) in coverage.iter().enumerate() {
+                    let x = left + (index % metrics.width.max(1)) as i32;
+                    let y = top + (index / metrics.width.max(1)) as i32;
+                    if *value > 0 && (0..SIZE as i32).contains(&x) && (0..SIZE as i32).contains(&y)
+                    {
+                        self.blend(x as usize, y as usize, color, *value as f32 / 255.0);
+                    }
+                }
+                pen += metrics.advance_width;
+            }
+        }
+    }
+
+    fn end_frame(&mut self) -> bool {
+        let Some(event_loop) = &mut self.event_loop else {
+            return false;
+        };
+        if let PumpStatus::Exit(_) =
+            event_loop.pump_app_events(Some(Duration::ZERO), &mut self.surface)
+        {
+            self.surface.closed = true;
+        }
+        if let Some(pixels) = &mut self.surface.pixels {
+            pixels.frame_mut().copy_from_slice(&self.frame);
+            if let Err(err) = pixels.render() {
+                tracing::warn!(%err, "could not draw the frame");
+            }
+        }
+        !self.surface.closed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(renderer: &PixelsRenderer, x: usize, y: usize) -> [u8; 4] {
+        renderer.frame[(y * SIZE + x) * 4..][..4]
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_particles_are_painted_over_the_background() {
+        let mut renderer = PixelsRenderer::new();
+        renderer.begin_frame([0.0, 0.0, 1.0, 1.0]);
+        // The bottom left corner of the box is the bottom left of the frame
+        renderer.draw_particle(Vec2(100.0, 100.0), 10.0, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(pixel(&renderer, 80, SIZE - 80), [255, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 80, 80), [0, 0, 255, 255]);
+        // Halfway transparent halfway covers the background
+        renderer.draw_particle(Vec2(500.0, 500.0), 10.0, [1.0, 0.0, 0.0, 0.5]);
+        assert_eq!(pixel(&renderer, 400, 400), [128, 0, 128, 255]);
+
+        renderer.begin_frame([0.0, 0.0, 0.0, 1.0]);
+        renderer.overlay_text(&["frame: 1".to_string()], [1.0; 4]);
+        assert!(renderer.frame.chunks_exact(4).any(|pixel| pixel[0] > 0));
+        assert!(!renderer.end_frame());
+    }
+}
@@ -0,0 +1,207 @@
+use super::Renderer;
+use particle_core::error::SimResult;
+use particle_core::particle::{HEIGHT, WIDTH};
+use particle_core::utils::Vec2;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::SetTitle;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style};
+use ratatui::symbols::Marker;
+use ratatui::text::Line;
+use ratatui::widgets::canvas::{Canvas, Points};
+use ratatui::widgets::Paragraph;
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+use std::time::Duration;
+
+/// What one frame shows, gathered up until the frame ends.
+#[derive(Default)]
+struct Picture {
+    background: [f32; 4],
+    particles: Vec<(Vec2, f64, Color)>,
+    text: Vec<String>,
+    text_color: [f32; 4],
+}
+
+impl Picture {
+    fn color(&self, color: [f32; 4]) -> Color {
+        //! The terminal color of `color` laid over the background, since
+        //! characters cannot be see-through.
+        let [r, g, b] = [0, 1, 2].map(|channel| {
+            let blended = color[channel] * color[3] + self.background[channel] * (1.0 - color[3]);
+            (blended.clamp(0.0, 1.0) * 255.0).round() as u8
+        });
+        Color::Rgb(r, g, b)
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let area = fit(frame.area());
+        if area.is_empty() {
+            return;
+        }
+        let dot = (
+            WIDTH / (area.width as f64 * 2.0),
+            HEIGHT / (area.height as f64 * 4.0),
+        );
+        let canvas = Canvas::default()
+            .marker(Marker::Braille)
+            .background_color(self.color(self.background))
+            .x_bounds([0.0, WIDTH])
+            .y_bounds([0.0, HEIGHT])
+            .paint(|ctx| {
+                for (position, radius, color) in &self.particles {
+                    ctx.draw(&Points {
+                        coords: &disc(*position, *radius, dot),
+                        color: *color,
+                    });
+                }
+            });
+        frame.render_widget(canvas, area);
+
+        let width = self.text.iter().map(|line| line.chars().count()).max();
+        let text = Rect {
+            width: width.unwrap_or(0) as u16,
+            height: self.text.len() as u16,
+            ..area
+        };
+        let lines: Vec<Line> = self
+            .text
+            .iter()
+            .map(|line| Line::raw(line.as_str()))
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).style(Style::default().fg(self.color(self.text_color))),
+            text.intersection(area),
+        );
+    }
+}
+
+fn fit(area: Rect) -> Rect {
+    //! The middle of `area` that shows the square box with square dots. A braille
+    //! character has two columns of dots and four rows, and is about twice as
+    //! tall as it is wide, so the box takes twice as many columns as rows.
+    let height = area.height.min(area.width / 2);
+    let width = height * 2;
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+fn disc(center: Vec2, radius: f64, dot: (f64, f64)) -> Vec<(f64, f64)> {
+    //! The dots, `dot` apart, that cover a particle, or its center alone when it
+    //! is smaller than a dot.
+    let mut coords = vec![(center.0, center.1)];
+    let columns = (radius / dot.0) as i64;
+    let rows = (radius / dot.1) as i64;
+    for column in -columns..=columns {
+        for row in -rows..=rows {
+            let (x, y) = (column as f64 * dot.0, row as f64 * dot.1);
+            if (column, row) != (0, 0) && x * x + y * y <= radius * radius {
+                coords.push((center.0 + x, center.1 + y));
+            }
+        }
+    }
+    coords
+}
+
+/// Draws into the terminal with braille characters, eight dots to a character,
+/// so that the simulation can be watched over SSH or without a desktop. Quits
+/// on Escape, Q or Ctrl+C.
+#[derive(Default)]
+pub struct TerminalRenderer {
+    terminal: Option<DefaultTerminal>,
+    picture: Picture,
+}
+
+impl TerminalRenderer {
+    pub fn new() -> Self {
+        TerminalRenderer::default()
+    }
+}
+
+impl Renderer for TerminalRenderer {
+    fn init(&mut self, title: &str) -> SimResult<()> {
+        self.terminal = Some(ratatui::try_init()?);
+        execute!(io::stdout(), SetTitle(title))?;
+        Ok(())
+    }
+
+    fn begin_frame(&mut self, background: [f32; 4]) {
+        self.picture.background = background;
+        self.picture.particles.clear();
+    }
+
+    fn draw_particle(&mut self, position: Vec2, radius: f64, color: [f32; 4]) {
+        let color = self.picture.color(color);
+        self.picture.particles.push((position, radius, color));
+    }
+
+    fn overlay_text(&mut self, lines: &[String], color: [f32; 4]) {
+        self.picture.text = lines.to_vec();
+        self.picture.text_color = color;
+    }
+
+    fn end_frame(&mut self) -> bool {
+        let Some(terminal) = &mut self.terminal else {
+            return false;
+        };
+        if let Err(err) = terminal.draw(|frame| self.picture.draw(frame)) {
+            tracing::warn!(%err, "could not draw the frame");
+        }
+        while let Ok(true) = event::poll(Duration::ZERO) {
+            let Ok(Event::Key(key)) = event::read() else {
+                continue;
+            };
+            let quit = match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => true,
+                KeyCode::Char('c') => key.modifiers.contains(KeyModifiers::CONTROL),
+                _ => false,
+            };
+            if quit && key.kind == KeyEventKind::Press {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Drop for TerminalRenderer {
+    fn drop(&mut self) {
+        //! Give the terminal back as it was found.
+        if self.terminal.take().is_some() {
+            ratatui::restore();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    #[test]
+    fn test_particles_show_as_braille_dots() {
+        let mut renderer = TerminalRenderer::new();
+        renderer.begin_frame([0.0, 0.0, 0.0, 1.0]);
+        // Bottom left, and big enough to cover whole characters
+        renderer.draw_particle(Vec2(100.0, 100.0), 60.0, [1.0, 0.0, 0.0, 1.0]);
+        renderer.overlay_text(&["frame: 1".to_string()], [1.0; 4]);
+
+        // Wider than the box, which stays square in the middle
+        let mut terminal = Terminal::new(TestBackend::new(60, 20)).unwrap();
+        terminal.draw(|frame| renderer.picture.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let cell = &buffer[(12, 17)];
+        assert_eq!(cell.symbol(), "⣿");
+        assert_eq!(cell.fg, Color::Rgb(255, 0, 0));
+        assert_eq!(buffer[(10, 0)].symbol(), "f");
+        assert_eq!(buffer[(9, 0)].symbol(), " ");
+        // Without a terminal there is nowhere to show the frame
+        assert!(!renderer.end_frame());
+    }
+}
@@ -0,0 +1,116 @@
+//! Screenshots: pressing F12 in the window draws the frame on screen a second time
+//! into an offscreen canvas, and saves it as `screenshot-<unix time>-frame-<frame>.png`
+//! in the working directory. A `.json` file of the same name sits next to it,
+//! describing the world in the picture: the frame, the particle count, the energy
+//! and temperature, the physics settings and the command line.
+use crate::utils;
+use flo_canvas::Draw;
+use flo_draw::{initialize_offscreen_rendering, render_canvas_offscreen};
+use futures::executor;
+use futures::stream;
+use particle_core::error::SimResult;
+use particle_core::settings::PhysicsSettings;
+use particle_core::simulation::Simulation;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Screenshots are as big as the canvas, one pixel per unit.
+const SIZE: usize = 1000;
+
+/// What the sidecar file says about the world in the picture.
+#[derive(Debug, Clone, Serialize)]
+pub struct Metadata {
+    pub frame: u64,
+    pub particles: usize,
+    pub kinetic_energy: f64,
+    pub temperature: f64,
+    pub settings: PhysicsSettings,
+    pub command_line: Vec<String>,
+}
+
+impl Metadata {
+    pub fn new(simulation: &Simulation, command_line: &[String]) -> Self {
+        Metadata {
+            frame: simulation.frame(),
+            particles: simulation.particle_count(),
+            kinetic_energy: simulation.kinetic_energy(),
+            temperature: simulation.temperature(),
+            settings: *simulation.settings(),
+            command_line: command_line.to_vec(),
+        }
+    }
+}
+
+fn encode_png(width: usize, height: usize, pixels: &[u8]) -> SimResult<Vec<u8>> {
+    //! Encode RGBA pixels whose rows run from the bottom of the picture up, the
+    //! way the canvas is laid out and the renderer returns them.
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let top_down: Vec<u8> = pixels.chunks(width * 4).rev().flatten().copied().collect();
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&top_down))
+        .map_err(io::Error::other)?;
+    Ok(png)
+}
+
+pub fn save(dir: &Path, drawing: Vec<Draw>, metadata: &Metadata) -> SimResult<PathBuf> {
+    //! Render `drawing` offscreen and write it and the metadata into `dir`,
+    //! returning the path of the picture. Rendering needs a graphics device,
+    //! so this can take a moment and is best done away from the frame loop.
+    let mut context = initialize_offscreen_rendering()
+        .map_err(|err| io::Error::other(format!("no offscreen renderer: {:?}", err)))?;
+    let pixels = executor::block_on(render_canvas_offscreen(
+        &mut context,
+        SIZE,
+        SIZE,
+        1.0,
+        stream::iter(drawing),
+    ));
+
+    let path = dir.join(format!(
+        "screenshot-{}-frame-{}.png",
+        utils::unix_time(),
+        metadata.frame
+    ));
+    fs::write(&path, encode_png(SIZE, SIZE, &pixels)?)?;
+    let json = serde_json::to_vec_pretty(metadata).map_err(io::Error::from)?;
+    fs::write(path.with_extension("json"), json)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_png_is_flipped_to_top_down() {
+        // A 1 by 2 picture, red at the bottom and blue at the top
+        let pixels = [255, 0, 0, 255, 0, 0, 255, 255];
+        let png = encode_png(1, 2, &pixels).unwrap();
+
+        let decoder = png::Decoder::new(Cursor::new(png));
+        let mut reader = decoder.read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut buffer).unwrap();
+        assert_eq!(buffer, [0, 0, 255, 255, 255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_metadata_describes_the_world() {
+        let mut simulation = Simulation::with_seed(3);
+        simulation.spawn_random(10);
+        simulation.step(1.0).unwrap();
+        let metadata = Metadata::new(&simulation, &["particle-simulator".to_string()]);
+        assert_eq!(metadata.frame, 1);
+        assert_eq!(metadata.particles, 10);
+        assert_eq!(metadata.kinetic_energy, simulation.kinetic_energy());
+        let json = serde_json::to_value(&metadata).unwrap();
+        assert_eq!(json["command_line"][0], "particle-simulator");
+    }
+}
@@ -0,0 +1,212 @@
+use crate::coloring::Palette;
+use hecs::{Entity, World};
+use particle_core::components::{Appearance, Species};
+use particle_core::particle::Particle;
+use particle_core::simulation::Simulation;
+use particle_core::utils::Vec2;
+use std::collections::BTreeSet;
+
+/// How many species S steps the selection through before starting over, as
+/// many as the palettes tell apart.
+const SPECIES_COUNT: u32 = 8;
+
+/// The particles picked out with a left drag in the window, followed from frame
+/// to frame by their ids, so that they can be changed all at once.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    ids: BTreeSet<u64>,
+    /// How many times the selection has been recolored, to give it a different
+    /// color of the palette each time.
+    recolors: u32,
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Selection::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.ids.clear();
+    }
+
+    pub fn select_within(&mut self, simulation: &Simulation, corner: Vec2, opposite: Vec2) {
+        //! Select every particle whose center lies in the rectangle between two
+        //! opposite corners, given in any order, in place of the last selection.
+        let (min, max) = (
+            Vec2(corner.0.min(opposite.0), corner.1.min(opposite.1)),
+            Vec2(corner.0.max(opposite.0), corner.1.max(opposite.1)),
+        );
+        self.ids = simulation
+            .world()
+            .query::<&Particle>()
+            .iter()
+            .filter(|(_, particle)| {
+                (min.0..=max.0).contains(&particle.position.0)
+                    && (min.1..=max.1).contains(&particle.position.1)
+            })
+            .map(|(_, particle)| particle.id)
+            .collect();
+    }
+
+    pub fn particles(&mut self, simulation: &Simulation) -> Vec<Particle> {
+        //! Copies of the selected particles, forgetting any that have gone since.
+        let particles: Vec<Particle> = simulation
+            .world()
+            .query::<&Particle>()
+            .iter()
+            .filter(|(_, particle)| self.ids.contains(&particle.id))
+            .map(|(_, particle)| *particle)
+            .collect();
+        self.ids = particles.iter().map(|particle| particle.id).collect();
+        particles
+    }
+
+    fn entities(&self, world: &World) -> Vec<Entity> {
+        world
+            .query::<&Particle>()
+            .iter()
+            .filter(|(_, particle)| self.ids.contains(&particle.id))
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+
+    fn change(&self, simulation: &mut Simulation, mut change: impl FnMut(&mut Particle)) {
+        for (_, particle) in simulation.world_mut().query_mut::<&mut Particle>() {
+            if self.ids.contains(&particle.id) {
+                change(particle);
+            }
+        }
+    }
+
+    pub fn stop(&self, simulation: &mut Simulation) {
+        self.change(simulation, |particle| particle.velocity = Vec2::ZERO);
+    }
+
+    pub fn share_velocity(&mut self, simulation: &mut Simulation) {
+        //! Set every selected particle moving at their average velocity, so that
+        //! the group moves as one. Pinned particles stay put and don't count.
+        let moving: Vec<Vec2> = self
+            .particles(simulation)
+            .iter()
+            .filter(|particle| !particle.fixed)
+            .map(|particle| particle.velocity)
+            .collect();
+        if moving.is_empty() {
+            return;
+        }
+        let average = moving
+            .iter()
+            .fold(Vec2::ZERO, |sum, velocity| sum + *velocity)
+            .scale(1.0 / moving.len() as f64);
+        self.change(simulation, |particle| {
+            if !particle.fixed {
+                particle.velocity = average;
+            }
+        });
+    }
+
+    pub fn next_species(&mut self, simulation: &mut Simulation) {
+        //! Move the whole selection to the species after that of its lowest id.
+        let Some(first) = self.ids.first().copied() else {
+            return;
+        };
+        let world = simulation.world_mut();
+        let current = world
+            .query::<(&Particle, Option<&Species>)>()
+            .iter()
+            .find(|(_, (particle, _))| particle.id == first)
+            .map_or(0, |(_, (_, species))| {
+                species.map_or(0, |species| species.0)
+            });
+        let species = Species((current + 1) % SPECIES_COUNT);
+        for entity in self.entities(world) {
+            world.insert_one(entity, species).ok();
+        }
+    }
+
+    pub fn recolor(&mut self, simulation: &mut Simulation, palette: Palette) {
+        //! Give the whole selection the next color of `palette`. It shows while
+        //! particles are drawn in their own color, the `random` attribute.
+        let color = palette.category(self.recolors);
+        self.recolors += 1;
+        for (_, (particle, appearance)) in simulation
+            .world_mut()
+            .query_mut::<(&Particle, &mut Appearance)>()
+        {
+            if self.ids.contains(&particle.id) {
+                appearance.color = color;
+            }
+        }
+    }
+
+    pub fn toggle_pinned(&mut self, simulation: &mut Simulation) {
+        //! Pin the selection in place, or free it if all of it is pinned already.
+        let pinned = self
+            .particles(simulation)
+            .iter()
+            .all(|particle| particle.fixed);
+        self.change(simulation, |particle| {
+            particle.fixed = !pinned;
+            particle.velocity = Vec2::ZERO;
+        });
+    }
+
+    pub fn delete(&mut self, simulation: &mut Simulation) -> Vec<u64> {
+        //! Remove every selected particle, emptying the selection. Returns the ids
+        //! of those removed.
+        let removed = self
+            .ids
+            .iter()
+            .copied()
+            .filter(|id| simulation.remove_particle(*id))
+            .collect();
+        self.ids.clear();
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bulk_operations_change_only_the_selection() {
+        let mut simulation = Simulation::new();
+        for (id, x) in [(1, 100.0), (2, 200.0), (3, 800.0)] {
+            simulation.spawn(Particle::new(id, 5.0, Vec2(x, 500.0), Vec2(x / 100.0, 0.0)));
+        }
+        let mut selection = Selection::new();
+        selection.select_within(&simulation, Vec2(250.0, 600.0), Vec2(50.0, 400.0));
+        assert_eq!(selection.len(), 2);
+
+        selection.share_velocity(&mut simulation);
+        assert_eq!(simulation.particle(1).unwrap().velocity, Vec2(1.5, 0.0));
+        assert_eq!(simulation.particle(3).unwrap().velocity, Vec2(8.0, 0.0));
+
+        selection.toggle_pinned(&mut simulation);
+        assert!(simulation.particle(2).unwrap().fixed);
+        selection.toggle_pinned(&mut simulation);
+        assert!(!simulation.particle(2).unwrap().fixed);
+
+        selection.next_species(&mut simulation);
+        let species: Vec<u32> = simulation
+            .world()
+            .query::<(&Particle, &Species)>()
+            .iter()
+            .map(|(_, (_, species))| species.0)
+            .collect();
+        assert_eq!(species, [1, 1]);
+
+        assert_eq!(selection.delete(&mut simulation), [1, 2]);
+        assert!(selection.is_empty());
+        assert_eq!(simulation.particle_count(), 1);
+    }
+}
@@ -0,0 +1,547 @@
+//! `--serve` mode: run headless and stream every frame over WebSocket.
+//! The REST API offered by `--http` is served from the same loop, see `http_api`.
+//!
+//! Each frame is sent to every connected client, either as JSON:
+//!
+//! ```json
+//! {"frame": 12, "paused": false, "particles": [{"id": 0, "x": 1.0, "y": 2.0, "vx": 0.5, "vy": 0.0, "radius": 10.0}]}
+//! ```
+//!
+//! or as a little endian binary message: a `u64` frame number, a `u32` particle
+//! count, then for each particle its `u64` id followed by `x`, `y` and `radius` as `f32`s.
+//!
+//! Clients control the simulation by sending JSON text messages such as
+//! `{"type": "pause"}`, `{"type": "resume"}`, `{"type": "spawn", "count": 10}`,
+//! `{"type": "spawn_particle", "radius": 10, "x": 500, "y": 500, "vx": 1, "vy": 0}`
+//! (add `"fixed": true` for a particle that never moves)
+//! `{"type": "set_gravity", "x": 0, "y": -0.2}`
+//! or `{"type": "explode", "x": 500, "y": 500, "strength": 1000000}`.
+//! Spawning stops at `--max-particles`.
+//!
+//! Anyone who can reach the ports can control the simulation, so they are only
+//! offered on the loopback interface unless `--bind` picks another address.
+use crate::clock::{FrameRate, FrameScheduler};
+use crate::http_api;
+use crate::metrics::{Metrics, MetricsServer};
+use clap::ValueEnum;
+use particle_core::error::{SimError, SimResult};
+use particle_core::particle::Particle;
+use particle_core::simulation::Simulation;
+use particle_core::utils::Vec2;
+use serde::{Deserialize, Serialize};
+use std::io::{self, ErrorKind};
+use std::mem;
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+use tungstenite::handshake::server::{NoCallback, ServerHandshake};
+use tungstenite::handshake::{HandshakeError, MidHandshake};
+use tungstenite::protocol::WebSocketConfig;
+use tungstenite::{Message, WebSocket};
+
+/// How long a new client has to complete the WebSocket handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The most bytes of frames that may wait to be sent to a client, a few seconds
+/// of a few thousand particles as JSON. A client that falls further behind is
+/// dropped rather than queued for without end.
+const MAX_QUEUED_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StreamFormat {
+    Json,
+    Binary,
+}
+
+/// A message sent by a client to control the simulation.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Control {
+    Pause,
+    Resume,
+    Spawn {
+        count: usize,
+    },
+    SpawnParticle {
+        radius: f64,
+        x: f64,
+        y: f64,
+        #[serde(default)]
+        vx: f64,
+        #[serde(default)]
+        vy: f64,
+        #[serde(default)]
+        fixed: bool,
+    },
+    SetGravity {
+        x: f64,
+        y: f64,
+    },
+    /// A radial impulse from a point, see `Simulation::explode`.
+    Explode {
+        x: f64,
+        y: f64,
+        strength: f64,
+    },
+}
+
+/// The state of one particle, as sent to clients.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ParticleState {
+    pub id: u64,
+    pub x: f64,
+    pub y: f64,
+    pub vx: f64,
+    pub vy: f64,
+    pub radius: f64,
+}
+
+/// The state of the whole simulation for one frame.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FrameState {
+    pub frame: u64,
+    pub paused: bool,
+    pub particles: Vec<ParticleState>,
+}
+
+/// A simulation being driven by remote clients.
+pub struct ServerState {
+    pub simulation: Simulation,
+    pub paused: bool,
+    /// The most particles clients may spawn up to, from `--max-particles`.
+    pub max_particles: Option<usize>,
+}
+
+impl ServerState {
+    pub fn new(simulation: Simulation) -> Self {
+        ServerState {
+            simulation,
+            paused: false,
+            max_particles: None,
+        }
+    }
+
+    fn room(&self) -> usize {
+        //! How many more particles fit under `max_particles`.
+        self.max_particles.map_or(usize::MAX, |max| {
+            max.saturating_sub(self.simulation.particle_count())
+        })
+    }
+
+    pub fn apply(&mut self, control: Control) -> SimResult<()> {
+        //! Carry out a control message from a client. A spawn is cut short at
+        //! `max_particles`, and a particle that cannot be simulated is refused.
+        tracing::info!(?control, "control message received");
+        match control {
+            Control::Pause => self.paused = true,
+            Control::Resume => self.paused = false,
+            Control::Spawn { count } => self.simulation.spawn_random(count.min(self.room())),
+            Control::SpawnParticle {
+                radius,
+                x,
+                y,
+                vx,
+                vy,
+                fixed,
+            } => {
+                if !(radius.is_finite() && radius > 0.0) {
+                    return Err(SimError::InvalidArgument {
+                        name: "particle radius".to_string(),
+                        value: radius.to_string(),
+                    });
+                }
+                if self.room() == 0 {
+                    return Err(SimError::InvalidArgument {
+                        name: "particle (the simulation is at --max-particles)".to_string(),
+                        value: self.simulation.particle_count().to_string(),
+                    });
+                }
+                let id = self.simulation.allocate_id();
+                let particle = Particle::new(id, radius, Vec2(x, y), Vec2(vx, vy));
+                self.simulation
+                    .spawn(if fixed { particle.pinned() } else { particle });
+            }
+            Control::SetGravity { x, y } => self.simulation.settings_mut().gravity = Vec2(x, y),
+            Control::Explode { x, y, strength } => {
+                self.simulation.explode(Vec2(x, y), strength);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn step(&mut self) -> SimResult<()> {
+        //! Advance one frame, unless a client has paused the simulation.
+        if !self.paused {
+            self.simulation.step(1.0)?;
+        }
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> FrameState {
+        let mut particles: Vec<ParticleState> = self
+            .simulation
+            .world()
+            .query::<&Particle>()
+            .iter()
+            .map(|(_, particle)| ParticleState {
+                id: particle.id,
+                x: particle.position.0,
+                y: particle.position.1,
+                vx: particle.velocity.0,
+                vy: particle.velocity.1,
+                radius: particle.radius,
+            })
+            .collect();
+        particles.sort_by_key(|particle| particle.id);
+        FrameState {
+            frame: self.simulation.frame(),
+            paused: self.paused,
+            particles,
+        }
+    }
+}
+
+pub fn encode_frame(state: &FrameState, format: StreamFormat) -> Message {
+    //! Encode a frame in the wire format described in the module docs.
+    match format {
+        StreamFormat::Json => {
+            Message::text(serde_json::to_string(state).expect("frame state is always serializable"))
+        }
+        StreamFormat::Binary => {
+            let mut bytes = Vec::with_capacity(12 + state.particles.len() * 20);
+            bytes.extend_from_slice(&state.frame.to_le_bytes());
+            bytes.extend_from_slice(&(state.particles.len() as u32).to_le_bytes());
+            for particle in state.particles.iter() {
+                bytes.extend_from_slice(&particle.id.to_le_bytes());
+                bytes.extend_from_slice(&(particle.x as f32).to_le_bytes());
+                bytes.extend_from_slice(&(particle.y as f32).to_le_bytes());
+                bytes.extend_from_slice(&(particle.radius as f32).to_le_bytes());
+            }
+            Message::binary(bytes)
+        }
+    }
+}
+
+fn is_would_block(err: &tungstenite::Error) -> bool {
+    matches!(err, tungstenite::Error::Io(io_err) if io_err.kind() == ErrorKind::WouldBlock)
+}
+
+type Handshake = ServerHandshake<TcpStream, NoCallback>;
+
+/// A client whose WebSocket handshake is still under way.
+struct Pending {
+    address: SocketAddr,
+    connected: Instant,
+    handshake: MidHandshake<Handshake>,
+}
+
+/// The connected clients, and those still shaking hands. Every socket is
+/// non-blocking, so that a slow client can never stall the simulation.
+#[derive(Default)]
+struct Clients {
+    pending: Vec<Pending>,
+    sockets: Vec<WebSocket<TcpStream>>,
+}
+
+impl Clients {
+    fn accept(&mut self, listener: &TcpListener) -> SimResult<()> {
+        //! Accept every client that has connected since the last frame, and take
+        //! each handshake as far as it goes without waiting for the client.
+        for pending in mem::take(&mut self.pending) {
+            if pending.connected.elapsed() > HANDSHAKE_TIMEOUT {
+                tracing::warn!(address = %pending.address, "WebSocket handshake timed out");
+                continue;
+            }
+            let result = pending.handshake.handshake();
+            self.shake_hands(result, pending.address, pending.connected);
+        }
+        loop {
+            match listener.accept() {
+                Ok((stream, address)) => {
+                    if let Err(err) = stream.set_nonblocking(true) {
+                        tracing::warn!(%address, %err, "could not accept client");
+                        continue;
+                    }
+                    let config = WebSocketConfig::default().max_write_buffer_size(MAX_QUEUED_BYTES);
+                    let result = tungstenite::accept_with_config(stream, Some(config));
+                    self.shake_hands(result, address, Instant::now());
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(SimError::Io(err)),
+            }
+        }
+    }
+
+    fn shake_hands(
+        &mut self,
+        result: Result<WebSocket<TcpStream>, HandshakeError<Handshake>>,
+        address: SocketAddr,
+        connected: Instant,
+    ) {
+        match result {
+            Ok(socket) => {
+                tracing::info!(%address, "client connected");
+                self.sockets.push(socket);
+            }
+            Err(HandshakeError::Interrupted(handshake)) => self.pending.push(Pending {
+                address,
+                connected,
+                handshake,
+            }),
+            Err(HandshakeError::Failure(err)) => {
+                tracing::warn!(%address, %err, "WebSocket handshake failed")
+            }
+        }
+    }
+}
+
+fn read_controls(socket: &mut WebSocket<TcpStream>, state: &mut ServerState) -> bool {
+    //! Apply every control message the client has sent since the last frame.
+    //! Returns false once the client has gone away.
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => match serde_json::from_str::<Control>(&text) {
+                Ok(control) => {
+                    if let Err(err) = state.apply(control) {
+                        tracing::warn!(%err, message = %text, "control message refused");
+                    }
+                }
+                Err(err) => tracing::warn!(%err, message = %text, "invalid control message"),
+            },
+            Ok(Message::Close(_)) => return false,
+            Ok(_) => {}
+            Err(err) if is_would_block(&err) => return true,
+            Err(_) => return false,
+        }
+    }
+}
+
+/// Which network interfaces to offer a headless simulation on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServeOptions {
+    /// The address to listen on, the loopback interface unless `--bind` says otherwise.
+    pub address: IpAddr,
+    pub websocket_port: Option<u16>,
+    pub http_port: Option<u16>,
+    pub metrics_port: Option<u16>,
+    pub format: StreamFormat,
+    pub max_particles: Option<usize>,
+}
+
+pub fn serve(simulation: Simulation, options: ServeOptions) -> SimResult<()> {
+    //! Run the simulation forever, streaming each frame to every connected
+    //! WebSocket client and answering REST requests between frames.
+    let listener = match options.websocket_port {
+        Some(port) => {
+            let listener = TcpListener::bind((options.address, port))?;
+            listener.set_nonblocking(true)?;
+            tracing::info!(
+                address = %options.address,
+                port,
+                format = ?options.format,
+                "streaming simulation over WebSocket"
+            );
+            Some(listener)
+        }
+        None => None,
+    };
+    let http_server = match options.http_port {
+        Some(port) => {
+            let server = tiny_http::Server::http((options.address, port))
+                .map_err(|err| SimError::Io(io::Error::other(err.to_string())))?;
+            tracing::info!(address = %options.address, port, "serving the REST API");
+            Some(server)
+        }
+        None => None,
+    };
+    let metrics_server = options
+        .metrics_port
+        .map(|port| MetricsServer::bind(options.address, port))
+        .transpose()?;
+
+    let mut state = ServerState::new(simulation);
+    state.max_particles = options.max_particles;
+    let mut clients = Clients::default();
+    let mut scheduler = FrameScheduler::new(FrameRate::default());
+    let mut metrics = Metrics::new();
+    loop {
+        if let Some(listener) = &listener {
+            clients.accept(listener)?;
+        }
+        clients
+            .sockets
+            .retain_mut(|socket| read_controls(socket, &mut state));
+        if let Some(server) = &http_server {
+            http_api::poll(server, &mut state);
+        }
+
+        let started = Instant::now();
+        state.step()?;
+        if !state.paused {
+            metrics.record_step(&state.simulation, started.elapsed());
+        }
+
+        if !clients.sockets.is_empty() {
+            let message = encode_frame(&state.snapshot(), options.format);
+            clients
+                .sockets
+                .retain_mut(|socket| match socket.send(message.clone()) {
+                    Ok(()) => true,
+                    // The frame is queued, and will be flushed along with the next one
+                    Err(err) if is_would_block(&err) => true,
+                    Err(tungstenite::Error::WriteBufferFull(_)) => {
+                        tracing::warn!("client fell too far behind, disconnecting it");
+                        false
+                    }
+                    Err(err) => {
+                        tracing::info!(%err, "client disconnected");
+                        false
+                    }
+                });
+        }
+
+        if let Some(server) = &metrics_server {
+            metrics.record_lag(scheduler.lag());
+            server.poll(&metrics);
+        }
+        scheduler.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_controls() {
+        let control: Control = serde_json::from_str(r#"{"type": "pause"}"#).unwrap();
+        assert_eq!(control, Control::Pause);
+        let control: Control =
+            serde_json::from_str(r#"{"type": "set_gravity", "x": 0, "y": -0.5}"#).unwrap();
+        assert_eq!(control, Control::SetGravity { x: 0.0, y: -0.5 });
+        let control: Control =
+            serde_json::from_str(r#"{"type": "spawn_particle", "radius": 5, "x": 1, "y": 2}"#)
+                .unwrap();
+        assert_eq!(
+            control,
+            Control::SpawnParticle {
+                radius: 5.0,
+                x: 1.0,
+                y: 2.0,
+                vx: 0.0,
+                vy: 0.0,
+                fixed: false
+            }
+        );
+        let control: Control =
+            serde_json::from_str(r#"{"type": "explode", "x": 1, "y": 2, "strength": 3}"#).unwrap();
+        assert_eq!(
+            control,
+            Control::Explode {
+                x: 1.0,
+                y: 2.0,
+                strength: 3.0
+            }
+        );
+        assert!(serde_json::from_str::<Control>(r#"{"type": "implode"}"#).is_err());
+    }
+
+    #[test]
+    fn test_apply_controls() {
+        let mut state = ServerState::new(Simulation::new());
+        state.max_particles = Some(5);
+        let particle = |radius| Control::SpawnParticle {
+            radius,
+            x: 100.0,
+            y: 100.0,
+            vx: 0.0,
+            vy: 0.0,
+            fixed: true,
+        };
+        state.apply(Control::Spawn { count: 3 }).unwrap();
+        state.apply(particle(5.0)).unwrap();
+        assert_eq!(state.simulation.particle_count(), 4);
+        assert_eq!(state.snapshot().particles.last().unwrap().id, 3);
+        assert!(state.apply(particle(0.0)).is_err());
+        assert!(state.apply(particle(f64::NAN)).is_err());
+
+        // Only one more fits under the limit
+        state.apply(Control::Spawn { count: 100 }).unwrap();
+        assert_eq!(state.simulation.particle_count(), 5);
+        assert!(state.apply(particle(5.0)).is_err());
+
+        // Ids are never handed out twice, even once the particle with the
+        // highest one is gone
+        let last = state.snapshot().particles.last().unwrap().id;
+        assert!(state.simulation.remove_particle(last));
+        state.apply(particle(5.0)).unwrap();
+        assert_eq!(state.snapshot().particles.last().unwrap().id, last + 1);
+
+        state
+            .apply(Control::SetGravity { x: 0.0, y: -1.0 })
+            .unwrap();
+        assert_eq!(state.simulation.settings().gravity, Vec2(0.0, -1.0));
+
+        state.apply(Control::Pause).unwrap();
+        state.step().unwrap();
+        assert_eq!(state.simulation.frame(), 0);
+        state.apply(Control::Resume).unwrap();
+        state.step().unwrap();
+        assert_eq!(state.simulation.frame(), 1);
+    }
+
+    #[test]
+    fn test_encode_binary_frame() {
+        let state = FrameState {
+            frame: 7,
+            paused: false,
+            particles: vec![ParticleState {
+                id: 3,
+                x: 1.5,
+                y: 2.5,
+                vx: 0.0,
+                vy: 0.0,
+                radius: 4.0,
+            }],
+        };
+        let bytes = encode_frame(&state, StreamFormat::Binary).into_data();
+        assert_eq!(bytes.len(), 32);
+        assert_eq!(u64::from_le_bytes(bytes[0..8].try_into().unwrap()), 7);
+        assert_eq!(u32::from_le_bytes(bytes[8..12].try_into().unwrap()), 1);
+        assert_eq!(u64::from_le_bytes(bytes[12..20].try_into().unwrap()), 3);
+        assert_eq!(f32::from_le_bytes(bytes[20..24].try_into().unwrap()), 1.5);
+        assert_eq!(f32::from_le_bytes(bytes[28..32].try_into().unwrap()), 4.0);
+    }
+
+    #[test]
+    fn test_handshakes_never_wait_for_the_client() {
+        use std::io::Write;
+        use std::thread;
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut clients = Clients::default();
+        // A client that has not sent its request yet is left for the next frame
+        while clients.pending.is_empty() {
+            clients.accept(&listener).unwrap();
+        }
+        assert!(clients.sockets.is_empty());
+
+        client
+            .write_all(
+                b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+                  Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                  Sec-WebSocket-Version: 13\r\n\r\n",
+            )
+            .unwrap();
+        for _ in 0..200 {
+            clients.accept(&listener).unwrap();
+            if !clients.sockets.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(clients.sockets.len(), 1);
+        assert!(clients.pending.is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+//! `--shm PATH`: publish every frame to a ring buffer in a memory mapped file,
+//! such as one under `/dev/shm`, so that other processes on the same machine can
+//! follow the live simulation without a socket or any parsing. Every number is
+//! little endian, on any machine, and every offset is in bytes. The frame counts
+//! that mark a frame as published are 8 byte aligned atomics, stored as their
+//! little endian value, so a reader loads them atomically and converts them,
+//! which costs nothing on a little endian machine.
+//!
+//! The file starts with a 64 byte header:
+//!
+//! | Offset | Type     | Field                                       |
+//! |--------|----------|---------------------------------------------|
+//! | 0      | `[u8;8]` | `PSIMRING`                                  |
+//! | 8      | `u32`    | layout version, 1                           |
+//! | 12     | `u32`    | number of slots                             |
+//! | 16     | `u32`    | particles each slot has room for            |
+//! | 20     | `u32`    | bytes per particle, 32                      |
+//! | 24     | `u64`    | bytes per slot                              |
+//! | 32     | `u64`    | frames published so far, 0 before the first |
+//!
+//! followed by the slots. Frame `n` of those published, counting from 1, goes
+//! into slot `(n - 1) % slots`, which starts with a 32 byte header:
+//!
+//! | Offset | Type  | Field                                                       |
+//! |--------|-------|-------------------------------------------------------------|
+//! | 0      | `u64` | `n`, or 0 while the slot is being written                   |
+//! | 8      | `u64` | simulation frame number                                     |
+//! | 16     | `u32` | particles in the slot                                       |
+//! | 20     | `u32` | particles in the simulation, more if the slot was too small |
+//!
+//! and then each particle, sorted by id: its `u64` id, then `x`, `y`, `vx`, `vy`,
+//! `radius` and `mass` as `f32`s.
+//!
+//! To read the latest frame, load `n` from the file header, copy slot
+//! `(n - 1) % slots`, and keep the copy only if the slot's own `n` read the same
+//! before and after copying. Otherwise the writer has come round to the slot in
+//! the meantime, and the copy should be retried with the new latest frame.
+use memmap2::MmapMut;
+use particle_core::error::SimResult;
+use particle_core::export;
+use particle_core::simulation::Simulation;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+const MAGIC: &[u8; 8] = b"PSIMRING";
+const VERSION: u32 = 1;
+/// How many frames the ring holds, so that a reader copying one frame has this
+/// many frame times before the writer overwrites it.
+const SLOTS: usize = 8;
+const HEADER_SIZE: usize = 64;
+const SLOT_HEADER_SIZE: usize = 32;
+const PARTICLE_SIZE: usize = 32;
+/// Where the number of frames published so far is kept in the file header.
+const PUBLISHED: usize = 32;
+
+/// The writing end of the ring buffer.
+pub struct FrameRing {
+    map: MmapMut,
+    capacity: usize,
+    published: u64,
+}
+
+impl FrameRing {
+    pub fn create(path: &Path, capacity: usize) -> SimResult<Self> {
+        //! Create or replace the file at `path`, with slots for `capacity` particles.
+        let capacity = capacity.clamp(1, u32::MAX as usize);
+        let slot_size = SLOT_HEADER_SIZE + capacity * PARTICLE_SIZE;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len((HEADER_SIZE + SLOTS * slot_size) as u64)?;
+        // SAFETY: the file was just sized, and other processes only ever read it.
+        // Words that are written while readers may be reading them are atomics.
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        map[..8].copy_from_slice(MAGIC);
+        map[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        map[12..16].copy_from_slice(&(SLOTS as u32).to_le_bytes());
+        map[16..20].copy_from_slice(&(capacity as u32).to_le_bytes());
+        map[20..24].copy_from_slice(&(PARTICLE_SIZE as u32).to_le_bytes());
+        map[24..32].copy_from_slice(&(slot_size as u64).to_le_bytes());
+        tracing::info!(path = %path.display(), capacity, "publishing frames to shared memory");
+        Ok(FrameRing {
+            map,
+            capacity,
+            published: 0,
+        })
+    }
+
+    fn slot_size(&self) -> usize {
+        SLOT_HEADER_SIZE + self.capacity * PARTICLE_SIZE
+    }
+
+    fn word(&mut self, offset: usize) -> &AtomicU64 {
+        //! The `u64` at `offset`, which is always a multiple of 8 in a page aligned map.
+        assert!(offset.is_multiple_of(8) && offset + 8 <= self.map.len());
+        // SAFETY: the word is in bounds and aligned, and is only accessed atomically.
+        unsafe { &*(self.map.as_mut_ptr().add(offset) as *const AtomicU64) }
+    }
+
+    pub fn publish(&mut self, simulation: &Simulation) {
+        //! Write the current frame to the next slot, then make it the latest.
+        let sequence = self.published + 1;
+        let slot_size = self.slot_size();
+        let start = HEADER_SIZE + (self.published as usize % SLOTS) * slot_size;
+        self.word(start).store(0, Ordering::Release);
+        // Nothing written to the slot below may be seen before it is marked as
+        // being written
+        fence(Ordering::Release);
+
+        let particles = export::sorted_particles(simulation.world());
+        let count = particles.len().min(self.capacity);
+        let slot = &mut self.map[start + 8..start + slot_size];
+        slot[..8].copy_from_slice(&simulation.frame().to_le_bytes());
+        slot[8..12].copy_from_slice(&(count as u32).to_le_bytes());
+        slot[12..16].copy_from_slice(&(particles.len() as u32).to_le_bytes());
+        let records = slot[SLOT_HEADER_SIZE - 8..].chunks_exact_mut(PARTICLE_SIZE);
+        for (record, particle) in records.zip(&particles) {
+            record[..8].copy_from_slice(&particle.id.to_le_bytes());
+            let values = [
+                particle.position.0,
+                particle.position.1,
+                particle.velocity.0,
+                particle.velocity.1,
+                particle.radius,
+                particle.mass,
+            ];
+            for (bytes, value) in record[8..].chunks_exact_mut(4).zip(values) {
+                bytes.copy_from_slice(&(value as f32).to_le_bytes());
+            }
+        }
+
+        self.word(start).store(sequence.to_le(), Ordering::Release);
+        self.word(PUBLISHED)
+            .store(sequence.to_le(), Ordering::Release);
+        self.published = sequence;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use particle_core::particle::Particle;
+    use particle_core::utils::Vec2;
+    use std::fs;
+
+    fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_frames_follow_the_layout() {
+        let path = std::env::temp_dir().join(format!("particle-ring-{}", std::process::id()));
+        let mut simulation = Simulation::new();
+        for id in 0..3 {
+            simulation.spawn(Particle::new(
+                id,
+                2.0,
+                Vec2(100.0 * (id + 1) as f64, 50.0),
+                Vec2(1.0, 0.0),
+            ));
+        }
+        let mut ring = FrameRing::create(&path, 2).unwrap();
+        ring.publish(&simulation);
+        simulation.step(1.0).unwrap();
+        ring.publish(&simulation);
+
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(&bytes[..8], MAGIC);
+        assert_eq!(u32_at(&bytes, 12), SLOTS as u32);
+        assert_eq!(u32_at(&bytes, 16), 2);
+        let slot_size = u64_at(&bytes, 24) as usize;
+        assert_eq!(bytes.len(), HEADER_SIZE + SLOTS * slot_size);
+        assert_eq!(u64_at(&bytes, PUBLISHED), 2);
+
+        // The second frame went into the second slot
+        let slot = &bytes[HEADER_SIZE + slot_size..];
+        assert_eq!(u64_at(slot, 0), 2);
+        assert_eq!(u64_at(slot, 8), 1);
+        assert_eq!(u32_at(slot, 16), 2);
+        assert_eq!(u32_at(slot, 20), 3);
+        let second = &slot[SLOT_HEADER_SIZE + PARTICLE_SIZE..];
+        assert_eq!(u64_at(second, 0), 1);
+        assert_eq!(f32::from_le_bytes(second[8..12].try_into().unwrap()), 201.0);
+        assert_eq!(f32::from_le_bytes(second[24..28].try_into().unwrap()), 2.0);
+    }
+}
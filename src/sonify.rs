@@ -0,0 +1,90 @@
+//! `--sonify`: an ambient drone whose pitch follows the temperature and whose
+//! volume follows the collision rate, to hear a long run change phase without
+//! watching it. `Sonifier` works out the tone, and `Drone` plays it, which needs
+//! the `sound` feature.
+#[cfg(feature = "sound")]
+mod drone;
+
+#[cfg(feature = "sound")]
+pub use self::drone::Drone;
+
+/// The pitch of the temperature the drone starts at, A3.
+const BASE_FREQUENCY: f64 = 220.0;
+/// The drone stays within these pitches, from A1 to A6, however far the
+/// temperature goes.
+const LOWEST_FREQUENCY: f64 = 55.0;
+const HIGHEST_FREQUENCY: f64 = 1760.0;
+/// The volume at the busiest collision rate so far, kept well below full scale
+/// since the drone plays on and on.
+const MAX_VOLUME: f64 = 0.2;
+
+/// A pitch in hertz and a volume from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub frequency: f64,
+    pub volume: f64,
+}
+
+impl Tone {
+    pub const SILENT: Tone = Tone {
+        frequency: BASE_FREQUENCY,
+        volume: 0.0,
+    };
+}
+
+/// Turns the measurements of each frame into a tone. The first temperature sets
+/// the base pitch and every doubling of it raises the pitch an octave, while the
+/// volume is the collision rate as a share of the busiest rate heard so far.
+#[derive(Debug, Clone, Default)]
+pub struct Sonifier {
+    reference: Option<f64>,
+    busiest: f64,
+}
+
+impl Sonifier {
+    pub fn new() -> Self {
+        Sonifier::default()
+    }
+
+    pub fn tone(&mut self, temperature: f64, collisions_per_second: f64) -> Tone {
+        if !(temperature.is_finite() && temperature > 0.0) {
+            return Tone::SILENT;
+        }
+        let reference = *self.reference.get_or_insert(temperature);
+        if collisions_per_second.is_finite() {
+            self.busiest = self.busiest.max(collisions_per_second);
+        }
+        let volume = if self.busiest > 0.0 {
+            (collisions_per_second / self.busiest).clamp(0.0, 1.0) * MAX_VOLUME
+        } else {
+            0.0
+        };
+        Tone {
+            frequency: (BASE_FREQUENCY * temperature / reference)
+                .clamp(LOWEST_FREQUENCY, HIGHEST_FREQUENCY),
+            volume,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pitch_follows_temperature_and_volume_collisions() {
+        let mut sonifier = Sonifier::new();
+        assert_eq!(sonifier.tone(0.0, 10.0), Tone::SILENT);
+        assert_eq!(
+            sonifier.tone(2.0, 100.0),
+            Tone {
+                frequency: BASE_FREQUENCY,
+                volume: MAX_VOLUME
+            }
+        );
+        let hotter = sonifier.tone(4.0, 50.0);
+        assert_eq!(hotter.frequency, 2.0 * BASE_FREQUENCY);
+        assert_eq!(hotter.volume, MAX_VOLUME / 2.0);
+        assert_eq!(sonifier.tone(1e6, 0.0).frequency, HIGHEST_FREQUENCY);
+    }
+}
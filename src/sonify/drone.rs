@@ -0,0 +1,57 @@
+use super::Tone;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use particle_core::error::SimResult;
+use std::f64::consts::TAU;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+/// How much of the way to a new tone the drone moves each sample, so that
+/// it glides between the tones of successive frames instead of clicking.
+const GLIDE: f64 = 0.0005;
+
+/// A sine wave played on the default output device for as long as this lives.
+pub struct Drone {
+    tone: Arc<Mutex<Tone>>,
+    _stream: cpal::Stream,
+}
+
+impl Drone {
+    pub fn start() -> SimResult<Drone> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| io::Error::other("no audio output device"))?;
+        let config = device.default_output_config().map_err(io::Error::other)?;
+        let sample_rate = config.sample_rate() as f64;
+        let channels = config.channels() as usize;
+        let tone = Arc::new(Mutex::new(Tone::SILENT));
+        let target = Arc::clone(&tone);
+        let (mut playing, mut phase) = (Tone::SILENT, 0.0);
+        let stream = device
+            .build_output_stream(
+                config.into(),
+                move |data: &mut [f32], _| {
+                    let target = *target.lock().unwrap();
+                    for frame in data.chunks_mut(channels) {
+                        playing.frequency += (target.frequency - playing.frequency) * GLIDE;
+                        playing.volume += (target.volume - playing.volume) * GLIDE;
+                        phase = (phase + playing.frequency / sample_rate) % 1.0;
+                        let sample = ((phase * TAU).sin() * playing.volume) as f32;
+                        frame.fill(sample);
+                    }
+                },
+                |err| tracing::warn!(%err, "audio output failed"),
+                None,
+            )
+            .map_err(io::Error::other)?;
+        stream.play().map_err(io::Error::other)?;
+        Ok(Drone {
+            tone,
+            _stream: stream,
+        })
+    }
+
+    pub fn play(&self, tone: Tone) {
+        //! Glide over to `tone`.
+        *self.tone.lock().unwrap() = tone;
+    }
+}
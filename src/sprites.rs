@@ -0,0 +1,144 @@
+use flo_canvas::*;
+use std::collections::HashMap;
+
+/// Each color channel is rounded to one of this many levels before
+/// picking a sprite, which bounds the number of distinct sprites.
+const COLOR_LEVELS: f32 = 4.0;
+/// Radii smaller than this all share the smallest bucket.
+const MIN_BUCKET_RADIUS: f64 = 0.5;
+
+/// Identifies a shared sprite: a radius bucket and a quantized color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct SpriteKey {
+    bucket: i32,
+    color: [u8; 4],
+}
+
+/// Particles are rendered from a small pool of shared circle sprites rather than
+/// one sprite per particle. Sprites are bucketed by radius (the buckets grow
+/// geometrically, by a factor of √2) and scaled to the exact radius when drawn,
+/// so the rendering identity of a particle is independent of its physics id.
+#[derive(Default)]
+pub struct SpritePool {
+    sprites: HashMap<SpriteKey, SpriteId>,
+}
+
+fn bucket_for(radius: f64) -> i32 {
+    //! The smallest bucket whose radius is at least `radius`.
+    (radius.max(MIN_BUCKET_RADIUS).log2() * 2.0).ceil() as i32
+}
+
+fn bucket_radius(bucket: i32) -> f64 {
+    2.0_f64.powf(bucket as f64 / 2.0)
+}
+
+fn quantize(color: Color) -> [u8; 4] {
+    let (r, g, b, a) = color.to_rgba_components();
+    [r, g, b, a].map(|channel| (channel.clamp(0.0, 1.0) * (COLOR_LEVELS - 1.0)).round() as u8)
+}
+
+fn dequantize(color: [u8; 4]) -> Color {
+    let [r, g, b, a] = color.map(|level| level as f32 / (COLOR_LEVELS - 1.0));
+    Color::Rgba(r, g, b, a)
+}
+
+impl SpritePool {
+    pub fn new() -> Self {
+        SpritePool::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    pub fn draw_circle(
+        &mut self,
+        gc: &mut impl GraphicsContext,
+        layer: LayerId,
+        center: (f64, f64),
+        radius: f64,
+        color: Color,
+    ) {
+        //! Draw a filled circle on `layer`, defining its sprite first
+        //! if no particle of a similar size and color has been drawn before.
+        let key = SpriteKey {
+            bucket: bucket_for(radius),
+            color: quantize(color),
+        };
+
+        let next_id = self.sprites.len() as u64;
+        let sprite_id = match self.sprites.get(&key) {
+            Some(sprite_id) => *sprite_id,
+            None => {
+                let sprite_id = SpriteId(next_id);
+                gc.sprite(sprite_id);
+                gc.clear_sprite();
+                gc.new_path();
+                gc.circle(0.0, 0.0, bucket_radius(key.bucket) as f32);
+                gc.fill_color(dequantize(key.color));
+                gc.fill();
+                // Defining a sprite switches away from the layer being drawn on
+                gc.layer(layer);
+                self.sprites.insert(key, sprite_id);
+                sprite_id
+            }
+        };
+
+        let scale = (radius / bucket_radius(key.bucket)) as f32;
+        gc.sprite_transform(SpriteTransform::Identity);
+        gc.sprite_transform(SpriteTransform::Translate(center.0 as f32, center.1 as f32));
+        gc.sprite_transform(SpriteTransform::Scale(scale, scale));
+        gc.draw_sprite(sprite_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_covers_radius() {
+        for radius in [0.1, 0.5, 1.0, 3.3, 17.0, 49.9, 50.0] {
+            let bucket_size = bucket_radius(bucket_for(radius));
+            // The scale applied when drawing is always a shrink of at most √2
+            assert!(bucket_size >= radius);
+            assert!(bucket_size <= radius.max(MIN_BUCKET_RADIUS) * 2.0_f64.sqrt() + 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_similar_particles_share_sprites() {
+        let mut pool = SpritePool::new();
+        let mut drawing: Vec<Draw> = vec![];
+        for offset in 0..100 {
+            pool.draw_circle(
+                &mut drawing,
+                LayerId(0),
+                (offset as f64, 0.0),
+                20.0 + offset as f64 * 0.01,
+                Color::Rgba(0.9, 0.1, 0.1, 1.0),
+            );
+        }
+        assert_eq!(pool.len(), 1);
+
+        pool.draw_circle(
+            &mut drawing,
+            LayerId(0),
+            (0.0, 0.0),
+            3.0,
+            Color::Rgba(0.9, 0.1, 0.1, 1.0),
+        );
+        pool.draw_circle(
+            &mut drawing,
+            LayerId(0),
+            (0.0, 0.0),
+            20.0,
+            Color::Rgba(0.1, 0.1, 0.9, 1.0),
+        );
+        assert_eq!(pool.len(), 3);
+    }
+}
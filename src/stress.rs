@@ -0,0 +1,149 @@
+//! `--stress` mode: find out how many particles this machine can simulate.
+//!
+//! The particle count is doubled, starting from the requested count, until a step
+//! takes longer than the frame budget on average, and then the largest count that
+//! still fits is narrowed down by bisection. Every count is a fresh simulation from
+//! the same seed, so a run that breaks (with `--paranoid`, any broken invariant)
+//! can be reproduced from the seed and count that are printed.
+//!
+//! Crowding that many particles into the box also makes rare collision states,
+//! such as several particles overlapping at once, far more common than in a normal
+//! run, so a stress test with `--paranoid` doubles as a fuzzer for the solver.
+use particle_core::error::SimResult;
+use particle_core::simulation::Simulation;
+use particle_core::spawn::SpawnSettings;
+use particle_core::sweep_prune::SweepAxis;
+use std::time::{Duration, Instant};
+
+/// Steps run before timing starts, while the random placement settles.
+const WARMUP_STEPS: u64 = 5;
+/// Counts within this share of each other are close enough to stop bisecting.
+const PRECISION: f64 = 0.01;
+
+#[derive(Debug, Clone)]
+pub struct StressOptions {
+    /// The longest a step may take on average.
+    pub budget: Duration,
+    /// Number of steps timed at every particle count.
+    pub steps: u64,
+    pub start: usize,
+    /// No count above this is tried.
+    pub limit: usize,
+    pub seed: u64,
+    pub spawn_settings: SpawnSettings,
+    pub paranoid: bool,
+    pub sweep_axis: SweepAxis,
+}
+
+/// The result of a stress test for one broadphase.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StressReport {
+    pub broadphase: &'static str,
+    /// The most particles whose steps fit in the budget, if even the
+    /// starting count did.
+    pub max_stable: Option<usize>,
+    /// The average step time at `max_stable`.
+    pub step_time: Duration,
+}
+
+pub fn time_steps(count: usize, options: &StressOptions) -> SimResult<Duration> {
+    //! The average time of a step with `count` random particles.
+    let mut simulation = Simulation::with_seed(options.seed);
+    *simulation.spawn_settings_mut() = options.spawn_settings;
+    simulation.set_paranoid(options.paranoid);
+    simulation.settings_mut().sweep_axis = options.sweep_axis;
+    simulation.spawn_random(count);
+    for _ in 0..WARMUP_STEPS {
+        simulation.step(1.0)?;
+    }
+    let start = Instant::now();
+    for _ in 0..options.steps {
+        simulation.step(1.0)?;
+    }
+    Ok(start.elapsed() / options.steps.max(1) as u32)
+}
+
+pub fn max_stable(
+    start: usize,
+    limit: usize,
+    mut fits: impl FnMut(usize) -> SimResult<bool>,
+) -> SimResult<Option<usize>> {
+    //! The largest count between `start` and `limit` for which `fits` holds,
+    //! assuming that it holds up to some count and never again after it.
+    let start = start.clamp(1, limit.max(1));
+    if !fits(start)? {
+        return Ok(None);
+    }
+    // Double until a count no longer fits, or the limit is reached
+    let mut good = start;
+    let mut bad = loop {
+        if good >= limit {
+            return Ok(Some(good));
+        }
+        let next = (good * 2).min(limit);
+        if !fits(next)? {
+            break next;
+        }
+        good = next;
+    };
+    // Bisect between the last count that fit and the first that did not
+    while bad - good > 1 && (bad - good) as f64 > PRECISION * good as f64 {
+        let middle = good + (bad - good) / 2;
+        if fits(middle)? {
+            good = middle;
+        } else {
+            bad = middle;
+        }
+    }
+    Ok(Some(good))
+}
+
+pub fn run(options: &StressOptions) -> SimResult<StressReport> {
+    //! Ramp up the particle count, printing the step time of every count tried.
+    let mut step_times = Vec::new();
+    let max_stable = max_stable(options.start, options.limit, |count| {
+        let step_time = time_steps(count, options)?;
+        println!(
+            "{:>9} particles: {:>8.3} ms per step",
+            count,
+            step_time.as_secs_f64() * 1000.0
+        );
+        tracing::debug!(count, ?step_time, "stress test step");
+        step_times.push((count, step_time));
+        Ok(step_time <= options.budget)
+    })?;
+    let step_time = step_times
+        .iter()
+        .find(|(count, _)| Some(*count) == max_stable)
+        .map(|(_, step_time)| *step_time)
+        .unwrap_or_default();
+    Ok(StressReport {
+        broadphase: options.sweep_axis.name(),
+        max_stable,
+        step_time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_stable_is_found_by_doubling_and_bisecting() {
+        let mut tried = Vec::new();
+        let found = max_stable(10, 1_000_000, |count| {
+            tried.push(count);
+            Ok(count <= 1234)
+        })
+        .unwrap();
+        // Within one percent, and never over
+        assert!(found.is_some_and(|found| (1222..=1234).contains(&found)));
+        assert_eq!(&tried[..9], &[10, 20, 40, 80, 160, 320, 640, 1280, 960]);
+    }
+
+    #[test]
+    fn test_max_stable_respects_the_limits() {
+        assert_eq!(max_stable(10, 300, |_| Ok(true)).unwrap(), Some(300));
+        assert_eq!(max_stable(10, 300, |_| Ok(false)).unwrap(), None);
+    }
+}
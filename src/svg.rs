@@ -0,0 +1,247 @@
+//! SVG snapshots: the current frame as true vector circles, for figures that stay
+//! sharp at any size. F9 in the window saves `snapshot-<unix time>-frame-<frame>.svg`
+//! in the working directory, and `--svg FILE` saves the last frame of a headless
+//! run. With `--svg-trails N` each particle also trails a line through where it
+//! was over the last N frames.
+use crate::coloring::Coloring;
+use crate::theme::Theme;
+use crate::utils;
+use hecs::World;
+use particle_core::components::{Appearance, Charge, Species};
+use particle_core::error::SimResult;
+use particle_core::particle::{Particle, HEIGHT, WIDTH};
+use particle_core::shapes::Body;
+use particle_core::simulation::Simulation;
+use particle_core::utils::Vec2;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A step between two positions of a trail longer than this is a jump, through
+/// a portal or a periodic wall, and breaks the line rather than crossing the box.
+const JUMP: f64 = WIDTH / 2.0;
+
+/// Where every particle has been over the last few frames.
+#[derive(Debug, Clone, Default)]
+pub struct Trails {
+    length: usize,
+    paths: HashMap<u64, VecDeque<Vec2>>,
+}
+
+impl Trails {
+    pub fn new(length: usize) -> Self {
+        Trails {
+            length,
+            paths: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, world: &World) {
+        //! Add where every particle is now, forgetting the oldest positions and
+        //! the particles that are gone.
+        let mut paths = HashMap::with_capacity(self.paths.len());
+        for (_, particle) in world.query::<&Particle>().iter() {
+            let mut path = self.paths.remove(&particle.id).unwrap_or_default();
+            if path.len() >= self.length {
+                path.pop_front();
+            }
+            path.push_back(particle.position);
+            paths.insert(particle.id, path);
+        }
+        self.paths = paths;
+    }
+}
+
+fn rgb(color: [f32; 4]) -> String {
+    let [r, g, b] = [0, 1, 2].map(|channel| (color[channel].clamp(0.0, 1.0) * 255.0).round());
+    format!("#{:02x}{:02x}{:02x}", r as u8, g as u8, b as u8)
+}
+
+fn flip(point: Vec2) -> (f64, f64) {
+    //! SVG y runs down from the top, while the box's runs up from the bottom.
+    (point.0, HEIGHT - point.1)
+}
+
+fn points(points: &[Vec2]) -> String {
+    let flipped: Vec<String> = points
+        .iter()
+        .map(|point| {
+            let (x, y) = flip(*point);
+            format!("{:.2},{:.2}", x, y)
+        })
+        .collect();
+    flipped.join(" ")
+}
+
+pub fn render(
+    simulation: &Simulation,
+    coloring: &Coloring,
+    theme: Theme,
+    trails: Option<&Trails>,
+) -> String {
+    //! The SVG document of the frame, one unit of the box to a pixel.
+    let (r, g, b, _) = theme.background().to_rgba_components();
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n\
+         <rect width=\"{w}\" height=\"{h}\" fill=\"{}\"/>\n",
+        rgb([r, g, b, 1.0]),
+        w = WIDTH,
+        h = HEIGHT,
+    );
+    for obstacle in simulation.obstacles() {
+        let (x, y) = flip(Vec2(obstacle.min_x, obstacle.max_y));
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"#666666\"/>",
+            x,
+            y,
+            obstacle.max_x - obstacle.min_x,
+            obstacle.max_y - obstacle.min_y
+        );
+    }
+
+    let blend = if theme.glows() {
+        " style=\"mix-blend-mode:screen\""
+    } else {
+        ""
+    };
+    let mut particles = String::new();
+    let mut lines = String::new();
+    for (_, (particle, appearance, body, charge, species)) in simulation
+        .world()
+        .query::<(
+            &Particle,
+            &Appearance,
+            Option<&Body>,
+            Option<&Charge>,
+            Option<&Species>,
+        )>()
+        .iter()
+    {
+        let color = coloring.color(particle, appearance, charge, species);
+        let paint = if theme.outlines_only() {
+            format!(
+                "fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\"",
+                rgb(color)
+            )
+        } else {
+            format!("fill=\"{}\" fill-opacity=\"{:.3}\"", rgb(color), color[3])
+        };
+
+        if let Some(path) = trails.and_then(|trails| trails.paths.get(&particle.id)) {
+            let path: Vec<Vec2> = path.iter().copied().collect();
+            for stretch in path.chunk_by(|a, b| (*b - *a).length() < JUMP) {
+                if stretch.len() > 1 {
+                    let _ = writeln!(
+                        lines,
+                        "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-opacity=\"0.5\" stroke-width=\"1.5\"/>",
+                        points(stretch),
+                        rgb(color)
+                    );
+                }
+            }
+        }
+
+        let vertices = body
+            .map(|body| body.vertices(particle.position))
+            .unwrap_or_default();
+        let _ = match (body, vertices.as_slice()) {
+            (Some(body), [start, end]) => {
+                // A capsule is its segment, drawn with a thick round pen
+                writeln!(
+                    particles,
+                    "<polyline points=\"{}\" stroke=\"{}\" stroke-width=\"{:.2}\" stroke-linecap=\"round\"/>",
+                    points(&[*start, *end]),
+                    rgb(color),
+                    2.0 * body.shape.rounding()
+                )
+            }
+            (_, [_, _, _, ..]) => {
+                writeln!(
+                    particles,
+                    "<polygon points=\"{}\" {}/>",
+                    points(&vertices),
+                    paint
+                )
+            }
+            _ => {
+                let (x, y) = flip(particle.position);
+                writeln!(
+                    particles,
+                    "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" {}/>",
+                    x, y, particle.radius, paint
+                )
+            }
+        };
+    }
+    // Trails run behind every particle
+    let _ = write!(svg, "{}<g{}>\n{}</g>\n</svg>\n", lines, blend, particles);
+    svg
+}
+
+pub fn save(dir: &Path, frame: u64, svg: &str) -> SimResult<PathBuf> {
+    //! Write `svg` into `dir`, returning its path.
+    let path = dir.join(format!(
+        "snapshot-{}-frame-{}.svg",
+        utils::unix_time(),
+        frame
+    ));
+    fs::write(&path, svg)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coloring::{ColorBy, Palette};
+
+    fn spawn(simulation: &mut Simulation, position: Vec2, velocity: Vec2) {
+        let entity = simulation.spawn(Particle::new(0, 5.0, position, velocity));
+        let appearance = Appearance {
+            color: [1.0, 0.0, 0.0, 1.0],
+        };
+        simulation
+            .world_mut()
+            .insert_one(entity, appearance)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_particles_are_circles_flipped_upright() {
+        let mut simulation = Simulation::new();
+        spawn(&mut simulation, Vec2(100.0, 200.0), Vec2(3.0, 0.0));
+        let mut trails = Trails::new(3);
+        for _ in 0..5 {
+            trails.record(simulation.world());
+            simulation.step(1.0).unwrap();
+        }
+        let coloring = Coloring::new(ColorBy::Random, Palette::Viridis);
+
+        let svg = render(&simulation, &coloring, Theme::Dark, None);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("<circle cx=\"115.00\" cy=\"800.00\" r=\"5.00\" fill=\"#ff0000\""));
+        assert!(!svg.contains("<polyline"));
+
+        // Only the last three positions are kept
+        let svg = render(&simulation, &coloring, Theme::Wireframe, Some(&trails));
+        assert!(svg.contains("points=\"106.00,800.00 109.00,800.00 112.00,800.00\""));
+        assert!(svg.contains("fill=\"none\" stroke=\"#ff0000\""));
+    }
+
+    #[test]
+    fn test_jumps_break_the_trail() {
+        let mut simulation = Simulation::new();
+        spawn(&mut simulation, Vec2(990.0, 500.0), Vec2::ZERO);
+        let mut trails = Trails::new(10);
+        for x in [990.0, 995.0, 5.0, 10.0] {
+            for (_, particle) in simulation.world_mut().query_mut::<&mut Particle>() {
+                particle.position = Vec2(x, 500.0);
+            }
+            trails.record(simulation.world());
+        }
+        let coloring = Coloring::new(ColorBy::Random, Palette::Viridis);
+        let svg = render(&simulation, &coloring, Theme::Dark, Some(&trails));
+        assert_eq!(svg.matches("<polyline").count(), 2);
+    }
+}
@@ -0,0 +1,210 @@
+//! The `sweep` subcommand: run the same headless simulation once for every value
+//! of a parameter in a range, and write a CSV of one metric against the
+//! parameter, for quick sensitivity studies.
+use crate::experiments::{self, RunSpec, RunStats};
+use clap::ValueEnum;
+use particle_core::error::{SimError, SimResult};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// A physics setting that can be swept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parameter {
+    /// The restitution of every wall.
+    Restitution,
+    /// The friction of every wall.
+    Friction,
+    /// The strength of gravity pulling towards the floor.
+    Gravity,
+    MaxSpeed,
+    Particles,
+}
+
+impl Parameter {
+    pub fn name(self) -> &'static str {
+        match self {
+            Parameter::Restitution => "restitution",
+            Parameter::Friction => "friction",
+            Parameter::Gravity => "gravity",
+            Parameter::MaxSpeed => "max_speed",
+            Parameter::Particles => "particles",
+        }
+    }
+
+    fn apply(self, spec: &mut RunSpec, value: f64) {
+        match self {
+            Parameter::Restitution => spec.wall_restitution = value,
+            Parameter::Friction => spec.wall_friction = value,
+            Parameter::Gravity => spec.gravity = [0.0, -value],
+            Parameter::MaxSpeed => spec.max_speed = Some(value),
+            Parameter::Particles => spec.particles = value.round() as usize,
+        }
+    }
+}
+
+impl FromStr for Parameter {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        [
+            Parameter::Restitution,
+            Parameter::Friction,
+            Parameter::Gravity,
+            Parameter::MaxSpeed,
+            Parameter::Particles,
+        ]
+        .into_iter()
+        .find(|parameter| parameter.name() == value)
+        .ok_or(SimError::InvalidArgument {
+            name: "sweep parameter".to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
+/// The values a parameter is swept over, written `<name>=<start>:<end>:<step>`.
+/// Both ends are included.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamRange {
+    pub parameter: Parameter,
+    pub start: f64,
+    pub end: f64,
+    pub step: f64,
+}
+
+impl ParamRange {
+    pub fn values(&self) -> Vec<f64> {
+        //! Every value in the range. They are computed from the start rather than
+        //! added up, and rounded, so that `0.1:1.0:0.1` gives `0.3` rather than
+        //! `0.30000000000000004` and does not drop the end.
+        let count = ((self.end - self.start) / self.step + 1e-9).floor() as usize + 1;
+        (0..count)
+            .map(|i| ((self.start + i as f64 * self.step) * 1e9).round() / 1e9)
+            .collect()
+    }
+}
+
+impl FromStr for ParamRange {
+    type Err = SimError;
+
+    fn from_str(value: &str) -> SimResult<Self> {
+        let invalid = || SimError::InvalidArgument {
+            name: "sweep range".to_string(),
+            value: value.to_string(),
+        };
+        let (name, range) = value.split_once('=').ok_or_else(invalid)?;
+        let bounds = range
+            .split(':')
+            .map(|bound| bound.trim().parse::<f64>().map_err(|_| invalid()))
+            .collect::<SimResult<Vec<_>>>()?;
+        let [start, end, step] = bounds[..] else {
+            return Err(invalid());
+        };
+        if !bounds.iter().all(|bound| bound.is_finite()) || step <= 0.0 || end < start {
+            return Err(invalid());
+        }
+        Ok(ParamRange {
+            parameter: name.trim().parse()?,
+            start,
+            end,
+            step,
+        })
+    }
+}
+
+/// What is measured from each run of a sweep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum Metric {
+    /// The change in kinetic energy over the run, relative to the start
+    EnergyDrift,
+    FinalEnergy,
+    FinalTemperature,
+    Collisions,
+    /// The wall time the run took
+    Seconds,
+}
+
+impl Metric {
+    pub fn name(self) -> String {
+        self.to_possible_value()
+            .map_or_else(String::new, |value| value.get_name().to_string())
+    }
+
+    pub fn of(self, stats: &RunStats) -> f64 {
+        match self {
+            Metric::EnergyDrift => stats.energy_drift,
+            Metric::FinalEnergy => stats.final_energy,
+            Metric::FinalTemperature => stats.final_temperature,
+            Metric::Collisions => stats.collisions as f64,
+            Metric::Seconds => stats.seconds,
+        }
+    }
+}
+
+pub fn run(
+    base: &RunSpec,
+    range: &ParamRange,
+    metric: Metric,
+    mut out: impl Write,
+) -> SimResult<()> {
+    //! Run `base` once for every value in `range`, writing a row of the CSV
+    //! as each run finishes.
+    writeln!(out, "{},{}", range.parameter.name(), metric.name())?;
+    for value in range.values() {
+        let mut spec = base.clone();
+        range.parameter.apply(&mut spec, value);
+        let name = format!("{}={}", range.parameter.name(), value);
+        let stats = experiments::run(&spec, &name, io::sink())?;
+        tracing::info!(
+            parameter = value,
+            metric = metric.of(&stats),
+            "sweep run finished"
+        );
+        writeln!(out, "{},{}", value, metric.of(&stats))?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranges_include_both_ends() {
+        let range: ParamRange = "restitution=0.1:1.0:0.1".parse().unwrap();
+        assert_eq!(range.parameter, Parameter::Restitution);
+        let values = range.values();
+        assert_eq!(values.len(), 10);
+        assert_eq!(values[2], 0.3);
+        assert_eq!(values[9], 1.0);
+        assert_eq!(
+            "particles=10:10:5".parse::<ParamRange>().unwrap().values(),
+            [10.0]
+        );
+
+        assert!("restitution=1:0:0.1".parse::<ParamRange>().is_err());
+        assert!("restitution=0:1:0".parse::<ParamRange>().is_err());
+        assert!("restitution=0:1".parse::<ParamRange>().is_err());
+        assert!("viscosity=0:1:0.5".parse::<ParamRange>().is_err());
+    }
+
+    #[test]
+    fn test_sweep_writes_a_row_per_value() {
+        let base = RunSpec {
+            seed: 3,
+            particles: 10,
+            steps: 50,
+            ..RunSpec::default()
+        };
+        let range = "gravity=0:0.2:0.1".parse().unwrap();
+        let mut csv = Vec::new();
+        run(&base, &range, Metric::FinalEnergy, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "gravity,final_energy");
+        assert!(lines[1].starts_with("0,"));
+    }
+}
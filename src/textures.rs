@@ -0,0 +1,195 @@
+//! `--textures DIR`: paint particles with PNG images instead of flat colors, such as
+//! balls, planets or emoji. Each image is stretched over the square around its
+//! particle and cut to the circle. The directory is searched for:
+//!
+//! - `particle-<id>.png`, for the particle with that id,
+//! - `species-<n>.png`, for every particle of species `n`,
+//! - `particle.png`, for every other particle,
+//!
+//! and particles that none of these match keep their flat color.
+use flo_canvas::*;
+use particle_core::components::Species;
+use particle_core::error::{SimError, SimResult};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+/// An image as RGBA bytes, from the top row down.
+#[derive(Debug, Clone, PartialEq)]
+struct Image {
+    width: u32,
+    height: u32,
+    pixels: Arc<Vec<u8>>,
+}
+
+fn read_png(path: &Path) -> SimResult<Image> {
+    let invalid = |err: png::DecodingError| SimError::InvalidArgument {
+        name: "texture".to_string(),
+        value: format!("{}: {}", path.display(), err),
+    };
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(invalid)?;
+    let mut buffer = vec![0; reader.output_buffer_size().unwrap_or(0)];
+    let info = reader.next_frame(&mut buffer).map_err(invalid)?;
+
+    let channels = info.color_type.samples();
+    let mut pixels = Vec::with_capacity(info.width as usize * info.height as usize * 4);
+    for line in buffer.chunks(info.line_size).take(info.height as usize) {
+        for pixel in line.chunks(channels).take(info.width as usize) {
+            let rgba = match *pixel {
+                [gray] => [gray, gray, gray, 255],
+                [gray, alpha] => [gray, gray, gray, alpha],
+                [r, g, b] => [r, g, b, 255],
+                [r, g, b, alpha] => [r, g, b, alpha],
+                _ => [0; 4],
+            };
+            pixels.extend_from_slice(&rgba);
+        }
+    }
+    Ok(Image {
+        width: info.width,
+        height: info.height,
+        pixels: Arc::new(pixels),
+    })
+}
+
+/// Which particles each image paints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Target {
+    Particle(u64),
+    Species(u32),
+    Everything,
+}
+
+impl Target {
+    fn from_file_name(name: &str) -> Option<Target> {
+        let stem = name.strip_suffix(".png")?;
+        if stem == "particle" {
+            return Some(Target::Everything);
+        }
+        if let Some(id) = stem.strip_prefix("particle-") {
+            return id.parse().ok().map(Target::Particle);
+        }
+        stem.strip_prefix("species-")?
+            .parse()
+            .ok()
+            .map(Target::Species)
+    }
+}
+
+/// The images of a texture directory, and the canvas textures they are loaded into.
+#[derive(Debug, Clone, Default)]
+pub struct Textures {
+    images: Vec<Image>,
+    targets: HashMap<Target, TextureId>,
+}
+
+impl Textures {
+    pub fn load(dir: &Path) -> SimResult<Self> {
+        //! Read every image in `dir` that is named after the particles it paints.
+        let mut textures = Textures::default();
+        let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Some(target) = Target::from_file_name(&name) else {
+                continue;
+            };
+            let id = TextureId(textures.images.len() as u64);
+            textures.images.push(read_png(&entry.path())?);
+            textures.targets.insert(target, id);
+        }
+        if textures.images.is_empty() {
+            return Err(SimError::InvalidArgument {
+                name: "textures (no particle.png, species-N.png or particle-ID.png)".to_string(),
+                value: dir.display().to_string(),
+            });
+        }
+        tracing::info!(dir = %dir.display(), count = textures.images.len(), "loaded textures");
+        Ok(textures)
+    }
+
+    pub fn define(&self, gc: &mut impl GraphicsContext) {
+        //! Load every image into the canvas. Needed once for each canvas,
+        //! and again after it is cleared.
+        for (index, image) in self.images.iter().enumerate() {
+            let id = TextureId(index as u64);
+            gc.create_texture(id, image.width, image.height, TextureFormat::Rgba);
+            gc.set_texture_bytes(id, 0, 0, image.width, image.height, image.pixels.clone());
+        }
+    }
+
+    pub fn lookup(&self, id: u64, species: Option<&Species>) -> Option<TextureId> {
+        //! The texture of a particle, if any, the most specific first.
+        self.targets
+            .get(&Target::Particle(id))
+            .or_else(|| species.and_then(|species| self.targets.get(&Target::Species(species.0))))
+            .or_else(|| self.targets.get(&Target::Everything))
+            .copied()
+    }
+
+    pub fn draw_circle(
+        gc: &mut impl GraphicsContext,
+        texture: TextureId,
+        center: (f64, f64),
+        radius: f64,
+    ) {
+        //! Fill a circle with `texture`, its top row at the top of the circle.
+        let (x, y, r) = (center.0 as f32, center.1 as f32, radius as f32);
+        gc.new_path();
+        gc.circle(x, y, r);
+        gc.fill_texture(texture, x - r, y + r, x + r, y - r);
+        gc.fill();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_png(path: &Path, pixels: &[u8]) {
+        let mut encoder = png::Encoder::new(File::create(path).unwrap(), 1, 1);
+        encoder.set_color(png::ColorType::GrayscaleAlpha);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(pixels).unwrap();
+    }
+
+    #[test]
+    fn test_the_most_specific_texture_wins() {
+        let dir = std::env::temp_dir().join(format!("particle-textures-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        write_png(&dir.join("particle.png"), &[10, 255]);
+        write_png(&dir.join("species-1.png"), &[20, 128]);
+        write_png(&dir.join("particle-7.png"), &[30, 255]);
+        fs::write(dir.join("notes.txt"), "not a texture").unwrap();
+        let textures = Textures::load(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Loaded in the order of their names
+        assert_eq!(textures.images.len(), 3);
+        assert_eq!(*textures.images[2].pixels, [20, 20, 20, 128]);
+        assert_eq!(textures.lookup(7, Some(&Species(1))), Some(TextureId(0)));
+        assert_eq!(textures.lookup(8, Some(&Species(1))), Some(TextureId(2)));
+        assert_eq!(textures.lookup(8, Some(&Species(0))), Some(TextureId(1)));
+        assert_eq!(textures.lookup(8, None), Some(TextureId(1)));
+
+        let mut drawing: Vec<Draw> = vec![];
+        textures.define(&mut drawing);
+        assert_eq!(
+            drawing[0],
+            Draw::Texture(TextureId(0), TextureOp::Create(1, 1, TextureFormat::Rgba))
+        );
+    }
+
+    #[test]
+    fn test_a_directory_without_textures_is_an_error() {
+        assert!(matches!(
+            Textures::load(Path::new("assets")),
+            Err(SimError::InvalidArgument { .. })
+        ));
+    }
+}
@@ -0,0 +1,94 @@
+use clap::ValueEnum;
+use flo_canvas::{BlendMode, Color};
+
+/// How the window looks. Every theme draws the same scene, only the
+/// background and the way particles are painted change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Theme {
+    /// Filled particles on black
+    Dark,
+    /// Filled particles on white
+    Light,
+    /// Glowing particles on black, blended so that overlaps brighten
+    Neon,
+    /// Only the outlines of particles, on black
+    Wireframe,
+}
+
+impl Theme {
+    pub fn next(self) -> Theme {
+        //! The theme after this one, wrapping around to the first.
+        let themes = Theme::value_variants();
+        let index = themes.iter().position(|theme| *theme == self).unwrap_or(0);
+        themes[(index + 1) % themes.len()]
+    }
+
+    pub fn background(self) -> Color {
+        match self {
+            Theme::Light => Color::Rgba(1.0, 1.0, 1.0, 1.0),
+            Theme::Dark | Theme::Neon | Theme::Wireframe => Color::Rgba(0.0, 0.0, 0.0, 1.0),
+        }
+    }
+
+    pub fn text(self) -> Color {
+        //! The color of the HUD, the labels and the highlight ring.
+        match self {
+            Theme::Light => Color::Rgba(0.1, 0.1, 0.1, 0.9),
+            Theme::Dark | Theme::Neon | Theme::Wireframe => Color::Rgba(0.9, 0.9, 0.9, 0.9),
+        }
+    }
+
+    pub fn grid(self) -> (Color, Color) {
+        //! The colors of the grid lines and of their labels.
+        match self {
+            Theme::Light => (
+                Color::Rgba(0.8, 0.8, 0.8, 1.0),
+                Color::Rgba(0.4, 0.4, 0.4, 1.0),
+            ),
+            Theme::Dark | Theme::Neon | Theme::Wireframe => (
+                Color::Rgba(0.3, 0.3, 0.3, 1.0),
+                Color::Rgba(0.6, 0.6, 0.6, 1.0),
+            ),
+        }
+    }
+
+    pub fn blend_mode(self) -> BlendMode {
+        //! How particles are painted over what is behind them. The canvas has no
+        //! purely additive mode, and screen is the closest: overlapping glows add
+        //! up towards white without ever overflowing.
+        match self {
+            Theme::Neon => BlendMode::Screen,
+            Theme::Dark | Theme::Light | Theme::Wireframe => BlendMode::SourceOver,
+        }
+    }
+
+    pub fn outlines_only(self) -> bool {
+        self == Theme::Wireframe
+    }
+
+    pub fn glows(self) -> bool {
+        self == Theme::Neon
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_themes_cycle_through_all_of_them() {
+        let mut theme = Theme::Dark;
+        let mut seen = vec![];
+        for _ in 0..4 {
+            seen.push(theme);
+            theme = theme.next();
+        }
+        assert_eq!(theme, Theme::Dark);
+        assert_eq!(
+            seen,
+            [Theme::Dark, Theme::Light, Theme::Neon, Theme::Wireframe]
+        );
+        assert_ne!(Theme::Light.background(), Theme::Dark.background());
+        assert_eq!(Theme::Neon.blend_mode(), BlendMode::Screen);
+    }
+}
@@ -0,0 +1,191 @@
+//! Timed events of a scenario, such as opening a gate ten seconds in or doubling
+//! gravity at thirty, listed under `events` in a `--config` file:
+//!
+//! ```json
+//! { "events": [
+//!     { "at": 10, "action": "remove-obstacles", "min_x": 480, "min_y": 0, "max_x": 520, "max_y": 200 },
+//!     { "at": 30, "action": "scale-gravity", "factor": 2 }
+//! ] }
+//! ```
+//!
+//! `at` is in seconds of simulated time, so a run that is slowed down, paused or
+//! falling behind still meets each event at the same point of the simulation.
+//! Every event runs once, right after the step that reaches its time.
+use crate::clock::simulated_seconds;
+use particle_core::obstacles::Obstacle;
+use particle_core::simulation::Simulation;
+use particle_core::utils::Vec2;
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+/// What an event does to the simulation.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum Action {
+    /// Pull everything down this hard, like `--gravity`.
+    SetGravity { gravity: f64 },
+    /// Multiply gravity, such as by 2 to double it.
+    ScaleGravity { factor: f64 },
+    /// Take away every obstacle that reaches into a box, such as the cells of
+    /// the map that make up a gate.
+    RemoveObstacles {
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+    },
+    /// Put up an obstacle between two opposite corners.
+    AddObstacle {
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+    },
+    /// Spawn this many particles around a point, as far as `--max-particles` allows.
+    Burst { x: f64, y: f64, count: usize },
+    /// Push every particle near a point away from it.
+    Explode { x: f64, y: f64, strength: f64 },
+}
+
+impl Action {
+    pub fn apply(&self, simulation: &mut Simulation, max_particles: Option<usize>) {
+        match *self {
+            Action::SetGravity { gravity } => {
+                simulation.settings_mut().gravity = Vec2(0.0, -gravity);
+            }
+            Action::ScaleGravity { factor } => {
+                let gravity = &mut simulation.settings_mut().gravity;
+                *gravity = gravity.scale(factor);
+            }
+            Action::RemoveObstacles {
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+            } => {
+                let mut obstacles = simulation.obstacles().to_vec();
+                obstacles.retain(|obstacle| {
+                    obstacle.max_x <= min_x
+                        || obstacle.min_x >= max_x
+                        || obstacle.max_y <= min_y
+                        || obstacle.min_y >= max_y
+                });
+                simulation.set_obstacles(obstacles);
+            }
+            Action::AddObstacle {
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+            } => {
+                let mut obstacles = simulation.obstacles().to_vec();
+                obstacles.push(Obstacle::new(min_x, min_y, max_x, max_y));
+                simulation.set_obstacles(obstacles);
+            }
+            Action::Burst { x, y, count } => {
+                let room = max_particles.map_or(count, |max| {
+                    max.saturating_sub(simulation.particle_count()).min(count)
+                });
+                simulation.spawn_burst(Vec2(x, y), room);
+            }
+            Action::Explode { x, y, strength } => {
+                simulation.explode(Vec2(x, y), strength);
+            }
+        }
+    }
+}
+
+/// An action, and when to take it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TimedEvent {
+    /// Seconds of simulated time from the start.
+    pub at: f64,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+/// The events of a scenario that are still to come, the soonest first.
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    events: VecDeque<TimedEvent>,
+}
+
+impl Timeline {
+    pub fn new(events: &[TimedEvent], frame: u64) -> Self {
+        //! Schedule `events` for a simulation at `frame`. When it is resumed past
+        //! the start, the events before then have already run, and are left out.
+        let now = simulated_seconds(frame);
+        let mut events: Vec<TimedEvent> = events
+            .iter()
+            .filter(|event| frame == 0 || event.at > now)
+            .cloned()
+            .collect();
+        events.sort_by(|a, b| a.at.total_cmp(&b.at));
+        Timeline {
+            events: events.into(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn run(&mut self, simulation: &mut Simulation, max_particles: Option<usize>) -> usize {
+        //! Take every action whose time the simulation has reached, in order.
+        //! Returns how many were taken.
+        let now = simulated_seconds(simulation.frame());
+        let mut taken = 0;
+        while self.events.front().is_some_and(|event| event.at <= now) {
+            if let Some(event) = self.events.pop_front() {
+                tracing::info!(at = event.at, action = ?event.action, "scenario event");
+                event.action.apply(simulation, max_particles);
+                taken += 1;
+            }
+        }
+        taken
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::STEPS_PER_SECOND;
+
+    #[test]
+    fn test_events_run_once_their_time_comes() {
+        let events: Vec<TimedEvent> = serde_json::from_str(
+            r#"[
+                {"at": 2, "action": "scale-gravity", "factor": 2},
+                {"at": 1, "action": "set-gravity", "gravity": 0.1},
+                {"at": 1, "action": "remove-obstacles",
+                 "min_x": 450, "min_y": 0, "max_x": 550, "max_y": 100}
+            ]"#,
+        )
+        .unwrap();
+        let mut simulation = Simulation::new();
+        simulation.set_obstacles(vec![
+            Obstacle::new(400.0, 0.0, 600.0, 300.0),
+            Obstacle::new(0.0, 700.0, 100.0, 800.0),
+        ]);
+        let mut timeline = Timeline::new(&events, 0);
+
+        let mut taken = vec![];
+        for _ in 0..2 * STEPS_PER_SECOND as usize {
+            simulation.step(1.0).unwrap();
+            taken.push(timeline.run(&mut simulation, None));
+        }
+        // Both events at one second after its 45th step, then the last at two
+        assert_eq!(taken.iter().sum::<usize>(), 3);
+        assert_eq!(taken[STEPS_PER_SECOND as usize - 1], 2);
+        assert_eq!(simulation.obstacles().len(), 1);
+        assert_eq!(simulation.settings().gravity, Vec2(0.0, -0.2));
+        assert!(timeline.is_empty());
+
+        // Resumed at a second and a half, only the last event is still to come
+        let resumed = Timeline::new(&events, (1.5 * STEPS_PER_SECOND) as u64);
+        assert_eq!(resumed.events.len(), 1);
+
+        let unknown = r#"{"at": 1, "action": "open-the-gate"}"#;
+        assert!(serde_json::from_str::<TimedEvent>(unknown).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! `--touch`: gestures for touchscreens, where the left button is a finger. A tap
+//! on empty space spawns a particle, dragging a particle flings it, and pinching
+//! with two fingers zooms. Every finger is told apart by the id of its pointer,
+//! and positions are in window units, so that a pinch measures the same however
+//! far the camera has zoomed.
+use particle_core::utils::Vec2;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// How far a finger can move, in window units, and still count as a tap.
+const TAP_DISTANCE: f64 = 10.0;
+
+/// What a finger asks the window to do once a gesture is over, or as it goes on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    /// Add a particle under this point of the window.
+    Spawn(Vec2),
+    /// Throw the particle with this id at a velocity in window units a second.
+    Fling { id: u64, velocity: Vec2 },
+    /// Zoom the camera by this factor, above 1 to zoom in.
+    Zoom(f64),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Finger {
+    start: Vec2,
+    started: Instant,
+    position: Vec2,
+    /// The particle the finger came down on.
+    particle: Option<u64>,
+    /// Set once a second finger has joined, after which lifting it does nothing.
+    pinched: bool,
+}
+
+/// The fingers on the screen, by pointer id.
+#[derive(Debug, Clone, Default)]
+pub struct Touches {
+    fingers: BTreeMap<u64, Finger>,
+}
+
+impl Touches {
+    pub fn new() -> Self {
+        Touches::default()
+    }
+
+    pub fn down(&mut self, pointer: u64, position: Vec2, particle: Option<u64>, at: Instant) {
+        //! A finger touches the screen, on top of `particle` if there is one there.
+        self.fingers.insert(
+            pointer,
+            Finger {
+                start: position,
+                started: at,
+                position,
+                particle,
+                pinched: false,
+            },
+        );
+        if self.fingers.len() > 1 {
+            for finger in self.fingers.values_mut() {
+                finger.pinched = true;
+            }
+        }
+    }
+
+    pub fn moved(&mut self, pointer: u64, position: Vec2) -> Option<Gesture> {
+        //! A finger moves, which zooms while exactly two are down.
+        let before = self.spread();
+        self.fingers.get_mut(&pointer)?.position = position;
+        let after = self.spread();
+        match (before, after) {
+            (Some(before), Some(after)) if before > 0.0 => Some(Gesture::Zoom(after / before)),
+            _ => None,
+        }
+    }
+
+    pub fn up(&mut self, pointer: u64, position: Vec2, at: Instant) -> Option<Gesture> {
+        //! A finger lifts, ending a tap or a fling unless it was part of a pinch.
+        let finger = self.fingers.remove(&pointer)?;
+        if finger.pinched {
+            return None;
+        }
+        let offset = position - finger.start;
+        match finger.particle {
+            None if offset.length() < TAP_DISTANCE => Some(Gesture::Spawn(finger.start)),
+            Some(id) if offset.length() >= TAP_DISTANCE => {
+                let seconds = at.duration_since(finger.started).as_secs_f64().max(1e-3);
+                Some(Gesture::Fling {
+                    id,
+                    velocity: offset.scale(1.0 / seconds),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn cancel(&mut self, pointer: u64) {
+        self.fingers.remove(&pointer);
+    }
+
+    fn spread(&self) -> Option<f64> {
+        //! The distance between two fingers, when there are exactly two.
+        let mut fingers = self.fingers.values();
+        match (fingers.next(), fingers.next(), fingers.next()) {
+            (Some(a), Some(b), None) => Some(a.position.distance(&b.position)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_taps_flings_and_pinches() {
+        let start = Instant::now();
+        let mut touches = Touches::new();
+        touches.down(1, Vec2(100.0, 100.0), None, start);
+        assert_eq!(
+            touches.up(1, Vec2(103.0, 100.0), start),
+            Some(Gesture::Spawn(Vec2(100.0, 100.0)))
+        );
+
+        touches.down(1, Vec2(100.0, 100.0), Some(7), start);
+        touches.moved(1, Vec2(200.0, 100.0));
+        assert_eq!(
+            touches.up(1, Vec2(200.0, 100.0), start + Duration::from_millis(500)),
+            Some(Gesture::Fling {
+                id: 7,
+                velocity: Vec2(200.0, 0.0)
+            })
+        );
+
+        touches.down(1, Vec2(100.0, 100.0), Some(7), start);
+        touches.down(2, Vec2(200.0, 100.0), None, start);
+        assert_eq!(
+            touches.moved(2, Vec2(300.0, 100.0)),
+            Some(Gesture::Zoom(2.0))
+        );
+        assert_eq!(touches.up(2, Vec2(300.0, 100.0), start), None);
+        assert_eq!(touches.up(1, Vec2(500.0, 100.0), start), None);
+    }
+}
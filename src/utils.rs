@@ -1,68 +1,178 @@
-use flo_canvas::*;
-use rand::*;
-use std::env;
-use std::ops::{Add, Mul, Sub};
-
-const MAX_NUMBER_OF_PARTICLES: usize = 52;
-
-pub fn read_args() -> usize {
-    //! Reads the command line args, looking specifically
-    //! for a passed in integer number of particles for the simulation
-    let particle_count = match env::args().nth(1) {
-        Some(number) => number.parse().unwrap_or(20),
-        None => 20,
-    };
+use crate::cli::Args;
+use clap::Parser;
+use particle_core::error::{SimError, SimResult};
+use particle_core::export::Probe;
+use particle_core::settings::{Medium, Side};
+use std::fs;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn read_args() -> Args {
+    //! Reads the command line args. The first positional argument is the
+    //! integer number of particles for the simulation, see `cli::Args` for the flags.
+    //! Invalid arguments print a usage message and exit.
+    Args::parse()
+}
+
+pub fn unix_time() -> u64 {
+    //! Seconds since the Unix epoch, for naming files that should not overwrite each other.
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+pub fn parse_particle_count(arg: &str) -> SimResult<usize> {
+    //! Refuses anything that is not a whole number.
+    arg.parse().map_err(|_| SimError::InvalidArgument {
+        name: "particle count".to_string(),
+        value: arg.to_string(),
+    })
+}
+
+fn parse_positive(arg: &str, name: &str) -> SimResult<f64> {
+    //! Refuses anything that is not a positive, finite number.
+    match arg.parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        _ => Err(SimError::InvalidArgument {
+            name: name.to_string(),
+            value: arg.to_string(),
+        }),
+    }
+}
+
+pub fn parse_time_scale(arg: &str) -> SimResult<f64> {
+    parse_positive(arg, "time scale")
+}
+
+pub fn parse_max_speed(arg: &str) -> SimResult<f64> {
+    parse_positive(arg, "maximum speed")
+}
+
+pub fn parse_mutual_gravity(arg: &str) -> SimResult<f64> {
+    parse_positive(arg, "gravitational constant")
+}
 
-    //For safety, we will cap the user at a max number of particles
-    particle_count.min(MAX_NUMBER_OF_PARTICLES)
+pub fn parse_step_length(arg: &str) -> SimResult<f64> {
+    parse_positive(arg, "step length")
 }
 
-pub fn get_random_color() -> Color {
-    //!Utility to get a Random Color for rendering purposes
-    Color::Rgba(random::<f32>(), random::<f32>(), random::<f32>(), 1.0)
+pub fn parse_rdf_range(arg: &str) -> SimResult<f64> {
+    parse_positive(arg, "g(r) range")
 }
 
-/// Quickly building a MathVec type for much more
-///readable vectorized operations.
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-pub struct MathVec(pub f64, pub f64);
+pub fn parse_motion_blur(arg: &str) -> SimResult<f64> {
+    //! Refuses anything but a fraction between 0 and 1, both left out.
+    match arg.parse::<f64>() {
+        Ok(value) if value > 0.0 && value < 1.0 => Ok(value),
+        _ => Err(SimError::InvalidArgument {
+            name: "motion blur".to_string(),
+            value: arg.to_string(),
+        }),
+    }
+}
 
-impl MathVec {
-    pub fn inner_product(&self, other: &Self) -> f64 {
-        // We are in the Real domain only, so we can ignore
-        // the inner_product requirements for complex vectors.
-        // This isnt't Quantum Mechanics!
-        self.0 * other.0 + self.1 * other.1
+pub fn parse_thermal_wall(arg: &str) -> SimResult<(Side, f64)> {
+    //! Parse `<side>:<temperature>`, with a temperature that is not negative.
+    let invalid = || SimError::InvalidArgument {
+        name: "thermal wall".to_string(),
+        value: arg.to_string(),
+    };
+    let (side, temperature) = arg.split_once(':').ok_or_else(invalid)?;
+    match temperature.trim().parse::<f64>() {
+        Ok(temperature) if temperature.is_finite() && temperature >= 0.0 => {
+            Ok((side.trim().parse()?, temperature))
+        }
+        _ => Err(invalid()),
     }
+}
 
-    pub fn distance(&self, other: &Self) -> f64 {
-        //! Returns the distance between two MathVecs
-        f64::sqrt((self.0 - other.0).powi(2) + (self.1 - other.1).powi(2))
+pub fn parse_gravity(arg: &str) -> SimResult<f64> {
+    //! Any finite number, negative for gravity that pulls up.
+    match arg.parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        _ => Err(SimError::InvalidArgument {
+            name: "gravity".to_string(),
+            value: arg.to_string(),
+        }),
     }
 }
 
-impl Add for MathVec {
-    type Output = Self;
+fn parse_density_pair(arg: &str, name: &str) -> SimResult<(f64, f64)> {
+    //! Parse `<first>:<second>`, or a single density for both.
+    let (first, second) = arg.split_once(':').unwrap_or((arg, arg));
+    Ok((
+        parse_positive(first.trim(), name)?,
+        parse_positive(second.trim(), name)?,
+    ))
+}
+
+pub fn parse_medium(arg: &str) -> SimResult<Medium> {
+    //! Parse `<floor density>:<ceiling density>`, or one density for a uniform medium.
+    let (floor_density, ceiling_density) = parse_density_pair(arg, "medium density")?;
+    Ok(Medium {
+        floor_density,
+        ceiling_density,
+    })
+}
 
-    fn add(self, other: Self) -> Self {
-        Self(self.0 + other.0, self.1 + other.1)
+pub fn parse_densities(arg: &str) -> SimResult<(f64, f64)> {
+    //! Parse `<min>:<max>`, or one density for every particle.
+    match parse_density_pair(arg, "particle density")? {
+        (min, max) if min <= max => Ok((min, max)),
+        _ => Err(SimError::InvalidArgument {
+            name: "particle density".to_string(),
+            value: arg.to_string(),
+        }),
     }
 }
 
-impl Sub for MathVec {
-    type Output = Self;
+pub fn parse_species_gravity(arg: &str) -> SimResult<(u32, f64)> {
+    //! Parse `<species>:<scale>`, with any finite scale.
+    let invalid = || SimError::InvalidArgument {
+        name: "species gravity".to_string(),
+        value: arg.to_string(),
+    };
+    let (species, scale) = arg.split_once(':').ok_or_else(invalid)?;
+    let species = species.trim().parse::<u32>().map_err(|_| invalid())?;
+    match scale.trim().parse::<f64>() {
+        Ok(scale) if scale.is_finite() => Ok((species, scale)),
+        _ => Err(invalid()),
+    }
+}
 
-    fn sub(self, other: Self) -> Self {
-        Self(self.0 - other.0, self.1 - other.1)
+pub fn parse_midi_channel(arg: &str) -> SimResult<u8> {
+    //! Refuses anything but a channel from 1 to 16.
+    match arg.parse::<u8>() {
+        Ok(channel) if (1..=16).contains(&channel) => Ok(channel),
+        _ => Err(SimError::InvalidArgument {
+            name: "MIDI channel".to_string(),
+            value: arg.to_string(),
+        }),
     }
 }
 
-impl Mul<MathVec> for f64 {
-    type Output = MathVec;
+pub fn parse_osc_target(arg: &str) -> SimResult<SocketAddr> {
+    //! Resolve `<host>:<port>`, taking the first address a host name resolves to.
+    arg.to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or_else(|| SimError::InvalidArgument {
+            name: "OSC address".to_string(),
+            value: arg.to_string(),
+        })
+}
 
-    fn mul(self, rhs: MathVec) -> MathVec {
-        MathVec(self * rhs.0, self * rhs.1)
+pub fn load_probes(path: &Path) -> SimResult<Vec<Probe>> {
+    //! Read a JSON list of probe regions, such as
+    //! `[{"name": "hot", "min_x": 0, "min_y": 0, "max_x": 200, "max_y": 1000}]`.
+    let probes: Vec<Probe> = serde_json::from_slice(&fs::read(path)?).map_err(io::Error::from)?;
+    for probe in &probes {
+        probe.validate()?;
     }
+    Ok(probes)
 }
 
 #[cfg(test)]
@@ -70,30 +180,55 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_get_random_color() {
-        let rand_color = get_random_color();
-        println!("{:?}", rand_color);
+    fn test_parse_particle_count() {
+        assert_eq!(parse_particle_count("30").unwrap(), 30);
+        assert_eq!(parse_particle_count("5000").unwrap(), 5000);
+        assert!(parse_particle_count("fifty").is_err());
+    }
+
+    #[test]
+    fn test_parse_time_scale() {
+        assert_eq!(parse_time_scale("0.25").unwrap(), 0.25);
+        assert!(parse_time_scale("0").is_err());
+        assert!(parse_time_scale("-1").is_err());
+        assert!(parse_time_scale("inf").is_err());
+    }
+
+    #[test]
+    fn test_parse_motion_blur() {
+        assert_eq!(parse_motion_blur("0.3").unwrap(), 0.3);
+        assert!(parse_motion_blur("0").is_err());
+        assert!(parse_motion_blur("1").is_err());
     }
 
     #[test]
-    fn test_add_subtract_math_vec() {
-        let v1 = MathVec(5.0, 3.2);
-        let v2 = MathVec(4.7, -2.6);
-        //Unfortunately, float equality is rather fickle to test.
-        assert!((v1 + v2) - MathVec(9.7, 0.6) < MathVec(0.00001, 0.00001));
-        assert!((v1 - v2) - MathVec(0.3, 5.8) < MathVec(0.00001, 0.00001));
+    fn test_parse_thermal_wall() {
+        assert_eq!(parse_thermal_wall("left:20").unwrap(), (Side::Left, 20.0));
+        assert!(parse_thermal_wall("left").is_err());
+        assert!(parse_thermal_wall("left:-1").is_err());
+        assert!(parse_thermal_wall("middle:5").is_err());
     }
 
     #[test]
-    fn test_inner_product() {
-        let v1 = MathVec(5.0, 4.0);
-        let v2 = MathVec(3.0, -7.0);
-        assert_eq!(v1.inner_product(&v2), -13.0);
+    fn test_parse_medium_and_densities() {
+        assert_eq!(
+            parse_medium("3:1").unwrap(),
+            Medium {
+                floor_density: 3.0,
+                ceiling_density: 1.0
+            }
+        );
+        assert_eq!(parse_medium("2").unwrap(), Medium::uniform(2.0));
+        assert!(parse_medium("0:1").is_err());
+        assert_eq!(parse_densities("0.5:2").unwrap(), (0.5, 2.0));
+        assert!(parse_densities("2:0.5").is_err());
     }
 
     #[test]
-    fn test_scalar_multiply() {
-        let v1 = MathVec(5.0, 4.0);
-        assert_eq!(3.0 * v1, MathVec(15.0, 12.0));
+    fn test_parse_species_gravity() {
+        assert_eq!(parse_species_gravity("1:-0.5").unwrap(), (1, -0.5));
+        assert!(parse_species_gravity("1").is_err());
+        assert!(parse_species_gravity("-1:2").is_err());
+        assert!(parse_species_gravity("1:nan").is_err());
     }
 }
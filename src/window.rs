@@ -0,0 +1,926 @@
+//! The interactive flo_draw windows: the 2D window with its overlays and key
+//! bindings, the side by side comparison of `--compare` and the 3D view.
+use super::{apply_settings, exit_with, start_simulation};
+use flo_canvas::*;
+use flo_draw::*;
+use particle_core::particle::{HEIGHT, WIDTH};
+use particle_core::particle3d::{Particle3d, DEPTH};
+use particle_core::profiler::Phase;
+use particle_core::simulation::Simulation;
+use particle_core::simulation3d::Simulation3d;
+use particle_core::sweep_prune::SweepAxis;
+use particle_core::utils::Vec2;
+use particle_simulator::blur::MotionBlur;
+use particle_simulator::camera::{Camera, CameraPath};
+use particle_simulator::charts::Charts;
+use particle_simulator::cli::Args;
+use particle_simulator::clock::{
+    simulated_seconds, Clock, FrameRate, FrameScheduler, DEFAULT_FPS, STEPS_PER_SECOND,
+};
+use particle_simulator::coloring::Coloring;
+use particle_simulator::config::ConfigWatcher;
+use particle_simulator::controls::Controls;
+use particle_simulator::dashboard::{self, Dashboard};
+use particle_simulator::dump::{self, EventHistory};
+#[cfg(feature = "gamepad")]
+use particle_simulator::gamepad::{self, Gamepads};
+use particle_simulator::heat::Heat;
+use particle_simulator::help;
+use particle_simulator::hud::Hud;
+use particle_simulator::inspector::Inspector;
+use particle_simulator::layers::{Layer, Layers};
+use particle_simulator::lod::Detail;
+#[cfg(feature = "midi")]
+use particle_simulator::midi::{MidiOut, Notes};
+use particle_simulator::minimap;
+use particle_simulator::osc::{self, OscSender};
+use particle_simulator::quality::{AdaptiveQuality, Degradation};
+use particle_simulator::render::{self, Scene};
+use particle_simulator::renderer::{draw_particles, rgba, window_events, FloRenderer, Renderer};
+use particle_simulator::screenshot::{self, Metadata};
+use particle_simulator::selection::Selection;
+use particle_simulator::shm::FrameRing;
+#[cfg(feature = "sound")]
+use particle_simulator::sonify::{Drone, Sonifier};
+use particle_simulator::sprites::SpritePool;
+use particle_simulator::svg::{self, Trails};
+use particle_simulator::textures::Textures;
+use particle_simulator::theme::Theme;
+use particle_simulator::timeline::Timeline;
+use particle_simulator::touch::{Gesture, Touches};
+use particle_simulator::utils;
+use std::env;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How far the pointer may move between pressing and releasing the right button
+/// for it to count as a click on a particle rather than a box to erase.
+const CLICK_DISTANCE: f64 = 5.0;
+
+/// The strength of the explosions set off with X, enough to send a medium sized
+/// particle 100 units away off at about 15 units per step.
+const EXPLOSION_STRENGTH: f64 = 500_000.0;
+
+/// The furthest ahead the path of the inspected particle is predicted, in steps.
+const PREDICTION_STEPS: usize = 400;
+/// Pairs of particles times steps predicted per frame, which shortens the
+/// prediction in crowded boxes so that it does not hold up the frame.
+const PREDICTION_WORK: usize = 2_000_000;
+
+/// How much smaller a sphere at the back of the box looks than one at the front.
+const PERSPECTIVE: f64 = 0.6;
+
+fn room_for(count: usize, args: &Args, simulation: &Simulation) -> usize {
+    //! How many of `count` new particles fit under `--max-particles`.
+    args.max_particles.map_or(count, |limit| {
+        count.min(limit.saturating_sub(simulation.particle_count()))
+    })
+}
+
+pub fn run_windowed(mut simulation: Simulation, mut args: Args) {
+    //! Step the simulation in real time in the interactive window, until it is closed.
+    let catch_up_steps = args.catch_up_steps;
+    // Applied to the command line as given, again whenever the file changes
+    let config =
+        args.config
+            .as_ref()
+            .map(|path| match ConfigWatcher::new(path, utils::read_args()) {
+                Ok(watcher) => watcher,
+                Err(err) => exit_with(err),
+            });
+    let textures = load_textures(&args);
+    with_2d_graphics(move || {
+        let (canvas, events) = create_drawing_window_with_events("Particle Simulator");
+        let events = window_events(events);
+        // Clicking on a particle inspects it, until it is gone or Escape is pressed
+        let mut inspector: Option<Inspector> = None;
+        // Kept for the state dumps written by F5
+        let mut history = EventHistory::new();
+        let command_line: Vec<String> = env::args().collect();
+        // Set by F12, and taken once the frame has been drawn
+        let mut screenshot: Option<Metadata> = None;
+        // Drawn behind the particles of the snapshots F9 saves
+        let mut trails = args.svg_trails.map(Trails::new);
+        // The fading frames of --motion-blur
+        let mut motion_blur = args.motion_blur.map(MotionBlur::new);
+        // G turns the grid on and off
+        let mut grid = args.grid;
+        // L turns the particle ids on and off
+        let mut labels = args.labels;
+        // E turns the collision heat glow on and off
+        let mut heat = args.heat.then(Heat::new);
+        // M turns the center of mass and momentum arrow on and off
+        let mut center_of_mass = args.center_of_mass;
+        // C and P step through the attributes and palettes
+        let mut coloring = Coloring::new(args.color_by, args.palette);
+        // T steps through the themes
+        let mut theme = args.theme;
+        // Where X sets off an explosion: wherever the pointer was last seen
+        let mut pointer = Vec2(WIDTH / 2.0, HEIGHT / 2.0);
+        // The corner a right drag started from and where it has got to, while
+        // dragging out a box to erase
+        let mut eraser: Option<(Vec2, Vec2)> = None;
+        // The same for a left drag, which selects the particles in its box for
+        // the bulk changes of Z, V, S, K, F and Delete
+        let mut selecting: Option<(Vec2, Vec2)> = None;
+        let mut selection = Selection::new();
+        // Tab, `,` and `.` adjust the physics
+        let mut controls = Controls::new();
+        // F1 and H show the help overlay
+        let mut help = false;
+        // F2 and F3 hide the overlays of the box and the HUD
+        let mut layers = Layers::new();
+        // Space stops and starts the simulation
+        let mut paused = false;
+        // The drone of --sonify
+        #[cfg(feature = "sound")]
+        let mut sonification = if args.sonify {
+            match Drone::start() {
+                Ok(drone) => Some((Sonifier::new(), drone)),
+                Err(err) => exit_with(err),
+            }
+        } else {
+            None
+        };
+        // --osc streams every frame and collision
+        let osc = args.osc.map(|target| match OscSender::new(target) {
+            Ok(sender) => sender,
+            Err(err) => exit_with(err),
+        });
+        // --shm publishes every frame for other processes to read
+        let mut ring = args.shm.as_ref().map(|path| {
+            let capacity = args.max_particles.unwrap_or(args.particle_count());
+            match FrameRing::create(path, capacity) {
+                Ok(ring) => ring,
+                Err(err) => exit_with(err),
+            }
+        });
+        // The MIDI port of --midi, and the notes for the collisions
+        #[cfg(feature = "midi")]
+        let mut midi = match &args.midi {
+            Some(port) => match MidiOut::connect(port, args.midi_channel - 1) {
+                Ok(output) => Some((Notes::new(args.midi_notes), output)),
+                Err(err) => exit_with(err),
+            },
+            None => None,
+        };
+        // Pinching and a controller move the camera
+        let mut camera = Camera::new();
+        // Whether the left button went down on the minimap, which it then drags
+        let mut navigating = false;
+        // The fingers on the screen, with --touch
+        let mut touches = Touches::new();
+        #[cfg(feature = "gamepad")]
+        let mut gamepads = Gamepads::new();
+        // The gravity a controller switched off, to switch back on
+        #[cfg(feature = "gamepad")]
+        let mut saved_gravity: Option<Vec2> = None;
+
+        let hud = Hud::new();
+
+        //Clear the canvas to set a background colour
+        canvas.draw(|gc| {
+            gc.clear_canvas(theme.background());
+            if let Some(textures) = &textures {
+                textures.define(gc);
+            }
+        });
+        let mut renderer = FloRenderer::with_canvas(canvas);
+
+        // The charts get a window of their own, so that they never cover the particles
+        let mut charts = args.charts.then(|| {
+            let canvas = create_drawing_window("Particle Simulator Charts");
+            canvas.draw(|gc| {
+                gc.clear_canvas(Color::Rgba(0.0, 0.0, 0.0, 1.0));
+                hud.load_font(gc);
+            });
+            (canvas, Charts::new())
+        });
+        // The dashboard is redrawn on a thread of its own, from statistics that the
+        // frame loop shares with it
+        let dashboard = args.dashboard.then(|| {
+            let canvas = create_drawing_window("Particle Simulator Dashboard");
+            canvas.draw(|gc| {
+                gc.clear_canvas(Color::Rgba(0.0, 0.0, 0.0, 1.0));
+                hud.load_font(gc);
+            });
+            let dashboard = Arc::new(Mutex::new(Dashboard::new()));
+            dashboard::spawn_render_loop(Arc::downgrade(&dashboard), canvas);
+            dashboard
+        });
+        let mut last_frame = Instant::now();
+        // Collisions since the charts or the dashboard were last updated
+        let mut collisions = 0;
+        // Decides how many steps each frame takes, from the time that has passed
+        let mut clock = Clock::new(args.time_scale);
+        clock.set_max_steps(catch_up_steps);
+        let mut scheduler = FrameScheduler::new(args.fps);
+        // The physics may use half of each frame before things are turned down
+        let frame_interval = args
+            .fps
+            .interval()
+            .unwrap_or(Duration::from_secs_f64(1.0 / DEFAULT_FPS));
+        let mut quality = AdaptiveQuality::new(frame_interval / 2);
+
+        let mut obstacles = simulation.obstacles().to_vec();
+        let mut portals = simulation.portals().to_vec();
+        let mut time_regions = simulation.time_regions().to_vec();
+        // The timed events of the scenario, and when it started in real time
+        let mut timeline = Timeline::new(&args.events, simulation.frame());
+        let mut camera_path = CameraPath::new(&args.camera_path);
+        let mut started = Instant::now();
+        // How many times R has started the scenario over, which numbers the files it writes
+        let mut restarts = 0;
+
+        loop {
+            // Integrate, expire and collide all of the particles
+            let physics_start = Instant::now();
+            let mut frame_collisions = 0;
+            let steps = if paused { 0 } else { clock.tick() };
+            controls.spawn(&mut simulation, steps, args.max_particles);
+            for _ in 0..steps {
+                if let Err(err) = simulation.step(1.0) {
+                    exit_with(err);
+                }
+                if timeline.run(&mut simulation, args.max_particles) > 0 {
+                    obstacles = simulation.obstacles().to_vec();
+                }
+                frame_collisions += simulation.events().len();
+                history.record(&simulation);
+                if let Some(trails) = &mut trails {
+                    trails.record(simulation.world());
+                }
+                if let Some(heat) = &mut heat {
+                    heat.record(simulation.events());
+                }
+                if let Some(dashboard) = &dashboard {
+                    dashboard.lock().unwrap().record_step(simulation.events());
+                }
+                if let Some(current) = &mut inspector {
+                    current.record(simulation.events());
+                }
+                if let Some(osc) = &osc {
+                    osc.send(&osc::collision_packets(simulation.events()));
+                }
+                #[cfg(feature = "midi")]
+                if let Some((notes, output)) = &mut midi {
+                    let played = if simulation.events().is_empty() {
+                        Vec::new()
+                    } else {
+                        let masses = simulation
+                            .world()
+                            .query::<&particle_core::particle::Particle>()
+                            .iter()
+                            .map(|(_, particle)| (particle.id, particle.mass))
+                            .collect();
+                        notes.notes(simulation.events(), &masses)
+                    };
+                    // Called every step, to release the notes that are done
+                    output.play(&played);
+                }
+            }
+            collisions += frame_collisions;
+            if !args.fixed_quality && quality.record(physics_start.elapsed(), frame_collisions) {
+                simulation.settings_mut().sweep_axis =
+                    if quality.is_active(Degradation::AutoSweepAxis) {
+                        SweepAxis::Auto
+                    } else {
+                        args.sweep_axis
+                    };
+                clock.set_max_steps(if quality.is_active(Degradation::FewerCatchUpSteps) {
+                    Some(catch_up_steps.map_or(2, |steps| steps.min(2)))
+                } else {
+                    catch_up_steps
+                });
+            }
+
+            // Particles deleted from the window, for the colors and heat to forget
+            let mut removed = Vec::new();
+            for event in events.try_iter() {
+                match event {
+                    DrawEvent::Pointer(action, PointerId(finger), state) => {
+                        if let Some((x, y)) = state.location_in_canvas {
+                            let screen = Vec2(x, y);
+                            pointer = camera.to_world(screen);
+                            let right = state.buttons.contains(&Button::Right);
+                            if !right {
+                                match action {
+                                    PointerAction::ButtonDown
+                                        if minimap::contains(&camera, screen) =>
+                                    {
+                                        navigating = true
+                                    }
+                                    PointerAction::ButtonUp | PointerAction::Cancel
+                                        if navigating =>
+                                    {
+                                        navigating = false;
+                                        continue;
+                                    }
+                                    _ => {}
+                                }
+                                if navigating {
+                                    camera.look_at(minimap::to_world(screen));
+                                    continue;
+                                }
+                            }
+                            // With --touch the left button is a finger, and the right
+                            // one still erases
+                            if args.touch && !right && eraser.is_none() {
+                                let gesture = match action {
+                                    PointerAction::ButtonDown => {
+                                        let particle = simulation.particle_at(pointer);
+                                        touches.down(finger, screen, particle, Instant::now());
+                                        None
+                                    }
+                                    PointerAction::Drag => touches.moved(finger, screen),
+                                    PointerAction::ButtonUp => {
+                                        touches.up(finger, screen, Instant::now())
+                                    }
+                                    PointerAction::Cancel => {
+                                        touches.cancel(finger);
+                                        None
+                                    }
+                                    _ => None,
+                                };
+                                match gesture {
+                                    Some(Gesture::Spawn(at)) => {
+                                        let count = room_for(1, &args, &simulation);
+                                        simulation.spawn_burst(camera.to_world(at), count);
+                                    }
+                                    Some(Gesture::Fling { id, velocity }) => {
+                                        // From window units a second to box units a step
+                                        let velocity =
+                                            velocity.scale(1.0 / (camera.zoom * STEPS_PER_SECOND));
+                                        if let Some(particle) = simulation.particle_mut(id) {
+                                            if !particle.fixed {
+                                                particle.velocity = velocity;
+                                            }
+                                        }
+                                    }
+                                    Some(Gesture::Zoom(factor)) => camera.zoom_by(factor),
+                                    None => {}
+                                }
+                                continue;
+                            }
+                            match action {
+                                PointerAction::ButtonDown if right => {
+                                    eraser = Some((pointer, pointer));
+                                }
+                                PointerAction::ButtonDown => {
+                                    inspector = simulation.particle_at(pointer).map(Inspector::new);
+                                    selecting = Some((pointer, pointer));
+                                }
+                                PointerAction::Drag => {
+                                    if let Some((_, corner)) = &mut eraser {
+                                        *corner = pointer;
+                                    }
+                                    if let Some((_, corner)) = &mut selecting {
+                                        *corner = pointer;
+                                    }
+                                }
+                                PointerAction::ButtonUp if !right => {
+                                    // A left click selects nothing, so it clears the selection
+                                    if let Some((start, _)) = selecting.take() {
+                                        if start.distance(&pointer) < CLICK_DISTANCE {
+                                            selection.clear();
+                                        } else {
+                                            selection.select_within(&simulation, start, pointer);
+                                        }
+                                    }
+                                    // A right click that barely moved deletes the particle under it
+                                    removed.extend(match eraser.take() {
+                                        Some((start, _))
+                                            if start.distance(&pointer) < CLICK_DISTANCE =>
+                                        {
+                                            simulation
+                                                .particle_at(start)
+                                                .filter(|id| simulation.remove_particle(*id))
+                                                .into_iter()
+                                                .collect()
+                                        }
+                                        Some((start, _)) => {
+                                            simulation.remove_within(start, pointer)
+                                        }
+                                        None => Vec::new(),
+                                    });
+                                }
+                                PointerAction::Cancel => {
+                                    eraser = None;
+                                    selecting = None;
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    DrawEvent::KeyDown(_, Some(Key::KeyX)) => {
+                        simulation.explode(pointer, EXPLOSION_STRENGTH);
+                    }
+                    DrawEvent::KeyDown(_, Some(Key::KeyB)) => {
+                        simulation.spawn_burst(pointer, room_for(args.burst, &args, &simulation));
+                    }
+                    DrawEvent::KeyDown(_, Some(Key::KeySpace)) => paused = !paused,
+                    DrawEvent::KeyDown(_, Some(Key::KeyEscape)) => {
+                        inspector = None;
+                        selection.clear();
+                    }
+                    DrawEvent::KeyDown(_, Some(Key::KeyF5)) => {
+                        match dump::write_dump(Path::new("."), &simulation, &command_line, &history)
+                        {
+                            Ok(path) => println!("State dumped to {}", path.display()),
+                            Err(err) => tracing::warn!(%err, "could not write the state dump"),
+                        }
+                    }
+                    DrawEvent::KeyDown(_, Some(Key::KeyG)) => grid = !grid,
+                    DrawEvent::KeyDown(_, Some(Key::KeyL)) => labels = !labels,
+                    DrawEvent::KeyDown(_, Some(Key::KeyE)) => {
+                        heat = match heat {
+                            Some(_) => None,
+                            None => Some(Heat::new()),
+                        };
+                    }
+                    DrawEvent::KeyDown(_, Some(Key::KeyM)) => center_of_mass = !center_of_mass,
+                    DrawEvent::KeyDown(_, Some(Key::KeyC)) => coloring.cycle_attribute(),
+                    DrawEvent::KeyDown(_, Some(Key::KeyP)) => coloring.cycle_palette(),
+                    DrawEvent::KeyDown(_, Some(Key::KeyT)) => theme = theme.next(),
+                    DrawEvent::KeyDown(_, Some(Key::KeyF1 | Key::KeyH)) => help = !help,
+                    DrawEvent::KeyDown(_, Some(Key::KeyF2)) => layers.toggle(Layer::Debug),
+                    DrawEvent::KeyDown(_, Some(Key::KeyF3)) => layers.toggle(Layer::Hud),
+                    DrawEvent::KeyDown(_, Some(Key::KeyTab)) => controls.select_next(),
+                    DrawEvent::KeyDown(_, Some(Key::KeyComma)) => {
+                        controls.nudge(&mut simulation, &mut clock, -1.0);
+                    }
+                    DrawEvent::KeyDown(_, Some(Key::KeyFullstop)) => {
+                        controls.nudge(&mut simulation, &mut clock, 1.0);
+                    }
+                    DrawEvent::KeyDown(_, Some(Key::KeyF9)) => {
+                        let snapshot = svg::render(&simulation, &coloring, theme, trails.as_ref());
+                        match svg::save(Path::new("."), simulation.frame(), &snapshot) {
+                            Ok(path) => println!("Snapshot saved to {}", path.display()),
+                            Err(err) => tracing::warn!(%err, "could not save the snapshot"),
+                        }
+                    }
+                    DrawEvent::KeyDown(_, Some(Key::KeyF12)) => {
+                        screenshot = Some(Metadata::new(&simulation, &command_line));
+                    }
+                    // R starts the scenario over, with the latest config file applied
+                    DrawEvent::KeyDown(_, Some(Key::KeyR)) => {
+                        match start_simulation(&args.numbered_outputs(restarts + 1)) {
+                            Ok(restarted) => {
+                                restarts += 1;
+                                if let Err(err) = simulation.finish_exports() {
+                                    tracing::warn!(%err, "could not finish the exports");
+                                }
+                                simulation = restarted;
+                                obstacles = simulation.obstacles().to_vec();
+                                portals = simulation.portals().to_vec();
+                                time_regions = simulation.time_regions().to_vec();
+                                timeline = Timeline::new(&args.events, simulation.frame());
+                                camera_path = CameraPath::new(&args.camera_path);
+                                started = Instant::now();
+                                history = EventHistory::new();
+                                trails = args.svg_trails.map(Trails::new);
+                                if let Some(motion_blur) = &mut motion_blur {
+                                    motion_blur.clear();
+                                }
+                                heat = heat.map(|_| Heat::new());
+                                inspector = None;
+                                selection.clear();
+                            }
+                            Err(err) => tracing::warn!(%err, "could not reload the scenario"),
+                        }
+                    }
+                    DrawEvent::KeyDown(_, Some(key)) => {
+                        if let Some(inspector) = &mut inspector {
+                            match key {
+                                Key::KeyUp => inspector.select_next(false),
+                                Key::KeyDown => inspector.select_next(true),
+                                Key::KeyMinus => inspector.nudge(&mut simulation, -1.0),
+                                Key::KeyEquals => inspector.nudge(&mut simulation, 1.0),
+                                _ => {}
+                            }
+                        }
+                        match key {
+                            Key::KeyZ => selection.stop(&mut simulation),
+                            Key::KeyV => selection.share_velocity(&mut simulation),
+                            Key::KeyS => selection.next_species(&mut simulation),
+                            Key::KeyK => selection.recolor(&mut simulation, coloring.palette),
+                            Key::KeyF => selection.toggle_pinned(&mut simulation),
+                            Key::KeyDelete | Key::KeyBackspace => {
+                                removed.extend(selection.delete(&mut simulation));
+                            }
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            #[cfg(feature = "gamepad")]
+            for action in gamepads
+                .as_mut()
+                .map_or(Vec::new(), |gamepads| gamepads.poll(&mut camera))
+            {
+                match action {
+                    gamepad::Action::Pause => paused = !paused,
+                    gamepad::Action::Burst => {
+                        simulation
+                            .spawn_burst(camera.center, room_for(args.burst, &args, &simulation));
+                    }
+                    gamepad::Action::ToggleGravity => {
+                        let gravity = &mut simulation.settings_mut().gravity;
+                        match saved_gravity.take() {
+                            Some(saved) => *gravity = saved,
+                            None => saved_gravity = Some(std::mem::replace(gravity, Vec2::ZERO)),
+                        }
+                    }
+                    gamepad::Action::ResetCamera => camera = Camera::new(),
+                }
+            }
+            for id in removed {
+                coloring.forget(id);
+                if let Some(heat) = &mut heat {
+                    heat.forget(id);
+                }
+            }
+            if let Some((watcher, reloaded)) = config
+                .as_ref()
+                .and_then(|watcher| Some((watcher, watcher.poll()?)))
+            {
+                match reloaded {
+                    Ok(reloaded) => {
+                        apply_settings(&mut simulation, &reloaded);
+                        simulation.set_track_contacts(reloaded.profile);
+                        clock.set_time_scale(reloaded.time_scale);
+                        // Keep what C, P and T picked unless the file changed it
+                        if (reloaded.color_by, reloaded.palette) != (args.color_by, args.palette) {
+                            coloring = Coloring::new(reloaded.color_by, reloaded.palette);
+                        }
+                        if reloaded.theme != args.theme {
+                            theme = reloaded.theme;
+                        }
+                        args = reloaded;
+                        println!("Applied the settings in {}", watcher.path().display());
+                    }
+                    Err(err) => tracing::warn!(%err, "could not apply the config file"),
+                }
+            }
+            let inspected = inspector.as_ref().and_then(|inspector| {
+                let lines = inspector.lines(&simulation)?;
+                Some((lines, simulation.particle(inspector.id())?))
+            });
+            if inspected.is_none() {
+                inspector = None;
+            }
+
+            if let Some(osc) = &osc {
+                osc.send(&osc::frame_packets(&simulation));
+            }
+            if let Some(ring) = &mut ring {
+                ring.publish(&simulation);
+            }
+
+            if !scheduler.should_render() {
+                // Running late, so leave drawing to the next frame
+                if let Err(err) = simulation.profiler_mut().end_frame() {
+                    exit_with(err);
+                }
+                scheduler.wait();
+                continue;
+            }
+
+            if charts.is_some() || dashboard.is_some() || args.sonify {
+                let collisions_per_second = collisions as f64 / last_frame.elapsed().as_secs_f64();
+                last_frame = Instant::now();
+                collisions = 0;
+                if let Some((chart_canvas, charts)) = &mut charts {
+                    charts.record(
+                        simulation.kinetic_energy(),
+                        simulation.temperature(),
+                        collisions_per_second,
+                    );
+                    chart_canvas.draw(|gc| charts.draw(gc));
+                }
+                if let Some(dashboard) = &dashboard {
+                    dashboard
+                        .lock()
+                        .unwrap()
+                        .record_frame(&simulation, collisions_per_second);
+                }
+                #[cfg(feature = "sound")]
+                if let Some((sonifier, drone)) = &mut sonification {
+                    drone.play(sonifier.tone(simulation.temperature(), collisions_per_second));
+                }
+            }
+
+            // A scripted camera path overrides the mouse and controller until it ends
+            if let Some(scripted) = camera_path.update(simulated_seconds(simulation.frame())) {
+                camera = scripted;
+            }
+
+            let render_span = tracing::debug_span!("rendering").entered();
+            let mut hud_lines = if args.profile {
+                let contacts = simulation.contacts();
+                let mut lines = simulation.profiler().summary();
+                lines.push(format!(
+                    "contacts: {} ({} persistent)",
+                    contacts.len(),
+                    contacts.persistent()
+                ));
+                lines
+            } else {
+                Vec::new()
+            };
+            if let Some((lines, _)) = &inspected {
+                if !hud_lines.is_empty() {
+                    hud_lines.push(String::new());
+                }
+                hud_lines.extend(lines.iter().cloned());
+            }
+            let detail =
+                Detail::choose(simulation.particle_count(), camera.zoom, args.lod_threshold);
+            let mut load_lines = quality.hud_lines();
+            if detail != Detail::Full {
+                load_lines.push(format!("Crowded: particles drawn as {}", detail.describe()));
+            }
+            if !load_lines.is_empty() {
+                if !hud_lines.is_empty() {
+                    hud_lines.push(String::new());
+                }
+                hud_lines.extend(load_lines);
+            }
+            if !hud_lines.is_empty() {
+                hud_lines.push(String::new());
+            }
+            hud_lines.push(format!(
+                "time: {:.1} s simulated, {:.1} s wall clock",
+                simulated_seconds(simulation.frame()),
+                started.elapsed().as_secs_f64()
+            ));
+            if paused {
+                hud_lines.push("paused".to_string());
+            }
+            let selected = selection.particles(&simulation);
+            if !selected.is_empty() {
+                hud_lines.push(format!("selected: {} particles", selected.len()));
+            }
+            hud_lines.extend(controls.lines(&simulation, &clock));
+            if !layers.is_shown(Layer::Hud) {
+                hud_lines.clear();
+            }
+            let help_lines = help.then(|| {
+                help::lines(
+                    &simulation,
+                    &clock,
+                    inspected.is_some(),
+                    !selected.is_empty(),
+                )
+            });
+            let plain = quality.is_active(Degradation::PlainRendering) || detail != Detail::Full;
+            // Plain discs need nothing the renderer cannot draw
+            let plain_particles = quality.is_active(Degradation::PlainRendering)
+                && detail == Detail::Full
+                && !args.metaballs;
+            // Under mutual gravity, the inspected particle shows where it is headed
+            let prediction = match &inspected {
+                Some((_, particle)) if simulation.settings().mutual_gravity.is_some() => {
+                    let count = simulation.particle_count().max(1);
+                    let steps = (PREDICTION_WORK / (count * count)).min(PREDICTION_STEPS);
+                    Some(simulation.predict_path(particle.id, steps))
+                }
+                _ => None,
+            };
+            let center = center_of_mass
+                .then(|| simulation.center_of_mass())
+                .flatten();
+            coloring.update(simulation.frame(), simulation.events(), simulation.world());
+            let (profiler, world) = simulation.profiler_and_world();
+            let scene = Scene {
+                world,
+                obstacles: &obstacles,
+                portals: &portals,
+                time_regions: &time_regions,
+                highlight: inspected.as_ref().map(|(_, particle)| particle),
+                selected: &selected,
+                grid,
+                labels: labels && !plain,
+                coloring: &coloring,
+                heat: heat.as_ref().filter(|_| !plain),
+                prediction: prediction.as_deref(),
+                center_of_mass: center,
+                eraser,
+                selecting,
+                camera,
+                // Only the neon theme draws more than the dark one
+                theme: match theme {
+                    Theme::Neon if plain => Theme::Dark,
+                    theme => theme,
+                },
+                textures: textures.as_ref().filter(|_| !plain),
+                metaballs: args.metaballs,
+                detail,
+                motion_blur: motion_blur.as_ref().filter(|_| !plain),
+                layers,
+            };
+            profiler.time(Phase::Rendering, || {
+                let (gc, sprites) = renderer.drawing();
+                if plain_particles && scene.layers.is_shown(Layer::Particles) {
+                    let mut layers = scene.layers;
+                    layers.toggle(Layer::Particles);
+                    render::draw_scene(gc, &Scene { layers, ..scene }, sprites);
+                    renderer.begin_particles(scene.camera);
+                    draw_particles(&mut renderer, scene.world, scene.coloring);
+                } else {
+                    render::draw_scene(gc, &scene, sprites);
+                }
+                renderer.overlay_text(&hud_lines, rgba(theme.text()));
+                help::draw(renderer.drawing().0, help_lines.as_deref(), theme);
+                renderer.end_frame();
+            });
+            if let Some(metadata) = screenshot.take() {
+                // The offscreen canvas starts out empty, so it needs sprites of its own
+                let mut drawing: Vec<Draw> = vec![];
+                drawing.clear_canvas(theme.background());
+                hud.load_font(&mut drawing);
+                if let Some(textures) = scene.textures {
+                    textures.define(&mut drawing);
+                }
+                render::draw_scene(&mut drawing, &scene, &mut SpritePool::new());
+                hud.draw(&mut drawing, &hud_lines, theme.text());
+                thread::spawn(
+                    move || match screenshot::save(Path::new("."), drawing, &metadata) {
+                        Ok(path) => println!("Screenshot saved to {}", path.display()),
+                        Err(err) => tracing::warn!(%err, "could not save the screenshot"),
+                    },
+                );
+            }
+            if let Some(motion_blur) = &mut motion_blur {
+                motion_blur.record(simulation.world(), &coloring);
+            }
+            render_span.exit();
+            if let Err(err) = simulation.profiler_mut().end_frame() {
+                exit_with(err);
+            }
+            // Wait for the next frame
+            scheduler.wait();
+        }
+    })
+}
+
+fn project(particle: &Particle3d) -> ((f64, f64), f64, f32) {
+    //! Where to draw a sphere, how big, and how brightly, as seen through the
+    //! front of the box (z = 0). Further spheres are smaller, nearer the
+    //! middle of the window and darker.
+    let depth = particle.position.2.clamp(0.0, DEPTH) / DEPTH;
+    let scale = 1.0 / (1.0 + PERSPECTIVE * depth);
+    let center = (WIDTH / 2.0, HEIGHT / 2.0);
+    let position = (
+        center.0 + (particle.position.0 - center.0) * scale,
+        center.1 + (particle.position.1 - center.1) * scale,
+    );
+    (position, particle.radius * scale, 1.0 - 0.6 * depth as f32)
+}
+
+fn sphere_color(id: u64, brightness: f32) -> Color {
+    //! Spread the hues of consecutive ids around the color wheel.
+    let hue = (id as f32 * 0.618_034).fract() * std::f32::consts::TAU;
+    let [r, g, b] = [0.0, 1.0, 2.0].map(|third: f32| {
+        (0.5 + 0.5 * (hue + third * std::f32::consts::TAU / 3.0).sin()) * brightness
+    });
+    Color::Rgba(r, g, b, 1.0)
+}
+
+fn load_textures(args: &Args) -> Option<Textures> {
+    //! The images of `--textures`, if given, exiting if they cannot be read.
+    args.textures.as_ref().map(|dir| match Textures::load(dir) {
+        Ok(textures) => textures,
+        Err(err) => exit_with(err),
+    })
+}
+
+pub fn run_side_by_side(mut left: Simulation, mut right: Simulation, args: Args) {
+    //! Step both simulations in lockstep and draw them next to each other, with
+    //! the settings `--compare` changed on the right written above them.
+    let textures = load_textures(&args);
+    with_2d_graphics(move || {
+        let canvas = create_drawing_window("Particle Simulator Comparison");
+        let hud = Hud::new();
+        let theme = args.theme;
+        canvas.draw(|gc| {
+            gc.clear_canvas(theme.background());
+            hud.load_font(gc);
+            if let Some(textures) = &textures {
+                textures.define(gc);
+            }
+        });
+        let mut sprites = SpritePool::new();
+        let mut clock = Clock::new(args.time_scale);
+        clock.set_max_steps(args.catch_up_steps);
+        let mut scheduler = FrameScheduler::new(args.fps);
+        let mut colorings = [&left, &right].map(|_| Coloring::new(args.color_by, args.palette));
+        let changes: Vec<String> = args.compare.iter().map(ToString::to_string).collect();
+        let title = format!("left: as given, right: {}", changes.join(", "));
+
+        loop {
+            for _ in 0..clock.tick() {
+                for simulation in [&mut left, &mut right] {
+                    if let Err(err) = simulation.step(1.0) {
+                        exit_with(err);
+                    }
+                }
+            }
+            if !scheduler.should_render() {
+                scheduler.wait();
+                continue;
+            }
+
+            let lines = [
+                title.clone(),
+                format!(
+                    "kinetic energy: {:.1} left, {:.1} right",
+                    left.kinetic_energy(),
+                    right.kinetic_energy()
+                ),
+            ];
+            for (coloring, simulation) in colorings.iter_mut().zip([&left, &right]) {
+                coloring.update(simulation.frame(), simulation.events(), simulation.world());
+            }
+            let [left_scene, right_scene] =
+                [(&left, &colorings[0]), (&right, &colorings[1])].map(|(simulation, coloring)| {
+                    Scene {
+                        world: simulation.world(),
+                        obstacles: simulation.obstacles(),
+                        portals: simulation.portals(),
+                        time_regions: simulation.time_regions(),
+                        highlight: None,
+                        selected: &[],
+                        grid: args.grid,
+                        labels: args.labels,
+                        coloring,
+                        heat: None,
+                        prediction: None,
+                        center_of_mass: None,
+                        eraser: None,
+                        selecting: None,
+                        camera: Camera::new(),
+                        theme,
+                        textures: textures.as_ref(),
+                        metaballs: args.metaballs,
+                        motion_blur: None,
+                        layers: Layers::new(),
+                        detail: Detail::choose(
+                            simulation.particle_count(),
+                            1.0,
+                            args.lod_threshold,
+                        ),
+                    }
+                });
+            canvas.draw(|gc| {
+                render::draw_side_by_side(gc, &left_scene, &right_scene, &mut sprites);
+                hud.draw(gc, &lines, theme.text());
+            });
+            scheduler.wait();
+        }
+    })
+}
+
+pub fn run_windowed_3d(mut simulation: Simulation3d, time_scale: f64, frame_rate: FrameRate) {
+    with_2d_graphics(move || {
+        let canvas = create_drawing_window("Particle Simulator 3D");
+        canvas.draw(|gc| gc.clear_canvas(Color::Rgba(0.0, 0.0, 0.0, 1.0)));
+        let mut sprites = SpritePool::new();
+        let mut clock = Clock::new(time_scale);
+        let mut scheduler = FrameScheduler::new(frame_rate);
+
+        loop {
+            for _ in 0..clock.tick() {
+                simulation.step(1.0);
+            }
+            if !scheduler.should_render() {
+                scheduler.wait();
+                continue;
+            }
+
+            // Draw the furthest spheres first, so that nearer ones cover them
+            let mut particles = simulation.particles().to_vec();
+            particles.sort_by(|a, b| b.position.2.total_cmp(&a.position.2));
+            canvas.draw(|gc| {
+                Layer::Particles.clear(gc);
+                gc.canvas_height(1000.0);
+                gc.center_region(0.0, 0.0, 1000.0, 1000.0);
+
+                for particle in &particles {
+                    let (position, radius, brightness) = project(particle);
+                    sprites.draw_circle(
+                        gc,
+                        Layer::Particles.id(),
+                        position,
+                        radius,
+                        sphere_color(particle.id, brightness),
+                    );
+                }
+            });
+            scheduler.wait();
+        }
+    })
+}